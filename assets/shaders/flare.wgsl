@@ -0,0 +1,62 @@
+// Anamorphic lens flare, see src/flare.rs. Runs on the frame before tone mapping, so in the HDR
+// modes highlights above 1.0 flare the most.
+
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+struct FlareUniform {
+    color: vec4<f32>, // linear RGB tint of the streaks, w unused
+    intensity: f32,
+    threshold: f32,   // brightness above which a pixel casts streaks
+    length: f32,      // reach of the horizontal streak as a fraction of the image width
+    starburst: f32,   // strength of the vertical and diagonal rays, 0=streaks only
+};
+
+@group(0) @binding(0)
+var screen_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var screen_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> flare: FlareUniform;
+
+// Samples on each side of a pixel along a streak
+const FLARE_TAPS: i32 = 24;
+
+// Starburst rays are shorter than the anamorphic streak
+const STARBURST_REACH: f32 = 0.35;
+
+// The part of a pixel brighter than the threshold, keeping its hue
+fn bright_part(uv: vec2<f32>) -> vec3<f32> {
+    let col = textureSampleLevel(screen_texture, screen_sampler, uv, 0.0).rgb;
+    let peak = max(col.r, max(col.g, col.b));
+    return col * (max(peak - flare.threshold, 0.0) / max(peak, 1e-4));
+}
+
+// Light gathered from bright pixels along reach in both directions, fading with distance
+fn streak(uv: vec2<f32>, reach: vec2<f32>) -> vec3<f32> {
+    var sum = vec3<f32>(0.0);
+    for (var i = 1; i <= FLARE_TAPS; i++) {
+        let f = f32(i) / f32(FLARE_TAPS);
+        let falloff = (1.0 - f) * (1.0 - f);
+        sum += (bright_part(uv + reach * f) + bright_part(uv - reach * f)) * falloff;
+    }
+    return sum / f32(FLARE_TAPS);
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let base = textureSampleLevel(screen_texture, screen_sampler, in.uv, 0.0);
+    let size = vec2<f32>(textureDimensions(screen_texture));
+    // uv units per unit of image width on each axis, so rays keep their angles at any aspect
+    let width = vec2<f32>(1.0, size.x / size.y);
+
+    var light = streak(in.uv, vec2<f32>(flare.length, 0.0));
+    if (flare.starburst > 0.0) {
+        let reach = flare.length * STARBURST_REACH;
+        let diagonal = reach * 0.7071;
+        let rays = streak(in.uv, vec2<f32>(0.0, reach) * width)
+            + streak(in.uv, vec2<f32>(diagonal, diagonal) * width)
+            + streak(in.uv, vec2<f32>(diagonal, -diagonal) * width);
+        light += rays * flare.starburst;
+    }
+    return vec4<f32>(base.rgb + light * flare.color.rgb * flare.intensity, base.a);
+}
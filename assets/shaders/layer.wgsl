@@ -0,0 +1,26 @@
+// Draws a fractal layer rendered offscreen over the main view, see src/layers.rs. The layer's
+// colors are premultiplied by its coverage, and the pipeline's blend state sets the blend mode.
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+struct LayerUniform {
+    opacity: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+};
+
+@group(2) @binding(0)
+var layer_texture: texture_2d<f32>;
+@group(2) @binding(1)
+var layer_sampler: sampler;
+@group(2) @binding(2)
+var<uniform> layer: LayerUniform;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(layer_texture, layer_sampler, in.uv) * layer.opacity;
+}
@@ -1,33 +1,111 @@
+#ifdef CUSTOM_SHADING
+#import fractal3d::shading_graph::graph_color
+#endif
+
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(2) uv: vec2<f32>,
 };
 
-struct MandelbulbMaterial {
-    resolution: vec2<f32>, // 8 bytes
-    power: f32,            // 4 bytes
-    ray_steps: u32,        // 4 bytes
-    mandel_iters: u32,     // 4 bytes
-    max_dist: f32,         // 4 bytes
-    hit_threshold: f32,    // 4 bytes
-    camera_zoom: f32,      // 4 bytes
-    camera_position: vec3<f32>, // x, y, z
-    camera_rotation: vec4<f32>, // Quaternion rotation (x, y, z, w)
-    palette_id: u32,   // 0=Standard, 1=Ice, 2=Fire, 3=Neon
+// Uniform blocks, laid out in 16 byte rows to mirror the structs in src/material.rs
+struct CameraUniform {
+    position: vec3<f32>, // x, y, z
+    zoom: f32,           // distance from the orbit center
+    rotation: vec4<f32>, // Quaternion rotation (x, y, z, w)
+    resolution: vec2<f32>,
+    pixel_aspect: f32,   // width / height of one pixel, above 1.0 for anamorphic squeeze
+    focal_length: f32,   // distance to the image plane, smaller is a wider field of view
+    fov_fit: u32,        // 0=vertical extent fixed, 1=horizontal extent fixed, 2=shorter side fixed
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+};
+
+struct ShapeUniform {
+    julia: vec4<f32>,   // xyz are the constant, w is enabled flag
+    power: f32,
+    mandel_iters: u32,
+    ray_steps: u32,
+    max_dist: f32,
+    hit_threshold: f32,
+    stall_limit: u32,   // steps near the surface without progress before a ray stops, 0=off
+    breath_amplitude: f32, // how far the breathing displaces the domain, 0=off
+    breath_speed: f32,     // only read on the CPU, which advances animation.breath_phase
+
+    plugin_params: array<vec4<f32>, 2>, // parameters of a plugin formula, see plugin_param
+};
+
+struct ShadingUniform {
     light_pos_x: f32,  // Move the light left/right
     light_pos_y: f32,  // Move the light up/down
+    palette_id: u32,   // index into the palette table of src/palette.rs, see palette
     background_glow_intensity: f32, // Intensity of the background glow
+
     color_scale: f32,  // Stretches the gradient
     color_offset: f32, // Shifts the colors
     ao_strength: f32,  // Ambient occlusion strength
     rim_strength: f32, // Rim lighting strength
+
     fog_density: f32,  // Fog density
-    julia: vec4<f32>, // xyz are the constant, w is enabled flag
     supersampling: u32, // 0=off, 1=2x2 SSAA
+    output_mode: u32,   // encoded by the output pass, see shaders/output.wgsl
+    paper_white_nits: f32, // luminance that a diffuse white surface maps to in HDR modes
+
+    test_pattern: u32,  // 0=off, 1=color bars, 2=gradient ramps, 3=resolution chart
+    streamline_density: f32, // noise frequency of the flow lines
+    streamline_length: f32,  // world units traced on each side of a point
+    isoline_mode: u32,       // 0=off, 1=contours on the surface, 2=shells in empty space
+
+    isoline_spacing: f32,    // distance estimate between neighbouring isolines
+    isoline_intensity: f32,
+    slice_mode: u32,         // 0=off, 1=inset, 2=fullscreen heatmap of the DE on a plane
+    slice_axis: u32,         // 0=XY, 1=XZ, 2=YZ plane
+
+    slice_offset: f32,       // position of the plane along its normal axis
+    slice_extent: f32,       // half the width of the plane area shown
+    show_stalled: u32,       // 1=color rays stopped by stall_limit instead of shading them
+    transparent_background: u32, // 1=rays that miss are transparent, for layers in src/layers.rs
+
+    light_color: vec4<f32>,   // linear RGB of the key light, w unused
+    ambient_color: vec4<f32>, // linear RGB of the light reaching every surface, w unused
+    rim_color: vec4<f32>,     // linear RGB of the rim light, w unused
+
+    specular_intensity: f32, // brightness of the key light's highlight, 0 is matte
+    shininess: f32,          // Blinn-Phong exponent, higher is a smaller, sharper highlight
+    rim_direction: u32,      // 0=every silhouette edge, 1=only edges facing the light
+    lens_strength: f32, // pull bending rays toward the origin like a black hole, 0=straight rays
+
+    lens_distortion: f32, // radial distortion of the image, positive is barrel, negative pincushion
+    chromatic_aberration: f32, // extra distortion of red and less of blue, 0=off
+    grain: f32,      // strength of the film grain, 0=off
+    noise_seed: u32, // hashed into the grain, the same every frame or a new one, see NoiseLock
+
+    palette: array<vec4<f32>, 4>, // cosine palette coefficients a, b, c and d of palette_id
+};
+
+struct AnimationUniform {
+    time: f32,       // seconds since startup
+    delta_time: f32, // seconds since the previous frame
+    frame: u32,
+    breath_phase: f32, // position in the current breath, 0 to 1
+};
+
+struct ReprojectionUniform {
+    previous: CameraUniform, // camera of the frame in previous_depth
+    valid: u32,              // 0 when the previous distances can't be reused
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
 };
 
 @group(2) @binding(0)
-var<uniform> material: MandelbulbMaterial;
+var<uniform> camera: CameraUniform;
+@group(2) @binding(1)
+var<uniform> shape: ShapeUniform;
+@group(2) @binding(2)
+var<uniform> shading: ShadingUniform;
+@group(2) @binding(3)
+var<uniform> animation: AnimationUniform;
 
 // rotation helper, rotates point p around Y axis by angle in radians
 fn rotate_y(p: vec3<f32>, angle: f32) -> vec3<f32> {
@@ -53,31 +131,12 @@ fn rotate_x(p: vec3<f32>, angle: f32) -> vec3<f32> {
 
 // Inigo Quilez's cosine palette function, makes nice smooth color gradients
 // https://iquilezles.org/articles/palettes/
+// The coefficients of the selected palette come from the table in src/palette.rs
 fn palette(t: f32) -> vec3<f32> {
-    var a = vec3<f32>(0.5);
-    var b = vec3<f32>(0.5);
-    var c = vec3<f32>(1.0);
-    var d = vec3<f32>(0.263, 0.416, 0.557);
-
-    // Standard
-    if (material.palette_id == 0u) {
-        d = vec3<f32>(0.263, 0.416, 0.557);
-    }
-    // Fire (Red/Yellow)
-    else if (material.palette_id == 1u) {
-        a = vec3<f32>(0.500, 0.500, 0.000);
-        b = vec3<f32>(0.500, 0.500, 0.000);
-        c = vec3<f32>(0.100, 0.500, 0.000);
-        d = vec3<f32>(0.000, 0.000, 0.000);
-    }
-    // Neon (Purple/Green)
-    else if (material.palette_id == 2u) {
-        a = vec3<f32>(0.5, 0.5, 0.5);
-        b = vec3<f32>(0.5, 0.5, 0.5);
-        c = vec3<f32>(2.0, 1.0, 0.0);
-        d = vec3<f32>(0.5, 0.2, 0.25);
-    }
-
+    let a = shading.palette[0].xyz;
+    let b = shading.palette[1].xyz;
+    let c = shading.palette[2].xyz;
+    let d = shading.palette[3].xyz;
     return a + b * cos(6.28318 * (c * t + d));
 }
 
@@ -91,14 +150,14 @@ fn sd_mandelbulb(p: vec3<f32>) -> vec2<f32> {
 
     var trap = 1e20; // Initialize trap to a large value, will store minimum radius reached
 
-    for (var i = 0u; i < material.mandel_iters; i++) {
+    for (var i = 0u; i < shape.mandel_iters; i++) {
         r = length(z);
         if (r > 2.0) { break; }
 
         // Update Trap, keeping minimum radius reached
         var c = p;
-        if (material.julia.w > 0.5) {
-            c = material.julia.xyz;
+        if (shape.julia.w > 0.5) {
+            c = shape.julia.xyz;
         }
         trap = min(trap, r);
 
@@ -107,12 +166,12 @@ fn sd_mandelbulb(p: vec3<f32>) -> vec2<f32> {
         var phi = atan2(z.y, z.x);
 
         // calculate the derivative, needed at end for distance estimation
-        dr = pow(r, material.power - 1.0) * material.power * dr + 1.0;
+        dr = pow(r, shape.power - 1.0) * shape.power * dr + 1.0;
 
         // scale and rotate the point
-        let zr = pow(r, material.power);
-        theta = theta * material.power;
-        phi = phi * material.power;
+        let zr = pow(r, shape.power);
+        theta = theta * shape.power;
+        phi = phi * shape.power;
 
         // convert back to cartesian
         z = zr * vec3<f32>(
@@ -129,6 +188,7 @@ fn sd_mandelbulb(p: vec3<f32>) -> vec2<f32> {
     let dist = 0.5 * log(r) * r / dr;
     return vec2<f32>(dist, trap);
 }
+
 fn sphere_fold(z: vec3<f32>) -> vec3<f32> {
     let min_r = 0.5;
     let fixed_r = 1.0;
@@ -149,16 +209,16 @@ fn sd_mandelbox(p: vec3<f32>) -> vec2<f32> {
     var z = p;
     var dr = 1.0;
 
-    let scale = material.power;
+    let scale = shape.power;
 
     var offset = p;
-    if (material.julia.w > 0.5) {
-        offset = material.julia.xyz;
+    if (shape.julia.w > 0.5) {
+        offset = shape.julia.xyz;
     }
 
     var trap = 1e20;
 
-    for (var i = 0u; i < material.mandel_iters; i++) {
+    for (var i = 0u; i < shape.mandel_iters; i++) {
         z = box_fold(z);
         z = sphere_fold(z);
 
@@ -185,10 +245,79 @@ fn rotate_vector_inverse(p: vec3<f32>, q: vec4<f32>) -> vec3<f32> {
     return rotate_vector(p, q_conj);
 }
 
-// Wrapper to handle rotation and return full data (not anymore, todo:remove)
-fn map_full(p: vec3<f32>) -> vec2<f32> {
-    return sd_mandelbulb(p);
-//    return sd_mandelbox(p);
+// Ray origin in world space, the same for every pixel
+fn ray_origin(cam: CameraUniform) -> vec3<f32> {
+    return cam.position + rotate_vector_inverse(vec3<f32>(0.0, 0.0, -cam.zoom), cam.rotation);
+}
+
+// Parameter i of a plugin formula. Plugin shaders are this file with the plugin's
+// plugin_de(p) appended, see src/formulas.rs. The geometry graph appends geometry_de(p) the
+// same way, see src/geometry_graph.rs.
+fn plugin_param(i: u32) -> f32 {
+    return shape.plugin_params[i / 4u][i % 4u];
+}
+
+// Waves per unit of the breathing displacement, low so the whole shape swells together
+const BREATH_FREQUENCY: f32 = 1.5;
+// How much the breathing can stretch distances per unit of amplitude, steps are shortened by it
+const BREATH_STRETCH: f32 = 5.0;
+
+fn breath_hash(p: vec3<f32>) -> vec3<f32> {
+    let q = vec3<f32>(
+        dot(p, vec3<f32>(127.1, 311.7, 74.7)),
+        dot(p, vec3<f32>(269.5, 183.3, 246.1)),
+        dot(p, vec3<f32>(113.5, 271.9, 124.6))
+    );
+    return fract(sin(q) * 43758.5453) * 2.0 - 1.0;
+}
+
+// Smooth vector value noise in [-1, 1]
+fn breath_noise(p: vec3<f32>) -> vec3<f32> {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    return mix(
+        mix(
+            mix(breath_hash(i), breath_hash(i + vec3<f32>(1.0, 0.0, 0.0)), u.x),
+            mix(breath_hash(i + vec3<f32>(0.0, 1.0, 0.0)), breath_hash(i + vec3<f32>(1.0, 1.0, 0.0)), u.x),
+            u.y
+        ),
+        mix(
+            mix(breath_hash(i + vec3<f32>(0.0, 0.0, 1.0)), breath_hash(i + vec3<f32>(1.0, 0.0, 1.0)), u.x),
+            mix(breath_hash(i + vec3<f32>(0.0, 1.0, 1.0)), breath_hash(i + vec3<f32>(1.0, 1.0, 1.0)), u.x),
+            u.y
+        ),
+        u.z
+    );
+}
+
+// The breathing, a low frequency noise offset of the domain that swells and relaxes once per
+// breath
+fn breathe(p: vec3<f32>) -> vec3<f32> {
+    if (shape.breath_amplitude == 0.0) {
+        return p;
+    }
+    let swell = sin(animation.breath_phase * 6.2831853);
+    return p + breath_noise(p * BREATH_FREQUENCY) * shape.breath_amplitude * swell;
+}
+
+// Distance estimate plus orbit trap for the formula selected by the material's shader defs
+fn map_full(position: vec3<f32>) -> vec2<f32> {
+    let p = breathe(position);
+    var de: vec2<f32>;
+#ifdef GEOMETRY_GRAPH
+    de = geometry_de(p);
+#else ifdef FORMULA_PLUGIN
+    de = plugin_de(p);
+#else ifdef FORMULA_MANDELBOX
+    de = sd_mandelbox(p);
+#else
+    de = sd_mandelbulb(p);
+#endif
+    // the displaced field isn't a true distance anymore, shorter steps keep rays from
+    // overshooting it
+    de.x /= 1.0 + BREATH_STRETCH * shape.breath_amplitude;
+    return de;
 }
 
 // Wrapper that just returns distance (cheaper for normals)
@@ -199,7 +328,7 @@ fn map(p: vec3<f32>) -> f32 {
 // Calculate the normal at point p using "central differences"
 // see: https://iquilezles.org/articles/normalsSDF/
 fn calculate_normal(p: vec3<f32>) -> vec3<f32> {
-    let e = material.hit_threshold * 0.5;
+    let e = shape.hit_threshold * 0.5;
     return normalize(vec3<f32>(
         map(p + vec3<f32>(e, 0.0, 0.0)) - map(p - vec3<f32>(e, 0.0, 0.0)),
         map(p + vec3<f32>(0.0, e, 0.0)) - map(p - vec3<f32>(0.0, e, 0.0)),
@@ -207,93 +336,633 @@ fn calculate_normal(p: vec3<f32>) -> vec3<f32> {
     ));
 }
 
-fn render_ray(uv: vec2<f32>) -> vec3<f32> {
-    // Camera Setup
-    let local_offset = vec3<f32>(0.0, 0.0, -material.camera_zoom);
+#ifdef SOFT_SHADOWS
+// Soft shadows by marching from the surface towards the light, tracking how close the ray
+// passes to occluders, see: https://iquilezles.org/articles/rmshadows/
+fn soft_shadow(ro: vec3<f32>, rd: vec3<f32>, max_t: f32) -> f32 {
+    let k = 8.0; // penumbra sharpness
+    var res = 1.0;
+    var t = shape.hit_threshold * 10.0; // start off the surface to avoid self-shadowing
+    for (var i = 0; i < 64; i++) {
+        let d = map(ro + rd * t);
+        res = min(res, k * d / t);
+        if (res < 0.001 || t > max_t) { break; }
+        t += clamp(d, shape.hit_threshold * 2.0, 0.25); // shortest step follows the detail level
+    }
+    return clamp(res, 0.0, 1.0);
+}
+#endif
 
-    // rotate camera offset by the rotation quaternion
-    let rotated_offset = rotate_vector_inverse(local_offset, material.camera_rotation);
-    let ro = material.camera_position + rotated_offset; // ray origin in world space
+// Empty space skipping, see src/brick_map.rs. A grid of cells around the origin, each holding a
+// distance every point in the cell is at least that far from the surface.
+const BRICK_RESOLUTION: u32 = 64u;
+// Half the edge of the cube the grid covers, outside it the march estimates every step
+const BRICK_BOUNDS: f32 = 4.0;
 
-    // ray direction in camera space, then rotate to world space
-    let local_rd = normalize(vec3<f32>(uv, 1.5)); // ray direction (focal length 1.5)
-    let rd = rotate_vector_inverse(local_rd, material.camera_rotation);
+#ifdef BRICK_BAKE
+@group(3) @binding(0)
+var brick_cells: texture_storage_3d<r32float, write>;
+
+@compute @workgroup_size(4, 4, 4)
+fn bake_brick_map(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= vec3<u32>(BRICK_RESOLUTION))) {
+        return;
+    }
+    let cell = 2.0 * BRICK_BOUNDS / f32(BRICK_RESOLUTION);
+    let center = (vec3<f32>(id) + 0.5) * cell - BRICK_BOUNDS;
+    // no point in the cell is further than half its diagonal from the center, and the margin
+    // covers estimators that overestimate a little
+    let bound = (map(center) - cell * 0.8660254) * 0.9;
+    textureStore(brick_cells, id, vec4<f32>(max(bound, 0.0), 0.0, 0.0, 0.0));
+}
+#else ifdef BRICK_MAP
+@group(2) @binding(5)
+var brick_map: texture_3d<f32>;
 
-    var t = 0.0; // distance along the ray
+// Distance p is known to be from the surface, 0 when unknown
+fn brick_bound(p: vec3<f32>) -> f32 {
+    let cell = (p + BRICK_BOUNDS) / (2.0 * BRICK_BOUNDS) * f32(BRICK_RESOLUTION);
+    if (any(cell < vec3<f32>(0.0)) || any(cell >= vec3<f32>(f32(BRICK_RESOLUTION)))) {
+        return 0.0;
+    }
+    // the bounds are baked without the breathing, which moves the surface by up to its amplitude
+    return max(textureLoad(brick_map, vec3<i32>(cell), 0).x - shape.breath_amplitude, 0.0);
+}
+#endif
+
+#ifdef REPROJECTION
+// Temporal reuse, see src/reprojection.rs. Every pixel writes how far its ray marched, and the
+// next frame starts its rays close to where the previous one stopped instead of at the camera.
+@group(2) @binding(6)
+var<uniform> reprojection: ReprojectionUniform;
+@group(2) @binding(7)
+var previous_depth: texture_2d<f32>;
+@group(2) @binding(8)
+var depth: texture_storage_2d<r32float, write>;
+
+// Fraction of the reprojected distance marching starts at, so surfaces that came closer since
+// the previous frame are not stepped over
+const REPROJECTION_BACKOFF: f32 = 0.9;
+
+// Nearest previous distance around a [0, 1] screen uv
+fn previous_distance(screen_uv: vec2<f32>) -> f32 {
+    let size = vec2<i32>(textureDimensions(previous_depth));
+    let texel = vec2<i32>(screen_uv * vec2<f32>(size) - 0.5);
+    var d = shape.max_dist;
+    for (var y = 0; y < 2; y++) {
+        for (var x = 0; x < 2; x++) {
+            let at = clamp(texel + vec2<i32>(x, y), vec2<i32>(0), size - 1);
+            d = min(d, textureLoad(previous_depth, at, 0).x);
+        }
+    }
+    return d;
+}
+
+// Screen uv where a point in the previous camera's space appeared, the inverse of
+// image_plane_uv for the previous camera
+fn previous_screen_uv(local: vec3<f32>) -> vec2<f32> {
+    let prev = reprojection.previous;
+    var uv = local.xy / local.z * prev.focal_length;
+    let aspect = prev.resolution.x / prev.resolution.y * prev.pixel_aspect;
+    let fit_horizontal = prev.fov_fit == 1u || (prev.fov_fit == 2u && aspect < 1.0);
+    if (fit_horizontal) {
+        uv.y *= aspect;
+    } else {
+        uv.x /= aspect;
+    }
+    return (uv + 1.0) * 0.5;
+}
+
+// Distance along the ray through a [0, 1] screen uv to start marching at, 0 when unknown
+fn reprojected_start(screen_uv: vec2<f32>) -> f32 {
+    if (reprojection.valid == 0u) {
+        return 0.0;
+    }
+    let uv = image_plane_uv(screen_uv);
+    let ro = camera.position + rotate_vector_inverse(vec3<f32>(0.0, 0.0, -camera.zoom), camera.rotation);
+    let rd = rotate_vector_inverse(normalize(vec3<f32>(uv, camera.focal_length)), camera.rotation);
+    let prev = reprojection.previous;
+    let prev_ro = prev.position + rotate_vector_inverse(vec3<f32>(0.0, 0.0, -prev.zoom), prev.rotation);
 
-    // background color, simple gradient with halo effect
-    let bg = exp(uv.y - 2.0) * vec3<f32>(0.2, 0.4, 0.8) * material.background_glow_intensity;
+    // the previous distance at the same pixel as a first guess, refined by looking up where the
+    // guessed point was on the previous frame's screen
+    var t = previous_distance(screen_uv);
+    for (var i = 0; i < 2; i++) {
+        let to_point = ro + rd * t - prev_ro;
+        let local = rotate_vector(to_point, prev.rotation);
+        if (local.z <= 0.0) {
+            return 0.0;
+        }
+        let prev_uv = previous_screen_uv(local);
+        if (any(prev_uv < vec2<f32>(0.0)) || any(prev_uv > vec2<f32>(1.0))) {
+            return 0.0;
+        }
+        // where the previous ray through that spot stopped, seen along the current ray
+        let stop = prev_ro + normalize(to_point) * previous_distance(prev_uv);
+        t = max(dot(stop - ro, rd), 0.0);
+    }
+
+    let start = t * REPROJECTION_BACKOFF;
+    // starting inside the surface means the guess is off
+    if (map(ro + rd * start) < shape.hit_threshold) {
+        return 0.0;
+    }
+    return start;
+}
+#endif
+
+// A ray counts as stalled while it is within this many hit thresholds of a surface without
+// getting closer, e.g. grazing an edge or bouncing between estimates that disagree
+const STALL_BAND: f32 = 16.0;
+// Fraction the distance has to drop below the closest seen so far to count as progress
+const STALL_PROGRESS: f32 = 0.9;
+
+// Distance the last render_ray call marched, to the hit or where it gave up
+var<private> march_distance: f32;
+// Whether the last render_ray call hit the surface
+var<private> march_hit: bool;
+
+// Colors the ray through an image plane uv, marching from `start` along it
+// Largest step of a bent ray as a fraction of its distance to the center, the pull changes
+// fastest close to it
+const LENS_STEP_FRACTION: f32 = 0.5;
+
+// Background color for a ray leaving in direction rd, simple gradient with halo effect
+fn background(uv: vec2<f32>, ro: vec3<f32>, rd: vec3<f32>) -> vec3<f32> {
+    let bg = exp(uv.y - 2.0) * vec3<f32>(0.2, 0.4, 0.8) * shading.background_glow_intensity;
     let halo = clamp(dot(normalize(vec3<f32>(-ro.x, -ro.y, -ro.z)), rd), 0.0, 1.0);
-    var col = bg + vec3<f32>(0.02, 0.02, 0.08) * pow(halo, 17.0);
+    return bg + vec3<f32>(0.02, 0.02, 0.08) * pow(halo, 17.0);
+}
+
+// Moves a ray bent by gravity lensing step along its direction, then turns the direction toward
+// the fractal's center, the origin, with an inverse square pull.
+fn bend_ray(pos: ptr<function, vec3<f32>>, dir: ptr<function, vec3<f32>>, step: f32) {
+    *pos += *dir * step;
+    let to_center = -*pos;
+    let r2 = max(dot(to_center, to_center), 1e-6);
+    let pull = shading.lens_strength * step / r2;
+    *dir = normalize(*dir + to_center * inverseSqrt(r2) * pull);
+}
+
+fn render_ray(uv: vec2<f32>, start: f32) -> vec3<f32> {
+    // Camera Setup
+    let ro = ray_origin(camera); // ray origin in world space
+
+    // ray direction in camera space, then rotate to world space
+    let local_rd = normalize(vec3<f32>(uv, camera.focal_length)); // ray direction
+    let rd = rotate_vector_inverse(local_rd, camera.rotation);
+
+    // bent rays are integrated step by step, the straight ones keep computing positions from t
+    // so rounding doesn't build up over the steps
+    let lensing = shading.lens_strength > 0.0;
+    // a reprojected start is a distance along the straight ray, so bent rays start over
+    var t = select(start, 0.0, lensing); // distance along the ray
+    var bent_pos = ro;
+    var bent_dir = rd;
+    march_hit = false;
+
+    var col = background(uv, ro, rd);
+
+    let steps = shape.ray_steps;
+
+    // isoline shells crossed in front of the surface
+    var shells = 0.0;
+    var prev_d = -1.0;
 
-    let steps = material.ray_steps;
+    // convergence tracking for stall_limit
+    var closest = 1e20;
+    var stalled = 0u;
 
     // ray march loop
     for (var i = 0u; i < steps; i++) {
         // current position along the ray
-        let p = ro + rd * t;
+        var p = ro + rd * t;
+        if (lensing) {
+            p = bent_pos;
+        }
+#ifdef BRICK_MAP
+        // far from the surface, step over without estimating. Shells are not counted across the
+        // jump.
+        let bound = brick_bound(p);
+        if (bound > 0.0) {
+            var jump = bound;
+            if (lensing) {
+                jump = min(bound, LENS_STEP_FRACTION * length(p));
+                bend_ray(&bent_pos, &bent_dir, jump);
+            }
+            t += jump;
+            prev_d = -1.0;
+            if (t > shape.max_dist) { break; }
+            continue;
+        }
+#endif
         let data = map_full(p); // .x = dist, .y = trap
         let d = data.x;
 
+        if (shading.isoline_mode == 2u && prev_d >= 0.0) {
+            shells += isoline_shells_crossed(prev_d, d) * exp(-shading.fog_density * t);
+        }
+        prev_d = d;
+
+        if (d < closest * STALL_PROGRESS) {
+            closest = d;
+            stalled = 0u;
+        } else if (d < shape.hit_threshold * STALL_BAND) {
+            stalled++;
+        }
+        // the surface is close but the ray stopped converging on it, shade it where it is
+        // rather than spend the rest of the step budget
+        let stalled_out = shape.stall_limit > 0u && stalled >= shape.stall_limit;
+
         // hit condition, close enough to the surface
-        if (d < material.hit_threshold) {
+        if (d < shape.hit_threshold || stalled_out) {
             let normal = calculate_normal(p);
             let trap = data.y; // The orbit trap value
 
+#ifdef COLOR_BY_NORMAL
+            let raw_val = dot(normal, vec3<f32>(0.577)) * 0.5 + 0.5; // surface orientation
+#else
             let raw_val = trap + (f32(i) / f32(steps)); // combine orbit trap and steps for more variation
-            let color_variation = (raw_val * material.color_scale) + material.color_offset;
+#endif
+#ifdef COLOR_EQUALIZED
+            // the value's place in this frame's distribution, so colors spread evenly
+            let color_variation = histogram.cdf[histogram_bin(raw_val)] * shading.color_scale + shading.color_offset;
+#else
+            let color_variation = (raw_val * shading.color_scale) + shading.color_offset;
+#endif
+#ifdef CUSTOM_SHADING
+            // node graph from src/shading_graph.rs
+            let albedo = graph_color(trap, normal, t, f32(i) / f32(steps), p);
+#else
             let albedo = palette(color_variation);
+#endif
 
             // lighting Setup
-            let light_pos = vec3<f32>(material.light_pos_x, material.light_pos_y, -3.0);
+            let light_pos = vec3<f32>(shading.light_pos_x, shading.light_pos_y, -3.0);
             let light_dir = normalize(light_pos - p);
-            let view_dir = normalize(ro - p);
+            var view_dir = normalize(ro - p);
+            if (lensing) {
+                view_dir = -bent_dir;
+            }
 
             // basic diffuse lighting based on angle to light
-            let diff = max(dot(normal, light_dir), 0.0);
+            var diff = max(dot(normal, light_dir), 0.0);
+#ifdef SOFT_SHADOWS
+            let shadow = soft_shadow(p, light_dir, length(light_pos - p));
+            diff *= shadow;
+#else
+            let shadow = 1.0;
+#endif
 
             // specular, see https://en.wikipedia.org/wiki/Blinn%E2%80%93Phong_reflection_model
             let half_vec = normalize(light_dir + view_dir);
-            let spec = pow(max(dot(normal, half_vec), 0.0), 32.0);
+            let spec = pow(max(dot(normal, half_vec), 0.0), shading.shininess);
 
-            // rim lighting, edges perpendicular to view get a glow
-            let rim = pow(1.0 - max(dot(normal, view_dir), 0.0), 4.0);
+            // rim lighting, edges perpendicular to view get a glow, optionally only on the side
+            // the light comes from so it reads as a back light
+            var rim = pow(1.0 - max(dot(normal, view_dir), 0.0), 4.0);
+            if (shading.rim_direction == 1u) {
+                rim *= max(dot(normal, light_dir), 0.0);
+            }
 
             // fake ambient occlusion based on number of steps taken to hit surface
-            let ao = 1.0 - (f32(i) / f32(steps)) * material.ao_strength;
+            let ao = 1.0 - (f32(i) / f32(steps)) * shading.ao_strength;
 
             // Combine lighting components
-            let ambient = vec3<f32>(0.1) * albedo;
-            let diffuse_light = albedo * diff * vec3<f32>(1.0, 0.9, 0.8);
-            let specular_light = vec3<f32>(1.0) * spec * 0.8;
-            let rim_light = vec3<f32>(0.0, 0.5, 1.0) * rim * material.rim_strength;
+            let ambient = shading.ambient_color.rgb * albedo;
+            let diffuse_light = albedo * diff * shading.light_color.rgb;
+            let specular_light = shading.light_color.rgb * spec * shading.specular_intensity * shadow;
+            let rim_light = shading.rim_color.rgb * rim * shading.rim_strength;
 
             col = (ambient + diffuse_light + specular_light + rim_light) * ao;
+#ifdef STREAMLINES
+            col *= 0.25 + 0.75 * streamlines(p, normal);
+#endif
+            if (shading.isoline_mode == 1u) {
+                col = mix(col, vec3<f32>(0.02), surface_isoline(p, normal) * shading.isoline_intensity);
+            }
 
             // some fog based on distance
-            col = mix(col, vec3<f32>(0.01, 0.01, 0.02), 1.0 - exp(-material.fog_density * t));
+            col = mix(col, vec3<f32>(0.01, 0.01, 0.02), 1.0 - exp(-shading.fog_density * t));
 
+            if (stalled_out && shading.show_stalled == 1u) {
+                col = vec3<f32>(1.0, 0.0, 1.0);
+            }
+            march_hit = true;
             break;
         }
 
-        t += d; // march the ray
+        // march the ray
+        var step = d;
+        if (lensing) {
+            step = min(d, LENS_STEP_FRACTION * length(p));
+            bend_ray(&bent_pos, &bent_dir, step);
+        }
+        t += step;
 
         // ray exceeded max distance
-        if (t > material.max_dist) { break; }
+        if (t > shape.max_dist) { break; }
     }
 
+    // a bent ray sees the background in the direction it ended up going
+    if (lensing && !march_hit) {
+        col = background(uv, ro, bent_dir);
+    }
+
+    col += vec3<f32>(0.3, 0.6, 1.0) * shells * 0.15 * shading.isoline_intensity;
+    march_distance = t;
     return col;
 }
 
+// Number of isoline shells drawn around the surface in the shells mode
+const ISOLINE_SHELLS: f32 = 8.0;
+
+// Shells of constant distance estimate a march step passed through, going from prev_d to d.
+// Only the innermost few are counted, the field has a shell at every multiple of the spacing.
+fn isoline_shells_crossed(prev_d: f32, d: f32) -> f32 {
+    let max_d = shading.isoline_spacing * ISOLINE_SHELLS;
+    let a = floor(min(prev_d, max_d) / shading.isoline_spacing);
+    let b = floor(min(d, max_d) / shading.isoline_spacing);
+    return abs(a - b);
+}
+
+// Contours of the distance estimate a few isoline spacings above the surface. The estimate there
+// drops in creases and cavities, where nearby geometry is closer, so the lines outline the
+// surface's relief. Returns 1 on a line, 0 between lines.
+fn surface_isoline(p: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let level = map(p + normal * shading.isoline_spacing * ISOLINE_SHELLS) / shading.isoline_spacing;
+    // derivatives are not available inside the march loop, so the width is fixed in level units
+    return 1.0 - smoothstep(0.03, 0.08, abs(fract(level + 0.5) - 0.5));
+}
+
+#ifdef STREAMLINES
+fn streamline_hash(p: vec3<f32>) -> f32 {
+    return fract(sin(dot(p, vec3<f32>(127.1, 311.7, 74.7))) * 43758.5453);
+}
+
+// Smooth 3D value noise, the texture smeared along the flow
+fn streamline_noise(p: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    return mix(
+        mix(
+            mix(streamline_hash(i), streamline_hash(i + vec3<f32>(1.0, 0.0, 0.0)), u.x),
+            mix(streamline_hash(i + vec3<f32>(0.0, 1.0, 0.0)), streamline_hash(i + vec3<f32>(1.0, 1.0, 0.0)), u.x),
+            u.y
+        ),
+        mix(
+            mix(streamline_hash(i + vec3<f32>(0.0, 0.0, 1.0)), streamline_hash(i + vec3<f32>(1.0, 0.0, 1.0)), u.x),
+            mix(streamline_hash(i + vec3<f32>(0.0, 1.0, 1.0)), streamline_hash(i + vec3<f32>(1.0, 1.0, 1.0)), u.x),
+            u.y
+        ),
+        u.z
+    );
+}
+
+// Flow lines by line integral convolution: noise is averaged along a streamline through p, so
+// it smears into streaks that follow the flow, see https://en.wikipedia.org/wiki/Line_integral_convolution
+// The flow is the part of the DE gradient, sampled a little above the surface, that runs along
+// the surface. It bends towards creases and away from ridges. Returns 0 (line) to 1 (gap).
+fn streamlines(p: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let steps = 6;
+    let h = shading.streamline_length / f32(steps);
+    var sum = streamline_noise(p * shading.streamline_density);
+    for (var side = -1.0; side <= 1.0; side += 2.0) {
+        var q = p;
+        var n = normal;
+        for (var i = 0; i < steps; i++) {
+            let g = calculate_normal(q + n * h);
+            var flow = g - n * dot(g, n);
+            // a fixed direction takes over where the surface is flat and the gradient has no
+            // tangential part
+            flow += (vec3<f32>(0.0, 1.0, 0.0) - n * n.y) * 0.05;
+            q += normalize(flow + vec3<f32>(1e-6)) * h * side;
+            // back onto the surface
+            q -= n * map(q);
+            n = g;
+            sum += streamline_noise(q * shading.streamline_density);
+        }
+    }
+    let lic = sum / f32(steps * 2 + 1);
+    // averaging flattens the noise towards 0.5, stretch the contrast back
+    return smoothstep(0.35, 0.65, lic);
+}
+#endif
+
+// Distance estimate on the slice plane as a heatmap: warm outside the surface, cool inside,
+// with a contour every eighth of the extent and a white line on the surface itself. uv spans
+// [-1, 1] across the shown area, y up.
+fn slice_heatmap(uv: vec2<f32>) -> vec3<f32> {
+    let u = uv.x * shading.slice_extent;
+    let v = -uv.y * shading.slice_extent;
+    var p: vec3<f32>;
+    switch shading.slice_axis {
+        case 1u: { p = vec3<f32>(u, shading.slice_offset, v); }
+        case 2u: { p = vec3<f32>(shading.slice_offset, u, v); }
+        default: { p = vec3<f32>(u, v, shading.slice_offset); }
+    }
+    let d = map(p);
+
+    let s = clamp(d / shading.slice_extent, -1.0, 1.0);
+    var col: vec3<f32>;
+    if (s >= 0.0) {
+        col = mix(vec3<f32>(1.0, 0.8, 0.4), vec3<f32>(0.25, 0.02, 0.02), sqrt(s));
+    } else {
+        col = mix(vec3<f32>(0.4, 0.7, 1.0), vec3<f32>(0.02, 0.03, 0.25), sqrt(-s));
+    }
+
+    // derivatives are unavailable in the inset's non-uniform branch, so line widths are fixed
+    // in level units
+    let level = d / (shading.slice_extent / 8.0);
+    let contour = 1.0 - smoothstep(0.02, 0.06, abs(fract(level + 0.5) - 0.5));
+    col = mix(col, col * 0.4, contour);
+    let surface = 1.0 - smoothstep(0.0, shading.slice_extent * 0.01, abs(d));
+    return mix(col, vec3<f32>(1.0), surface);
+}
+
+// Radial distortion of an image plane uv by k, positive squeezes the edges in (barrel) and
+// negative stretches them out (pincushion), like a real lens
+fn distort(uv: vec2<f32>, k: f32) -> vec2<f32> {
+    return uv / (1.0 + k * dot(uv, uv));
+}
+
+// render_ray through the lens, each channel bent by a slightly different amount with chromatic
+// aberration. Green is traced last so march_hit and march_distance are its, the middle of the
+// fringes.
+fn lens_ray(uv: vec2<f32>, start: f32) -> vec3<f32> {
+    let k = shading.lens_distortion;
+    let spread = shading.chromatic_aberration;
+    if (spread == 0.0) {
+        return render_ray(distort(uv, k), start);
+    }
+    let red = render_ray(distort(uv, k + spread), start).r;
+    let blue = render_ray(distort(uv, k - spread), start).b;
+    let green = render_ray(distort(uv, k), start).g;
+    return vec3<f32>(red, green, blue);
+}
+
+// PCG hash, see https://www.jcgt.org/published/0009/03/02/
+fn pcg_hash(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Film grain, a random brightness change per pixel. The pattern only depends on the pixel and
+// noise_seed, so it holds still or flickers like film depending on how the seed is set.
+fn film_grain(col: vec3<f32>, screen_uv: vec2<f32>) -> vec3<f32> {
+    let pixel = vec2<u32>(screen_uv * camera.resolution);
+    let hash = pcg_hash(pixel.x + pcg_hash(pixel.y + pcg_hash(shading.noise_seed)));
+    let n = f32(hash) / 4294967295.0 - 0.5;
+    return max(col * (1.0 + 2.0 * n * shading.grain), vec3<f32>(0.0));
+}
+
+// Maps a [0, 1] screen uv to the camera's image plane. Only one axis spans [-1, 1], the other
+// is scaled by the display aspect ratio so pixels stay square in world space at any window shape.
+fn image_plane_uv(screen_uv: vec2<f32>) -> vec2<f32> {
+    var uv = (screen_uv * 2.0) - 1.0;
+    // display aspect, corrected for non-square (anamorphic) pixels
+    let aspect = camera.resolution.x / camera.resolution.y * camera.pixel_aspect;
+
+    let fit_horizontal = camera.fov_fit == 1u || (camera.fov_fit == 2u && aspect < 1.0);
+    if (fit_horizontal) {
+        uv.y /= aspect;
+    } else {
+        uv.x *= aspect;
+    }
+    return uv;
+}
+
+// Raw march data instead of a color, used by the data export in src/export.rs and the coloring
+// histogram: march steps taken, orbit trap, distance along the ray and 1 on a hit, 0 on a miss
+fn march_data(uv: vec2<f32>) -> vec4<f32> {
+    let ro = ray_origin(camera);
+    let rd = rotate_vector_inverse(normalize(vec3<f32>(uv, camera.focal_length)), camera.rotation);
+    var t = 0.0;
+    for (var i = 0u; i < shape.ray_steps; i++) {
+        let data = map_full(ro + rd * t);
+        if (data.x < shape.hit_threshold) {
+            return vec4<f32>(f32(i), data.y, t, 1.0);
+        }
+        t += data.x;
+        if (t > shape.max_dist) {
+            return vec4<f32>(f32(i), 0.0, t, 0.0);
+        }
+    }
+    return vec4<f32>(f32(shape.ray_steps), 0.0, t, 0.0);
+}
+
+const HISTOGRAM_BINS: u32 = 256u;
+
+// Histogram bin of a coloring value. [0, inf) is compressed into the bins, so the values fit
+// whatever range the parameters produce.
+fn histogram_bin(v: f32) -> u32 {
+    let x = max(v, 0.0);
+    return min(u32(x / (1.0 + x) * f32(HISTOGRAM_BINS)), HISTOGRAM_BINS - 1u);
+}
+
+#ifdef HISTOGRAM_COMPUTE
+// Histogram of the frame's coloring values and its normalized cumulative sum, see
+// src/histogram.rs. The fragment shader reads the same buffer read-only.
+
+struct HistogramBins {
+    counts: array<atomic<u32>, 256>,
+    cdf: array<f32, 256>,
+};
+
+@group(3) @binding(0)
+var<storage, read_write> histogram_bins: HistogramBins;
+
+// Rays traced per frame, a coarse grid over the view
+const HISTOGRAM_SAMPLES: vec2<u32> = vec2<u32>(160u, 90u);
+
+@compute @workgroup_size(64)
+fn clear_histogram(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x < HISTOGRAM_BINS) {
+        atomicStore(&histogram_bins.counts[id.x], 0u);
+    }
+}
+
+@compute @workgroup_size(8, 8)
+fn accumulate_histogram(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id.xy >= HISTOGRAM_SAMPLES)) {
+        return;
+    }
+    let screen_uv = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(HISTOGRAM_SAMPLES);
+    let data = march_data(image_plane_uv(screen_uv));
+    if (data.w > 0.5) {
+        // same value as raw_val in render_ray
+        let raw_val = data.y + data.x / f32(shape.ray_steps);
+        atomicAdd(&histogram_bins.counts[histogram_bin(raw_val)], 1u);
+    }
+}
+
+@compute @workgroup_size(1)
+fn finish_histogram() {
+    var total = 0u;
+    for (var i = 0u; i < HISTOGRAM_BINS; i++) {
+        total += atomicLoad(&histogram_bins.counts[i]);
+    }
+    var running = 0u;
+    for (var i = 0u; i < HISTOGRAM_BINS; i++) {
+        running += atomicLoad(&histogram_bins.counts[i]);
+        // nothing hit, fall back to an even ramp
+        if (total > 0u) {
+            histogram_bins.cdf[i] = f32(running) / f32(total);
+        } else {
+            histogram_bins.cdf[i] = f32(i) / f32(HISTOGRAM_BINS);
+        }
+    }
+}
+#else
+struct Histogram {
+    counts: array<u32, 256>,
+    cdf: array<f32, 256>,
+};
+
+@group(2) @binding(4)
+var<storage, read> histogram: Histogram;
+#endif
+
 @fragment
 fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
-    let aspect = material.resolution.x / material.resolution.y;
+#ifdef DATA_OUTPUT
+    return march_data(image_plane_uv(in.uv));
+#else
+    if (shading.test_pattern > 0u) {
+        return vec4<f32>(test_pattern(in.uv), 1.0);
+    }
+    if (shading.slice_mode == 2u) {
+        return vec4<f32>(slice_heatmap(image_plane_uv(in.uv)), 1.0);
+    }
+    if (shading.slice_mode == 1u) {
+        // square inset in the bottom right corner
+        let side = min(camera.resolution.x, camera.resolution.y) * 0.35;
+        let margin = 12.0;
+        let corner = camera.resolution - vec2<f32>(side + margin);
+        let local = (in.uv * camera.resolution - corner) / side;
+        if (all(local >= vec2<f32>(0.0)) && all(local <= vec2<f32>(1.0))) {
+            return vec4<f32>(slice_heatmap(local * 2.0 - 1.0), 1.0);
+        }
+    }
+
     var col: vec3<f32>;
+    var start = 0.0;
+#ifdef REPROJECTION
+    // the previous distances are along rays through an undistorted lens
+    if (shading.lens_distortion == 0.0 && shading.chromatic_aberration == 0.0) {
+        start = reprojected_start(in.uv);
+    }
+#endif
+    var nearest = shape.max_dist;
+    // share of samples that hit, the alpha with a transparent background
+    var coverage = 1.0;
+    let transparent = shading.transparent_background == 1u;
 
-    if (material.supersampling > 0u) {
-        // size of one pixel in UV space, currently hardcoded estimate
-        // ideally, we do let pixel_size = 1.0 / vec2<f32>(screen_width, screen_height);
-        let px = vec2<f32>(1.0 / material.resolution.x, 1.0 / material.resolution.y);
+    if (shading.supersampling > 0u) {
+        // size of one pixel in UV space
+        let px = vec2<f32>(1.0 / camera.resolution.x, 1.0 / camera.resolution.y);
 
         // 2x2 super sampling offsets
         let offsets = array<vec2<f32>, 4>(
@@ -304,27 +973,275 @@ fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
         );
 
         var total_color = vec3<f32>(0.0);
+        var hits = 0.0;
 
         // grab colors from each sub-pixel sample
         for (var i = 0; i < 4; i++) {
             // calculate the specific sub-pixel UV
             let sub_uv_raw = in.uv + (offsets[i] * px);
 
-            // remap to [-1, 1]
-            var sub_uv = (sub_uv_raw * 2.0) - 1.0;
-            sub_uv.x *= aspect;
-
-            total_color += render_ray(sub_uv);
+            let sample = lens_ray(image_plane_uv(sub_uv_raw), start);
+            nearest = min(nearest, march_distance);
+            if (march_hit || !transparent) {
+                total_color += sample;
+                hits += 1.0;
+            }
         }
-        // average the samples
+        // average the samples, premultiplied by coverage with a transparent background
         col = total_color / 4.0;
+        coverage = hits / 4.0;
     } else {
-        var uv = (in.uv * 2.0) - 1.0;
-        uv.x *= aspect;
-        col = render_ray(uv);
+        col = lens_ray(image_plane_uv(in.uv), start);
+        nearest = march_distance;
+        if (transparent && !march_hit) {
+            col = vec3<f32>(0.0);
+            coverage = 0.0;
+        }
+    }
+#ifdef REPROJECTION
+    textureStore(depth, vec2<i32>(in.uv * camera.resolution), vec4<f32>(nearest, 0.0, 0.0, 0.0));
+#endif
+    if (shading.grain > 0.0) {
+        col = film_grain(col, in.uv);
+    }
+
+    // linear, the output pass at the end of the post chain encodes it, see src/output.rs
+    return vec4<f32>(col, coverage);
+#endif
+}
+
+// Test patterns. Levels are given as SDR signal values and converted to the linear values that
+// the output pass maps back to them, so the bars are exact in SDR and relative to paper white in
+// the HDR modes.
+fn signal_to_linear(v: vec3<f32>) -> vec3<f32> {
+    return pow(v, vec3<f32>(1.0 / 0.5545));
+}
+
+// 75% bars over a row of reversed blue bars and a black level strip
+fn color_bars(uv: vec2<f32>) -> vec3<f32> {
+    let bars = array<vec3<f32>, 7>(
+        vec3<f32>(0.75, 0.75, 0.75), // white
+        vec3<f32>(0.75, 0.75, 0.0),  // yellow
+        vec3<f32>(0.0, 0.75, 0.75),  // cyan
+        vec3<f32>(0.0, 0.75, 0.0),   // green
+        vec3<f32>(0.75, 0.0, 0.75),  // magenta
+        vec3<f32>(0.75, 0.0, 0.0),   // red
+        vec3<f32>(0.0, 0.0, 0.75)    // blue
+    );
+    let i = min(u32(uv.x * 7.0), 6u);
+
+    if (uv.y < 0.67) {
+        return bars[i];
     }
+    if (uv.y < 0.75) {
+        // blue, black, magenta, black, cyan, black, white
+        if (i % 2u == 1u) {
+            return vec3<f32>(0.0);
+        }
+        return bars[6u - i];
+    }
+
+    // 100% white patch, then a black level check with 0%, 2% and 4% patches
+    let x = uv.x * 7.0;
+    if (x < 2.0) {
+        return vec3<f32>(1.0);
+    }
+    if (x >= 4.0 && x < 5.0) {
+        let pluge = floor((x - 4.0) * 3.0) * 0.02;
+        return vec3<f32>(pluge);
+    }
+    return vec3<f32>(0.0);
+}
+
+// Smooth gray, red, green and blue ramps above an 11-step gray scale
+fn gradient_ramps(uv: vec2<f32>) -> vec3<f32> {
+    let band = min(u32(uv.y * 5.0), 4u);
+    let t = uv.x;
+    switch band {
+        case 0u: { return vec3<f32>(t); }
+        case 1u: { return vec3<f32>(t, 0.0, 0.0); }
+        case 2u: { return vec3<f32>(0.0, t, 0.0); }
+        case 3u: { return vec3<f32>(0.0, 0.0, t); }
+        default: { return vec3<f32>(min(floor(t * 11.0), 10.0) / 10.0); }
+    }
+}
+
+// Pixel grid, border, centered circle and line pair patches at 1, 2 and 4 pixels
+fn resolution_chart(uv: vec2<f32>) -> vec3<f32> {
+    let size = camera.resolution;
+    let px = uv * size;
+    let center = size * 0.5;
+
+    // 1 pixel border, anything cut off by overscan is immediately visible
+    if (px.x < 1.0 || px.y < 1.0 || px.x >= size.x - 1.0 || px.y >= size.y - 1.0) {
+        return vec3<f32>(1.0);
+    }
+
+    // circle touching the top and bottom edges, it looks oval if the pixel aspect is off
+    let radius = min(size.x, size.y) * 0.45;
+    let d = length(px - center);
+    if (abs(d - radius) < 1.0) {
+        return vec3<f32>(1.0);
+    }
+
+    // line pair patches along the horizontal center line
+    let patch_size = min(size.x, size.y) * 0.1;
+    for (var i = 0u; i < 3u; i++) {
+        let width = f32(1u << i);
+        let origin = center + vec2<f32>((f32(i) - 1.0) * patch_size * 1.25, 0.0) - patch_size * 0.5;
+        let local = px - origin;
+        if (all(local >= vec2<f32>(0.0)) && all(local < vec2<f32>(patch_size))) {
+            // vertical lines in the top half, horizontal in the bottom half
+            let coord = select(local.y, local.x, local.y < patch_size * 0.5);
+            return vec3<f32>(f32(u32(coord / width) % 2u));
+        }
+    }
+
+    // grid every 64 pixels from the center
+    let grid = abs(px - center) % vec2<f32>(64.0);
+    if (any(grid < vec2<f32>(1.0))) {
+        return vec3<f32>(0.5);
+    }
+    return vec3<f32>(0.1);
+}
+
+fn test_pattern(uv: vec2<f32>) -> vec3<f32> {
+    // A/V sync marker in the top right corner, flashes white for the first 100ms of every second
+    // and a bar below it sweeps across once per second
+    let px = uv * camera.resolution;
+    let marker = vec2<f32>(camera.resolution.x - 96.0, 32.0);
+    let local = px - marker;
+    if (all(local >= vec2<f32>(0.0)) && all(local < vec2<f32>(64.0))) {
+        let second = fract(animation.time);
+        if (local.y >= 56.0) {
+            return signal_to_linear(vec3<f32>(f32(local.x < second * 64.0)));
+        }
+        return signal_to_linear(vec3<f32>(f32(second < 0.1)));
+    }
+
+    var col: vec3<f32>;
+    switch shading.test_pattern {
+        case 1u: { col = color_bars(uv); }
+        case 2u: { col = gradient_ramps(uv); }
+        default: { col = resolution_chart(uv); }
+    }
+    return signal_to_linear(col);
+}
+
+#ifdef PARTICLE_COMPUTE
+// Particles seeded on the fractal surface, see src/particles.rs. This entry point lives in the
+// fractal shader so it evaluates exactly the estimator the material renders.
 
-    // Gamma correction
-    let final_col = pow(col, vec3<f32>(0.5545)); // approx 1/2.2 + 0.1
-    return vec4<f32>(final_col, 1.0);
-}
\ No newline at end of file
+struct Particle {
+    position: vec3<f32>,
+    life: f32, // seconds left, dead at or below zero
+    velocity: vec3<f32>,
+    seed: f32,
+};
+
+struct ParticleSettings {
+    count: u32,
+    motion: u32, // 0=along the DE gradient, 1=curl noise
+    speed: f32,
+    lifetime: f32,
+    spawn_radius: f32,
+    delta_time: f32,
+    seed: f32, // offsets spawn positions, a new draw from the run's seed every frame
+};
+
+@group(3) @binding(0)
+var<storage, read_write> particles: array<Particle>;
+@group(3) @binding(1)
+var<uniform> particle_settings: ParticleSettings;
+
+fn particle_hash(n: f32) -> f32 {
+    return fract(sin(n) * 43758.5453);
+}
+
+fn particle_hash3(p: vec3<f32>) -> f32 {
+    return particle_hash(dot(p, vec3<f32>(127.1, 311.7, 74.7)));
+}
+
+// Trilinear value noise
+fn particle_noise(p: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    let x00 = mix(particle_hash3(i), particle_hash3(i + vec3<f32>(1.0, 0.0, 0.0)), u.x);
+    let x10 = mix(particle_hash3(i + vec3<f32>(0.0, 1.0, 0.0)), particle_hash3(i + vec3<f32>(1.0, 1.0, 0.0)), u.x);
+    let x01 = mix(particle_hash3(i + vec3<f32>(0.0, 0.0, 1.0)), particle_hash3(i + vec3<f32>(1.0, 0.0, 1.0)), u.x);
+    let x11 = mix(particle_hash3(i + vec3<f32>(0.0, 1.0, 1.0)), particle_hash3(i + vec3<f32>(1.0, 1.0, 1.0)), u.x);
+    return mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z);
+}
+
+fn particle_potential(p: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        particle_noise(p),
+        particle_noise(p + vec3<f32>(31.4, 17.3, 5.9)),
+        particle_noise(p + vec3<f32>(-12.7, 43.1, 27.2))
+    );
+}
+
+// Divergence-free flow from the curl of a noise potential, so particles swirl without
+// bunching up
+fn curl_noise(p: vec3<f32>) -> vec3<f32> {
+    let e = 0.01;
+    let dx = particle_potential(p + vec3<f32>(e, 0.0, 0.0)) - particle_potential(p - vec3<f32>(e, 0.0, 0.0));
+    let dy = particle_potential(p + vec3<f32>(0.0, e, 0.0)) - particle_potential(p - vec3<f32>(0.0, e, 0.0));
+    let dz = particle_potential(p + vec3<f32>(0.0, 0.0, e)) - particle_potential(p - vec3<f32>(0.0, 0.0, e));
+    return vec3<f32>(dy.z - dz.y, dz.x - dx.z, dx.y - dy.x) / (2.0 * e);
+}
+
+// Random point in the spawn sphere pulled onto the surface along the gradient. A point that
+// does not converge is rejected and the particle tries again next frame.
+fn spawn_particle(seed: f32) -> vec4<f32> {
+    let u = particle_hash(seed);
+    let v = particle_hash(seed + 1.0);
+    let w = particle_hash(seed + 2.0);
+    let z = u * 2.0 - 1.0;
+    let a = v * 6.2831853;
+    let dir = vec3<f32>(sqrt(1.0 - z * z) * cos(a), sqrt(1.0 - z * z) * sin(a), z);
+    var p = dir * pow(w, 1.0 / 3.0) * particle_settings.spawn_radius;
+    for (var i = 0; i < 12; i++) {
+        let d = map(p);
+        if (abs(d) < shape.hit_threshold * 4.0) {
+            return vec4<f32>(p, 1.0);
+        }
+        p -= calculate_normal(p) * d;
+    }
+    return vec4<f32>(p, 0.0);
+}
+
+@compute @workgroup_size(64)
+fn update_particles(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= particle_settings.count) {
+        return;
+    }
+    var particle = particles[i];
+    let dt = particle_settings.delta_time;
+    particle.life -= dt;
+
+    if (particle.life <= 0.0) {
+        let seed = f32(i) * 1.618 + particle_settings.seed;
+        let spawned = spawn_particle(seed);
+        if (spawned.w > 0.0) {
+            particle.position = spawned.xyz;
+            particle.life = particle_settings.lifetime * (0.5 + 0.5 * particle_hash(seed + 3.0));
+            particle.velocity = vec3<f32>(0.0);
+            particle.seed = particle_hash(seed + 4.0);
+        }
+    } else {
+        var target_velocity: vec3<f32>;
+        if (particle_settings.motion == 0u) {
+            // away from the surface, like sparks thrown off it
+            target_velocity = calculate_normal(particle.position);
+        } else {
+            target_velocity = curl_noise(particle.position * 3.0 + particle.seed * 10.0);
+        }
+        particle.velocity = mix(particle.velocity, target_velocity * particle_settings.speed, min(dt * 4.0, 1.0));
+        particle.position += particle.velocity * dt;
+    }
+    particles[i] = particle;
+}
+#endif
@@ -0,0 +1,65 @@
+// Output transfer function, see src/output.rs. The last pass on the frame: everything before it
+// is linear, this encodes it once for the selected output mode.
+
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+const OUTPUT_MODE_SDR: u32 = 0u;
+const OUTPUT_MODE_SCRGB: u32 = 1u;
+const OUTPUT_MODE_HDR10: u32 = 2u;
+
+struct OutputUniform {
+    mode: u32,             // 0=SDR, 1=scRGB (linear, 1.0 = 80 nits), 2=HDR10 (Rec.2020 + PQ)
+    paper_white_nits: f32, // luminance that a diffuse white surface maps to in HDR modes
+};
+
+@group(0) @binding(0)
+var screen_texture: texture_2d<f32>;
+@group(0) @binding(1)
+var screen_sampler: sampler;
+@group(0) @binding(2)
+var<uniform> output: OutputUniform;
+
+// SMPTE ST 2084 (PQ) inverse EOTF, takes luminance normalized to 10000 nits
+fn pq_encode(l: vec3<f32>) -> vec3<f32> {
+    let m1 = 0.1593017578125;
+    let m2 = 78.84375;
+    let c1 = 0.8359375;
+    let c2 = 18.8515625;
+    let c3 = 18.6875;
+    let lp = pow(clamp(l, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(m1));
+    return pow((c1 + c2 * lp) / (1.0 + c3 * lp), vec3<f32>(m2));
+}
+
+// Applies the output transfer function for the selected display mode
+fn encode_output(col: vec3<f32>, mode: u32) -> vec3<f32> {
+    // scRGB, linear Rec.709 where 1.0 is 80 nits, values above 1.0 are kept
+    if (mode == OUTPUT_MODE_SCRGB) {
+        return max(col, vec3<f32>(0.0)) * (output.paper_white_nits / 80.0);
+    }
+
+    // HDR10, convert Rec.709 primaries to Rec.2020, then PQ encode absolute luminance
+    if (mode == OUTPUT_MODE_HDR10) {
+        let to_2020 = mat3x3<f32>(
+            vec3<f32>(0.6274, 0.0691, 0.0164),
+            vec3<f32>(0.3293, 0.9195, 0.0880),
+            vec3<f32>(0.0433, 0.0114, 0.8956)
+        );
+        let nits = (to_2020 * max(col, vec3<f32>(0.0))) * output.paper_white_nits;
+        return pq_encode(nits / 10000.0);
+    }
+
+    // Gamma correction, the sRGB target adds its own curve on top
+    return pow(max(col, vec3<f32>(0.0)), vec3<f32>(0.5545)); // approx 1/2.2 + 0.1
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let col = textureSampleLevel(screen_texture, screen_sampler, in.uv, 0.0);
+#ifdef SDR_SURFACE
+    // an 8-bit surface can't hold the HDR modes, so they are previewed in SDR
+    let mode = OUTPUT_MODE_SDR;
+#else
+    let mode = output.mode;
+#endif
+    return vec4<f32>(encode_output(col.rgb, mode), col.a);
+}
@@ -0,0 +1,99 @@
+// Draws the particles simulated by update_particles in mandelbulb.wgsl as additive sprites over
+// the fractal. Each particle is a quad of six vertices whose position attribute holds the
+// corner in xy and the particle index in z, see src/particles.rs.
+#import bevy_sprite::mesh2d_functions::{get_world_from_local, mesh2d_position_local_to_clip}
+
+struct Particle {
+    position: vec3<f32>,
+    life: f32,
+    velocity: vec3<f32>,
+    seed: f32,
+};
+
+// Same layout as in mandelbulb.wgsl
+struct CameraUniform {
+    position: vec3<f32>,
+    zoom: f32,
+    rotation: vec4<f32>,
+    resolution: vec2<f32>,
+    pixel_aspect: f32,
+    focal_length: f32,
+    fov_fit: u32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+};
+
+struct ParticleLook {
+    color: vec4<f32>, // rgb and intensity
+    size: f32,        // pixels at distance 1
+    lifetime: f32,
+    _padding0: f32,
+    _padding1: f32,
+};
+
+@group(2) @binding(0)
+var<storage, read> particles: array<Particle>;
+@group(2) @binding(1)
+var<uniform> camera: CameraUniform;
+@group(2) @binding(2)
+var<uniform> look: ParticleLook;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) corner: vec2<f32>,
+    @location(1) alpha: f32,
+};
+
+fn rotate_vector(p: vec3<f32>, q: vec4<f32>) -> vec3<f32> {
+    return p + 2.0 * cross(q.xyz, cross(q.xyz, p) + q.w * p);
+}
+
+@vertex
+fn vertex(in: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.corner = in.position.xy;
+    let particle = particles[u32(in.position.z)];
+
+    // world to camera space, the inverse of the ray setup in render_ray
+    let q_conj = vec4<f32>(-camera.rotation.xyz, camera.rotation.w);
+    let ro = camera.position + rotate_vector(vec3<f32>(0.0, 0.0, -camera.zoom), q_conj);
+    let local = rotate_vector(particle.position - ro, camera.rotation);
+    if (particle.life <= 0.0 || local.z <= 0.0) {
+        // dead or behind the camera, collapse the quad outside the clip volume
+        out.clip_position = vec4<f32>(2.0, 2.0, 0.0, 1.0);
+        out.alpha = 0.0;
+        return out;
+    }
+
+    // onto the image plane, then the inverse of image_plane_uv
+    var uv = local.xy / local.z * camera.focal_length;
+    let aspect = camera.resolution.x / camera.resolution.y * camera.pixel_aspect;
+    let fit_horizontal = camera.fov_fit == 1u || (camera.fov_fit == 2u && aspect < 1.0);
+    if (fit_horizontal) {
+        uv.y *= aspect;
+    } else {
+        uv.x /= aspect;
+    }
+
+    // the quad spans [-0.5, 0.5] with y up, screen uv has y down
+    let size = look.size * camera.focal_length / local.z;
+    let offset = in.position.xy * size / camera.resolution;
+    let position = vec2<f32>(uv.x, -uv.y) * 0.5 + offset;
+    let world_from_local = get_world_from_local(in.instance_index);
+    out.clip_position = mesh2d_position_local_to_clip(world_from_local, vec4<f32>(position, 0.0, 1.0));
+    // fade out before dying
+    out.alpha = smoothstep(0.0, 0.3, particle.life / look.lifetime);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let falloff = 1.0 - smoothstep(0.0, 1.0, length(in.corner));
+    return vec4<f32>(look.color.rgb * look.color.a * falloff * in.alpha, 0.0);
+}
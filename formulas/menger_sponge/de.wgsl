@@ -0,0 +1,26 @@
+// Menger sponge by iterated folding, see https://iquilezles.org/articles/menger/
+fn plugin_de(p: vec3<f32>) -> vec2<f32> {
+    let scale = plugin_param(0u);
+    let offset = plugin_param(1u);
+
+    var z = p;
+    var dr = 1.0;
+    var trap = 1e20;
+    for (var i = 0u; i < shape.mandel_iters; i++) {
+        z = abs(z);
+        // sort the components so the fold is symmetric
+        if (z.x < z.y) { z = z.yxz; }
+        if (z.x < z.z) { z = z.zyx; }
+        if (z.y < z.z) { z = z.xzy; }
+
+        z = z * scale - offset * (scale - 1.0);
+        if (z.z < -0.5 * offset * (scale - 1.0)) {
+            z.z += offset * (scale - 1.0);
+        }
+        dr *= scale;
+        trap = min(trap, length(z));
+    }
+
+    let d = max(abs(z) - vec3<f32>(1.0), vec3<f32>(0.0));
+    return vec2<f32>(length(d) / dr, trap);
+}
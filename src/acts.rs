@@ -0,0 +1,383 @@
+use crate::crossfade::Crossfade;
+use crate::easing::Easing;
+#[cfg(feature = "ui")]
+use crate::easing::easing_combo;
+#[cfg(feature = "ui")]
+use crate::material::FractalMaterial;
+use crate::material::{MandelbulbMaterial, aim_all_views};
+use crate::midi::MidiNote;
+#[cfg(feature = "ui")]
+use crate::midi::note_name;
+use crate::params::{ParamChanged, apply_param_changes};
+use crate::playlist::Playlist;
+use crate::power::keep_awake;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// Keys an act can be bound to. F9 is taken by the test patterns.
+#[cfg(feature = "ui")]
+const HOTKEYS: [KeyCode; 8] = [
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+];
+
+/// Crossfade length of transitions created with "Connect All"
+#[cfg(feature = "ui")]
+const DEFAULT_FADE_SECS: f32 = 2.0;
+
+pub struct ActsPlugin;
+
+impl Plugin for ActsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Show>().add_systems(
+            Update,
+            (
+                // the main view takes over an act in the frame its crossfade goes away
+                run_show.before(apply_param_changes),
+                keep_awake.run_if(|show: Res<Show>| show.fade.is_some()),
+            ),
+        );
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, show_window);
+    }
+}
+
+/// A named state of a live set
+pub struct Act {
+    #[cfg(feature = "ui")]
+    pub name: String,
+    pub material: MandelbulbMaterial,
+    pub hotkey: Option<KeyCode>,
+    pub note: Option<u8>,
+}
+
+/// Permission to go from one act to another, with the crossfade used to get there
+pub struct Transition {
+    pub from: usize,
+    pub to: usize,
+    pub fade_secs: f32,
+    pub easing: Easing,
+}
+
+/// A live set structured as acts. An act can only be entered from the current one through a
+/// [`Transition`], so a stray key or note during the show cannot jump somewhere unplanned. The
+/// first act is entered with a cut from whatever is on screen.
+#[derive(Resource, Default)]
+pub struct Show {
+    #[cfg(feature = "ui")]
+    pub open: bool,
+    pub acts: Vec<Act>,
+    pub transitions: Vec<Transition>,
+    pub current: Option<usize>,
+    fade: Option<Fade>,
+    /// Act waiting for the next MIDI note to become its trigger
+    learning: Option<usize>,
+    /// Acts picked in the add-transition row
+    #[cfg(feature = "ui")]
+    new_transition: (usize, usize),
+}
+
+struct Fade {
+    /// `None` until the crossfade is spawned, which needs the window
+    crossfade: Option<Crossfade>,
+    to: usize,
+    elapsed: f32,
+    duration: f32,
+    easing: Easing,
+}
+
+impl Show {
+    /// Starts the transition from the current act to `to`, if one is allowed and no other
+    /// transition is running
+    pub fn trigger(&mut self, to: usize) {
+        if self.fade.is_some() || to >= self.acts.len() || self.current == Some(to) {
+            return;
+        }
+        let (duration, easing) = match self.current {
+            None => (0.0, Easing::Linear),
+            Some(from) => match self
+                .transitions
+                .iter()
+                .find(|t| t.from == from && t.to == to)
+            {
+                Some(transition) => (transition.fade_secs, transition.easing),
+                None => return,
+            },
+        };
+        self.fade = Some(Fade {
+            crossfade: None,
+            to,
+            elapsed: 0.0,
+            duration,
+            easing,
+        });
+    }
+
+    #[cfg(feature = "ui")]
+    fn is_allowed(&self, from: usize, to: usize) -> bool {
+        self.transitions
+            .iter()
+            .any(|t| t.from == from && t.to == to)
+    }
+
+    /// Allows every transition between acts that is not allowed yet
+    #[cfg(feature = "ui")]
+    fn connect_all(&mut self) {
+        for from in 0..self.acts.len() {
+            for to in 0..self.acts.len() {
+                if from != to && !self.is_allowed(from, to) {
+                    self.transitions.push(Transition {
+                        from,
+                        to,
+                        fade_secs: DEFAULT_FADE_SECS,
+                        easing: Easing::Linear,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Removes an act and its transitions, keeping the indices of the others valid
+    #[cfg(feature = "ui")]
+    fn remove_act(&mut self, index: usize) {
+        self.acts.remove(index);
+        self.transitions
+            .retain(|t| t.from != index && t.to != index);
+        let shift = |i: usize| if i > index { i - 1 } else { i };
+        for transition in &mut self.transitions {
+            transition.from = shift(transition.from);
+            transition.to = shift(transition.to);
+        }
+        self.current = self.current.filter(|i| *i != index).map(shift);
+        self.learning = None;
+        self.new_transition = (0, 0);
+    }
+}
+
+/// Triggers acts from hotkeys and MIDI notes and runs the crossfade into the next act
+fn run_show(
+    mut commands: Commands,
+    time: Res<Time>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut notes: MessageReader<MidiNote>,
+    mut show: ResMut<Show>,
+    mut playlist: ResMut<Playlist>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut sprites: Query<&mut Sprite>,
+    mut param_changes: MessageWriter<ParamChanged>,
+    window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let show = &mut *show;
+    for note in notes.read() {
+        if let Some(index) = show.learning.take() {
+            show.acts[index].note = Some(note.note);
+            continue;
+        }
+        if let Some(index) = show.acts.iter().position(|act| act.note == Some(note.note)) {
+            show.trigger(index);
+        }
+    }
+    for key in keys.get_just_pressed() {
+        if let Some(index) = show.acts.iter().position(|act| act.hotkey == Some(*key)) {
+            show.trigger(index);
+        }
+    }
+
+    let Some(fade) = &mut show.fade else {
+        return;
+    };
+    let act = &show.acts[fade.to];
+    // the playlist would fight over the main view
+    playlist.stop();
+
+    if fade.elapsed < fade.duration {
+        let Ok(win) = window.single() else {
+            return;
+        };
+        let crossfade = fade.crossfade.get_or_insert_with(|| {
+            Crossfade::spawn(
+                &mut commands,
+                &mut materials,
+                &mut meshes,
+                &mut images,
+                win,
+                act.material.clone(),
+            )
+        });
+        crossfade.set_alpha(
+            &mut sprites,
+            fade.easing.apply(fade.elapsed / fade.duration),
+        );
+        fade.elapsed += time.delta_secs();
+        return;
+    }
+
+    // fade finished, the main quad takes over the act
+    aim_all_views(&mut materials, &act.material.camera);
+    param_changes.write_batch(ParamChanged::all(&act.material));
+    show.current = Some(fade.to);
+    if let Some(crossfade) = show.fade.take().and_then(|fade| fade.crossfade) {
+        crossfade.despawn(&mut commands);
+    }
+}
+
+#[cfg(feature = "ui")]
+fn show_window(
+    mut contexts: EguiContexts,
+    mut show: ResMut<Show>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    if !show.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let show = &mut *show;
+
+    let mut open = show.open;
+    let mut trigger = None;
+    let mut remove_act = None;
+    let mut remove_transition = None;
+    egui::Window::new("Show")
+        .open(&mut open)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            ui.heading("Acts");
+            let fading = show.fade.as_ref().map(|fade| fade.to);
+            for (index, act) in show.acts.iter_mut().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        let current = show.current == Some(index) || fading == Some(index);
+                        if ui.selectable_label(current, "Go").clicked() {
+                            trigger = Some(index);
+                        }
+                        ui.add(egui::TextEdit::singleline(&mut act.name).desired_width(110.0));
+
+                        egui::ComboBox::from_id_salt("hotkey")
+                            .width(60.0)
+                            .selected_text(act.hotkey.map_or("No key".into(), |k| format!("{k:?}")))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut act.hotkey, None, "No key");
+                                for key in HOTKEYS {
+                                    ui.selectable_value(
+                                        &mut act.hotkey,
+                                        Some(key),
+                                        format!("{key:?}"),
+                                    );
+                                }
+                            });
+
+                        let note = match (show.learning == Some(index), act.note) {
+                            (true, _) => "Play a note...".to_string(),
+                            (false, Some(note)) => note_name(note),
+                            (false, None) => "No note".to_string(),
+                        };
+                        if ui
+                            .button(note)
+                            .on_hover_text("Click, then play a note on the MIDI controller")
+                            .clicked()
+                        {
+                            show.learning = Some(index);
+                        }
+                        // indices shift on removal, so not while a transition is running
+                        if ui
+                            .add_enabled(fading.is_none(), egui::Button::new("x").small())
+                            .clicked()
+                        {
+                            remove_act = Some(index);
+                        }
+                    });
+                });
+            }
+            if ui.button("Add Current View").clicked()
+                && let Some(mat) = materials.get(&fractal.0)
+            {
+                show.acts.push(Act {
+                    name: format!("Act {}", show.acts.len() + 1),
+                    material: mat.clone(),
+                    hotkey: HOTKEYS.get(show.acts.len()).copied(),
+                    note: None,
+                });
+            }
+
+            ui.separator();
+            ui.heading("Transitions");
+            let name = |i: usize| show.acts.get(i).map_or("?", |act| act.name.as_str());
+            for (index, transition) in show.transitions.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!(
+                        "{} -> {}",
+                        name(transition.from),
+                        name(transition.to)
+                    ));
+                    ui.add(
+                        egui::DragValue::new(&mut transition.fade_secs)
+                            .range(0.0..=30.0)
+                            .suffix("s fade"),
+                    );
+                    easing_combo(ui, ("easing", index), &mut transition.easing);
+                    if ui.small_button("x").clicked() {
+                        remove_transition = Some(index);
+                    }
+                });
+            }
+
+            if show.acts.len() > 1 {
+                let (mut from, mut to) = show.new_transition;
+                ui.horizontal(|ui| {
+                    for (salt, index) in [("from", &mut from), ("to", &mut to)] {
+                        egui::ComboBox::from_id_salt(salt)
+                            .selected_text(name(*index))
+                            .show_ui(ui, |ui| {
+                                for i in 0..show.acts.len() {
+                                    ui.selectable_value(index, i, name(i));
+                                }
+                            });
+                    }
+                    if ui
+                        .add_enabled(
+                            from != to
+                                && !show
+                                    .transitions
+                                    .iter()
+                                    .any(|t| t.from == from && t.to == to),
+                            egui::Button::new("Add"),
+                        )
+                        .clicked()
+                    {
+                        show.transitions.push(Transition {
+                            from,
+                            to,
+                            fade_secs: DEFAULT_FADE_SECS,
+                            easing: Easing::Linear,
+                        });
+                    }
+                });
+                show.new_transition = (from, to);
+                if ui.button("Connect All").clicked() {
+                    show.connect_all();
+                }
+            }
+        });
+
+    if let Some(index) = trigger {
+        show.trigger(index);
+    }
+    if let Some(index) = remove_transition {
+        show.transitions.remove(index);
+    }
+    if let Some(index) = remove_act {
+        show.remove_act(index);
+    }
+    show.open = open;
+}
@@ -0,0 +1,256 @@
+use crate::compose::ComposeSettings;
+use crate::estimator::CpuEstimator;
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use serde::{Deserialize, Serialize};
+
+/// Where the markers are kept between sessions, relative to the working directory like the
+/// screenshots folder
+const ANNOTATIONS_FILE: &str = "annotations.ron";
+
+/// Named markers pinned to points on the fractal surface, with notes for planning shots and
+/// documenting finds. Markers are placed by clicking the surface and drawn as labels over the
+/// view.
+pub struct AnnotationsPlugin;
+
+impl Plugin for AnnotationsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Annotations::load())
+            .add_systems(Update, place_annotation)
+            .add_systems(
+                EguiPrimaryContextPass,
+                (annotations_window, draw_annotation_labels),
+            );
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Annotation {
+    name: String,
+    note: String,
+    /// World space point on the surface
+    position: [f32; 3],
+}
+
+#[derive(Resource, Default)]
+pub struct Annotations {
+    pub open: bool,
+    pub show_labels: bool,
+    /// The next click on the view places a marker
+    placing: bool,
+    markers: Vec<Annotation>,
+    selected: Option<usize>,
+}
+
+impl Annotations {
+    fn load() -> Self {
+        let markers = match std::fs::read_to_string(ANNOTATIONS_FILE) {
+            Ok(text) => ron::from_str(&text).unwrap_or_else(|err| {
+                warn!("Ignoring {ANNOTATIONS_FILE}: {err}");
+                Vec::new()
+            }),
+            Err(_) => Vec::new(),
+        };
+        Self {
+            show_labels: true,
+            markers,
+            ..default()
+        }
+    }
+
+    fn save(&self) {
+        let result = ron::ser::to_string_pretty(&self.markers, default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| std::fs::write(ANNOTATIONS_FILE, text).map_err(|e| e.to_string()));
+        if let Err(err) = result {
+            error!("Failed to save {ANNOTATIONS_FILE}: {err}");
+        }
+    }
+}
+
+/// Casts a ray through the cursor on click while placing, and pins a marker where it hits
+fn place_annotation(
+    mut annotations: ResMut<Annotations>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    compose: Res<ComposeSettings>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    mut contexts: EguiContexts,
+) {
+    if !annotations.placing || !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    if ctx.is_pointer_over_area() || ctx.wants_pointer_input() {
+        return;
+    }
+    let (Ok(window), Some(mat)) = (window.single(), materials.get(&fractal.0)) else {
+        return;
+    };
+    let Some(estimator) = CpuEstimator::new(mat) else {
+        annotations.placing = false;
+        return;
+    };
+    let Some(uv) = compose.cursor_uv(window) else {
+        return;
+    };
+    let (origin, dir) = mat.camera.ray(uv);
+    let Some(t) = estimator.trace(&mat.shape, origin, dir) else {
+        // missed, stay in placing mode for another try
+        return;
+    };
+
+    let number = annotations.markers.len() + 1;
+    annotations.markers.push(Annotation {
+        name: format!("Marker {number}"),
+        note: String::new(),
+        position: (origin + dir * t).to_array(),
+    });
+    annotations.selected = Some(annotations.markers.len() - 1);
+    annotations.placing = false;
+    annotations.save();
+}
+
+fn annotations_window(
+    mut contexts: EguiContexts,
+    mut annotations: ResMut<Annotations>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    if !annotations.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let supported = materials
+        .get(&fractal.0)
+        .and_then(CpuEstimator::new)
+        .is_some();
+    let annotations = &mut *annotations;
+
+    let mut open = annotations.open;
+    let mut changed = false;
+    egui::Window::new("Annotations")
+        .open(&mut open)
+        .default_width(300.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.add_enabled_ui(supported, |ui| {
+                    ui.toggle_value(&mut annotations.placing, "Place Marker")
+                        .on_hover_text("Click the surface to pin a marker")
+                        .on_disabled_hover_text(
+                            "Picking is not available for plugin formulas and the geometry graph",
+                        );
+                });
+                ui.checkbox(&mut annotations.show_labels, "Show Labels");
+            });
+            if annotations.placing {
+                ui.small("Click a point on the fractal.");
+            }
+            ui.separator();
+
+            let mut remove = None;
+            for (i, marker) in annotations.markers.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    let selected = annotations.selected == Some(i);
+                    if ui.selectable_label(selected, &marker.name).clicked() {
+                        annotations.selected = (!selected).then_some(i);
+                    }
+                    if ui.small_button("x").on_hover_text("Delete").clicked() {
+                        remove = Some(i);
+                    }
+                });
+            }
+            if annotations.markers.is_empty() {
+                ui.label("No markers yet.");
+            }
+            if let Some(i) = remove {
+                annotations.markers.remove(i);
+                annotations.selected = None;
+                changed = true;
+            }
+
+            if let Some(marker) = annotations
+                .selected
+                .and_then(|i| annotations.markers.get_mut(i))
+            {
+                ui.separator();
+                let [x, y, z] = marker.position;
+                ui.label(format!("Position: {x:.4}, {y:.4}, {z:.4}"));
+                changed |= ui.text_edit_singleline(&mut marker.name).changed();
+                changed |= ui
+                    .add(
+                        egui::TextEdit::multiline(&mut marker.note)
+                            .hint_text("Notes")
+                            .desired_rows(4),
+                    )
+                    .changed();
+            }
+        });
+    annotations.open = open;
+    if changed {
+        annotations.save();
+    }
+}
+
+/// Draws each marker in front of the camera as a dot with its name, dimmed when the surface
+/// hides it
+fn draw_annotation_labels(
+    mut contexts: EguiContexts,
+    annotations: Res<Annotations>,
+    compose: Res<ComposeSettings>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    if !annotations.show_labels || annotations.markers.is_empty() {
+        return;
+    }
+    let (Ok(win), Some(mat)) = (window.single(), materials.get(&fractal.0)) else {
+        return;
+    };
+    let ctx = contexts.ctx_mut().unwrap();
+    let estimator = CpuEstimator::new(mat);
+    let painter = ctx.layer_painter(egui::LayerId::background());
+
+    let origin = mat.camera.ray_origin();
+    for (i, marker) in annotations.markers.iter().enumerate() {
+        let position = Vec3::from_array(marker.position);
+        let Some(uv) = mat.camera.project(position) else {
+            continue;
+        };
+        if uv.cmplt(Vec2::ZERO).any() || uv.cmpgt(Vec2::ONE).any() {
+            continue;
+        }
+        // hidden when the surface is hit well before the marker
+        let to_marker = position - origin;
+        let hidden = estimator.is_some_and(|estimator| {
+            estimator
+                .trace(&mat.shape, origin, to_marker.normalize())
+                .is_some_and(|t| t < to_marker.length() * 0.98)
+        });
+
+        let pos = compose.uv_to_window(win, uv);
+        let pos = egui::pos2(pos.x, pos.y);
+        let color = match (hidden, annotations.selected == Some(i)) {
+            (true, _) => egui::Color32::from_white_alpha(90),
+            (false, true) => egui::Color32::YELLOW,
+            (false, false) => egui::Color32::WHITE,
+        };
+        painter.circle(
+            pos,
+            4.0,
+            color,
+            egui::Stroke::new(1.0, egui::Color32::BLACK),
+        );
+        painter.text(
+            pos + egui::vec2(7.0, -7.0),
+            egui::Align2::LEFT_BOTTOM,
+            &marker.name,
+            egui::FontId::proportional(13.0),
+            color,
+        );
+    }
+}
@@ -0,0 +1,183 @@
+//! Typed commands for driving the viewer from code. Host applications embedding the plugins and
+//! integration tests write these messages instead of reaching into the material, the parameter
+//! store or the export plumbing, which are free to change. The `--power`, `--preset` and
+//! `--record` command line flags are sent as these commands too, so scripted renders go through
+//! the same path, and so do the steps of a `--replay` script (see [`crate::replay`]).
+
+use crate::args;
+use crate::export::{ExportFrame, ExportSettings, FrameSaved, FrameSequence, timestamped_folder};
+use crate::material::{MandelbulbMaterial, rotate_all_views};
+use crate::navigation::View;
+use crate::params::{Param, ParamChanged};
+use bevy::prelude::*;
+use std::path::PathBuf;
+
+pub struct ApiPlugin;
+
+impl Plugin for ApiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recorder>()
+            .add_message::<SetPower>()
+            .add_message::<SetParam>()
+            .add_message::<Rotate>()
+            .add_message::<LoadPreset>()
+            .add_message::<StartRecording>()
+            .add_systems(PostStartup, send_argument_commands)
+            .add_systems(
+                Update,
+                (set_power, set_param, rotate, load_preset, record).chain(),
+            );
+    }
+}
+
+/// Sets the fractal's power, like the Power slider
+#[derive(Message, Clone, Copy, Debug)]
+pub struct SetPower(pub f32);
+
+/// Sets any parameter, like its widget in the settings panel
+#[derive(Message, Clone, Copy, Debug)]
+pub struct SetParam {
+    pub param: Param,
+    pub value: f32,
+}
+
+/// Turns the camera about the origin by yaw and pitch in radians, like dragging with the mouse
+#[derive(Message, Clone, Copy, Debug)]
+pub struct Rotate {
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+/// Opens a saved view: a file holding a share code, like the `.view` files saved next to stills
+#[derive(Message, Clone, Debug)]
+pub struct LoadPreset(pub PathBuf);
+
+/// Saves the main view every frame as a numbered image sequence, in the still export's format
+#[derive(Message, Clone, Debug)]
+pub struct StartRecording(pub RecordingOptions);
+
+#[derive(Clone, Debug)]
+pub struct RecordingOptions {
+    /// Folder the frames go to, a new timestamped one in the export directory when `None`
+    pub directory: Option<PathBuf>,
+    pub frames: u32,
+}
+
+/// The recording in progress, if any
+#[derive(Resource, Default)]
+struct Recorder(Option<FrameSequence>);
+
+/// Commands given on the command line, sent once the scene exists
+fn send_argument_commands(
+    mut power: MessageWriter<SetPower>,
+    mut presets: MessageWriter<LoadPreset>,
+    mut recordings: MessageWriter<StartRecording>,
+) {
+    let value = |flag: &str| {
+        args::value(flag)?
+            .inspect_err(|err| error!("Ignoring {flag}: {err}"))
+            .ok()
+    };
+    if let Some(path) = value("--preset") {
+        presets.write(LoadPreset(PathBuf::from(path)));
+    }
+    if let Some(text) = value("--power") {
+        match text.parse() {
+            Ok(value) => {
+                power.write(SetPower(value));
+            }
+            Err(_) => error!("Ignoring --power: '{text}' is not a number"),
+        }
+    }
+    if let Some(text) = value("--record") {
+        match text.parse() {
+            Ok(frames) => {
+                recordings.write(StartRecording(RecordingOptions {
+                    directory: None,
+                    frames,
+                }));
+            }
+            Err(_) => error!("Ignoring --record: '{text}' is not a number of frames"),
+        }
+    }
+}
+
+fn set_power(
+    mut commands: MessageReader<SetPower>,
+    mut param_changes: MessageWriter<ParamChanged>,
+) {
+    for SetPower(value) in commands.read() {
+        param_changes.write(ParamChanged {
+            param: Param::Power,
+            value: *value,
+        });
+    }
+}
+
+fn set_param(
+    mut commands: MessageReader<SetParam>,
+    mut param_changes: MessageWriter<ParamChanged>,
+) {
+    for SetParam { param, value } in commands.read() {
+        param_changes.write(ParamChanged {
+            param: *param,
+            value: *value,
+        });
+    }
+}
+
+fn rotate(mut commands: MessageReader<Rotate>, mut materials: ResMut<Assets<MandelbulbMaterial>>) {
+    for Rotate { yaw, pitch } in commands.read() {
+        rotate_all_views(
+            &mut materials,
+            Quat::from_rotation_y(*yaw) * Quat::from_rotation_x(*pitch),
+        );
+    }
+}
+
+fn load_preset(
+    mut commands: MessageReader<LoadPreset>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    mut param_changes: MessageWriter<ParamChanged>,
+) {
+    for LoadPreset(path) in commands.read() {
+        match std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|code| View::parse(code.trim()))
+        {
+            Ok(view) => view.open(&mut materials, &mut param_changes),
+            Err(err) => error!("Failed to load {}: {err}", path.display()),
+        }
+    }
+}
+
+/// Saves the recording's frames one at a time, see [`FrameSequence`]
+fn record(
+    mut commands: MessageReader<StartRecording>,
+    mut recorder: ResMut<Recorder>,
+    settings: Res<ExportSettings>,
+    mut frames: MessageWriter<ExportFrame>,
+    mut saved: MessageReader<FrameSaved>,
+) {
+    if let Some(StartRecording(options)) = commands.read().last() {
+        let directory = options
+            .directory
+            .clone()
+            .unwrap_or_else(|| timestamped_folder(&settings.directory, "recording"));
+        recorder.0 = Some(FrameSequence::start(directory, options.frames));
+    }
+    let saved: Vec<_> = saved.read().collect();
+    let Some(sequence) = &mut recorder.0 else {
+        return;
+    };
+    if let Some(frame) = sequence.poll(saved) {
+        frames.write(sequence.request(frame));
+    } else if sequence.is_complete() {
+        info!(
+            "Recorded {} frames to {}",
+            sequence.frames,
+            sequence.directory.display()
+        );
+        recorder.0 = None;
+    }
+}
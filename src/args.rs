@@ -0,0 +1,134 @@
+//! The command line. Every flag takes its value either as the next argument or after `=`, so
+//! `--seed 7` and `--seed=7` mean the same, and anything not listed here is warned about at
+//! startup instead of silently doing nothing.
+
+use bevy::prelude::*;
+
+/// Flags followed by a value
+const VALUE_FLAGS: [&str; 8] = [
+    "--view",
+    "--preset",
+    "--power",
+    "--record",
+    "--seed",
+    "--replay",
+    "--wallpaper-interval",
+    "--wallpaper-source",
+];
+
+/// Flags on their own
+const SWITCHES: [&str; 1] = ["--wallpaper"];
+
+pub struct ArgsPlugin;
+
+impl Plugin for ArgsPlugin {
+    fn build(&self, _app: &mut App) {
+        for arg in unknown(&arguments()) {
+            warn!("Ignoring unknown argument '{arg}'");
+        }
+    }
+}
+
+fn arguments() -> Vec<String> {
+    std::env::args().skip(1).collect()
+}
+
+/// The value given for `flag`, `None` when the flag is absent and an error when it has no value
+pub fn value(flag: &str) -> Option<Result<String, String>> {
+    value_in(&arguments(), flag)
+}
+
+/// Whether the switch `flag` was given
+pub fn is_set(flag: &str) -> bool {
+    arguments().iter().any(|arg| arg == flag)
+}
+
+fn value_in(args: &[String], flag: &str) -> Option<Result<String, String>> {
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            // the next flag is not a value, `--record --view x` forgot the frame count
+            return Some(
+                args.next_if(|value| !is_flag(value))
+                    .cloned()
+                    .ok_or_else(|| format!("missing value for {flag}")),
+            );
+        }
+        if let Some(value) = arg
+            .strip_prefix(flag)
+            .and_then(|rest| rest.strip_prefix('='))
+        {
+            return Some(Ok(value.to_string()));
+        }
+    }
+    None
+}
+
+fn is_flag(arg: &str) -> bool {
+    arg.starts_with("--")
+}
+
+/// Arguments that are neither a known flag nor the value following one
+fn unknown(args: &[String]) -> Vec<&String> {
+    let mut unknown = Vec::new();
+    let mut args = args.iter().peekable();
+    while let Some(arg) = args.next() {
+        let name = arg.split('=').next().unwrap_or_default();
+        if VALUE_FLAGS.contains(&name) {
+            if name == arg {
+                args.next_if(|value| !is_flag(value));
+            }
+        } else if !SWITCHES.contains(&arg.as_str()) {
+            unknown.push(arg);
+        }
+    }
+    unknown
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn values_follow_the_flag_or_an_equals_sign() {
+        let given = args("--seed 7 --power=8 --record");
+        assert_eq!(value_in(&given, "--seed"), Some(Ok("7".to_string())));
+        assert_eq!(value_in(&given, "--power"), Some(Ok("8".to_string())));
+        assert!(matches!(value_in(&given, "--record"), Some(Err(_))));
+        assert_eq!(value_in(&given, "--view"), None);
+    }
+
+    #[test]
+    fn a_flag_is_not_taken_for_a_value() {
+        let given = args("--record --still out.png");
+        assert_eq!(
+            value_in(&given, "--record"),
+            Some(Err("missing value for --record".to_string()))
+        );
+        assert_eq!(
+            unknown(&given),
+            [&"--still".to_string(), &"out.png".to_string()]
+        );
+        // negative numbers still are
+        assert_eq!(
+            value_in(&args("--power -2"), "--power"),
+            Some(Ok("-2".to_string()))
+        );
+    }
+
+    #[test]
+    fn a_longer_flag_is_not_taken_for_a_prefix() {
+        let given = args("--wallpaper-interval=5");
+        assert_eq!(value_in(&given, "--wallpaper"), None);
+    }
+
+    #[test]
+    fn unknown_flags_are_reported_but_values_are_not() {
+        let given = args("--seed 7 --wallpaper --sed 7 --view=abc");
+        assert_eq!(unknown(&given), [&"--sed".to_string(), &"7".to_string()]);
+    }
+}
@@ -0,0 +1,132 @@
+//! Idle attract: after some minutes without input the view starts moving on its own, a slow turn
+//! and a drift through the palette, and can hide the settings panel, for leaving the viewer
+//! running on a second monitor. Any key, click, mouse movement or touch hands control back at
+//! once, leaving the view where the drift took it.
+
+use crate::material::{MandelbulbMaterial, rotate_all_views};
+use crate::params::{Param, ParamChanged, ParamStore};
+use crate::power::keep_awake;
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::mouse::{MouseButtonInput, MouseMotion, MouseWheel};
+use bevy::input::touch::TouchInput;
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::egui;
+
+/// Radians per second the camera turns while attracting
+const ATTRACT_ROTATION_SPEED: f32 = 0.05;
+
+/// Color offset per second, a full pass through the palette takes a bit over three minutes
+const PALETTE_DRIFT_SPEED: f32 = 0.005;
+
+pub struct AttractPlugin;
+
+impl Plugin for AttractPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Attract>().add_systems(
+            Update,
+            (
+                (watch_input, drift).chain(),
+                keep_awake.run_if(|attract: Res<Attract>| attract.is_active()),
+            ),
+        );
+    }
+}
+
+#[derive(Resource)]
+pub struct Attract {
+    pub enabled: bool,
+    /// Minutes without input before the view starts moving
+    pub idle_minutes: f32,
+    #[cfg(feature = "ui")]
+    pub hide_ui: bool,
+    /// Seconds since the last input
+    idle_secs: f32,
+}
+
+impl Default for Attract {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_minutes: 5.0,
+            #[cfg(feature = "ui")]
+            hide_ui: false,
+            idle_secs: 0.0,
+        }
+    }
+}
+
+impl Attract {
+    /// Whether the view is currently moving on its own
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.idle_secs >= self.idle_minutes * 60.0
+    }
+
+    /// Whether the settings panel should stay out of the way
+    #[cfg(feature = "ui")]
+    pub fn hides_ui(&self) -> bool {
+        self.hide_ui && self.is_active()
+    }
+
+    /// Choices for the settings window
+    #[cfg(feature = "ui")]
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.checkbox(&mut self.enabled, "Move When Idle")
+            .on_hover_text(
+                "Slowly turns the view and drifts the palette after a while without input",
+            );
+        ui.add_enabled_ui(self.enabled, |ui| {
+            ui.add(
+                egui::Slider::new(&mut self.idle_minutes, 0.5..=60.0)
+                    .logarithmic(true)
+                    .text("Idle Time")
+                    .suffix(" min"),
+            );
+            ui.checkbox(&mut self.hide_ui, "Hide Settings While Idle");
+        });
+    }
+}
+
+/// Counts the time since the last input, any input resets it
+fn watch_input(
+    time: Res<Time>,
+    mut attract: ResMut<Attract>,
+    mut keys: MessageReader<KeyboardInput>,
+    mut buttons: MessageReader<MouseButtonInput>,
+    mut motion: MessageReader<MouseMotion>,
+    mut wheel: MessageReader<MouseWheel>,
+    mut touches: MessageReader<TouchInput>,
+) {
+    let input = keys.read().count()
+        + buttons.read().count()
+        + motion.read().count()
+        + wheel.read().count()
+        + touches.read().count();
+    if input > 0 {
+        attract.idle_secs = 0.0;
+    } else {
+        attract.idle_secs += time.delta_secs();
+    }
+}
+
+/// Turns the camera and moves the palette while attracting
+fn drift(
+    time: Res<Time>,
+    attract: Res<Attract>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    store: Res<ParamStore>,
+    mut param_changes: MessageWriter<ParamChanged>,
+) {
+    if !attract.is_active() {
+        return;
+    }
+    let dt = time.delta_secs();
+    rotate_all_views(
+        &mut materials,
+        Quat::from_rotation_y(ATTRACT_ROTATION_SPEED * dt),
+    );
+    param_changes.write(ParamChanged {
+        param: Param::ColorOffset,
+        value: (store.get(Param::ColorOffset) + PALETTE_DRIFT_SPEED * dt).fract(),
+    });
+}
@@ -0,0 +1,99 @@
+use crate::expr::{Expression, Vars};
+use crate::params::{EPSILON, Param, ParamChanged, ParamStore};
+use crate::power::keep_awake;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ParamBindings>().add_systems(
+            Update,
+            (
+                apply_bindings,
+                keep_awake.run_if(|bindings: Res<ParamBindings>| bindings.is_animating()),
+            ),
+        );
+    }
+}
+
+/// Expressions typed into parameter fields. A live binding is re-evaluated every frame, so e.g.
+/// `1/zoom` follows the zoom, while a one-off expression sets the value once. In both `t` is the
+/// time since startup and `key` the parameter's value before the expression is applied: its value
+/// when it was bound for a live binding, whose own output would otherwise feed back into it.
+#[derive(Resource, Default)]
+pub struct ParamBindings {
+    live: HashMap<Param, Binding>,
+    /// Evaluated on the next update, then dropped
+    once: Vec<(Param, Expression)>,
+}
+
+struct Binding {
+    expression: Expression,
+    /// Value of the parameter before it was bound
+    key: f32,
+}
+
+impl ParamBindings {
+    #[cfg(feature = "ui")]
+    pub fn get(&self, param: Param) -> Option<&Expression> {
+        self.live.get(&param).map(|binding| &binding.expression)
+    }
+
+    /// Binds `param` to `expression`, `current` being its value now. Rebinding keeps the value
+    /// from before the first binding, `current` is the old binding's output then.
+    #[cfg(feature = "ui")]
+    pub fn bind(&mut self, param: Param, expression: Expression, current: f32) {
+        let key = self.live.get(&param).map_or(current, |binding| binding.key);
+        self.live.insert(param, Binding { expression, key });
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn unbind(&mut self, param: Param) {
+        self.live.remove(&param);
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn set_once(&mut self, param: Param, expression: Expression) {
+        self.once.push((param, expression));
+    }
+
+    /// Whether a binding changes with time and needs every frame rendered
+    pub fn is_animating(&self) -> bool {
+        self.live
+            .values()
+            .any(|binding| binding.expression.uses_time())
+    }
+}
+
+fn apply_bindings(
+    time: Res<Time>,
+    mut bindings: ResMut<ParamBindings>,
+    store: Res<ParamStore>,
+    mut changes: MessageWriter<ParamChanged>,
+) {
+    if bindings.live.is_empty() && bindings.once.is_empty() {
+        return;
+    }
+    let t = time.elapsed_secs();
+    let once = std::mem::take(&mut bindings.once);
+    let live = bindings
+        .live
+        .iter()
+        .map(|(param, binding)| (*param, &binding.expression, binding.key));
+    let once = once
+        .iter()
+        .map(|(param, expression)| (*param, expression, store.get(*param)));
+
+    for (param, expression, key) in live.chain(once) {
+        let value = expression.eval(Vars {
+            t,
+            key,
+            store: &store,
+        });
+        if value.is_finite() && (value - store.get(param)).abs() > EPSILON {
+            changes.write(ParamChanged { param, value });
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use crate::fractal_compute::{
+    EstimatorKey, FractalBindGroups, FractalComputeLayouts, FractalUniforms,
+};
+use crate::material::{FractalMaterial, MandelbulbMaterial, ShapeUniform};
+use bevy::asset::{RenderAssetUsages, uuid_handle};
+use bevy::prelude::*;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::graph::CameraDriverLabel;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_graph::{self, RenderGraph, RenderLabel};
+use bevy::render::render_resource::binding_types::texture_storage_3d;
+use bevy::render::render_resource::{
+    BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, CachedComputePipelineId,
+    ComputePassDescriptor, ComputePipelineDescriptor, Extent3d, PipelineCache, ShaderStages,
+    SpecializedComputePipeline, SpecializedComputePipelines, StorageTextureAccess,
+    TextureDimension, TextureFormat, TextureUsages,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::texture::GpuImage;
+use bevy::render::{Render, RenderApp, RenderStartup, RenderSystems};
+
+/// The baked 64³ grid of distance bounds spanning `BRICK_BOUNDS` around the origin, see
+/// `shaders/mandelbulb.wgsl`. Written by the bake pass as a storage texture and sampled by the
+/// march as a plain 3D texture. Outside the grid, and until the first bake, the bound is zero and
+/// the march estimates the distance as usual.
+pub const BRICK_MAP_IMAGE: Handle<Image> = uuid_handle!("e7c3a9d2-1b5f-4a86-8d4e-3f0b6c2a9e17");
+
+/// Must match `BRICK_RESOLUTION`
+const RESOLUTION: u32 = 64;
+
+/// Must match `@workgroup_size` of `bake_brick_map`
+const WORKGROUP_SIZE: u32 = 4;
+
+/// Empty space skipping. A compute pass bakes a coarse grid around the origin with a lower bound
+/// of the distance to the surface in each cell, whenever the shape changes. The march steps
+/// through cells that are far from the surface with a texture read instead of a full distance
+/// estimate, which pays off most for formulas with a lot of empty space like the Mandelbox.
+pub struct BrickMapPlugin;
+
+impl Plugin for BrickMapPlugin {
+    fn build(&self, app: &mut App) {
+        let size = Extent3d {
+            width: RESOLUTION,
+            height: RESOLUTION,
+            depth_or_array_layers: RESOLUTION,
+        };
+        // zero bounds skip nothing until the first bake
+        let mut image = Image::new_fill(
+            size,
+            TextureDimension::D3,
+            &[0; 4],
+            TextureFormat::R32Float,
+            RenderAssetUsages::RENDER_WORLD,
+        );
+        image.texture_descriptor.usage |= TextureUsages::STORAGE_BINDING;
+        let _ = app
+            .world_mut()
+            .resource_mut::<Assets<Image>>()
+            .insert(&BRICK_MAP_IMAGE, image);
+
+        app.init_resource::<BrickBake>()
+            .add_plugins(ExtractResourcePlugin::<BrickBake>::default())
+            .add_systems(PostUpdate, sync_brick_bake);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<SpecializedComputePipelines<BrickPipeline>>()
+            .init_resource::<BakedBrickMap>()
+            .add_systems(RenderStartup, init_brick_pipeline)
+            .add_systems(
+                Render,
+                prepare_brick_bake.in_set(RenderSystems::PrepareBindGroups),
+            );
+        let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+        graph.add_node(BrickMapLabel, BrickMapNode);
+        graph.add_node_edge(BrickMapLabel, CameraDriverLabel);
+    }
+}
+
+/// What the baked grid depends on. A new bake runs whenever this differs from the last one.
+#[derive(Resource, Clone, Copy, PartialEq, Default, ExtractResource)]
+struct BrickBake {
+    enabled: bool,
+    key: EstimatorKey,
+    shape: ShapeUniform,
+    /// Bumped when a shader changes, since plugin and graph estimators are shaders
+    shader_generation: u32,
+}
+
+fn sync_brick_bake(
+    mut bake: ResMut<BrickBake>,
+    mut shader_events: MessageReader<AssetEvent<Shader>>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let shaders_changed = shader_events
+        .read()
+        .any(|event| matches!(event, AssetEvent::Modified { .. }));
+    let wanted = BrickBake {
+        enabled: mat.brick_map,
+        key: EstimatorKey {
+            formula: mat.formula,
+            geometry_graph: mat.geometry_graph,
+        },
+        shape: mat.shape,
+        shader_generation: bake.shader_generation.wrapping_add(shaders_changed as u32),
+    };
+    if *bake != wanted {
+        *bake = wanted;
+    }
+}
+
+#[derive(Resource)]
+struct BrickPipeline {
+    fractal: FractalComputeLayouts,
+    layout: BindGroupLayout,
+}
+
+impl SpecializedComputePipeline for BrickPipeline {
+    type Key = EstimatorKey;
+
+    fn specialize(&self, key: Self::Key) -> ComputePipelineDescriptor {
+        self.fractal
+            .descriptor(key, "BRICK_BAKE", "bake_brick_map", &self.layout)
+    }
+}
+
+fn init_brick_pipeline(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    fractal: Res<FractalComputeLayouts>,
+) {
+    let layout = render_device.create_bind_group_layout(
+        "brick_map_layout",
+        &BindGroupLayoutEntries::single(
+            ShaderStages::COMPUTE,
+            texture_storage_3d(TextureFormat::R32Float, StorageTextureAccess::WriteOnly),
+        ),
+    );
+    commands.insert_resource(BrickPipeline {
+        fractal: fractal.clone(),
+        layout,
+    });
+}
+
+/// The state the grid was last baked for
+#[derive(Resource, Default)]
+struct BakedBrickMap(Option<BrickBake>);
+
+/// A bake to run this frame, absent while the grid is up to date
+#[derive(Resource)]
+struct BrickDispatch {
+    pipeline: CachedComputePipelineId,
+    bind_group: BindGroup,
+}
+
+fn prepare_brick_bake(
+    mut commands: Commands,
+    bake: Res<BrickBake>,
+    mut baked: ResMut<BakedBrickMap>,
+    fractal: Res<FractalUniforms>,
+    pipeline: Res<BrickPipeline>,
+    mut pipelines: ResMut<SpecializedComputePipelines<BrickPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    images: Res<RenderAssets<GpuImage>>,
+    render_device: Res<RenderDevice>,
+) {
+    commands.remove_resource::<BrickDispatch>();
+    if !bake.enabled || baked.0 == Some(*bake) {
+        return;
+    }
+    let Some(image) = images.get(&BRICK_MAP_IMAGE) else {
+        return;
+    };
+    let id = pipelines.specialize(&pipeline_cache, &pipeline, fractal.key);
+    // still compiling, bake once it is ready
+    if pipeline_cache.get_compute_pipeline(id).is_none() {
+        return;
+    }
+    let bind_group = render_device.create_bind_group(
+        "brick_map_bind_group",
+        &pipeline.layout,
+        &BindGroupEntries::single(&image.texture_view),
+    );
+    commands.insert_resource(BrickDispatch {
+        pipeline: id,
+        bind_group,
+    });
+    baked.0 = Some(*bake);
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct BrickMapLabel;
+
+/// Bakes the grid before any camera marches through it
+struct BrickMapNode;
+
+impl render_graph::Node for BrickMapNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let (Some(dispatch), Some(fractal)) = (
+            world.get_resource::<BrickDispatch>(),
+            world.get_resource::<FractalBindGroups>(),
+        ) else {
+            return Ok(());
+        };
+        let Some(pipeline) = world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(dispatch.pipeline)
+        else {
+            return Ok(());
+        };
+
+        let mut pass =
+            render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("brick_map"),
+                    ..default()
+                });
+        pass.set_pipeline(pipeline);
+        fractal.set(&mut pass);
+        pass.set_bind_group(3, &dispatch.bind_group, &[]);
+        let groups = RESOLUTION.div_ceil(WORKGROUP_SIZE);
+        pass.dispatch_workgroups(groups, groups, groups);
+        Ok(())
+    }
+}
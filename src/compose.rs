@@ -0,0 +1,208 @@
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy::window::PrimaryWindow;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+pub struct ComposePlugin;
+
+impl Plugin for ComposePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ComposeSettings>()
+            // the letterbox bars are whatever is behind the quad
+            .insert_resource(ClearColor(Color::BLACK));
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, draw_guides);
+    }
+}
+
+/// Aspect ratios offered in the UI, as (label, width / height)
+#[cfg(feature = "ui")]
+pub const ASPECT_PRESETS: [(&str, f32); 7] = [
+    ("16:9", 16.0 / 9.0),
+    ("4:3", 4.0 / 3.0),
+    ("1:1", 1.0),
+    ("4:5", 4.0 / 5.0),
+    ("9:16", 9.0 / 16.0),
+    ("2.39:1", 2.39),
+    ("21:9", 21.0 / 9.0),
+];
+
+/// Golden ratio, used for the phi grid guide
+const PHI: f32 = 1.618_034;
+
+/// Fixed-aspect framing. When an aspect is locked the fractal is rendered into the largest
+/// centered rectangle of that shape and the rest of the window is left as letterbox bars, so
+/// the interactive view matches exports exactly.
+#[derive(Resource, Default)]
+pub struct ComposeSettings {
+    /// Width / height of the frame, `None` to fill the window
+    pub aspect: Option<f32>,
+    /// Draw action and title safe rectangles inside the frame
+    pub show_safe_areas: bool,
+    pub show_thirds: bool,
+    pub show_center: bool,
+    pub show_golden: bool,
+    /// Outline of a crop with this aspect inside the frame, e.g. a 9:16 story cut of a 16:9 shot
+    pub aspect_marker: Option<f32>,
+    /// Burn the guides into exported images too
+    pub guides_in_exports: bool,
+}
+
+impl ComposeSettings {
+    /// Size of the rendered frame inside a window of the given size
+    pub fn frame_size(&self, window: Vec2) -> Vec2 {
+        let Some(aspect) = self.aspect else {
+            return window;
+        };
+        if window.x / window.y > aspect {
+            Vec2::new(window.y * aspect, window.y) // pillarbox
+        } else {
+            Vec2::new(window.x, window.x / aspect) // letterbox
+        }
+    }
+
+    /// Position of the cursor on the frame as a [0, 1] uv, `None` outside the frame
+    #[cfg(feature = "ui")]
+    pub fn cursor_uv(&self, window: &Window) -> Option<Vec2> {
+        let cursor = window.cursor_position()?;
+        let uv = (cursor - self.frame_origin(window)) / self.frame_size(window.size());
+        (uv.cmpge(Vec2::ZERO).all() && uv.cmple(Vec2::ONE).all()).then_some(uv)
+    }
+
+    /// Window position of a [0, 1] uv on the frame, the inverse of [`Self::cursor_uv`]
+    #[cfg(feature = "ui")]
+    pub fn uv_to_window(&self, window: &Window, uv: Vec2) -> Vec2 {
+        self.frame_origin(window) + uv * self.frame_size(window.size())
+    }
+
+    /// Top left corner of the frame in the window
+    #[cfg(feature = "ui")]
+    fn frame_origin(&self, window: &Window) -> Vec2 {
+        (window.size() - self.frame_size(window.size())) / 2.0
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn aspect_label(&self) -> &'static str {
+        match self.aspect {
+            Some(_) => aspect_label(self.aspect),
+            None => "Free",
+        }
+    }
+
+    /// Line segments of every enabled guide, in frame coordinates from (0, 0) at the top left
+    /// to (1, 1) at the bottom right
+    pub fn guide_lines(&self, frame: Vec2) -> Vec<(Vec2, Vec2)> {
+        let mut lines = Vec::new();
+
+        let mut grid = |a: f32, b: f32| {
+            for t in [a, b] {
+                lines.push((Vec2::new(t, 0.0), Vec2::new(t, 1.0)));
+                lines.push((Vec2::new(0.0, t), Vec2::new(1.0, t)));
+            }
+        };
+        if self.show_thirds {
+            grid(1.0 / 3.0, 2.0 / 3.0);
+        }
+        if self.show_golden {
+            grid(1.0 - 1.0 / PHI, 1.0 / PHI);
+        }
+
+        if self.show_center {
+            let arm = 0.02;
+            lines.push((Vec2::new(0.5 - arm, 0.5), Vec2::new(0.5 + arm, 0.5)));
+            let arm = arm * frame.x / frame.y; // same length on screen
+            lines.push((Vec2::new(0.5, 0.5 - arm), Vec2::new(0.5, 0.5 + arm)));
+        }
+
+        if self.show_safe_areas {
+            // action safe and title safe
+            for fraction in [0.93, 0.90] {
+                push_rect(&mut lines, Vec2::splat(fraction));
+            }
+        }
+
+        if let Some(marker) = self.aspect_marker {
+            // largest centered rect of the marker aspect, relative to the frame
+            let frame_aspect = frame.x / frame.y;
+            let size = if marker < frame_aspect {
+                Vec2::new(marker / frame_aspect, 1.0)
+            } else {
+                Vec2::new(1.0, frame_aspect / marker)
+            };
+            push_rect(&mut lines, size);
+        }
+
+        lines
+    }
+}
+
+#[cfg(feature = "ui")]
+pub fn aspect_label(aspect: Option<f32>) -> &'static str {
+    let Some(aspect) = aspect else {
+        return "None";
+    };
+    ASPECT_PRESETS
+        .iter()
+        .find(|(_, a)| *a == aspect)
+        .map(|(label, _)| *label)
+        .unwrap_or("Custom")
+}
+
+/// Outline of a centered rectangle of the given frame-relative size
+fn push_rect(lines: &mut Vec<(Vec2, Vec2)>, size: Vec2) {
+    let min = Vec2::splat(0.5) - size / 2.0;
+    let max = Vec2::splat(0.5) + size / 2.0;
+    lines.push((min, Vec2::new(max.x, min.y)));
+    lines.push((Vec2::new(max.x, min.y), max));
+    lines.push((max, Vec2::new(min.x, max.y)));
+    lines.push((Vec2::new(min.x, max.y), min));
+}
+
+/// Draws the enabled guides over the frame. They are painted by egui behind its windows, so they
+/// only end up in exported images when `guides_in_exports` burns them in separately.
+#[cfg(feature = "ui")]
+fn draw_guides(
+    mut contexts: EguiContexts,
+    compose: Res<ComposeSettings>,
+    window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let Ok(win) = window.single() else {
+        return;
+    };
+    let frame = compose.frame_size(Vec2::new(win.width(), win.height()));
+    let lines = compose.guide_lines(frame);
+    if lines.is_empty() {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+
+    let origin = (Vec2::new(win.width(), win.height()) - frame) / 2.0;
+    let to_screen = |p: Vec2| {
+        let p = origin + p * frame;
+        egui::pos2(p.x, p.y)
+    };
+    let painter = ctx.layer_painter(egui::LayerId::background());
+    let stroke = egui::Stroke::new(1.0, egui::Color32::from_white_alpha(110));
+    for (a, b) in lines {
+        painter.line_segment([to_screen(a), to_screen(b)], stroke);
+    }
+}
+
+/// Draws guide lines from [`ComposeSettings::guide_lines`] into an RGBA float image, used when
+/// exporting with `guides_in_exports` enabled
+pub fn burn_in_guides(lines: &[(Vec2, Vec2)], pixels: &mut [f32], width: u32, height: u32) {
+    let size = Vec2::new(width as f32, height as f32);
+    for (a, b) in lines {
+        let (a, b) = (*a * size, *b * size);
+        let steps = (b - a).abs().max_element().ceil().max(1.0) as usize;
+        for i in 0..=steps {
+            let p = a.lerp(b, i as f32 / steps as f32);
+            let (x, y) = (p.x as u32, p.y as u32);
+            if x < width && y < height {
+                let index = ((y * width + x) * 4) as usize;
+                pixels[index..index + 3].fill(1.0);
+            }
+        }
+    }
+}
@@ -0,0 +1,35 @@
+//! Where the app keeps its settings between sessions: one folder in the platform's per-user
+//! config directory, shared by the startup choice, the controls, user palettes and the usage
+//! statistics.
+
+use std::path::{Path, PathBuf};
+
+/// `name` in this app's folder of the platform's per-user config directory, or in the working
+/// directory when the environment doesn't say where that is
+pub fn config_file(name: &str) -> PathBuf {
+    config_dir().map_or_else(|| PathBuf::from(name), |dir| dir.join(name))
+}
+
+fn config_dir() -> Option<PathBuf> {
+    let var = |name: &str| {
+        std::env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+    let base = if cfg!(target_os = "windows") {
+        var("APPDATA")?
+    } else if cfg!(target_os = "macos") {
+        var("HOME")?.join("Library/Application Support")
+    } else {
+        var("XDG_CONFIG_HOME").or_else(|| var("HOME").map(|home| home.join(".config")))?
+    };
+    Some(base.join("fractal3D"))
+}
+
+/// Writes a config file, creating the config folder on first use
+pub fn write_config(path: &Path, text: &str) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(path, text).map_err(|e| e.to_string())
+}
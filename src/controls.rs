@@ -0,0 +1,243 @@
+//! How the mouse and trackpad steer the camera: drag sensitivity per axis and which way dragging
+//! and zooming go, saved to the config folder as they change like the startup choice. Two-finger
+//! scrolling on a trackpad zooms like the wheel, and where the platform reports them, pinching
+//! zooms too and the rotate gesture rolls the camera. Pinch and rotate are only reported on
+//! macOS, so they add to the scrolling rather than replace it. Touchscreens orbit with one
+//! finger, zoom with a two-finger pinch and pan the Julia constant with three fingers, while
+//! touches on a panel are left to it.
+
+use crate::config::config_file;
+#[cfg(feature = "ui")]
+use crate::config::write_config;
+use crate::material::{MandelbulbMaterial, rotate_all_views};
+use crate::params::{Param, ParamChanged, ParamStore};
+use bevy::input::gestures::{PinchGesture, RotationGesture};
+use bevy::input::mouse::{MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, egui};
+use serde::{Deserialize, Serialize};
+
+const CONTROLS_FILE: &str = "controls.ron";
+
+/// Zoom factor of one wheel notch towards the fractal
+const ZOOM_PER_LINE: f32 = 0.9;
+
+/// Pixels of trackpad scrolling that zoom as far as one wheel notch
+const PIXELS_PER_LINE: f32 = 50.0;
+
+/// Change of the Julia constant per pixel a three-finger swipe moves
+const JULIA_PAN_PER_PIXEL: f32 = 0.002;
+
+pub struct ControlsPlugin;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MouseSettings::load())
+            .add_systems(Update, (trackpad_controls, touch_controls));
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct MouseSettings {
+    /// Radians of yaw per pixel dragged sideways
+    pub sensitivity_x: f32,
+    /// Radians of pitch per pixel dragged up or down
+    pub sensitivity_y: f32,
+    /// Dragging up tilts the fractal towards the camera instead of away
+    pub invert_y: bool,
+    /// Scrolling up zooms out instead of in
+    pub invert_zoom: bool,
+}
+
+impl Default for MouseSettings {
+    fn default() -> Self {
+        Self {
+            sensitivity_x: 0.005,
+            sensitivity_y: 0.005,
+            invert_y: false,
+            invert_zoom: false,
+        }
+    }
+}
+
+impl MouseSettings {
+    fn load() -> Self {
+        let path = config_file(CONTROLS_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => ron::from_str(&text).unwrap_or_else(|err| {
+                warn!("Ignoring {}: {err}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    fn save(&self) {
+        let path = config_file(CONTROLS_FILE);
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| write_config(&path, &text));
+        if let Err(err) = result {
+            error!("Failed to save {}: {err}", path.display());
+        }
+    }
+
+    /// Yaw and pitch in radians for a drag of `delta` pixels
+    pub fn drag_rotation(&self, delta: Vec2) -> (f32, f32) {
+        let pitch = delta.y * self.sensitivity_y;
+        (
+            -delta.x * self.sensitivity_x,
+            if self.invert_y { -pitch } else { pitch },
+        )
+    }
+
+    /// `zoom` after a wheel movement, scrolling up moves closer unless inverted. Scrolling in
+    /// pixels comes from trackpads and is scaled to match the notches of a wheel.
+    pub fn scrolled_zoom(&self, zoom: f32, wheel: &MouseWheel) -> f32 {
+        let lines = match wheel.unit {
+            MouseScrollUnit::Line => wheel.y,
+            MouseScrollUnit::Pixel => wheel.y / PIXELS_PER_LINE,
+        };
+        self.zoomed(zoom, ZOOM_PER_LINE.powf(lines))
+    }
+
+    /// `zoom` moved closer by `factor` below 1 or away above it, flipped when inverted
+    fn zoomed(&self, zoom: f32, factor: f32) -> f32 {
+        let factor = if self.invert_zoom {
+            1.0 / factor
+        } else {
+            factor
+        };
+        let range = Param::Zoom.range();
+        (zoom * factor).clamp(*range.start(), *range.end())
+    }
+
+    /// Choices for the Controls section, saved as they change
+    #[cfg(feature = "ui")]
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let before = self.clone();
+        for (label, sensitivity) in [
+            ("Horizontal Sensitivity", &mut self.sensitivity_x),
+            ("Vertical Sensitivity", &mut self.sensitivity_y),
+        ] {
+            ui.add(
+                egui::Slider::new(sensitivity, 0.0005..=0.05)
+                    .logarithmic(true)
+                    .text(label),
+            );
+        }
+        ui.checkbox(&mut self.invert_y, "Invert Y");
+        ui.checkbox(&mut self.invert_zoom, "Invert Zoom");
+        if ui.button("Reset").clicked() {
+            *self = Self::default();
+        }
+        if *self != before {
+            self.save();
+        }
+    }
+}
+
+/// Pinch zoom and rotate-to-roll, scrolling is handled with the wheel
+fn trackpad_controls(
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    mut pinch: MessageReader<PinchGesture>,
+    mut rotation: MessageReader<RotationGesture>,
+    mouse: Res<MouseSettings>,
+    store: Res<ParamStore>,
+    mut param_changes: MessageWriter<ParamChanged>,
+    #[cfg(feature = "ui")] mut contexts: EguiContexts,
+) {
+    let pinched: f32 = pinch.read().map(|ev| ev.0).sum();
+    let rolled: f32 = rotation.read().map(|ev| ev.0).sum();
+    if pinched == 0.0 && rolled == 0.0 {
+        return;
+    }
+    // gestures over a panel scroll or zoom the panel
+    #[cfg(feature = "ui")]
+    {
+        let ctx = contexts.ctx_mut().unwrap();
+        if ctx.is_pointer_over_area() || ctx.wants_pointer_input() {
+            return;
+        }
+    }
+
+    if pinched != 0.0 {
+        // spreading the fingers reports a positive pinch and moves closer
+        let current = store.get(Param::Zoom);
+        let zoom = mouse.zoomed(current, 1.0 / (1.0 + pinched).max(0.1));
+        if zoom != current {
+            param_changes.write(ParamChanged {
+                param: Param::Zoom,
+                value: zoom,
+            });
+        }
+    }
+
+    if rolled != 0.0 {
+        rotate_all_views(&mut materials, Quat::from_rotation_z(-rolled));
+    }
+}
+
+/// One-finger orbit, two-finger pinch zoom and three-finger Julia pan on touchscreens
+fn touch_controls(
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    touches: Res<Touches>,
+    mouse: Res<MouseSettings>,
+    store: Res<ParamStore>,
+    mut param_changes: MessageWriter<ParamChanged>,
+    #[cfg(feature = "ui")] mut contexts: EguiContexts,
+) {
+    let fingers: Vec<_> = touches.iter().collect();
+    if fingers.iter().all(|touch| touch.delta() == Vec2::ZERO) {
+        return;
+    }
+    // egui reads touches as its pointer, so a finger on a panel drags the panel
+    #[cfg(feature = "ui")]
+    {
+        let ctx = contexts.ctx_mut().unwrap();
+        if ctx.is_pointer_over_area() || ctx.wants_pointer_input() {
+            return;
+        }
+    }
+
+    match fingers.as_slice() {
+        [touch] => {
+            let (yaw, pitch) = mouse.drag_rotation(touch.delta());
+            rotate_all_views(
+                &mut materials,
+                Quat::from_rotation_y(yaw) * Quat::from_rotation_x(pitch),
+            );
+        }
+        [a, b] => {
+            let before = a.previous_position().distance(b.previous_position());
+            let after = a.position().distance(b.position());
+            if before > 0.0 && after > 0.0 {
+                // fingers moving apart move closer
+                let current = store.get(Param::Zoom);
+                let zoom = mouse.zoomed(current, before / after);
+                if zoom != current {
+                    param_changes.write(ParamChanged {
+                        param: Param::Zoom,
+                        value: zoom,
+                    });
+                }
+            }
+        }
+        [a, b, c] => {
+            let moved = (a.delta() + b.delta() + c.delta()) / 3.0;
+            // screen y grows downwards, swiping up raises the constant
+            for (param, delta) in [(Param::JuliaX, moved.x), (Param::JuliaY, -moved.y)] {
+                let range = param.range();
+                param_changes.write(ParamChanged {
+                    param,
+                    value: (store.get(param) + delta * JULIA_PAN_PER_PIXEL)
+                        .clamp(*range.start(), *range.end()),
+                });
+            }
+        }
+        _ => {}
+    }
+}
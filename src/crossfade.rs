@@ -0,0 +1,90 @@
+use crate::material::MandelbulbMaterial;
+use bevy::camera::RenderTarget;
+use bevy::camera::visibility::RenderLayers;
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::prelude::*;
+use bevy::render::view::{Hdr, ViewTarget};
+
+/// Render layer used by the quad that renders the incoming material offscreen
+const CROSSFADE_LAYER: usize = 1;
+
+/// Renders a second material offscreen and overlays it on the main view. The overlay starts
+/// invisible, so it can be spawned ahead of the fade to give the pipeline time to specialize.
+/// Used by the playlist and by scene acts.
+pub struct Crossfade {
+    camera: Entity,
+    quad: Entity,
+    overlay: Entity,
+}
+
+impl Crossfade {
+    pub fn spawn(
+        commands: &mut Commands,
+        materials: &mut Assets<MandelbulbMaterial>,
+        meshes: &mut Assets<Mesh>,
+        images: &mut Assets<Image>,
+        window: &Window,
+        material: MandelbulbMaterial,
+    ) -> Self {
+        let size = UVec2::new(window.width() as u32, window.height() as u32);
+        let target = images.add(Image::new_target_texture(
+            size.x,
+            size.y,
+            ViewTarget::TEXTURE_FORMAT_HDR,
+        ));
+
+        let camera = commands
+            .spawn((
+                Camera2d,
+                Camera {
+                    target: RenderTarget::Image(target.clone().into()),
+                    order: -1,
+                    ..default()
+                },
+                // kept linear and unclipped, the main view encodes it with everything else
+                Hdr,
+                Tonemapping::None,
+                RenderLayers::layer(CROSSFADE_LAYER),
+            ))
+            .id();
+        let quad = commands
+            .spawn((
+                Mesh2d(meshes.add(Rectangle::default())),
+                MeshMaterial2d(materials.add(material)),
+                Transform::default().with_scale(Vec3::new(window.width(), window.height(), 1.0)),
+                RenderLayers::layer(CROSSFADE_LAYER),
+            ))
+            .id();
+        // drawn in front of the main quad
+        let overlay = commands
+            .spawn((
+                Sprite {
+                    image: target,
+                    color: Color::srgba(1.0, 1.0, 1.0, 0.0),
+                    custom_size: Some(size.as_vec2()),
+                    ..default()
+                },
+                Transform::from_xyz(0.0, 0.0, 1.0),
+            ))
+            .id();
+
+        Self {
+            camera,
+            quad,
+            overlay,
+        }
+    }
+
+    /// Opacity of the incoming material, 0 shows only the main view
+    pub fn set_alpha(&self, sprites: &mut Query<&mut Sprite>, alpha: f32) {
+        if let Ok(mut sprite) = sprites.get_mut(self.overlay) {
+            sprite.color.set_alpha(alpha);
+        }
+    }
+
+    pub fn despawn(self, commands: &mut Commands) {
+        commands.entity(self.camera).despawn();
+        commands.entity(self.quad).despawn();
+        commands.entity(self.overlay).despawn();
+    }
+}
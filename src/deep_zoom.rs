@@ -0,0 +1,68 @@
+//! Keeping the picture consistent as the camera closes in. The hit threshold is an absolute
+//! distance, so a value that resolves fine detail from far away leaves a blurry, bloated surface
+//! up close and the view has to be retuned at every depth. With detail scaling on, the
+//! threshold follows the camera's distance to its target instead. The normal's sampling offset
+//! and the soft shadows' step sizes derive from the threshold in the shader, and ambient
+//! occlusion from the share of ray steps taken, so all of the shading scales along with it.
+
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use crate::params::{Param, ParamChanged};
+use crate::validation;
+use bevy::prelude::*;
+
+pub struct DeepZoomPlugin;
+
+impl Plugin for DeepZoomPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DeepZoom>()
+            .add_systems(PostUpdate, scale_detail);
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct DeepZoom {
+    /// Scale the hit threshold with the camera's zoom
+    pub keep_detail: bool,
+    /// Zoom and threshold seen last frame, to tell the camera moving from the threshold being
+    /// edited
+    last: Option<(f32, f32)>,
+    /// Threshold per unit of zoom, set whenever the threshold is edited
+    ratio: f32,
+}
+
+/// Runs after everything that moves the camera, so the threshold matches the frame's zoom
+fn scale_detail(
+    mut deep_zoom: ResMut<DeepZoom>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    mut param_changes: MessageWriter<ParamChanged>,
+) {
+    if !deep_zoom.keep_detail {
+        deep_zoom.last = None;
+        return;
+    }
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let (zoom, threshold) = (mat.camera.zoom, mat.shape.hit_threshold);
+    match deep_zoom.last {
+        // a threshold set by hand, or by opening a view, is the detail to keep from now on
+        Some((_, last_threshold)) if last_threshold != threshold => {
+            deep_zoom.ratio = threshold / zoom;
+        }
+        Some((last_zoom, _)) if last_zoom != zoom => {
+            let scaled = deep_zoom.ratio * zoom;
+            // clamped here already, so the threshold applied next frame is the one remembered
+            let scaled = validation::clamp(Param::HitThreshold, scaled).map_or(scaled, |(v, _)| v);
+            param_changes.write(ParamChanged {
+                param: Param::HitThreshold,
+                value: scaled,
+            });
+            deep_zoom.last = Some((zoom, scaled));
+            return;
+        }
+        Some(_) => return,
+        None => deep_zoom.ratio = threshold / zoom,
+    }
+    deep_zoom.last = Some((zoom, threshold));
+}
@@ -0,0 +1,220 @@
+use crate::estimator::CpuEstimator;
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use crate::power::keep_awake;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, poll_once};
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// Coarsest grid, boxes per side
+const COARSEST_GRID: u32 = 8;
+
+/// Finest grids offered in the UI, each run halves the box size down to one of these
+const FINEST_GRIDS: [u32; 3] = [64, 128, 256];
+
+const PLOT_SIZE: egui::Vec2 = egui::vec2(280.0, 180.0);
+
+/// Estimates the box-counting dimension of the current shape on a background thread
+pub struct DimensionPlugin;
+
+impl Plugin for DimensionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Dimension>()
+            .add_systems(
+                Update,
+                (
+                    poll_dimension,
+                    keep_awake.run_if(|dimension: Res<Dimension>| dimension.is_running()),
+                ),
+            )
+            .add_systems(EguiPrimaryContextPass, dimension_window);
+    }
+}
+
+/// Box counts of nested grids over a cube around the origin. A box counts when the estimated
+/// distance from its center is within its half diagonal, so it may touch the surface. The
+/// dimension is the slope of log(count) against log(1 / box size).
+#[derive(Resource)]
+pub struct Dimension {
+    pub open: bool,
+    /// Half the side of the cube, which has to contain the whole fractal
+    pub bounds: f32,
+    pub finest: u32,
+    task: Option<Task<Vec<[f64; 2]>>>,
+    /// (log(1 / box size), log(count)) per grid of the last run
+    points: Vec<[f64; 2]>,
+}
+
+impl Default for Dimension {
+    fn default() -> Self {
+        Self {
+            open: false,
+            bounds: 2.0,
+            finest: FINEST_GRIDS[1],
+            task: None,
+            points: Vec::new(),
+        }
+    }
+}
+
+impl Dimension {
+    pub fn is_running(&self) -> bool {
+        self.task.is_some()
+    }
+
+    /// Least squares line through the points as (slope, intercept)
+    fn fit(&self) -> Option<(f64, f64)> {
+        if self.points.len() < 2 {
+            return None;
+        }
+        let n = self.points.len() as f64;
+        let mean_x = self.points.iter().map(|p| p[0]).sum::<f64>() / n;
+        let mean_y = self.points.iter().map(|p| p[1]).sum::<f64>() / n;
+        let (mut sxy, mut sxx) = (0.0, 0.0);
+        for [x, y] in &self.points {
+            sxy += (x - mean_x) * (y - mean_y);
+            sxx += (x - mean_x) * (x - mean_x);
+        }
+        let slope = sxy / sxx;
+        Some((slope, mean_y - slope * mean_x))
+    }
+}
+
+fn count_boxes(estimator: CpuEstimator, bounds: f32, finest: u32) -> Vec<[f64; 2]> {
+    let mut points = Vec::new();
+    let mut grid = COARSEST_GRID;
+    while grid <= finest {
+        let size = 2.0 * bounds / grid as f32;
+        let half_diagonal = size * 3f32.sqrt() * 0.5;
+        let mut count = 0u64;
+        for z in 0..grid {
+            for y in 0..grid {
+                for x in 0..grid {
+                    let center = (UVec3::new(x, y, z).as_vec3() + 0.5) * size - bounds;
+                    if estimator.distance(center).abs() <= half_diagonal {
+                        count += 1;
+                    }
+                }
+            }
+        }
+        if count > 0 {
+            points.push([(1.0 / size as f64).ln(), (count as f64).ln()]);
+        }
+        grid *= 2;
+    }
+    points
+}
+
+fn poll_dimension(mut dimension: ResMut<Dimension>) {
+    if let Some(task) = &mut dimension.task
+        && let Some(points) = block_on(poll_once(task))
+    {
+        dimension.points = points;
+        dimension.task = None;
+    }
+}
+
+fn dimension_window(
+    mut contexts: EguiContexts,
+    mut dimension: ResMut<Dimension>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    if !dimension.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let estimator = materials.get(&fractal.0).and_then(CpuEstimator::new);
+    let dimension = &mut *dimension;
+
+    let mut open = dimension.open;
+    egui::Window::new("Fractal Dimension")
+        .open(&mut open)
+        .default_width(300.0)
+        .show(ctx, |ui| {
+            let Some(estimator) = estimator else {
+                ui.label("Not available for plugin formulas and the geometry graph.");
+                return;
+            };
+            ui.add(egui::Slider::new(&mut dimension.bounds, 0.5..=10.0).text("Bounds"))
+                .on_hover_text("Half the side of the sampled cube, it must contain the fractal");
+            ui.horizontal(|ui| {
+                ui.label("Finest Grid");
+                for grid in FINEST_GRIDS {
+                    ui.selectable_value(&mut dimension.finest, grid, format!("{grid}³"));
+                }
+            });
+            ui.horizontal(|ui| {
+                let running = dimension.is_running();
+                if ui
+                    .add_enabled(!running, egui::Button::new("Estimate"))
+                    .clicked()
+                {
+                    let (bounds, finest) = (dimension.bounds, dimension.finest);
+                    dimension.task = Some(
+                        AsyncComputeTaskPool::get()
+                            .spawn(async move { count_boxes(estimator, bounds, finest) }),
+                    );
+                }
+                if running {
+                    ui.spinner();
+                }
+            });
+            ui.separator();
+
+            match dimension.fit() {
+                Some((slope, _)) => ui.label(format!("Dimension: {slope:.3}")),
+                None => ui.label("Dimension: -"),
+            };
+            plot(ui, &dimension.points, dimension.fit());
+        });
+    dimension.open = open;
+}
+
+/// log-log plot of the box counts with the fitted line
+fn plot(ui: &mut egui::Ui, points: &[[f64; 2]], fit: Option<(f64, f64)>) {
+    let (response, painter) = ui.allocate_painter(PLOT_SIZE, egui::Sense::hover());
+    let rect = response.rect.shrink(8.0);
+    painter.rect_filled(response.rect, 2.0, egui::Color32::from_gray(25));
+    let (Some((slope, intercept)), Some(first), Some(last)) = (fit, points.first(), points.last())
+    else {
+        return;
+    };
+
+    let (min_x, max_x) = (first[0], last[0]);
+    let (min_y, max_y) = points.iter().fold((f64::MAX, f64::MIN), |(lo, hi), p| {
+        (lo.min(p[1]), hi.max(p[1]))
+    });
+    let to_screen = |x: f64, y: f64| {
+        let u = (x - min_x) / (max_x - min_x).max(f64::EPSILON);
+        let v = (y - min_y) / (max_y - min_y).max(f64::EPSILON);
+        egui::pos2(
+            rect.left() + u as f32 * rect.width(),
+            rect.bottom() - v as f32 * rect.height(),
+        )
+    };
+
+    painter.line_segment(
+        [
+            to_screen(min_x, slope * min_x + intercept),
+            to_screen(max_x, slope * max_x + intercept),
+        ],
+        egui::Stroke::new(1.0, egui::Color32::LIGHT_BLUE),
+    );
+    for [x, y] in points {
+        painter.circle_filled(to_screen(*x, *y), 3.0, egui::Color32::WHITE);
+    }
+    painter.text(
+        rect.left_top(),
+        egui::Align2::LEFT_TOP,
+        "log N",
+        egui::FontId::proportional(11.0),
+        egui::Color32::GRAY,
+    );
+    painter.text(
+        rect.right_bottom(),
+        egui::Align2::RIGHT_BOTTOM,
+        "log 1/size",
+        egui::FontId::proportional(11.0),
+        egui::Color32::GRAY,
+    );
+}
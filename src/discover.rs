@@ -0,0 +1,307 @@
+//! Automated exploration: random views of the current fractal are rendered small on the CPU in
+//! the background, scored by how interesting they look and the best kept as thumbnails to jump
+//! to. The score favors frames that are about half surface, with many edges and varied colors,
+//! over empty frames, frames filled by a wall and flat, single colored ones.
+
+use crate::estimator::CpuEstimator;
+use crate::material::{FractalMaterial, MandelbulbMaterial, aim_all_views};
+use crate::palette::palettes;
+use crate::params::{Param, ParamChanged, ParamStore};
+use crate::preview::{PreviewRender, Sample};
+use crate::rng::Rng;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, poll_once};
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// Width of the rendered candidates, the height follows the window's aspect
+const RENDER_WIDTH: u32 = 64;
+
+/// Candidates rendered by one background task
+const BATCH: usize = 4;
+
+/// Candidates kept, best first
+const KEPT: usize = 12;
+
+pub struct DiscoverPlugin;
+
+impl Plugin for DiscoverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Discovery>()
+            .add_systems(Update, run_discovery)
+            .add_systems(EguiPrimaryContextPass, discovery_window);
+    }
+}
+
+#[derive(Resource)]
+pub struct Discovery {
+    pub open: bool,
+    searching: bool,
+    /// Also vary the power, palette and coloring rather than only the camera
+    vary_shape: bool,
+    /// Also look at close-ups of points on the surface
+    close_ups: bool,
+    /// The material the candidates vary, taken when the search starts
+    base: Option<MandelbulbMaterial>,
+    task: Option<Task<Vec<Candidate>>>,
+    candidates: Vec<Candidate>,
+    checked: u32,
+    rng: Rng,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self {
+            open: false,
+            searching: false,
+            vary_shape: true,
+            close_ups: true,
+            base: None,
+            task: None,
+            candidates: Vec::new(),
+            checked: 0,
+            rng: Rng::stream("discover"),
+        }
+    }
+}
+
+impl Discovery {
+    fn random(&mut self) -> f32 {
+        self.rng.next_f32()
+    }
+
+    /// A random variation of `base`
+    fn sample(&mut self, base: &MandelbulbMaterial) -> MandelbulbMaterial {
+        let mut mat = base.clone();
+        if self.vary_shape {
+            mat.shape.power = 2.0 + self.random() * 10.0;
+            let palette = (self.random() * palettes().len() as f32).floor();
+            Param::Palette.set(&mut mat, palette);
+            mat.shading.color_offset = self.random();
+            mat.shading.color_scale = 0.5 + self.random() * 1.5;
+        }
+        let yaw = self.random() * std::f32::consts::TAU;
+        let pitch = (self.random() - 0.5) * 2.0;
+        let rotation = Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.0);
+        mat.camera.rotation = Vec4::from(rotation);
+        mat.camera.position = Vec3::ZERO;
+        mat.camera.zoom = 1.6 + self.random() * 1.4;
+
+        if self.close_ups
+            && self.random() < 0.5
+            && let Some(estimator) = CpuEstimator::new(&mat)
+        {
+            // orbit a point where a ray from outside meets the surface
+            let dir = Vec3::new(
+                self.random() - 0.5,
+                self.random() - 0.5,
+                self.random() - 0.5,
+            )
+            .normalize_or(Vec3::X);
+            let origin = dir * 3.0;
+            if let Some(t) = estimator.trace(&mat.shape, origin, -dir) {
+                mat.camera.position = origin - dir * t;
+                mat.camera.zoom = 0.15 + self.random() * 0.6;
+            }
+        }
+        mat
+    }
+
+    fn stop(&mut self) {
+        self.searching = false;
+        // a running batch is dropped, which cancels it
+        self.task = None;
+    }
+}
+
+/// A rendered and scored view
+struct Candidate {
+    material: MandelbulbMaterial,
+    score: f32,
+    image: egui::ColorImage,
+    texture: Option<egui::TextureHandle>,
+}
+
+/// Renders `mat` at `width` and scores it
+fn render_candidate(mat: MandelbulbMaterial, width: u32) -> Option<Candidate> {
+    let render = PreviewRender::new(&mat, width)?;
+    Some(Candidate {
+        score: score(&render),
+        image: render.image(1.0),
+        material: mat,
+        texture: None,
+    })
+}
+
+/// Interest of a render between 0 and 1: surface coverage close to half the frame, the share
+/// of pixels on an edge and the spread of the surface colors
+fn score(render: &PreviewRender) -> f32 {
+    let (samples, width) = (&render.samples, render.width);
+    let hits: Vec<&Sample> = samples.iter().filter(|s| s.hit).collect();
+    if hits.is_empty() {
+        return 0.0;
+    }
+    let coverage = hits.len() as f32 / samples.len() as f32;
+    let coverage_score = 1.0 - (coverage - 0.5).abs() * 2.0;
+
+    // an edge is a silhouette, a jump in depth or a crease between neighbors
+    let is_edge = |a: &Sample, b: &Sample| {
+        a.hit != b.hit
+            || (a.hit
+                && ((a.depth - b.depth).abs() > 0.05 * a.depth || a.normal.dot(b.normal) < 0.8))
+    };
+    let mut edges = 0;
+    for (i, sample) in samples.iter().enumerate() {
+        let right = (i % width + 1 < width).then(|| &samples[i + 1]);
+        let below = samples.get(i + width);
+        if right
+            .into_iter()
+            .chain(below)
+            .any(|other| is_edge(sample, other))
+        {
+            edges += 1;
+        }
+    }
+    let edge_score = (edges as f32 / samples.len() as f32 / 0.35).min(1.0);
+
+    let mean = hits.iter().map(|s| s.color).sum::<Vec3>() / hits.len() as f32;
+    let variance = hits
+        .iter()
+        .map(|s| (s.color - mean).length_squared())
+        .sum::<f32>()
+        / hits.len() as f32;
+    let color_score = (variance.sqrt() / 0.25).min(1.0);
+
+    0.4 * coverage_score + 0.35 * edge_score + 0.25 * color_score
+}
+
+/// Keeps one batch of candidates rendering while searching and merges finished ones in
+fn run_discovery(mut discovery: ResMut<Discovery>) {
+    let discovery = &mut *discovery;
+    if let Some(task) = &mut discovery.task
+        && let Some(batch) = block_on(poll_once(task))
+    {
+        discovery.task = None;
+        discovery.checked += batch.len() as u32;
+        discovery.candidates.extend(batch);
+        discovery
+            .candidates
+            .sort_by(|a, b| b.score.total_cmp(&a.score));
+        discovery.candidates.truncate(KEPT);
+    }
+    if !discovery.searching || discovery.task.is_some() {
+        return;
+    }
+    let Some(base) = discovery.base.clone() else {
+        return;
+    };
+    if CpuEstimator::new(&base).is_none() {
+        discovery.stop();
+        return;
+    }
+    let materials: Vec<MandelbulbMaterial> = (0..BATCH).map(|_| discovery.sample(&base)).collect();
+    discovery.task = Some(AsyncComputeTaskPool::get().spawn(async move {
+        materials
+            .into_iter()
+            .filter_map(|mat| render_candidate(mat, RENDER_WIDTH))
+            .collect()
+    }));
+}
+
+fn discovery_window(
+    mut contexts: EguiContexts,
+    mut discovery: ResMut<Discovery>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    store: Res<ParamStore>,
+    mut param_changes: MessageWriter<ParamChanged>,
+) {
+    if !discovery.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let discovery = &mut *discovery;
+    let current = materials.get(&fractal.0).cloned().map(|mut mat| {
+        store.restore(&mut mat);
+        mat
+    });
+    let supported = current.as_ref().and_then(CpuEstimator::new).is_some();
+
+    let mut open = discovery.open;
+    let mut jump = None;
+    egui::Window::new("Discover Views")
+        .open(&mut open)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            if !supported {
+                ui.label("Not available for plugin formulas and the geometry graph.");
+                return;
+            }
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut discovery.vary_shape, "Vary power and colors");
+                ui.checkbox(&mut discovery.close_ups, "Close-ups");
+            });
+            ui.horizontal(|ui| {
+                if discovery.searching {
+                    if ui.button("Stop").clicked() {
+                        discovery.stop();
+                    }
+                    ui.spinner();
+                } else if ui
+                    .button("Search")
+                    .on_hover_text("Varies the current view in the background")
+                    .clicked()
+                {
+                    discovery.base = current.clone();
+                    discovery.searching = true;
+                }
+                if ui
+                    .add_enabled(!discovery.candidates.is_empty(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    discovery.candidates.clear();
+                    discovery.checked = 0;
+                }
+                ui.label(format!("{} views checked", discovery.checked));
+            });
+            ui.separator();
+
+            if discovery.candidates.is_empty() {
+                ui.label("The most interesting views found appear here.");
+                return;
+            }
+            egui::ScrollArea::vertical()
+                .max_height(420.0)
+                .show(ui, |ui| {
+                    ui.horizontal_wrapped(|ui| {
+                        for (index, candidate) in discovery.candidates.iter_mut().enumerate() {
+                            let texture = candidate.texture.get_or_insert_with(|| {
+                                ui.ctx().load_texture(
+                                    format!("discover_{index}"),
+                                    candidate.image.clone(),
+                                    egui::TextureOptions::LINEAR,
+                                )
+                            });
+                            let size = texture.size_vec2() * 2.0;
+                            ui.vertical(|ui| {
+                                let image = egui::Image::new((texture.id(), size));
+                                if ui
+                                    .add(egui::Button::image(image))
+                                    .on_hover_text("Jump to this view")
+                                    .clicked()
+                                {
+                                    jump = Some(index);
+                                }
+                                ui.small(format!("Score {:.0}", candidate.score * 100.0));
+                            });
+                        }
+                    });
+                });
+        });
+    discovery.open = open;
+
+    if let Some(index) = jump {
+        let candidate = &discovery.candidates[index].material;
+        aim_all_views(&mut materials, &candidate.camera);
+        param_changes.write_batch(ParamChanged::all(candidate));
+    }
+}
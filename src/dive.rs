@@ -0,0 +1,330 @@
+//! Zoom dives: the camera turns towards a picked surface point and closes in on it
+//! exponentially, so every halving of the distance takes the same time, the signature fractal
+//! zoom. The threshold shrinks and the ray steps grow with the distance so the surface keeps
+//! the same level of detail all the way down. A dive can be previewed live or saved frame by
+//! frame as a numbered image sequence to assemble into a video.
+
+use crate::compose::ComposeSettings;
+use crate::easing::Easing;
+use crate::estimator::CpuEstimator;
+use crate::export::{ExportFrame, ExportSettings, FrameSaved, FrameSequence, timestamped_folder};
+use crate::material::{FractalMaterial, MandelbulbMaterial, aim_all_views};
+use crate::params::{Param, ParamChanged, ParamStore, apply_param_changes};
+use crate::validation;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// Share of a dive spent turning towards the point before closing in
+const AIM_SHARE: f32 = 0.1;
+
+/// Ray steps added for every halving of the distance, deeper views need longer marches
+const STEPS_PER_OCTAVE: f32 = 6.0;
+
+pub struct DivePlugin;
+
+impl Plugin for DivePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ZoomDive>()
+            .add_systems(
+                Update,
+                // a recorded frame shows the step of the dive sent in the same frame
+                (pick_dive_target, run_dive.before(apply_param_changes)),
+            )
+            .add_systems(EguiPrimaryContextPass, dive_window);
+    }
+}
+
+#[derive(Resource)]
+pub struct ZoomDive {
+    pub open: bool,
+    /// The next click on the view picks the point to dive towards
+    picking: bool,
+    target: Option<Dive>,
+    /// How many times closer the camera ends up than it started
+    depth: f32,
+    duration_secs: f32,
+    fps: u32,
+    playing: bool,
+    elapsed: f32,
+    recording: Option<FrameSequence>,
+    status: Option<String>,
+}
+
+impl Default for ZoomDive {
+    fn default() -> Self {
+        Self {
+            open: false,
+            picking: false,
+            target: None,
+            depth: 1000.0,
+            duration_secs: 20.0,
+            fps: 30,
+            playing: false,
+            elapsed: 0.0,
+            recording: None,
+            status: None,
+        }
+    }
+}
+
+/// A picked point and the view it was picked from
+struct Dive {
+    start: MandelbulbMaterial,
+    /// Where the camera was when the point was picked
+    origin: Vec3,
+    point: Vec3,
+    /// Rotation looking from `origin` straight at `point`, keeping the start's roll
+    aimed: Quat,
+}
+
+impl Dive {
+    /// The view `progress` of the way through a dive `depth` times closer
+    fn at(&self, progress: f32, depth: f32) -> MandelbulbMaterial {
+        let mut mat = self.start.clone();
+        let aim = Easing::Smoothstep.apply(progress / AIM_SHARE);
+        let rotation = Quat::from_vec4(self.start.camera.rotation)
+            .normalize()
+            .slerp(self.aimed, aim);
+        // equal time for every halving of the distance
+        let dive = ((progress - AIM_SHARE) / (1.0 - AIM_SHARE)).clamp(0.0, 1.0);
+        let scale = depth.powf(-dive);
+
+        // the camera orbits its position at the zoom distance: while turning it stays where it
+        // was, then it moves down the ray towards the point
+        let distance = self.origin.distance(self.point);
+        let forward = rotation.inverse() * Vec3::Z;
+        mat.camera.rotation = Vec4::from(rotation);
+        mat.camera.position = self.origin + forward * distance;
+        mat.camera.zoom = at_least(Param::Zoom, distance * scale);
+        mat.shape.hit_threshold = at_least(Param::HitThreshold, mat.shape.hit_threshold * scale);
+        mat.shape.ray_steps += (STEPS_PER_OCTAVE * dive * depth.log2()).round() as u32;
+        mat
+    }
+}
+
+/// `value` raised to the least `param` can render with
+fn at_least(param: Param, value: f32) -> f32 {
+    validation::clamp(param, value).map_or(value, |(value, _)| value)
+}
+
+fn pick_dive_target(
+    mut dive: ResMut<ZoomDive>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    compose: Res<ComposeSettings>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    store: Res<ParamStore>,
+    mut contexts: EguiContexts,
+) {
+    if !dive.picking || !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    if ctx.is_pointer_over_area() || ctx.wants_pointer_input() {
+        return;
+    }
+    let (Ok(window), Some(mat)) = (window.single(), materials.get(&fractal.0)) else {
+        return;
+    };
+    let Some(estimator) = CpuEstimator::new(mat) else {
+        dive.picking = false;
+        return;
+    };
+    let Some(uv) = compose.cursor_uv(window) else {
+        return;
+    };
+    let (origin, dir) = mat.camera.ray(uv);
+    let Some(t) = estimator.trace(&mat.shape, origin, dir) else {
+        return;
+    };
+
+    // turn the camera's forward axis onto the ray, which keeps the roll
+    let to_world = Quat::from_vec4(mat.camera.rotation).normalize().inverse();
+    let turn = Quat::from_rotation_arc(to_world * Vec3::Z, dir);
+    let mut start = mat.clone();
+    store.restore(&mut start);
+    dive.target = Some(Dive {
+        start,
+        origin,
+        point: origin + dir * t,
+        aimed: (turn * to_world).inverse(),
+    });
+    dive.picking = false;
+    dive.playing = false;
+    dive.status = None;
+}
+
+/// Plays a dive in real time, or steps through it one saved frame at a time while recording
+fn run_dive(
+    time: Res<Time>,
+    mut dive: ResMut<ZoomDive>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    mut param_changes: MessageWriter<ParamChanged>,
+    mut frames: MessageWriter<ExportFrame>,
+    mut saved: MessageReader<FrameSaved>,
+) {
+    // read every frame, so ones of a cancelled recording don't count for the next
+    let saved: Vec<_> = saved.read().collect();
+    let dive = &mut *dive;
+    let Some(target) = &dive.target else {
+        return;
+    };
+    let progress = if let Some(recording) = &mut dive.recording {
+        let Some(frame) = recording.poll(saved) else {
+            if recording.is_complete() {
+                dive.status = Some(format!(
+                    "Saved {} frames to {}",
+                    recording.frames,
+                    recording.directory.display()
+                ));
+                dive.recording = None;
+            }
+            return;
+        };
+        frames.write(recording.request(frame));
+        frame as f32 / (recording.frames - 1).max(1) as f32
+    } else if dive.playing {
+        dive.elapsed += time.delta_secs();
+        if dive.elapsed >= dive.duration_secs {
+            dive.playing = false;
+        }
+        (dive.elapsed / dive.duration_secs).min(1.0)
+    } else {
+        return;
+    };
+
+    let mat = target.at(progress, dive.depth);
+    aim_all_views(&mut materials, &mat.camera);
+    param_changes.write_batch(ParamChanged::all(&mat));
+}
+
+fn dive_window(
+    mut contexts: EguiContexts,
+    mut dive: ResMut<ZoomDive>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    mut param_changes: MessageWriter<ParamChanged>,
+    settings: Res<ExportSettings>,
+) {
+    if !dive.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let dive = &mut *dive;
+    let supported = materials
+        .get(&fractal.0)
+        .and_then(CpuEstimator::new)
+        .is_some();
+
+    let mut open = dive.open;
+    let mut restart = false;
+    egui::Window::new("Zoom Dive")
+        .open(&mut open)
+        .default_width(300.0)
+        .show(ctx, |ui| {
+            if !supported {
+                ui.label("Not available for plugin formulas and the geometry graph.");
+                return;
+            }
+            let busy = dive.playing || dive.recording.is_some();
+            ui.add_enabled_ui(!busy, |ui| {
+                ui.horizontal(|ui| {
+                    ui.toggle_value(&mut dive.picking, "Pick Point")
+                        .on_hover_text("Click the surface to dive towards");
+                    match &dive.target {
+                        Some(target) => ui.label(format!(
+                            "({:.3}, {:.3}, {:.3})",
+                            target.point.x, target.point.y, target.point.z
+                        )),
+                        None => ui.label("No point picked"),
+                    };
+                });
+                ui.add(
+                    egui::Slider::new(&mut dive.depth, 10.0..=10000.0)
+                        .logarithmic(true)
+                        .text("Depth"),
+                )
+                .on_hover_text("How many times closer the camera ends up");
+                ui.add(
+                    egui::Slider::new(&mut dive.duration_secs, 2.0..=120.0)
+                        .suffix(" s")
+                        .text("Duration"),
+                );
+                ui.add(egui::Slider::new(&mut dive.fps, 12..=60).text("Frames per Second"));
+            });
+
+            let Some(target) = &dive.target else {
+                return;
+            };
+            ui.separator();
+            ui.horizontal(|ui| {
+                if dive.playing {
+                    if ui.button("Stop").clicked() {
+                        dive.playing = false;
+                    }
+                } else if ui
+                    .add_enabled(dive.recording.is_none(), egui::Button::new("Preview"))
+                    .clicked()
+                {
+                    dive.playing = true;
+                    dive.elapsed = 0.0;
+                }
+                if let Some(recording) = &dive.recording {
+                    if ui.button("Cancel").clicked() {
+                        dive.recording = None;
+                    } else {
+                        ui.add(
+                            egui::ProgressBar::new(
+                                recording.frame as f32 / recording.frames as f32,
+                            )
+                            .text(format!(
+                                "Frame {} of {}",
+                                recording.frame + 1,
+                                recording.frames
+                            )),
+                        );
+                    }
+                } else if ui
+                    .add_enabled(!dive.playing, egui::Button::new("Save Frames"))
+                    .on_hover_text(format!(
+                        "Renders every frame into a new folder in {}",
+                        settings.directory.display()
+                    ))
+                    .clicked()
+                {
+                    let frames = (dive.duration_secs * dive.fps as f32).round() as u32;
+                    dive.recording = Some(FrameSequence::start(
+                        timestamped_folder(&settings.directory, "dive"),
+                        frames.max(2),
+                    ));
+                    dive.status = None;
+                }
+                if ui
+                    .add_enabled(!busy, egui::Button::new("Back to Start"))
+                    .clicked()
+                {
+                    restart = true;
+                }
+            });
+            let depth_steps = (STEPS_PER_OCTAVE * dive.depth.log2()).round() as u32;
+            ui.small(format!(
+                "Ends at threshold {:.2e} with {} ray steps",
+                at_least(
+                    Param::HitThreshold,
+                    target.start.shape.hit_threshold / dive.depth
+                ),
+                target.start.shape.ray_steps + depth_steps
+            ));
+            if let Some(status) = &dive.status {
+                ui.label(status);
+            }
+        });
+    dive.open = open;
+
+    if restart && let Some(target) = &dive.target {
+        aim_all_views(&mut materials, &target.start.camera);
+        param_changes.write_batch(ParamChanged::all(&target.start));
+    }
+}
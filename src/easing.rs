@@ -0,0 +1,108 @@
+//! Easing curves shared by everything that moves from one state to another over time: playlist
+//! transitions, act crossfades, timeline segments and the aim of zoom dives. Each takes the
+//! linear progress of a transition in 0..=1 and returns the eased progress, 0 at the start and
+//! 1 at the end. Elastic and bounce overshoot or rebound on the way.
+
+#[cfg(feature = "ui")]
+use bevy_egui::egui;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+/// Oscillations of the elastic curve before it settles
+const ELASTIC_PERIOD: f32 = 0.3;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Easing {
+    Linear,
+    /// Starts and ends gently
+    #[default]
+    Smoothstep,
+    /// Like smoothstep but slower at the ends and faster through the middle
+    Cubic,
+    /// Overshoots the end and springs back to it
+    Elastic,
+    /// Reaches the end and bounces back off it a few times
+    Bounce,
+}
+
+impl Easing {
+    #[cfg(feature = "ui")]
+    pub const ALL: [Easing; 5] = [
+        Easing::Linear,
+        Easing::Smoothstep,
+        Easing::Cubic,
+        Easing::Elastic,
+        Easing::Bounce,
+    ];
+
+    #[cfg(feature = "ui")]
+    pub fn label(self) -> &'static str {
+        match self {
+            Easing::Linear => "Linear",
+            Easing::Smoothstep => "Smoothstep",
+            Easing::Cubic => "Cubic",
+            Easing::Elastic => "Elastic",
+            Easing::Bounce => "Bounce",
+        }
+    }
+
+    /// Eased progress for the linear progress `t`, which is clamped to 0..=1
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Easing::Linear => t,
+            Easing::Smoothstep => t * t * (3.0 - 2.0 * t),
+            Easing::Cubic => {
+                if t < 0.5 {
+                    4.0 * t * t * t
+                } else {
+                    1.0 - (-2.0 * t + 2.0).powi(3) / 2.0
+                }
+            }
+            Easing::Elastic => {
+                if t <= 0.0 || t >= 1.0 {
+                    t
+                } else {
+                    1.0 + 2f32.powf(-10.0 * t)
+                        * ((t - ELASTIC_PERIOD / 4.0) * TAU / ELASTIC_PERIOD).sin()
+                }
+            }
+            Easing::Bounce => bounce_out(t),
+        }
+    }
+}
+
+/// Falls to 1 and bounces off it three times with decaying height
+fn bounce_out(t: f32) -> f32 {
+    const N: f32 = 7.5625;
+    const D: f32 = 2.75;
+    if t < 1.0 / D {
+        N * t * t
+    } else if t < 2.0 / D {
+        let t = t - 1.5 / D;
+        N * t * t + 0.75
+    } else if t < 2.5 / D {
+        let t = t - 2.25 / D;
+        N * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D;
+        N * t * t + 0.984375
+    }
+}
+
+/// Combo box picking an easing, `id_salt` tells apart several in one window
+#[cfg(feature = "ui")]
+pub fn easing_combo(ui: &mut egui::Ui, id_salt: impl std::hash::Hash, easing: &mut Easing) -> bool {
+    let mut changed = false;
+    egui::ComboBox::from_id_salt(id_salt)
+        .width(90.0)
+        .selected_text(easing.label())
+        .show_ui(ui, |ui| {
+            for choice in Easing::ALL {
+                changed |= ui
+                    .selectable_value(easing, choice, choice.label())
+                    .changed();
+            }
+        });
+    changed
+}
@@ -0,0 +1,169 @@
+//! CPU port of the built-in distance estimators in `shaders/mandelbulb.wgsl`, for analysis
+//! that runs outside the renderer. Keep the two in sync: a difference shows up as statistics
+//! that do not match the picture.
+
+#[cfg(feature = "ui")]
+use crate::material::ShapeUniform;
+use crate::material::{Formula, MandelbulbMaterial};
+use bevy::prelude::*;
+
+/// How a marched ray ended
+#[cfg(feature = "ui")]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum March {
+    /// Reached the surface at distance `t` after `steps` steps
+    Hit { t: f32, steps: u32 },
+    /// Went past the max distance
+    Escaped,
+    /// Used up the step limit first
+    OutOfSteps,
+}
+
+/// A material's distance estimator, evaluated on the CPU
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CpuEstimator {
+    mandelbox: bool,
+    /// Exponent of the Mandelbulb, scale of the Mandelbox
+    power: f32,
+    iterations: u32,
+    /// Constant added each iteration instead of the sample point
+    julia: Option<Vec3>,
+}
+
+impl CpuEstimator {
+    /// `None` for plugin formulas and the geometry graph, which only exist as WGSL
+    pub fn new(material: &MandelbulbMaterial) -> Option<Self> {
+        if material.geometry_graph {
+            return None;
+        }
+        let mandelbox = match material.formula {
+            Formula::Mandelbulb => false,
+            Formula::Mandelbox => true,
+            Formula::Plugin(_) => return None,
+        };
+        let shape = &material.shape;
+        Some(Self {
+            mandelbox,
+            power: shape.power,
+            iterations: shape.mandel_iters,
+            julia: (shape.julia.w > 0.5).then(|| shape.julia.truncate()),
+        })
+    }
+
+    /// Estimated distance from `p` to the surface, negative or near zero inside
+    pub fn distance(&self, p: Vec3) -> f32 {
+        self.distance_and_trap(p).0
+    }
+
+    /// Smallest orbit radius reached from `p`, the value the orbit trap coloring starts from
+    #[cfg(feature = "ui")]
+    pub fn orbit_trap(&self, p: Vec3) -> f32 {
+        self.distance_and_trap(p).1
+    }
+
+    fn distance_and_trap(&self, p: Vec3) -> (f32, f32) {
+        if self.mandelbox {
+            self.mandelbox(p)
+        } else {
+            self.mandelbulb(p)
+        }
+    }
+
+    /// Distance along the ray to the first hit, marched like the shader with the material's
+    /// step limit, range and hit threshold
+    #[cfg(feature = "ui")]
+    pub fn trace(&self, shape: &ShapeUniform, origin: Vec3, dir: Vec3) -> Option<f32> {
+        match self.march(shape, origin, dir) {
+            March::Hit { t, .. } => Some(t),
+            March::Escaped | March::OutOfSteps => None,
+        }
+    }
+
+    /// How a ray marched like [`Self::trace`] ends
+    #[cfg(feature = "ui")]
+    pub fn march(&self, shape: &ShapeUniform, origin: Vec3, dir: Vec3) -> March {
+        let mut t = 0.0;
+        for step in 0..shape.ray_steps {
+            let d = self.distance(origin + dir * t);
+            if d < shape.hit_threshold {
+                return March::Hit { t, steps: step };
+            }
+            t += d;
+            if t > shape.max_dist {
+                return March::Escaped;
+            }
+        }
+        March::OutOfSteps
+    }
+
+    /// Unit gradient of the distance, the surface normal near the surface
+    #[cfg(feature = "ui")]
+    pub fn normal(&self, p: Vec3) -> Vec3 {
+        const E: f32 = 1e-4;
+        let d = |offset: Vec3| self.distance(p + offset) - self.distance(p - offset);
+        Vec3::new(d(Vec3::X * E), d(Vec3::Y * E), d(Vec3::Z * E)).normalize_or_zero()
+    }
+
+    /// Moves `p` along the gradient onto the level set at `threshold`, a few Newton steps
+    #[cfg(feature = "ui")]
+    pub fn project_to_surface(&self, mut p: Vec3, threshold: f32) -> Vec3 {
+        for _ in 0..8 {
+            let d = self.distance(p) - threshold;
+            if d.abs() < threshold * 0.1 {
+                break;
+            }
+            p -= self.normal(p) * d;
+        }
+        p
+    }
+
+    fn mandelbulb(&self, p: Vec3) -> (f32, f32) {
+        let c = self.julia.unwrap_or(p);
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut r = 0.0;
+        let mut trap = f32::MAX;
+        for _ in 0..self.iterations {
+            r = z.length();
+            if r > 2.0 {
+                break;
+            }
+            trap = trap.min(r);
+            let theta = (z.z / r).acos() * self.power;
+            let phi = z.y.atan2(z.x) * self.power;
+            dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+            let zr = r.powf(self.power);
+            z =
+                zr * Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                ) + c;
+        }
+        (0.5 * r.ln() * r / dr, trap)
+    }
+
+    fn mandelbox(&self, p: Vec3) -> (f32, f32) {
+        const MIN_R: f32 = 0.5;
+        const FIXED_R: f32 = 1.0;
+        let offset = self.julia.unwrap_or(p);
+        let mut z = p;
+        let mut dr = 1.0;
+        let mut trap = f32::MAX;
+        for _ in 0..self.iterations {
+            // box fold
+            z = z.clamp(Vec3::splat(-1.0), Vec3::splat(1.0)) * 2.0 - z;
+            // sphere fold
+            let r2 = z.length_squared();
+            if r2 < MIN_R {
+                z *= FIXED_R / MIN_R;
+            } else if r2 < FIXED_R {
+                z *= FIXED_R / r2;
+            }
+            z = z * self.power + offset;
+            dr = dr * self.power.abs() + 1.0;
+            trap = trap.min(z.length());
+        }
+        (z.length() / dr.abs(), trap)
+    }
+}
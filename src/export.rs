@@ -0,0 +1,744 @@
+use crate::compose::{ComposeSettings, burn_in_guides};
+use crate::flare::LensFlare;
+use crate::material::{FractalMaterial, MandelbulbMaterial, OUTPUT_MODE_HDR10, OUTPUT_MODE_SCRGB};
+use crate::navigation::View;
+use crate::output::OutputEncoding;
+use crate::params::{ParamStore, apply_param_changes};
+use crate::power::keep_awake;
+use crate::rng::Rng;
+use bevy::camera::RenderTarget;
+use bevy::camera::visibility::RenderLayers;
+use bevy::core_pipeline::tonemapping::Tonemapping;
+#[cfg(feature = "ui")]
+use bevy::diagnostic::FrameCount;
+use bevy::prelude::*;
+use bevy::render::gpu_readback::{Readback, ReadbackComplete};
+use bevy::render::render_resource::{TextureFormat, TextureUsages};
+use bevy::render::view::Hdr;
+use bevy::window::PrimaryWindow;
+use half::f16;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of frames to let the offscreen camera render before reading it back. The first frames
+/// of a new view can be blank while its pipeline is still being specialized.
+const WARMUP_FRAMES: u32 = 3;
+
+/// Frames to wait before reading back a data export. Its shader variant is not compiled by the
+/// warmup, so the first export has to wait for the pipeline.
+const DATA_WARMUP_FRAMES: u32 = 30;
+
+/// Render layer of the quad rendering march data for a data export
+const DATA_LAYER: usize = 2;
+
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExportSettings>()
+            .add_message::<ExportStill>()
+            .add_message::<ExportData>()
+            .add_message::<StillSaved>()
+            .add_message::<ExportFrame>()
+            .add_message::<FrameSaved>()
+            .add_systems(
+                Update,
+                (
+                    // a still shows the parameters changed earlier in the same frame
+                    (export_hotkey, start_export)
+                        .chain()
+                        .after(apply_param_changes),
+                    start_data_export,
+                    update_noise_seed,
+                    // readbacks arrive over several frames, see the observers below
+                    keep_awake.run_if(any_with_component::<Readback>),
+                ),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StillFormat {
+    Png8,
+    #[cfg(feature = "ui")]
+    Png16,
+    #[cfg(all(feature = "ui", feature = "export"))]
+    Tiff16,
+    /// Scene-linear 32-bit floats, unclamped, for grading elsewhere
+    #[cfg(all(feature = "ui", feature = "export"))]
+    TiffFloat,
+}
+
+impl StillFormat {
+    #[cfg(all(feature = "ui", feature = "export"))]
+    pub const ALL: [StillFormat; 4] = [
+        StillFormat::Png8,
+        StillFormat::Png16,
+        StillFormat::Tiff16,
+        StillFormat::TiffFloat,
+    ];
+    /// TIFF is encoded with the export feature only
+    #[cfg(all(feature = "ui", not(feature = "export")))]
+    pub const ALL: [StillFormat; 2] = [StillFormat::Png8, StillFormat::Png16];
+
+    #[cfg(feature = "ui")]
+    pub fn label(self) -> &'static str {
+        match self {
+            StillFormat::Png8 => "PNG (8-bit)",
+            StillFormat::Png16 => "PNG (16-bit)",
+            #[cfg(feature = "export")]
+            StillFormat::Tiff16 => "TIFF (16-bit)",
+            #[cfg(feature = "export")]
+            StillFormat::TiffFloat => "TIFF (32-bit float, linear)",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            StillFormat::Png8 => "png",
+            #[cfg(feature = "ui")]
+            StillFormat::Png16 => "png",
+            #[cfg(all(feature = "ui", feature = "export"))]
+            StillFormat::Tiff16 => "tiff",
+            #[cfg(all(feature = "ui", feature = "export"))]
+            StillFormat::TiffFloat => "tiff",
+        }
+    }
+
+    /// Whether the still keeps the scene-linear values instead of what the screen shows
+    fn is_linear(self) -> bool {
+        match self {
+            #[cfg(all(feature = "ui", feature = "export"))]
+            StillFormat::TiffFloat => true,
+            _ => false,
+        }
+    }
+}
+
+/// How noise based effects like film grain change over time
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum NoiseLock {
+    /// The same pattern every frame, from the run's seed, a stable texture
+    Static,
+    /// A new pattern every frame, like the grain of film
+    #[cfg(feature = "ui")]
+    PerFrame,
+}
+
+impl NoiseLock {
+    #[cfg(feature = "ui")]
+    pub const ALL: [NoiseLock; 2] = [NoiseLock::Static, NoiseLock::PerFrame];
+
+    #[cfg(feature = "ui")]
+    pub fn label(self) -> &'static str {
+        match self {
+            NoiseLock::Static => "Static",
+            NoiseLock::PerFrame => "New Every Frame",
+        }
+    }
+}
+
+/// File format of per-pixel data exports
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DataFormat {
+    /// One row per pixel with its coordinates
+    Csv,
+    /// NumPy array of shape (height, width, 4)
+    #[cfg(feature = "ui")]
+    Npy,
+}
+
+impl DataFormat {
+    #[cfg(feature = "ui")]
+    pub const ALL: [DataFormat; 2] = [DataFormat::Csv, DataFormat::Npy];
+
+    #[cfg(feature = "ui")]
+    pub fn label(self) -> &'static str {
+        match self {
+            DataFormat::Csv => "CSV",
+            DataFormat::Npy => "NumPy (.npy)",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            DataFormat::Csv => "csv",
+            #[cfg(feature = "ui")]
+            DataFormat::Npy => "npy",
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct ExportSettings {
+    pub format: StillFormat,
+    pub data_format: DataFormat,
+    pub directory: PathBuf,
+    pub noise: NoiseLock,
+}
+
+impl Default for ExportSettings {
+    fn default() -> Self {
+        Self {
+            format: StillFormat::Png8,
+            data_format: DataFormat::Csv,
+            directory: PathBuf::from("screenshots"),
+            noise: NoiseLock::Static,
+        }
+    }
+}
+
+/// Request to save the current view as a still image using the current [`ExportSettings`].
+#[derive(Message)]
+pub struct ExportStill;
+
+/// A still image was written to `path`, with the view's share code next to it in a `.view` file
+#[derive(Message, Clone)]
+pub struct StillSaved {
+    pub path: PathBuf,
+}
+
+/// The share code saved next to a still, to restore the view it shows
+pub fn view_path(still: &Path) -> PathBuf {
+    still.with_extension("view")
+}
+
+/// Request to save the current view as frame of an image sequence at `path`, without a share
+/// code and without it showing up as a still in the gallery
+#[derive(Message)]
+pub struct ExportFrame {
+    pub path: PathBuf,
+}
+
+/// A frame requested with [`ExportFrame`] was written
+#[derive(Message, Clone)]
+pub struct FrameSaved {
+    pub path: PathBuf,
+}
+
+/// A numbered image sequence saved one frame at a time. The next frame is only requested once
+/// the previous one is written, so none is skipped however long it takes to save.
+pub struct FrameSequence {
+    pub directory: PathBuf,
+    /// Index of the frame being saved, or of the next one to request
+    pub frame: u32,
+    pub frames: u32,
+    /// A frame was requested and hasn't been written yet
+    waiting: bool,
+}
+
+impl FrameSequence {
+    pub fn start(directory: PathBuf, frames: u32) -> Self {
+        Self {
+            directory,
+            frame: 0,
+            frames,
+            waiting: false,
+        }
+    }
+
+    /// Moves on once the pending frame is among `saved`, the frames written since the last call.
+    /// Returns the frame to render and request next, `None` while waiting and once complete.
+    pub fn poll<'a>(&mut self, saved: impl IntoIterator<Item = &'a FrameSaved>) -> Option<u32> {
+        if self.is_complete() {
+            return None;
+        }
+        if self.waiting {
+            // other tools save frames too, only this sequence's own count
+            if !saved
+                .into_iter()
+                .any(|frame| frame.path.starts_with(&self.directory))
+            {
+                return None;
+            }
+            self.waiting = false;
+            self.frame += 1;
+            if self.is_complete() {
+                return None;
+            }
+        }
+        self.waiting = true;
+        Some(self.frame)
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.frame >= self.frames
+    }
+
+    /// The request saving `frame` of the sequence
+    pub fn request(&self, frame: u32) -> ExportFrame {
+        ExportFrame {
+            path: self.directory.join(format!("frame_{frame:05}")),
+        }
+    }
+}
+
+/// Request to save per-pixel march data of the current view: steps, orbit trap, depth and hit
+#[derive(Message)]
+pub struct ExportData;
+
+/// F12 saves a still image
+fn export_hotkey(keys: Res<ButtonInput<KeyCode>>, mut exports: MessageWriter<ExportStill>) {
+    if keys.just_pressed(KeyCode::F12) {
+        exports.write(ExportStill);
+    }
+}
+
+/// Renders the fractal once more into an offscreen 16-bit float target and reads it back, so the
+/// saved file keeps more precision than the 8-bit window surface.
+fn start_export(
+    mut commands: Commands,
+    mut requests: MessageReader<ExportStill>,
+    mut frames: MessageReader<ExportFrame>,
+    mut images: ResMut<Assets<Image>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<ExportSettings>,
+    compose: Res<ComposeSettings>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    store: Res<ParamStore>,
+) {
+    // a sequence frame goes to its own path, a still gets a timestamp and its view
+    let frame = frames.read().last().map(|frame| frame.path.clone());
+    if requests.read().count() == 0 && frame.is_none() {
+        return;
+    }
+    let view = materials
+        .get(&fractal.0)
+        .filter(|_| frame.is_none())
+        .map(|mat| View::share_code(&mat.camera, &store));
+    let Ok(win) = window.single() else {
+        return;
+    };
+    // same framing as the window, without the letterbox bars
+    let size = compose.frame_size(Vec2::new(win.width(), win.height()));
+    let width = size.x as u32;
+    let height = size.y as u32;
+    let guides = if compose.guides_in_exports {
+        compose.guide_lines(size)
+    } else {
+        Vec::new()
+    };
+
+    let mut target = Image::new_target_texture(width, height, TextureFormat::Rgba16Float);
+    target.texture_descriptor.usage |= TextureUsages::COPY_SRC;
+    let target = images.add(target);
+
+    let format = settings.format;
+    let is_frame = frame.is_some();
+    let path = match frame {
+        Some(path) => path.with_extension(format.extension()),
+        None => timestamped_path(&settings.directory, "fractal", format.extension()),
+    };
+
+    let camera = commands
+        .spawn((
+            Camera2d,
+            Camera {
+                target: RenderTarget::Image(target.clone().into()),
+                order: -1,
+                ..default()
+            },
+            Hdr,
+            Tonemapping::None,
+        ))
+        .id();
+    // flares are drawn by the camera, not the material, see FlarePlugin
+    if let Some(flare) = materials
+        .get(&fractal.0)
+        .map(|mat| mat.flare)
+        .filter(LensFlare::is_on)
+    {
+        commands.entity(camera).insert(flare);
+    }
+    // the float target holds the output mode's full range, see OutputPlugin. A float TIFF skips
+    // the encoding and keeps the scene-linear values.
+    let encoding = materials
+        .get(&fractal.0)
+        .map(OutputEncoding::of)
+        .filter(|_| !format.is_linear());
+    if let Some(encoding) = encoding {
+        commands.entity(camera).insert(encoding);
+    }
+
+    commands
+        .entity(camera)
+        .insert(Readback::texture(target))
+        .observe(
+            move |event: On<ReadbackComplete>, mut commands: Commands, mut frames: Local<u32>| {
+                *frames += 1;
+                if *frames <= WARMUP_FRAMES {
+                    return;
+                }
+                commands.entity(camera).despawn();
+
+                match write_still(&event.data, width, height, &guides, format, encoding, &path) {
+                    Ok(()) => {
+                        info!("Saved {}", path.display());
+                        if let Some(view) = &view
+                            && let Err(err) = std::fs::write(view_path(&path), view)
+                        {
+                            warn!("Failed to save the view of {}: {err}", path.display());
+                        }
+                        if is_frame {
+                            commands.write_message(FrameSaved { path: path.clone() });
+                        } else {
+                            commands.write_message(StillSaved { path: path.clone() });
+                        }
+                    }
+                    Err(err) => error!("Failed to save {}: {err}", path.display()),
+                }
+            },
+        );
+}
+
+/// Gives every material with film grain the seed the noise lock asks for: the same one from the
+/// run's seed, or a new one each frame. Materials without grain are left alone, every change
+/// re-uploads them.
+fn update_noise_seed(
+    settings: Res<ExportSettings>,
+    #[cfg(feature = "ui")] frame: Res<FrameCount>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    mut base: Local<Option<u32>>,
+) {
+    let base = *base.get_or_insert_with(|| Rng::stream("noise").next_u64() as u32);
+    let seed = match settings.noise {
+        NoiseLock::Static => base,
+        #[cfg(feature = "ui")]
+        NoiseLock::PerFrame => base.wrapping_add(frame.0),
+    };
+    let ids: Vec<_> = materials.ids().collect();
+    for id in ids {
+        let stale = materials
+            .get(id)
+            .is_some_and(|mat| mat.shading.grain > 0.0 && mat.shading.noise_seed != seed);
+        if stale && let Some(mat) = materials.get_mut(id) {
+            mat.shading.noise_seed = seed;
+        }
+    }
+}
+
+/// Renders a data variant of the material (see `march_data` in the shader) offscreen and reads it
+/// back. The view is a 16-bit float target like stills, so values keep about three significant
+/// digits.
+fn start_data_export(
+    mut commands: Commands,
+    mut requests: MessageReader<ExportData>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<ExportSettings>,
+    compose: Res<ComposeSettings>,
+) {
+    if requests.read().count() == 0 {
+        return;
+    }
+    let (Ok(win), Some(mat)) = (window.single(), materials.get(&fractal.0)) else {
+        return;
+    };
+    let size = compose.frame_size(Vec2::new(win.width(), win.height()));
+    let width = size.x as u32;
+    let height = size.y as u32;
+
+    let mut material = mat.clone();
+    material.data_output = true;
+    material.shading.supersampling_enabled = 0;
+    let material = materials.add(material);
+
+    let mut target = Image::new_target_texture(width, height, TextureFormat::Rgba16Float);
+    target.texture_descriptor.usage |= TextureUsages::COPY_SRC;
+    let target = images.add(target);
+
+    let format = settings.data_format;
+    let path = timestamped_path(&settings.directory, "fractal_data", format.extension());
+
+    let quad = commands
+        .spawn((
+            Mesh2d(meshes.add(Rectangle::default())),
+            MeshMaterial2d(material),
+            Transform::default().with_scale(size.extend(1.0)),
+            RenderLayers::layer(DATA_LAYER),
+        ))
+        .id();
+    let camera = commands
+        .spawn((
+            Camera2d,
+            Camera {
+                target: RenderTarget::Image(target.clone().into()),
+                order: -1,
+                ..default()
+            },
+            Hdr,
+            Tonemapping::None,
+            RenderLayers::layer(DATA_LAYER),
+        ))
+        .id();
+
+    commands
+        .entity(camera)
+        .insert(Readback::texture(target))
+        .observe(
+            move |event: On<ReadbackComplete>, mut commands: Commands, mut frames: Local<u32>| {
+                *frames += 1;
+                if *frames <= DATA_WARMUP_FRAMES {
+                    return;
+                }
+                commands.entity(camera).despawn();
+                commands.entity(quad).despawn();
+
+                let values: Vec<f32> = event
+                    .data
+                    .chunks_exact(2)
+                    .map(|texel| f16::from_le_bytes([texel[0], texel[1]]).to_f32())
+                    .collect();
+                match write_data(&values, width, height, format, &path) {
+                    Ok(()) => info!("Saved {}", path.display()),
+                    Err(err) => error!("Failed to save {}: {err}", path.display()),
+                }
+            },
+        );
+}
+
+/// Writes four values per pixel, in rows from the top of the view
+fn write_data(
+    values: &[f32],
+    width: u32,
+    height: u32,
+    format: DataFormat,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if values.len() != (width * height * 4) as usize {
+        return Err("readback size mismatch".into());
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    match format {
+        DataFormat::Csv => {
+            writeln!(file, "x,y,steps,trap,depth,hit")?;
+            for (i, pixel) in values.chunks_exact(4).enumerate() {
+                let (x, y) = (i as u32 % width, i as u32 / width);
+                let [steps, trap, depth, hit] = [pixel[0], pixel[1], pixel[2], pixel[3]];
+                writeln!(file, "{x},{y},{steps},{trap},{depth},{hit}")?;
+            }
+        }
+        #[cfg(feature = "ui")]
+        DataFormat::Npy => {
+            // format 1.0, see https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html
+            let mut header = format!(
+                "{{'descr': '<f4', 'fortran_order': False, 'shape': ({height}, {width}, 4), }}"
+            );
+            // magic, version and length take 10 bytes, the whole preamble is padded to 64
+            while (10 + header.len() + 1) % 64 != 0 {
+                header.push(' ');
+            }
+            header.push('\n');
+            file.write_all(b"\x93NUMPY\x01\x00")?;
+            file.write_all(&(header.len() as u16).to_le_bytes())?;
+            file.write_all(header.as_bytes())?;
+            for value in values {
+                file.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    file.flush()?;
+    Ok(())
+}
+
+pub fn timestamped_path(directory: &Path, stem: &str, extension: &str) -> PathBuf {
+    directory.join(format!("{stem}_{}.{extension}", timestamp()))
+}
+
+/// A new folder for an image sequence, e.g. `dive_<timestamp>`
+pub fn timestamped_folder(directory: &Path, stem: &str) -> PathBuf {
+    directory.join(format!("{stem}_{}", timestamp()))
+}
+
+fn timestamp() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default()
+}
+
+/// Converts raw `Rgba16Float` texels to the requested format and writes the file. `encoding` is
+/// the output encoding the texels were rendered with, `None` for scene-linear values.
+fn write_still(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    guides: &[(Vec2, Vec2)],
+    format: StillFormat,
+    encoding: Option<OutputEncoding>,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let mut encoded: Vec<f32> = data
+        .chunks_exact(2)
+        .enumerate()
+        .map(|(i, texel)| {
+            let v = f16::from_le_bytes([texel[0], texel[1]]).to_f32();
+            encode_still(v, i % 4 == 3, format, encoding)
+        })
+        .collect();
+    burn_in_guides(guides, &mut encoded, width, height);
+
+    match format {
+        StillFormat::Png8 => {
+            let pixels = encoded.iter().map(|v| (v * 255.0).round() as u8).collect();
+            image::RgbaImage::from_raw(width, height, pixels)
+                .ok_or("readback size mismatch")?
+                .save_with_format(path, image::ImageFormat::Png)?;
+        }
+        #[cfg(feature = "ui")]
+        StillFormat::Png16 => save_16_bit(encoded, width, height, path, image::ImageFormat::Png)?,
+        #[cfg(all(feature = "ui", feature = "export"))]
+        StillFormat::Tiff16 => save_16_bit(encoded, width, height, path, image::ImageFormat::Tiff)?,
+        #[cfg(all(feature = "ui", feature = "export"))]
+        StillFormat::TiffFloat => {
+            image::Rgba32FImage::from_raw(width, height, encoded)
+                .ok_or("readback size mismatch")?
+                .save_with_format(path, image::ImageFormat::Tiff)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "ui")]
+fn save_16_bit(
+    encoded: Vec<f32>,
+    width: u32,
+    height: u32,
+    path: &Path,
+    image_format: image::ImageFormat,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let pixels = encoded
+        .iter()
+        .map(|v| (v * 65535.0).round() as u16)
+        .collect();
+    image::ImageBuffer::<image::Rgba<u16>, Vec<u16>>::from_raw(width, height, pixels)
+        .ok_or("readback size mismatch")?
+        .save_with_format(path, image_format)?;
+    Ok(())
+}
+
+/// One channel of a still as written to the file. Only the integer formats are clamped, a float
+/// TIFF keeps highlights above 1.0 and stays linear.
+fn encode_still(v: f32, alpha: bool, format: StillFormat, encoding: Option<OutputEncoding>) -> f32 {
+    let mode = encoding.map(|encoding| encoding.mode);
+    if format.is_linear() {
+        v
+    } else if alpha || mode == Some(OUTPUT_MODE_HDR10) {
+        // alpha is stored linearly, and a PQ signal is already in its final 0..1 range
+        v.clamp(0.0, 1.0)
+    } else {
+        // scRGB puts paper white at paper_white / 80, scale it back to 1.0 so the still shows
+        // what SDR would instead of clipping everything above 80 nits
+        let v = match encoding {
+            Some(encoding) if encoding.mode == OUTPUT_MODE_SCRGB => {
+                v * 80.0 / encoding.paper_white_nits.max(1.0)
+            }
+            _ => v,
+        };
+        // The window surface is sRGB, so the hardware encodes the shader output on the way to
+        // the screen. The float target has no such step, so apply the same encoding here.
+        linear_to_srgb(v.clamp(0.0, 1.0))
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "ui")]
+    use crate::material::OUTPUT_MODE_SDR;
+
+    #[cfg(all(feature = "ui", feature = "export"))]
+    #[test]
+    fn float_stills_keep_highlights() {
+        assert_eq!(encode_still(4.0, false, StillFormat::TiffFloat, None), 4.0);
+        assert_eq!(
+            encode_still(-0.5, false, StillFormat::TiffFloat, None),
+            -0.5
+        );
+    }
+
+    #[cfg(feature = "ui")]
+    #[test]
+    fn integer_stills_are_clamped_and_encoded() {
+        let encoding = OutputEncoding {
+            mode: OUTPUT_MODE_SDR,
+            paper_white_nits: 203.0,
+        };
+        let sdr = Some(encoding);
+        assert!((encode_still(4.0, false, StillFormat::Png16, sdr) - 1.0).abs() < 1e-6);
+        assert_eq!(encode_still(4.0, true, StillFormat::Png8, sdr), 1.0);
+        assert!((encode_still(0.5, false, StillFormat::Png8, sdr) - 0.7354).abs() < 1e-3);
+        // an HDR10 signal is written as it is
+        let hdr10 = Some(OutputEncoding {
+            mode: OUTPUT_MODE_HDR10,
+            ..encoding
+        });
+        assert_eq!(encode_still(0.5, false, StillFormat::Png16, hdr10), 0.5);
+    }
+
+    #[cfg(feature = "ui")]
+    #[test]
+    fn scrgb_stills_are_display_referred() {
+        let scrgb = Some(OutputEncoding {
+            mode: OUTPUT_MODE_SCRGB,
+            paper_white_nits: 203.0,
+        });
+        // a scene value of 0.5 rendered at a paper white of 203 nits
+        let rendered = 0.5 * 203.0 / 80.0;
+        for format in [StillFormat::Png8, StillFormat::Png16] {
+            let sdr = encode_still(0.5, false, format, None);
+            assert!((encode_still(rendered, false, format, scrgb) - sdr).abs() < 1e-5);
+        }
+        // paper white is white, not a clipped highlight
+        assert!((encode_still(203.0 / 80.0, false, StillFormat::Png8, scrgb) - 1.0).abs() < 1e-5);
+        #[cfg(all(feature = "ui", feature = "export"))]
+        assert_eq!(
+            encode_still(rendered, false, StillFormat::TiffFloat, scrgb),
+            rendered
+        );
+    }
+
+    #[test]
+    fn frame_sequences_wait_for_each_frame() {
+        let mut sequence = FrameSequence::start(PathBuf::from("dive"), 2);
+        assert_eq!(sequence.poll([]), Some(0));
+        assert_eq!(sequence.poll([]), None);
+        // a frame of another tool doesn't count
+        let other = FrameSaved {
+            path: PathBuf::from("time_lapse/frame_00000"),
+        };
+        assert_eq!(sequence.poll([&other]), None);
+
+        let saved = FrameSaved {
+            path: sequence.request(0).path,
+        };
+        assert_eq!(sequence.poll([&saved]), Some(1));
+        let saved = FrameSaved {
+            path: sequence.request(1).path,
+        };
+        assert_eq!(sequence.poll([&saved]), None);
+        assert!(sequence.is_complete());
+        assert_eq!(sequence.frame, 2);
+    }
+}
@@ -0,0 +1,381 @@
+//! Math expressions for procedural timeline tracks and parameter fields, e.g. `4 + 2*sin(t*0.5)`
+//! or `1/zoom`. An expression is parsed once into a tree and evaluated on the CPU every frame.
+//!
+//! Supports numbers, `+ - * / % ^`, parentheses, the variables `t` (seconds) and `key` (the
+//! value the expression replaces), every parameter by its [`Param::name`], the constants `pi`
+//! and `tau`, and the functions in [`FUNCTIONS`].
+
+use crate::params::{Param, ParamStore};
+use std::f32::consts::{PI, TAU};
+use std::fmt;
+
+/// A callable function, given its evaluated arguments
+type Function = fn(&[f32]) -> f32;
+
+/// Name, argument count and implementation of every callable function
+const FUNCTIONS: [(&str, usize, Function); 18] = [
+    ("sin", 1, |a| a[0].sin()),
+    ("cos", 1, |a| a[0].cos()),
+    ("tan", 1, |a| a[0].tan()),
+    ("abs", 1, |a| a[0].abs()),
+    ("sqrt", 1, |a| a[0].sqrt()),
+    ("exp", 1, |a| a[0].exp()),
+    ("log", 1, |a| a[0].ln()),
+    ("floor", 1, |a| a[0].floor()),
+    ("ceil", 1, |a| a[0].ceil()),
+    ("fract", 1, |a| a[0] - a[0].floor()),
+    ("sign", 1, |a| a[0].signum()),
+    ("min", 2, |a| a[0].min(a[1])),
+    ("max", 2, |a| a[0].max(a[1])),
+    ("pow", 2, |a| a[0].powf(a[1])),
+    ("atan2", 2, |a| a[0].atan2(a[1])),
+    ("clamp", 3, |a| a[0].clamp(a[1], a[2].max(a[1]))),
+    ("mix", 3, |a| a[0] + (a[1] - a[0]) * a[2]),
+    ("smoothstep", 3, |a| {
+        let x = ((a[2] - a[0]) / (a[1] - a[0])).clamp(0.0, 1.0);
+        x * x * (3.0 - 2.0 * x)
+    }),
+];
+
+/// Most arguments any function in [`FUNCTIONS`] takes
+const MAX_ARGS: usize = 3;
+
+/// Values of the variables an expression can read
+#[derive(Clone, Copy)]
+pub struct Vars<'a> {
+    pub t: f32,
+    pub key: f32,
+    pub store: &'a ParamStore,
+}
+
+#[derive(Debug)]
+pub struct ExprError(pub String);
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+/// A parsed expression together with the text it was parsed from
+pub struct Expression {
+    source: String,
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ExprError> {
+        let mut parser = Parser {
+            tokens: tokenize(source)?,
+            pos: 0,
+        };
+        let root = parser.expr()?;
+        if let Some(token) = parser.peek() {
+            return Err(ExprError(format!("unexpected {token}")));
+        }
+        Ok(Self {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn eval(&self, vars: Vars) -> f32 {
+        self.root.eval(vars)
+    }
+
+    /// Whether the value changes over time, as opposed to only when the parameters it reads do
+    pub fn uses_time(&self) -> bool {
+        self.root.uses_time()
+    }
+}
+
+enum Node {
+    Num(f32),
+    Time,
+    Key,
+    Param(Param),
+    Neg(Box<Node>),
+    Binary(char, Box<Node>, Box<Node>),
+    Call(fn(&[f32]) -> f32, Vec<Node>),
+}
+
+impl Node {
+    fn eval(&self, vars: Vars) -> f32 {
+        match self {
+            Node::Num(value) => *value,
+            Node::Time => vars.t,
+            Node::Key => vars.key,
+            Node::Param(param) => vars.store.get(*param),
+            Node::Neg(node) => -node.eval(vars),
+            Node::Binary(op, lhs, rhs) => {
+                let (a, b) = (lhs.eval(vars), rhs.eval(vars));
+                match op {
+                    '+' => a + b,
+                    '-' => a - b,
+                    '*' => a * b,
+                    '/' => a / b,
+                    '%' => a.rem_euclid(b),
+                    _ => a.powf(b),
+                }
+            }
+            Node::Call(function, args) => {
+                let mut values = [0.0; MAX_ARGS];
+                for (value, arg) in values.iter_mut().zip(args) {
+                    *value = arg.eval(vars);
+                }
+                function(&values[..args.len()])
+            }
+        }
+    }
+
+    fn uses_time(&self) -> bool {
+        match self {
+            Node::Time => true,
+            Node::Num(_) | Node::Key | Node::Param(_) => false,
+            Node::Neg(node) => node.uses_time(),
+            Node::Binary(_, lhs, rhs) => lhs.uses_time() || rhs.uses_time(),
+            Node::Call(_, args) => args.iter().any(Node::uses_time),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(f32),
+    Ident(String),
+    Sym(char),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Num(value) => write!(f, "number {value}"),
+            Token::Ident(name) => write!(f, "'{name}'"),
+            Token::Sym(sym) => write!(f, "'{sym}'"),
+        }
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, ExprError> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_ascii_digit() || c == '.' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let value = rest[..len]
+                .parse()
+                .map_err(|_| ExprError(format!("invalid number {}", &rest[..len])))?;
+            tokens.push(Token::Num(value));
+            len
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].into()));
+            len
+        } else if "+-*/%^(),".contains(c) {
+            tokens.push(Token::Sym(c));
+            1
+        } else {
+            return Err(ExprError(format!("unexpected character '{c}'")));
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, one method per precedence level
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token, ExprError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| ExprError("unexpected end of expression".into()))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, sym: char) -> bool {
+        let found = self.peek() == Some(&Token::Sym(sym));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, sym: char) -> Result<(), ExprError> {
+        match self.next()? {
+            Token::Sym(found) if found == sym => Ok(()),
+            token => Err(ExprError(format!("expected '{sym}', found {token}"))),
+        }
+    }
+
+    /// `a + b`, `a - b`
+    fn expr(&mut self) -> Result<Node, ExprError> {
+        let mut node = self.term()?;
+        while let Some(Token::Sym(op @ ('+' | '-'))) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.term()?));
+        }
+        Ok(node)
+    }
+
+    /// `a * b`, `a / b`, `a % b`
+    fn term(&mut self) -> Result<Node, ExprError> {
+        let mut node = self.unary()?;
+        while let Some(Token::Sym(op @ ('*' | '/' | '%'))) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            node = Node::Binary(op, Box::new(node), Box::new(self.unary()?));
+        }
+        Ok(node)
+    }
+
+    /// `-a`, binds looser than `^` so `-2^2` is -4
+    fn unary(&mut self) -> Result<Node, ExprError> {
+        if self.eat('-') {
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    /// `a ^ b`, right associative
+    fn power(&mut self) -> Result<Node, ExprError> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            return Ok(Node::Binary('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Node, ExprError> {
+        match self.next()? {
+            Token::Num(value) => Ok(Node::Num(value)),
+            Token::Sym('(') => {
+                let node = self.expr()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            Token::Ident(name) if self.eat('(') => {
+                let (_, arity, function) = FUNCTIONS
+                    .iter()
+                    .find(|(f, ..)| *f == name)
+                    .ok_or_else(|| ExprError(format!("unknown function '{name}'")))?;
+                let mut args = Vec::new();
+                if !self.eat(')') {
+                    loop {
+                        args.push(self.expr()?);
+                        if self.eat(')') {
+                            break;
+                        }
+                        self.expect(',')?;
+                    }
+                }
+                if args.len() != *arity {
+                    return Err(ExprError(format!(
+                        "{name} takes {arity} argument(s), got {}",
+                        args.len()
+                    )));
+                }
+                Ok(Node::Call(*function, args))
+            }
+            Token::Ident(name) => match name.as_str() {
+                "t" => Ok(Node::Time),
+                "key" => Ok(Node::Key),
+                "pi" => Ok(Node::Num(PI)),
+                "tau" => Ok(Node::Num(TAU)),
+                _ => Param::from_name(&name)
+                    .map(Node::Param)
+                    .ok_or_else(|| ExprError(format!("unknown variable '{name}'"))),
+            },
+            token => Err(ExprError(format!("unexpected {token}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::MandelbulbMaterial;
+
+    fn eval(source: &str) -> f32 {
+        let store = ParamStore::capture(&MandelbulbMaterial::default());
+        let vars = Vars {
+            t: 2.0,
+            key: 10.0,
+            store: &store,
+        };
+        Expression::parse(source).unwrap().eval(vars)
+    }
+
+    fn error(source: &str) -> String {
+        Expression::parse(source).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn operators_follow_precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7.0);
+        assert_eq!(eval("(1 + 2) * 3"), 9.0);
+        assert_eq!(eval("10 - 4 - 3"), 3.0);
+        assert_eq!(eval("2 * 3 ^ 2"), 18.0);
+        assert_eq!(eval("2 ^ 3 ^ 2"), 512.0);
+        assert_eq!(eval("-7 % 3"), 2.0);
+    }
+
+    #[test]
+    fn unary_minus_binds_looser_than_power() {
+        assert_eq!(eval("-2 ^ 2"), -4.0);
+        assert_eq!(eval("(-2) ^ 2"), 4.0);
+        assert_eq!(eval("2 ^ -1"), 0.5);
+        assert_eq!(eval("--3"), 3.0);
+        assert_eq!(eval("4 * -t"), -8.0);
+    }
+
+    #[test]
+    fn functions_and_variables() {
+        assert_eq!(eval("max(t, key)"), 10.0);
+        assert_eq!(eval("clamp(key, 0, 1 + 1)"), 2.0);
+        assert_eq!(eval("sin(0) + cos(0)"), 1.0);
+        assert_eq!(eval("floor(pi)"), 3.0);
+        let power = MandelbulbMaterial::default().shape.power;
+        assert_eq!(eval("power * 2"), power * 2.0);
+    }
+
+    #[test]
+    fn errors_name_what_is_wrong() {
+        assert_eq!(error("foo(1)"), "unknown function 'foo'");
+        assert_eq!(error("sparkle + 1"), "unknown variable 'sparkle'");
+        assert_eq!(error("min(1)"), "min takes 2 argument(s), got 1");
+        assert_eq!(error("1 +"), "unexpected end of expression");
+        assert_eq!(error("(1 + 2"), "unexpected end of expression");
+        assert_eq!(error("1 2"), "unexpected number 2");
+        assert_eq!(error("sin(1; 2)"), "unexpected character ';'");
+        assert_eq!(error("1.2.3"), "invalid number 1.2.3");
+        assert_eq!(error("max(1 2)"), "expected ',', found number 2");
+    }
+
+    #[test]
+    fn time_dependence_is_tracked() {
+        assert!(Expression::parse("key + sin(t)").unwrap().uses_time());
+        assert!(!Expression::parse("1 / zoom").unwrap().uses_time());
+    }
+}
@@ -0,0 +1,163 @@
+use crate::MainCamera;
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use crate::post_pass::PostPass;
+use bevy::core_pipeline::FullscreenShader;
+use bevy::core_pipeline::core_2d::graph::{Core2d, Node2d};
+use bevy::ecs::query::QueryItem;
+use bevy::prelude::*;
+use bevy::render::extract_component::{
+    DynamicUniformIndex, ExtractComponent, ExtractComponentPlugin, UniformComponentPlugin,
+};
+use bevy::render::render_graph::{
+    NodeRunError, RenderGraphContext, RenderGraphExt, RenderLabel, ViewNode, ViewNodeRunner,
+};
+use bevy::render::render_resource::{
+    CachedRenderPipelineId, PipelineCache, ShaderType, TextureFormat,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::view::ViewTarget;
+use bevy::render::{RenderApp, RenderStartup};
+
+const SHADER_PATH: &str = "shaders/flare.wgsl";
+
+/// Anamorphic lens flares. A fullscreen pass after the main pass, before tone mapping, finds the
+/// pixels brighter than a threshold, like specular hits and glow, and smears them into long
+/// horizontal streaks with optional starburst rays. It runs on every camera with a [`LensFlare`],
+/// which the main camera and still exports take from the main material.
+pub struct FlarePlugin;
+
+impl Plugin for FlarePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            ExtractComponentPlugin::<LensFlare>::default(),
+            UniformComponentPlugin::<LensFlare>::default(),
+        ))
+        .add_systems(Update, sync_main_camera_flare);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_systems(RenderStartup, init_flare_pipeline)
+            .add_render_graph_node::<ViewNodeRunner<FlareNode>>(Core2d, FlareLabel)
+            .add_render_graph_edges(
+                Core2d,
+                (Node2d::EndMainPass, FlareLabel, Node2d::Tonemapping),
+            );
+    }
+}
+
+/// Flare settings of a camera, see `FlareUniform` in `shaders/flare.wgsl`. Also kept on the
+/// material so share codes and the parameter system see them.
+#[derive(Component, ExtractComponent, ShaderType, Reflect, Clone, Copy, PartialEq, Debug)]
+pub struct LensFlare {
+    pub color: Vec4, // linear RGB tint of the streaks, w unused
+    pub intensity: f32,
+    pub threshold: f32, // brightness above which a pixel casts streaks
+    pub length: f32,    // reach of the horizontal streak as a fraction of the image width
+    pub starburst: f32, // strength of the vertical and diagonal rays, 0=streaks only
+}
+
+impl Default for LensFlare {
+    fn default() -> Self {
+        Self {
+            color: Vec4::new(0.6, 0.8, 1.0, 0.0),
+            intensity: 0.0,
+            threshold: 0.8,
+            length: 0.3,
+            starburst: 0.0,
+        }
+    }
+}
+
+impl LensFlare {
+    /// Whether the pass has anything to draw
+    pub fn is_on(&self) -> bool {
+        self.intensity > 0.0
+    }
+}
+
+/// Gives the main camera the main material's flare, or takes it away when it is off so the
+/// pass is skipped
+fn sync_main_camera_flare(
+    mut commands: Commands,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    cameras: Query<(Entity, Option<&LensFlare>), With<MainCamera>>,
+) {
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let wanted = Some(mat.flare).filter(LensFlare::is_on);
+    for (entity, current) in cameras.iter() {
+        match wanted {
+            Some(flare) if current != Some(&flare) => {
+                commands.entity(entity).insert(flare);
+            }
+            None if current.is_some() => {
+                commands.entity(entity).remove::<LensFlare>();
+            }
+            _ => {}
+        }
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct FlareLabel;
+
+/// One pipeline per view target format, SDR and HDR
+#[derive(Resource)]
+struct FlarePipeline {
+    pass: PostPass<LensFlare>,
+    sdr: CachedRenderPipelineId,
+    hdr: CachedRenderPipelineId,
+}
+
+fn init_flare_pipeline(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    asset_server: Res<AssetServer>,
+    fullscreen_shader: Res<FullscreenShader>,
+    pipeline_cache: Res<PipelineCache>,
+) {
+    let pass = PostPass::new("flare", asset_server.load(SHADER_PATH), &render_device);
+    let sdr = pass.queue(
+        &pipeline_cache,
+        &fullscreen_shader,
+        TextureFormat::bevy_default(),
+        Vec::new(),
+    );
+    let hdr = pass.queue(
+        &pipeline_cache,
+        &fullscreen_shader,
+        ViewTarget::TEXTURE_FORMAT_HDR,
+        Vec::new(),
+    );
+    commands.insert_resource(FlarePipeline { pass, sdr, hdr });
+}
+
+#[derive(Default)]
+struct FlareNode;
+
+impl ViewNode for FlareNode {
+    type ViewQuery = (&'static ViewTarget, &'static DynamicUniformIndex<LensFlare>);
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (target, index): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let flare = world.resource::<FlarePipeline>();
+        let pipeline = if target.is_hdr() {
+            flare.hdr
+        } else {
+            flare.sdr
+        };
+        // reads the frame so far and writes it with the streaks added
+        flare
+            .pass
+            .run(render_context, world, target, pipeline, index)
+    }
+}
@@ -0,0 +1,189 @@
+use crate::material::Formula;
+#[cfg(feature = "scripting")]
+use crate::script;
+#[cfg(feature = "ui")]
+use crate::units::Quantity;
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// Folder scanned for formula plugins at startup, one sub-folder per plugin
+pub const FORMULAS_DIR: &str = "formulas";
+
+/// Number of parameter slots a plugin formula can use, see `ShapeUniform::plugin_params`
+pub const MAX_PLUGIN_PARAMS: usize = 8;
+
+/// The fractal shader, plugin shaders are this source with the plugin's distance estimator
+/// appended. Edits to the asset file are not hot reloaded into plugin variants.
+pub const BASE_SHADER: &str = include_str!("../assets/shaders/mandelbulb.wgsl");
+
+static PLUGINS: OnceLock<Vec<FormulaPlugin>> = OnceLock::new();
+static LABELS: OnceLock<Vec<&'static str>> = OnceLock::new();
+
+/// Loads third-party formulas from [`FORMULAS_DIR`].
+///
+/// A plugin is a folder containing `formula.ron`, describing its name and parameters, and
+/// `de.wgsl`, which defines `fn plugin_de(p: vec3<f32>) -> vec2<f32>` returning the distance
+/// estimate and an orbit trap value like the built-in formulas. The snippet can use everything
+/// in the fractal shader, its parameters are read with `plugin_param(i)`.
+///
+/// Instead of raw WGSL a plugin can ship `de.frac`, a sandboxed formula script (see
+/// [`script`]), which is the safer choice for formulas from untrusted sources.
+pub struct FormulasPlugin;
+
+impl Plugin for FormulasPlugin {
+    fn build(&self, app: &mut App) {
+        // loaded during build so the formula list is complete before any system runs
+        let mut shaders = app.world_mut().resource_mut::<Assets<Shader>>();
+        let plugins = load_plugins(Path::new(FORMULAS_DIR), &mut shaders);
+        if !plugins.is_empty() {
+            info!("Loaded {} formula plugin(s)", plugins.len());
+        }
+        let _ = PLUGINS.set(plugins);
+    }
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    name: String,
+    #[cfg(feature = "ui")]
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    params: Vec<PluginParam>,
+    #[serde(default)]
+    iterations: Option<u32>,
+    #[serde(default)]
+    zoom: Option<f32>,
+}
+
+/// A parameter exposed by a plugin formula, shown as a slider when the formula is selected
+#[derive(Deserialize, Clone, Debug)]
+pub struct PluginParam {
+    #[cfg(any(feature = "ui", feature = "scripting"))]
+    pub name: String,
+    pub default: f32,
+    #[cfg(feature = "ui")]
+    pub min: f32,
+    #[cfg(feature = "ui")]
+    pub max: f32,
+    /// What the value measures, `Angle` values are in radians
+    #[cfg(feature = "ui")]
+    #[serde(default)]
+    pub quantity: Quantity,
+}
+
+pub struct FormulaPlugin {
+    pub name: &'static str,
+    #[cfg(feature = "ui")]
+    pub description: String,
+    pub params: Vec<PluginParam>,
+    /// Iteration count to start from when the formula is selected, the current one if `None`
+    pub iterations: Option<u32>,
+    /// Camera distance that frames the shape, the current one if `None`
+    pub zoom: Option<f32>,
+    pub shader: Handle<Shader>,
+}
+
+/// Every loaded plugin, indexed by `Formula::Plugin`
+pub fn plugins() -> &'static [FormulaPlugin] {
+    PLUGINS.get().map(Vec::as_slice).unwrap_or_default()
+}
+
+/// Labels of the built-in formulas followed by the plugins, in `Formula::all` order
+pub fn formula_labels() -> &'static [&'static str] {
+    LABELS.get_or_init(|| Formula::all().map(Formula::label).collect())
+}
+
+fn load_plugins(dir: &Path, shaders: &mut Assets<Shader>) -> Vec<FormulaPlugin> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    // sorted, so a formula's index is stable between runs
+    let mut folders: Vec<_> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    folders.sort();
+
+    let mut plugins = Vec::new();
+    for folder in folders {
+        if plugins.len() > u8::MAX as usize {
+            warn!("Too many formula plugins, ignoring {}", folder.display());
+            continue;
+        }
+        match load_plugin(&folder, shaders) {
+            Ok(plugin) => plugins.push(plugin),
+            Err(err) => warn!("Skipping formula plugin {}: {err}", folder.display()),
+        }
+    }
+    plugins
+}
+
+fn load_plugin(
+    folder: &Path,
+    shaders: &mut Assets<Shader>,
+) -> Result<FormulaPlugin, Box<dyn std::error::Error>> {
+    let manifest: Manifest = ron::from_str(&std::fs::read_to_string(folder.join("formula.ron"))?)?;
+    let mut params = manifest.params;
+
+    // a script is preferred, it declares its own params
+    let script_path = folder.join("de.frac");
+    let (snippet_path, snippet) = if script_path.exists() {
+        let (wgsl, script_params) = compile_script(&script_path)?;
+        if !params.is_empty() {
+            return Err("params of a script formula are declared in de.frac".into());
+        }
+        params = script_params;
+        (script_path, wgsl)
+    } else {
+        let snippet_path = folder.join("de.wgsl");
+        let snippet = std::fs::read_to_string(&snippet_path)?;
+        if !snippet.contains("fn plugin_de") {
+            return Err("de.wgsl does not define plugin_de".into());
+        }
+        (snippet_path, snippet)
+    };
+
+    if params.len() > MAX_PLUGIN_PARAMS {
+        warn!(
+            "{} declares {} parameters, only the first {MAX_PLUGIN_PARAMS} are used",
+            manifest.name,
+            params.len()
+        );
+        params.truncate(MAX_PLUGIN_PARAMS);
+    }
+
+    let source = format!(
+        "{BASE_SHADER}\n// formula plugin: {}\n{snippet}",
+        manifest.name
+    );
+    let shader = shaders.add(Shader::from_wgsl(
+        source,
+        snippet_path.to_string_lossy().into_owned(),
+    ));
+
+    Ok(FormulaPlugin {
+        name: manifest.name.leak(),
+        #[cfg(feature = "ui")]
+        description: manifest.description,
+        params,
+        iterations: manifest.iterations,
+        zoom: manifest.zoom,
+        shader,
+    })
+}
+
+/// The WGSL of a `de.frac` script and the params it declares
+#[cfg(feature = "scripting")]
+fn compile_script(path: &Path) -> Result<(String, Vec<PluginParam>), Box<dyn std::error::Error>> {
+    let compiled = script::compile(&std::fs::read_to_string(path)?)?;
+    Ok((compiled.wgsl, compiled.params))
+}
+
+#[cfg(not(feature = "scripting"))]
+fn compile_script(_path: &Path) -> Result<(String, Vec<PluginParam>), Box<dyn std::error::Error>> {
+    Err("formula scripts need the scripting feature".into())
+}
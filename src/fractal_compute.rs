@@ -0,0 +1,207 @@
+//! Shared setup of compute passes that evaluate the fractal, like particles and the coloring
+//! histogram. Their entry points live in `shaders/mandelbulb.wgsl` behind a shader def, so they
+//! see exactly the estimator the main material renders. Groups 0 and 1 are empty (the view and
+//! mesh in the material pipeline), group 2 holds the material's uniforms in the same slots, and
+//! group 3 is free for the pass's own bindings.
+
+use crate::material::{
+    AnimationUniform, CameraUniform, Formula, FractalMaterial, MandelbulbMaterial, ShadingUniform,
+    ShapeUniform, estimator_shader,
+};
+use bevy::prelude::*;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::render_resource::binding_types::uniform_buffer;
+use bevy::render::render_resource::{
+    BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, ComputePass,
+    ComputePipelineDescriptor, ShaderStages, UniformBuffer,
+};
+use bevy::render::renderer::{RenderDevice, RenderQueue};
+use bevy::render::{Render, RenderApp, RenderSystems};
+
+pub struct FractalComputePlugin;
+
+impl Plugin for FractalComputePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FractalUniforms>()
+            .add_plugins(ExtractResourcePlugin::<FractalUniforms>::default())
+            .add_systems(PostUpdate, capture_fractal_uniforms);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<FractalUniformBuffers>()
+            .add_systems(
+                Render,
+                prepare_fractal_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        // created here rather than at render startup so passes can build their pipelines from
+        // it in their own startup systems
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<FractalComputeLayouts>();
+        }
+    }
+}
+
+/// Compute pipeline variant, one per distance estimator
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub struct EstimatorKey {
+    pub formula: Formula,
+    pub geometry_graph: bool,
+}
+
+/// The main material's uniforms, copied to the render world each frame
+#[derive(Resource, Clone, Default, ExtractResource)]
+pub struct FractalUniforms {
+    pub key: EstimatorKey,
+    camera: CameraUniform,
+    shape: ShapeUniform,
+    shading: ShadingUniform,
+    animation: AnimationUniform,
+}
+
+fn capture_fractal_uniforms(
+    mut uniforms: ResMut<FractalUniforms>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    *uniforms = FractalUniforms {
+        key: EstimatorKey {
+            formula: mat.formula,
+            geometry_graph: mat.geometry_graph,
+        },
+        camera: mat.camera,
+        shape: mat.shape,
+        shading: mat.shading,
+        animation: mat.animation,
+    };
+}
+
+#[derive(Resource, Clone)]
+pub struct FractalComputeLayouts {
+    empty: BindGroupLayout,
+    fractal: BindGroupLayout,
+    shader: Handle<Shader>,
+}
+
+impl FractalComputeLayouts {
+    /// Pipeline running `entry_point` of the fractal shader with `shader_def` set, for the
+    /// estimator selected by `key`. `layout` is the pass's own group 3.
+    pub fn descriptor(
+        &self,
+        key: EstimatorKey,
+        shader_def: &'static str,
+        entry_point: &'static str,
+        layout: &BindGroupLayout,
+    ) -> ComputePipelineDescriptor {
+        let mut shader_defs = vec![shader_def.into()];
+        let shader = estimator_shader(key.formula, key.geometry_graph, &mut shader_defs)
+            .unwrap_or_else(|| self.shader.clone());
+        ComputePipelineDescriptor {
+            label: Some(entry_point.into()),
+            layout: vec![
+                self.empty.clone(),
+                self.empty.clone(),
+                self.fractal.clone(),
+                layout.clone(),
+            ],
+            shader,
+            shader_defs,
+            entry_point: Some(entry_point.into()),
+            ..default()
+        }
+    }
+}
+
+impl FromWorld for FractalComputeLayouts {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let empty = render_device.create_bind_group_layout("fractal_compute_empty_layout", &[]);
+        let fractal = render_device.create_bind_group_layout(
+            "fractal_compute_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::COMPUTE,
+                (
+                    uniform_buffer::<CameraUniform>(false),
+                    uniform_buffer::<ShapeUniform>(false),
+                    uniform_buffer::<ShadingUniform>(false),
+                    uniform_buffer::<AnimationUniform>(false),
+                ),
+            ),
+        );
+        Self {
+            empty,
+            fractal,
+            shader: world
+                .resource::<AssetServer>()
+                .load("shaders/mandelbulb.wgsl"),
+        }
+    }
+}
+
+/// GPU copies of the uniforms, rewritten every frame
+#[derive(Resource, Default)]
+struct FractalUniformBuffers {
+    camera: UniformBuffer<CameraUniform>,
+    shape: UniformBuffer<ShapeUniform>,
+    shading: UniformBuffer<ShadingUniform>,
+    animation: UniformBuffer<AnimationUniform>,
+}
+
+/// Groups 0 to 2 of every fractal compute pass
+#[derive(Resource)]
+pub struct FractalBindGroups {
+    empty: BindGroup,
+    fractal: BindGroup,
+}
+
+impl FractalBindGroups {
+    pub fn set(&self, pass: &mut ComputePass) {
+        pass.set_bind_group(0, &self.empty, &[]);
+        pass.set_bind_group(1, &self.empty, &[]);
+        pass.set_bind_group(2, &self.fractal, &[]);
+    }
+}
+
+fn prepare_fractal_bind_groups(
+    mut commands: Commands,
+    uniforms: Res<FractalUniforms>,
+    layouts: Res<FractalComputeLayouts>,
+    mut buffers: ResMut<FractalUniformBuffers>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    buffers.camera.set(uniforms.camera);
+    buffers.shape.set(uniforms.shape);
+    buffers.shading.set(uniforms.shading);
+    buffers.animation.set(uniforms.animation);
+    buffers.camera.write_buffer(&render_device, &render_queue);
+    buffers.shape.write_buffer(&render_device, &render_queue);
+    buffers.shading.write_buffer(&render_device, &render_queue);
+    buffers
+        .animation
+        .write_buffer(&render_device, &render_queue);
+
+    let (Some(camera), Some(shape), Some(shading), Some(animation)) = (
+        buffers.camera.binding(),
+        buffers.shape.binding(),
+        buffers.shading.binding(),
+        buffers.animation.binding(),
+    ) else {
+        return;
+    };
+    let fractal = render_device.create_bind_group(
+        "fractal_compute_bind_group",
+        &layouts.fractal,
+        &BindGroupEntries::sequential((camera, shape, shading, animation)),
+    );
+    let empty =
+        render_device.create_bind_group("fractal_compute_empty_bind_group", &layouts.empty, &[]);
+    commands.insert_resource(FractalBindGroups { empty, fractal });
+}
@@ -0,0 +1,187 @@
+use crate::export::{StillSaved, view_path};
+use crate::material::MandelbulbMaterial;
+use crate::navigation::View;
+use crate::params::ParamChanged;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, poll_once};
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use std::path::{Path, PathBuf};
+
+/// Largest thumbnail size, the aspect of the still is kept
+const THUMBNAIL_SIZE: (u32, u32) = (160, 120);
+
+/// Thumbnails of the stills saved this session, to get back to a view after capturing it
+pub struct GalleryPlugin;
+
+impl Plugin for GalleryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Gallery>()
+            .add_systems(Update, collect_stills)
+            .add_systems(EguiPrimaryContextPass, gallery_window);
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct Gallery {
+    pub open: bool,
+    /// Newest first
+    shots: Vec<Shot>,
+}
+
+struct Shot {
+    path: PathBuf,
+    /// A share code was saved next to the still
+    has_view: bool,
+    thumbnail: Thumbnail,
+}
+
+enum Thumbnail {
+    Loading(Task<Option<egui::ColorImage>>),
+    Ready(egui::TextureHandle),
+    Failed,
+}
+
+fn collect_stills(mut gallery: ResMut<Gallery>, mut saved: MessageReader<StillSaved>) {
+    for still in saved.read() {
+        let path = still.path.clone();
+        let task = AsyncComputeTaskPool::get().spawn(async move { load_thumbnail(&path) });
+        gallery.shots.insert(
+            0,
+            Shot {
+                path: still.path.clone(),
+                has_view: view_path(&still.path).exists(),
+                thumbnail: Thumbnail::Loading(task),
+            },
+        );
+    }
+}
+
+fn load_thumbnail(path: &Path) -> Option<egui::ColorImage> {
+    let image = image::open(path)
+        .inspect_err(|err| warn!("No thumbnail for {}: {err}", path.display()))
+        .ok()?
+        .thumbnail(THUMBNAIL_SIZE.0, THUMBNAIL_SIZE.1)
+        .to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    Some(egui::ColorImage::from_rgba_unmultiplied(
+        size,
+        image.as_raw(),
+    ))
+}
+
+/// Opens the folder containing `path` in the system file manager, selecting the file where the
+/// platform supports it
+fn reveal(path: &Path) -> std::io::Result<()> {
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = std::process::Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    } else if cfg!(target_os = "macos") {
+        let mut command = std::process::Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    } else {
+        let mut command = std::process::Command::new("xdg-open");
+        command.arg(path.parent().unwrap_or(Path::new(".")));
+        command
+    };
+    command.spawn().map(|_| ())
+}
+
+fn restore(path: &Path) -> Result<View, String> {
+    let code = std::fs::read_to_string(view_path(path)).map_err(|err| err.to_string())?;
+    View::parse(&code)
+}
+
+fn gallery_window(
+    mut contexts: EguiContexts,
+    mut gallery: ResMut<Gallery>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    mut param_changes: MessageWriter<ParamChanged>,
+) {
+    let ctx = contexts.ctx_mut().unwrap();
+    for shot in &mut gallery.shots {
+        if let Thumbnail::Loading(task) = &mut shot.thumbnail
+            && let Some(image) = block_on(poll_once(task))
+        {
+            shot.thumbnail = match image {
+                Some(image) => Thumbnail::Ready(ctx.load_texture(
+                    shot.path.display().to_string(),
+                    image,
+                    egui::TextureOptions::LINEAR,
+                )),
+                None => Thumbnail::Failed,
+            };
+        }
+    }
+    if !gallery.open {
+        return;
+    }
+    let gallery = &mut *gallery;
+
+    let mut open = gallery.open;
+    let mut delete = None;
+    egui::Window::new("Gallery")
+        .open(&mut open)
+        .default_width(360.0)
+        .show(ctx, |ui| {
+            if gallery.shots.is_empty() {
+                ui.label("Stills saved this session show up here.");
+                return;
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for (i, shot) in gallery.shots.iter().enumerate() {
+                    ui.horizontal(|ui| {
+                        let size = egui::vec2(THUMBNAIL_SIZE.0 as f32, THUMBNAIL_SIZE.1 as f32);
+                        match &shot.thumbnail {
+                            Thumbnail::Ready(texture) => {
+                                ui.add(egui::Image::new(texture).max_size(size));
+                            }
+                            Thumbnail::Loading(_) => {
+                                ui.add_sized(size, egui::Spinner::new());
+                            }
+                            Thumbnail::Failed => {
+                                ui.add_sized(size, egui::Label::new("No preview"));
+                            }
+                        }
+                        ui.vertical(|ui| {
+                            let name = shot.path.file_name().unwrap_or_default();
+                            ui.label(name.to_string_lossy());
+                            if ui
+                                .add_enabled(shot.has_view, egui::Button::new("Restore View"))
+                                .on_disabled_hover_text("No view was saved with this still")
+                                .clicked()
+                            {
+                                match restore(&shot.path) {
+                                    Ok(view) => view.open(&mut materials, &mut param_changes),
+                                    Err(err) => error!(
+                                        "Failed to restore the view of {}: {err}",
+                                        shot.path.display()
+                                    ),
+                                }
+                            }
+                            if ui.button("Show in Folder").clicked()
+                                && let Err(err) = reveal(&shot.path)
+                            {
+                                error!("Failed to open the file manager: {err}");
+                            }
+                            if ui.button("Delete").clicked() {
+                                delete = Some(i);
+                            }
+                        });
+                    });
+                    ui.separator();
+                }
+            });
+        });
+    gallery.open = open;
+
+    if let Some(i) = delete {
+        let shot = gallery.shots.remove(i);
+        if let Err(err) = std::fs::remove_file(&shot.path) {
+            error!("Failed to delete {}: {err}", shot.path.display());
+        }
+        // the view file is optional
+        let _ = std::fs::remove_file(view_path(&shot.path));
+    }
+}
@@ -0,0 +1,168 @@
+//! Node graph for the distance estimator. Domain operations (rotate, twist, fold, repeat) are
+//! applied to the sample point before it reaches the formulas, and formula results can be
+//! blended, so hybrid shapes can be built without writing a plugin. Used instead of the
+//! selected formula when the material's `geometry_graph` flag is set.
+
+use crate::formulas::BASE_SHADER;
+#[cfg(feature = "ui")]
+use crate::node_graph::Graph;
+use crate::node_graph::{Node, NodeGraphPlugin, NodeKind, Port, PortType, port};
+use bevy::asset::uuid_handle;
+use bevy::prelude::*;
+
+/// The fractal shader with the compiled graph appended as `geometry_de`
+pub const GEOMETRY_GRAPH_SHADER: Handle<Shader> =
+    uuid_handle!("2d8a4c61-93b7-4e0f-a5d2-7f1e6b3c9a85");
+
+/// Helpers used by the nodes, appended after the fractal shader
+const GRAPH_PRELUDE: &str = r#"
+// rotates around the Y axis by an angle proportional to the height
+fn graph_twist(p: vec3<f32>, amount: f32) -> vec3<f32> {
+    return rotate_y(p, p.y * amount);
+}
+
+// polynomial smooth minimum, keeps the orbit trap of the closer surface
+fn graph_smooth_union(a: vec2<f32>, b: vec2<f32>, k: f32) -> vec2<f32> {
+    let h = clamp(0.5 + 0.5 * (b.x - a.x) / max(k, 1e-4), 0.0, 1.0);
+    let d = mix(b.x, a.x, h) - k * h * (1.0 - h);
+    return vec2<f32>(d, select(b.y, a.y, h > 0.5));
+}
+"#;
+
+#[cfg(feature = "ui")]
+pub type GeometryGraph = Graph<GeometryNode>;
+
+pub type GeometryGraphPlugin = NodeGraphPlugin<GeometryNode>;
+
+// only the default graph's nodes are built until the editor adds the others
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum GeometryNode {
+    Rotate,
+    Twist,
+    Fold,
+    Repeat,
+    Mandelbulb,
+    Mandelbox,
+    Sphere,
+    Blend,
+    SmoothUnion,
+    Output,
+}
+
+const POINT: Port = port("Point", PortType::Point, 0.0);
+
+const fn value(name: &'static str, default: f32) -> Port {
+    port(name, PortType::Value, default)
+}
+
+const fn distance(name: &'static str) -> Port {
+    port(name, PortType::Distance, 1.0)
+}
+
+impl NodeKind for GeometryNode {
+    const TITLE: &'static str = "Geometry Graph";
+    #[cfg(feature = "ui")]
+    const ADDABLE: &'static [Self] = &[
+        GeometryNode::Rotate,
+        GeometryNode::Twist,
+        GeometryNode::Fold,
+        GeometryNode::Repeat,
+        GeometryNode::Mandelbulb,
+        GeometryNode::Mandelbox,
+        GeometryNode::Sphere,
+        GeometryNode::Blend,
+        GeometryNode::SmoothUnion,
+    ];
+    const OUTPUT: Self = GeometryNode::Output;
+    const SHADER: Handle<Shader> = GEOMETRY_GRAPH_SHADER;
+
+    #[cfg(feature = "ui")]
+    fn label(self) -> &'static str {
+        match self {
+            GeometryNode::Rotate => "Rotate",
+            GeometryNode::Twist => "Twist",
+            GeometryNode::Fold => "Mirror Fold",
+            GeometryNode::Repeat => "Repeat",
+            GeometryNode::Mandelbulb => "Mandelbulb",
+            GeometryNode::Mandelbox => "Mandelbox",
+            GeometryNode::Sphere => "Sphere",
+            GeometryNode::Blend => "Blend",
+            GeometryNode::SmoothUnion => "Smooth Union",
+            GeometryNode::Output => "Distance",
+        }
+    }
+
+    fn inputs(self) -> &'static [Port] {
+        match self {
+            GeometryNode::Rotate => const { &[POINT, value("Yaw", 0.0), value("Pitch", 0.0)] },
+            GeometryNode::Twist => const { &[POINT, value("Amount", 0.5)] },
+            GeometryNode::Fold => const { &[POINT, value("Offset", 0.0)] },
+            GeometryNode::Repeat => const { &[POINT, value("Period", 3.0)] },
+            GeometryNode::Mandelbulb | GeometryNode::Mandelbox => &[POINT],
+            GeometryNode::Sphere => const { &[POINT, value("Radius", 1.0)] },
+            GeometryNode::Blend => const { &[distance("A"), distance("B"), value("T", 0.5)] },
+            GeometryNode::SmoothUnion => {
+                const { &[distance("A"), distance("B"), value("Smoothness", 0.2)] }
+            }
+            GeometryNode::Output => const { &[distance("Distance")] },
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    fn output(self) -> Option<PortType> {
+        match self {
+            GeometryNode::Rotate
+            | GeometryNode::Twist
+            | GeometryNode::Fold
+            | GeometryNode::Repeat => Some(PortType::Point),
+            GeometryNode::Output => None,
+            _ => Some(PortType::Distance),
+        }
+    }
+
+    fn code(self, _node: &Node<Self>, inputs: &[String]) -> String {
+        match self {
+            GeometryNode::Rotate => {
+                format!(
+                    "rotate_x(rotate_y({}, {}.x), {}.x)",
+                    inputs[0], inputs[1], inputs[2]
+                )
+            }
+            GeometryNode::Twist => format!("graph_twist({}, {}.x)", inputs[0], inputs[1]),
+            GeometryNode::Fold => format!("abs({}) - {}", inputs[0], inputs[1]),
+            // clamped so a zero period does not divide by zero
+            GeometryNode::Repeat => format!(
+                "{p} - max({s}.x, 0.01) * round({p} / max({s}.x, 0.01))",
+                p = inputs[0],
+                s = inputs[1]
+            ),
+            GeometryNode::Mandelbulb => format!("sd_mandelbulb({})", inputs[0]),
+            GeometryNode::Mandelbox => format!("sd_mandelbox({})", inputs[0]),
+            GeometryNode::Sphere => {
+                format!("vec2<f32>(length({}) - {}.x, 0.0)", inputs[0], inputs[1])
+            }
+            GeometryNode::Blend => format!("mix({}, {}, {}.x)", inputs[0], inputs[1], inputs[2]),
+            GeometryNode::SmoothUnion => format!(
+                "graph_smooth_union({}, {}, {}.x)",
+                inputs[0], inputs[1], inputs[2]
+            ),
+            GeometryNode::Output => inputs[0].clone(),
+        }
+    }
+
+    /// The plain Mandelbulb, the same shape as the default formula
+    fn default_nodes() -> Vec<Node<Self>> {
+        vec![
+            Node::new(GeometryNode::Mandelbulb, Vec2::new(20.0, 40.0)),
+            Node::new(GeometryNode::Output, Vec2::new(260.0, 40.0)).linked(0, 0),
+        ]
+    }
+
+    fn shader_source(body: &str) -> String {
+        format!(
+            "{BASE_SHADER}\n// geometry graph\n{GRAPH_PRELUDE}\n\
+             fn geometry_de(p: vec3<f32>) -> vec2<f32> {{\n{body}}}\n"
+        )
+    }
+}
@@ -0,0 +1,141 @@
+//! Binary glTF 2.0 (`.glb`) writer for extracted surfaces, optionally with the camera and light
+//! of the current view placed around them
+
+use crate::mesh_export::SceneSetup;
+use crate::surface::SurfaceMesh;
+use serde_json::{Value, json};
+use std::path::Path;
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+
+// glTF enums
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// Intensity of the exported point light in candela. The shader's light has no physical unit,
+/// this lights a fractal a few meters across well in Blender, and falls off with the square of
+/// the export scale.
+const LIGHT_INTENSITY: f32 = 100.0;
+
+/// Writes `mesh` and the scene, both already in meters as glTF requires
+pub fn write_glb(
+    path: &Path,
+    mesh: &SurfaceMesh,
+    scene: Option<&SceneSetup>,
+) -> Result<(), String> {
+    let mut bin = Vec::new();
+    let positions = push_floats(&mut bin, mesh.positions.iter().flat_map(|p| p.to_array()));
+    let normals = push_floats(&mut bin, mesh.normals.iter().flat_map(|n| n.to_array()));
+    let colored = mesh.colors.len() == mesh.positions.len();
+    let colors = push_floats(&mut bin, mesh.colors.iter().flat_map(|c| c.to_array()));
+    let indices = bin.len();
+    for index in &mesh.indices {
+        bin.extend_from_slice(&index.to_le_bytes());
+    }
+
+    let (min, max) = mesh.bounds();
+    let vertex_count = mesh.positions.len();
+    // baked colors multiply the base color, which is white then
+    let base_color = if colored { 1.0 } else { 0.8 };
+    let mut nodes = vec![json!({ "name": "Fractal", "mesh": 0 })];
+    let mut root = json!({
+        "asset": { "version": "2.0", "generator": "fractal3D" },
+        "buffers": [{ "byteLength": bin.len() }],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": positions, "byteLength": vertex_count * 12, "target": ARRAY_BUFFER },
+            { "buffer": 0, "byteOffset": normals, "byteLength": vertex_count * 12, "target": ARRAY_BUFFER },
+            { "buffer": 0, "byteOffset": indices, "byteLength": mesh.indices.len() * 4, "target": ELEMENT_ARRAY_BUFFER },
+        ],
+        "accessors": [
+            { "bufferView": 0, "componentType": FLOAT, "count": vertex_count, "type": "VEC3",
+              "min": min.to_array(), "max": max.to_array() },
+            { "bufferView": 1, "componentType": FLOAT, "count": vertex_count, "type": "VEC3" },
+            { "bufferView": 2, "componentType": UNSIGNED_INT, "count": mesh.indices.len(), "type": "SCALAR" },
+        ],
+        "materials": [{
+            "name": "Fractal",
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [base_color, base_color, base_color, 1.0],
+                "metallicFactor": 0.0,
+                "roughnessFactor": 0.6,
+            },
+        }],
+        "meshes": [{
+            "name": "Fractal",
+            "primitives": [{ "attributes": { "POSITION": 0, "NORMAL": 1 }, "indices": 2, "material": 0 }],
+        }],
+    });
+
+    if colored {
+        root["bufferViews"].as_array_mut().unwrap().push(json!(
+            { "buffer": 0, "byteOffset": colors, "byteLength": vertex_count * 12, "target": ARRAY_BUFFER }
+        ));
+        root["accessors"].as_array_mut().unwrap().push(json!(
+            { "bufferView": 3, "componentType": FLOAT, "count": vertex_count, "type": "VEC3" }
+        ));
+        root["meshes"][0]["primitives"][0]["attributes"]["COLOR_0"] = json!(3);
+    }
+
+    if let Some(scene) = scene {
+        root["cameras"] = json!([{
+            "name": "View",
+            "type": "perspective",
+            "perspective": { "yfov": scene.yfov(), "aspectRatio": scene.aspect(), "znear": 0.001 },
+        }]);
+        nodes.push(json!({
+            "name": "View",
+            "camera": 0,
+            "translation": scene.camera_position().to_array(),
+            "rotation": scene.camera_rotation().to_array(),
+        }));
+
+        root["extensionsUsed"] = json!(["KHR_lights_punctual"]);
+        root["extensions"] = json!({
+            "KHR_lights_punctual": {
+                "lights": [{ "name": "Light", "type": "point", "intensity": LIGHT_INTENSITY * scene.scale.powi(2) }],
+            },
+        });
+        nodes.push(json!({
+            "name": "Light",
+            "translation": scene.light_position().to_array(),
+            "extensions": { "KHR_lights_punctual": { "light": 0 } },
+        }));
+    }
+    root["scenes"] = json!([{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }]);
+    root["scene"] = json!(0);
+    root["nodes"] = Value::Array(nodes);
+
+    let mut json = serde_json::to_vec(&root).map_err(|err| err.to_string())?;
+    pad(&mut json, b' ');
+    pad(&mut bin, 0);
+    let length = 12 + 8 + json.len() + 8 + bin.len();
+
+    let mut glb = Vec::with_capacity(length);
+    glb.extend_from_slice(&GLB_MAGIC.to_le_bytes());
+    glb.extend_from_slice(&2u32.to_le_bytes());
+    glb.extend_from_slice(&(length as u32).to_le_bytes());
+    for (kind, chunk) in [(CHUNK_JSON, &json), (CHUNK_BIN, &bin)] {
+        glb.extend_from_slice(&(chunk.len() as u32).to_le_bytes());
+        glb.extend_from_slice(&kind.to_le_bytes());
+        glb.extend_from_slice(chunk);
+    }
+    std::fs::write(path, glb).map_err(|err| err.to_string())
+}
+
+/// Appends little endian floats, returning where they start
+fn push_floats(bin: &mut Vec<u8>, values: impl Iterator<Item = f32>) -> usize {
+    let start = bin.len();
+    for value in values {
+        bin.extend_from_slice(&value.to_le_bytes());
+    }
+    start
+}
+
+/// glTF chunks are 4 byte aligned
+fn pad(bytes: &mut Vec<u8>, with: u8) {
+    bytes.resize(bytes.len().next_multiple_of(4), with);
+}
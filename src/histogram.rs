@@ -0,0 +1,232 @@
+use crate::fractal_compute::{
+    EstimatorKey, FractalBindGroups, FractalComputeLayouts, FractalUniforms,
+};
+use crate::material::{ColoringMode, FractalMaterial, MandelbulbMaterial};
+use bevy::asset::{RenderAssetUsages, uuid_handle};
+use bevy::prelude::*;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::graph::CameraDriverLabel;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_graph::{self, RenderGraph, RenderLabel};
+use bevy::render::render_resource::binding_types::storage_buffer_sized;
+use bevy::render::render_resource::{
+    BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, CachedComputePipelineId,
+    ComputePassDescriptor, ComputePipelineDescriptor, PipelineCache, ShaderStages,
+    SpecializedComputePipeline, SpecializedComputePipelines,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::storage::{GpuShaderStorageBuffer, ShaderStorageBuffer};
+use bevy::render::{Render, RenderApp, RenderStartup, RenderSystems};
+
+/// Bin counts followed by the normalized cumulative sum, see `HistogramBins` in
+/// `shaders/mandelbulb.wgsl`. Every material binds it, the fragment shader only reads it with
+/// equalized coloring.
+pub const HISTOGRAM_BUFFER: Handle<ShaderStorageBuffer> =
+    uuid_handle!("b4e2f7a1-6c3d-4d8e-9f05-2a7c1e9b3d64");
+
+/// Must match `HISTOGRAM_BINS`
+const HISTOGRAM_BINS: u32 = 256;
+
+/// Must match `HISTOGRAM_SAMPLES`, the grid of rays traced per frame
+const SAMPLE_GRID: UVec2 = UVec2::new(160, 90);
+
+/// Equalized coloring. Each frame a compute pass traces a coarse grid of rays with the main
+/// material's estimator, bins their coloring values and turns the counts into a cumulative
+/// distribution that the fragment shader maps colors through.
+pub struct HistogramPlugin;
+
+impl Plugin for HistogramPlugin {
+    fn build(&self, app: &mut App) {
+        let _ = app
+            .world_mut()
+            .resource_mut::<Assets<ShaderStorageBuffer>>()
+            .insert(
+                &HISTOGRAM_BUFFER,
+                ShaderStorageBuffer::with_size(
+                    2 * HISTOGRAM_BINS as usize * size_of::<u32>(),
+                    RenderAssetUsages::RENDER_WORLD,
+                ),
+            );
+        app.init_resource::<HistogramEnabled>()
+            .add_plugins(ExtractResourcePlugin::<HistogramEnabled>::default())
+            .add_systems(PostUpdate, sync_histogram);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<SpecializedComputePipelines<HistogramPipeline>>()
+            .add_systems(RenderStartup, init_histogram_pipeline)
+            .add_systems(
+                Render,
+                prepare_histogram.in_set(RenderSystems::PrepareBindGroups),
+            );
+        let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+        graph.add_node(HistogramLabel, HistogramNode);
+        graph.add_node_edge(HistogramLabel, CameraDriverLabel);
+    }
+}
+
+/// Whether the main material uses equalized coloring, the pass is skipped otherwise
+#[derive(Resource, Clone, Copy, Default, ExtractResource)]
+struct HistogramEnabled(bool);
+
+fn sync_histogram(
+    mut enabled: ResMut<HistogramEnabled>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    let equalized = materials
+        .get(&fractal.0)
+        .is_some_and(|mat| mat.coloring == ColoringMode::Equalized);
+    if enabled.0 != equalized {
+        enabled.0 = equalized;
+    }
+}
+
+/// The three dispatches of a frame, in order
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum HistogramStage {
+    Clear,
+    Accumulate,
+    Finish,
+}
+
+impl HistogramStage {
+    const ALL: [HistogramStage; 3] = [
+        HistogramStage::Clear,
+        HistogramStage::Accumulate,
+        HistogramStage::Finish,
+    ];
+
+    fn entry_point(self) -> &'static str {
+        match self {
+            HistogramStage::Clear => "clear_histogram",
+            HistogramStage::Accumulate => "accumulate_histogram",
+            HistogramStage::Finish => "finish_histogram",
+        }
+    }
+
+    /// Workgroups to dispatch, from the `@workgroup_size` of each entry point
+    fn workgroups(self) -> UVec3 {
+        match self {
+            HistogramStage::Clear => UVec3::new(HISTOGRAM_BINS.div_ceil(64), 1, 1),
+            HistogramStage::Accumulate => {
+                UVec3::new(SAMPLE_GRID.x.div_ceil(8), SAMPLE_GRID.y.div_ceil(8), 1)
+            }
+            HistogramStage::Finish => UVec3::ONE,
+        }
+    }
+}
+
+#[derive(Resource)]
+struct HistogramPipeline {
+    fractal: FractalComputeLayouts,
+    layout: BindGroupLayout,
+}
+
+impl SpecializedComputePipeline for HistogramPipeline {
+    type Key = (EstimatorKey, HistogramStage);
+
+    fn specialize(&self, (key, stage): Self::Key) -> ComputePipelineDescriptor {
+        self.fractal
+            .descriptor(key, "HISTOGRAM_COMPUTE", stage.entry_point(), &self.layout)
+    }
+}
+
+fn init_histogram_pipeline(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    fractal: Res<FractalComputeLayouts>,
+) {
+    let layout = render_device.create_bind_group_layout(
+        "histogram_layout",
+        &BindGroupLayoutEntries::single(ShaderStages::COMPUTE, storage_buffer_sized(false, None)),
+    );
+    commands.insert_resource(HistogramPipeline {
+        fractal: fractal.clone(),
+        layout,
+    });
+}
+
+/// The frame's dispatches, absent while equalized coloring is off
+#[derive(Resource)]
+struct HistogramDispatch {
+    pipelines: [CachedComputePipelineId; 3],
+    bind_group: BindGroup,
+}
+
+fn prepare_histogram(
+    mut commands: Commands,
+    enabled: Res<HistogramEnabled>,
+    fractal: Res<FractalUniforms>,
+    pipeline: Res<HistogramPipeline>,
+    mut pipelines: ResMut<SpecializedComputePipelines<HistogramPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    gpu_buffers: Res<RenderAssets<GpuShaderStorageBuffer>>,
+    render_device: Res<RenderDevice>,
+) {
+    let storage = gpu_buffers.get(&HISTOGRAM_BUFFER);
+    let (true, Some(storage)) = (enabled.0, storage) else {
+        commands.remove_resource::<HistogramDispatch>();
+        return;
+    };
+    let bind_group = render_device.create_bind_group(
+        "histogram_bind_group",
+        &pipeline.layout,
+        &BindGroupEntries::single(storage.buffer.as_entire_buffer_binding()),
+    );
+    commands.insert_resource(HistogramDispatch {
+        pipelines: HistogramStage::ALL
+            .map(|stage| pipelines.specialize(&pipeline_cache, &pipeline, (fractal.key, stage))),
+        bind_group,
+    });
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct HistogramLabel;
+
+/// Rebuilds the histogram before any camera draws the fractal with it
+struct HistogramNode;
+
+impl render_graph::Node for HistogramNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let (Some(dispatch), Some(fractal)) = (
+            world.get_resource::<HistogramDispatch>(),
+            world.get_resource::<FractalBindGroups>(),
+        ) else {
+            return Ok(());
+        };
+        // all three or nothing, a half built histogram would flicker
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let Some(stages) = dispatch
+            .pipelines
+            .iter()
+            .map(|id| pipeline_cache.get_compute_pipeline(*id))
+            .collect::<Option<Vec<_>>>()
+        else {
+            return Ok(());
+        };
+
+        let mut pass =
+            render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("histogram"),
+                    ..default()
+                });
+        fractal.set(&mut pass);
+        pass.set_bind_group(3, &dispatch.bind_group, &[]);
+        for (stage, pipeline) in HistogramStage::ALL.into_iter().zip(stages) {
+            let groups = stage.workgroups();
+            pass.set_pipeline(pipeline);
+            pass.dispatch_workgroups(groups.x, groups.y, groups.z);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,30 @@
+//! Reflection based inspectors for developers, built with the `inspector` feature. F10 shows
+//! every field of the main settings and of the fractal materials, including the ones the
+//! settings panel doesn't expose, straight from their `Reflect` derives.
+
+#[cfg(feature = "inspector")]
+use crate::SimSettings;
+#[cfg(feature = "inspector")]
+use crate::material::MandelbulbMaterial;
+use bevy::prelude::*;
+#[cfg(feature = "inspector")]
+use bevy_inspector_egui::quick::{AssetInspectorPlugin, ResourceInspectorPlugin};
+
+pub struct InspectorPlugin;
+
+impl Plugin for InspectorPlugin {
+    #[cfg(feature = "inspector")]
+    fn build(&self, app: &mut App) {
+        use bevy::input::common_conditions::input_toggle_active;
+
+        app.add_plugins((
+            ResourceInspectorPlugin::<SimSettings>::default()
+                .run_if(input_toggle_active(false, KeyCode::F10)),
+            AssetInspectorPlugin::<MandelbulbMaterial>::default()
+                .run_if(input_toggle_active(false, KeyCode::F10)),
+        ));
+    }
+
+    #[cfg(not(feature = "inspector"))]
+    fn build(&self, _app: &mut App) {}
+}
@@ -0,0 +1,417 @@
+//! Compositions of several fractals. Every layer is a material of its own, rendered offscreen
+//! with a transparent background and drawn over the main view in the layers panel's order with
+//! its blend mode and opacity, like the layers of an image editor. The main view stays the
+//! bottom layer and the one the settings panel edits; a layer is edited by swapping it in.
+
+use crate::FractalQuad;
+use crate::compose::ComposeSettings;
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use crate::params::{ParamChanged, ParamStore};
+use bevy::camera::RenderTarget;
+use bevy::camera::visibility::RenderLayers;
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::mesh::MeshVertexBufferLayoutRef;
+use bevy::prelude::*;
+use bevy::render::render_resource::{
+    AsBindGroup, BlendComponent, BlendFactor, BlendOperation, BlendState, RenderPipelineDescriptor,
+    ShaderType, SpecializedMeshPipelineError,
+};
+use bevy::render::view::{Hdr, ViewTarget};
+use bevy::shader::ShaderRef;
+use bevy::sprite_render::{AlphaMode2d, Material2d, Material2dKey, Material2dPlugin};
+use bevy::window::PrimaryWindow;
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// Render layer of the first layer's offscreen quad, each layer takes the next one up. Clear of
+/// the crossfade and data export layers.
+const FIRST_RENDER_LAYER: usize = 8;
+
+pub struct LayersPlugin;
+
+impl Plugin for LayersPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Layers>()
+            .add_plugins(Material2dPlugin::<LayerMaterial>::default())
+            .add_systems(Update, sync_layers)
+            .add_systems(EguiPrimaryContextPass, layers_window);
+    }
+}
+
+/// How a layer combines with what is below it
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum LayerBlend {
+    /// Covers what is below where the layer's surface is
+    #[default]
+    Normal,
+    /// Brightens, good for glowing shapes
+    Add,
+    /// Darkens, white leaves what is below unchanged
+    Multiply,
+    /// Brightens without blowing out, black leaves what is below unchanged
+    Screen,
+}
+
+impl LayerBlend {
+    pub const ALL: [LayerBlend; 4] = [
+        LayerBlend::Normal,
+        LayerBlend::Add,
+        LayerBlend::Multiply,
+        LayerBlend::Screen,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LayerBlend::Normal => "Normal",
+            LayerBlend::Add => "Add",
+            LayerBlend::Multiply => "Multiply",
+            LayerBlend::Screen => "Screen",
+        }
+    }
+
+    /// Blend state for colors premultiplied by coverage and opacity
+    fn state(self) -> BlendState {
+        let color = |src_factor, dst_factor| BlendComponent {
+            src_factor,
+            dst_factor,
+            operation: BlendOperation::Add,
+        };
+        let color = match self {
+            LayerBlend::Normal => color(BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+            LayerBlend::Add => color(BlendFactor::One, BlendFactor::One),
+            // dst * src + dst * (1 - a), so uncovered pixels keep what is below
+            LayerBlend::Multiply => color(BlendFactor::Dst, BlendFactor::OneMinusSrcAlpha),
+            LayerBlend::Screen => color(BlendFactor::One, BlendFactor::OneMinusSrc),
+        };
+        BlendState {
+            color,
+            alpha: BlendComponent::OVER,
+        }
+    }
+}
+
+/// Mirrors `LayerUniform` in `shaders/layer.wgsl`
+#[derive(ShaderType, Clone, Copy, PartialEq, Debug)]
+struct LayerUniform {
+    opacity: f32,
+    _padding0: f32,
+    _padding1: f32,
+    _padding2: f32,
+}
+
+/// Draws a layer's offscreen render over the main view
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+#[bind_group_data(LayerBlend)]
+struct LayerMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    image: Handle<Image>,
+    #[uniform(2)]
+    uniform: LayerUniform,
+    blend: LayerBlend,
+}
+
+impl From<&LayerMaterial> for LayerBlend {
+    fn from(material: &LayerMaterial) -> Self {
+        material.blend
+    }
+}
+
+impl Material2d for LayerMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/layer.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode2d {
+        AlphaMode2d::Blend
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let Some(target) = descriptor
+            .fragment
+            .as_mut()
+            .and_then(|fragment| fragment.targets[0].as_mut())
+        {
+            target.blend = Some(key.bind_group_data.state());
+        }
+        Ok(())
+    }
+}
+
+struct Layer {
+    name: String,
+    material: MandelbulbMaterial,
+    visible: bool,
+    blend: LayerBlend,
+    opacity: f32,
+    /// The entities drawing the layer while it is visible
+    spawned: Option<SpawnedLayer>,
+}
+
+struct SpawnedLayer {
+    camera: Entity,
+    quad: Entity,
+    overlay: Entity,
+    material: Handle<LayerMaterial>,
+}
+
+impl SpawnedLayer {
+    fn despawn(self, commands: &mut Commands) {
+        commands.entity(self.camera).despawn();
+        commands.entity(self.quad).despawn();
+        commands.entity(self.overlay).despawn();
+    }
+}
+
+/// Layers over the main view, bottom first
+#[derive(Resource, Default)]
+pub struct Layers {
+    pub open: bool,
+    layers: Vec<Layer>,
+    /// Set when layers were added, removed, reordered, shown, hidden or changed their blend
+    /// mode, which rebuilds them all
+    rebuild: bool,
+    /// Size the spawned layers render at
+    size: UVec2,
+}
+
+impl Layers {
+    fn push(&mut self, name: String, mut material: MandelbulbMaterial) {
+        material.shading.transparent_background = 1;
+        self.layers.push(Layer {
+            name,
+            material,
+            visible: true,
+            blend: LayerBlend::Normal,
+            opacity: 1.0,
+            spawned: None,
+        });
+        self.rebuild = true;
+    }
+}
+
+/// Spawns the offscreen render of every visible layer and its overlay, keeping them in step
+/// with the panel and the size of the view
+fn sync_layers(
+    mut commands: Commands,
+    mut layers: ResMut<Layers>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    mut layer_materials: ResMut<Assets<LayerMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    compose: Res<ComposeSettings>,
+) {
+    let Ok(win) = window.single() else {
+        return;
+    };
+    let frame = compose.frame_size(Vec2::new(win.width(), win.height()));
+    let size = frame.as_uvec2().max(UVec2::ONE);
+    let layers = &mut *layers;
+
+    if layers.rebuild || layers.size != size {
+        layers.rebuild = false;
+        layers.size = size;
+        for (index, layer) in layers.layers.iter_mut().enumerate() {
+            if let Some(spawned) = layer.spawned.take() {
+                spawned.despawn(&mut commands);
+            }
+            if !layer.visible {
+                continue;
+            }
+            let target = images.add(Image::new_target_texture(
+                size.x,
+                size.y,
+                ViewTarget::TEXTURE_FORMAT_HDR,
+            ));
+            let render_layer = RenderLayers::layer(FIRST_RENDER_LAYER + index);
+            let camera = commands
+                .spawn((
+                    Camera2d,
+                    Camera {
+                        target: RenderTarget::Image(target.clone().into()),
+                        order: -1,
+                        clear_color: ClearColorConfig::Custom(Color::NONE),
+                        ..default()
+                    },
+                    // linear, blended and encoded in the main view
+                    Hdr,
+                    Tonemapping::None,
+                    render_layer.clone(),
+                ))
+                .id();
+            let mut material = layer.material.clone();
+            material.camera.resolution = frame;
+            let quad = commands
+                .spawn((
+                    Mesh2d(meshes.add(Rectangle::default())),
+                    MeshMaterial2d(materials.add(material)),
+                    Transform::default().with_scale(frame.extend(1.0)),
+                    render_layer,
+                ))
+                .id();
+            let overlay_material = layer_materials.add(LayerMaterial {
+                image: target,
+                uniform: LayerUniform {
+                    opacity: layer.opacity,
+                    _padding0: 0.0,
+                    _padding1: 0.0,
+                    _padding2: 0.0,
+                },
+                blend: layer.blend,
+            });
+            // over the main view and under the particles, higher layers in front
+            let overlay = commands
+                .spawn((
+                    Mesh2d(meshes.add(Rectangle::default())),
+                    MeshMaterial2d(overlay_material.clone()),
+                    Transform::from_xyz(0.0, 0.0, 0.1 + 0.001 * index as f32)
+                        .with_scale(frame.extend(1.0)),
+                    FractalQuad,
+                ))
+                .id();
+            layer.spawned = Some(SpawnedLayer {
+                camera,
+                quad,
+                overlay,
+                material: overlay_material,
+            });
+        }
+    }
+
+    // opacity changes while dragging a slider, so it updates in place
+    for layer in &layers.layers {
+        if let Some(spawned) = &layer.spawned
+            && let Some(material) = layer_materials.get(&spawned.material)
+            && material.uniform.opacity != layer.opacity
+            && let Some(material) = layer_materials.get_mut(&spawned.material)
+        {
+            material.uniform.opacity = layer.opacity;
+        }
+    }
+}
+
+fn layers_window(
+    mut commands: Commands,
+    mut contexts: EguiContexts,
+    mut layers: ResMut<Layers>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    store: Res<ParamStore>,
+    mut param_changes: MessageWriter<ParamChanged>,
+) {
+    if !layers.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let layers = &mut *layers;
+
+    let mut open = layers.open;
+    let mut move_up = None;
+    let mut remove = None;
+    let mut swap = None;
+    egui::Window::new("Layers")
+        .open(&mut open)
+        .default_width(380.0)
+        .show(ctx, |ui| {
+            if ui
+                .button("Add Current View")
+                .on_hover_text("Adds the main view as a new layer on top")
+                .clicked()
+                && let Some(mat) = materials.get(&fractal.0)
+            {
+                let name = format!("Layer {}", layers.layers.len() + 1);
+                let mut layer = mat.clone();
+                store.restore(&mut layer);
+                layers.push(name, layer);
+            }
+            ui.separator();
+
+            // top layer first, like an image editor
+            let count = layers.layers.len();
+            for index in (0..count).rev() {
+                let layer = &mut layers.layers[index];
+                ui.horizontal(|ui| {
+                    if ui
+                        .checkbox(&mut layer.visible, "")
+                        .on_hover_text("Visible")
+                        .changed()
+                    {
+                        layers.rebuild = true;
+                    }
+                    ui.add(egui::TextEdit::singleline(&mut layer.name).desired_width(90.0));
+                    let mut blend = layer.blend;
+                    egui::ComboBox::from_id_salt(("layer_blend", index))
+                        .width(80.0)
+                        .selected_text(blend.label())
+                        .show_ui(ui, |ui| {
+                            for mode in LayerBlend::ALL {
+                                ui.selectable_value(&mut blend, mode, mode.label());
+                            }
+                        });
+                    if blend != layer.blend {
+                        layer.blend = blend;
+                        layers.rebuild = true;
+                    }
+                    ui.add(
+                        egui::DragValue::new(&mut layer.opacity)
+                            .range(0.0..=1.0)
+                            .speed(0.01)
+                            .prefix("Opacity "),
+                    );
+                    if ui
+                        .add_enabled(index + 1 < count, egui::Button::new("↑"))
+                        .clicked()
+                    {
+                        move_up = Some(index);
+                    }
+                    if ui.add_enabled(index > 0, egui::Button::new("↓")).clicked() {
+                        move_up = Some(index - 1);
+                    }
+                    if ui
+                        .button("Edit")
+                        .on_hover_text("Swaps the layer with the main view to edit it there")
+                        .clicked()
+                    {
+                        swap = Some(index);
+                    }
+                    if ui.button("Remove").clicked() {
+                        remove = Some(index);
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.add_enabled(false, egui::Checkbox::new(&mut true, ""));
+                ui.label("Main View");
+            });
+        });
+    layers.open = open;
+
+    if let Some(index) = move_up {
+        layers.layers.swap(index, index + 1);
+        layers.rebuild = true;
+    }
+    if let Some(index) = remove {
+        if let Some(spawned) = layers.layers.remove(index).spawned {
+            spawned.despawn(&mut commands);
+        }
+        layers.rebuild = true;
+    }
+    if let Some(index) = swap
+        && let Some(mat) = materials.get_mut(&fractal.0)
+    {
+        // the main view keeps its size and background, the layer keeps its transparency
+        let layer = &mut layers.layers[index].material;
+        let mut main = layer.clone();
+        main.camera.resolution = mat.camera.resolution;
+        main.shading.transparent_background = 0;
+        *layer = std::mem::replace(mat, main);
+        store.restore(layer);
+        layer.shading.transparent_background = 1;
+        param_changes.write_batch(ParamChanged::all(mat));
+        layers.rebuild = true;
+    }
+}
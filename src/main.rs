@@ -1,20 +1,233 @@
-use bevy::input::mouse::MouseMotion;
-use bevy::sprite_render::{Material2d, Material2dPlugin};
+#![allow(clippy::too_many_arguments)]
+
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::diagnostic::FrameCount;
+use bevy::input::mouse::{MouseMotion, MouseWheel};
+use bevy::prelude::*;
+use bevy::render::view::Hdr;
+use bevy::sprite_render::Material2dPlugin;
+use bevy::window::{PrimaryWindow, WindowResized};
 use bevy::winit::{UpdateMode, WinitSettings};
-use bevy::{
-    prelude::*, reflect::TypePath, render::render_resource::AsBindGroup, shader::ShaderRef,
-};
-use bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass, egui};
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPlugin, EguiPrimaryContextPass};
 use std::time::Duration;
 
+mod acts;
+mod api;
+mod args;
+mod attract;
+#[cfg(feature = "ui")]
+mod annotations;
+mod bindings;
+mod brick_map;
+mod compose;
+mod config;
+mod controls;
+mod crossfade;
+mod deep_zoom;
+#[cfg(feature = "ui")]
+mod dimension;
+#[cfg(feature = "ui")]
+mod discover;
+#[cfg(feature = "ui")]
+mod dive;
+mod easing;
+mod estimator;
+mod export;
+// expressions are only parsed from what is typed in the editor
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+mod expr;
+mod flare;
+mod formulas;
+mod fractal_compute;
+#[cfg(feature = "ui")]
+mod gallery;
+mod geometry_graph;
+#[cfg(all(feature = "ui", feature = "export"))]
+mod gltf;
+mod histogram;
+mod inspector;
+#[cfg(feature = "ui")]
+mod layers;
+#[cfg(feature = "ui")]
+mod marketplace;
+mod material;
+#[cfg(feature = "ui")]
+mod measure;
+#[cfg(feature = "ui")]
+mod mesh_export;
+#[cfg(feature = "ui")]
+mod mesh_process;
+mod midi;
+mod navigation;
+mod node_graph;
+#[cfg(all(feature = "ui", feature = "export"))]
+mod obj;
+mod output;
+mod palette;
+mod params;
+mod particles;
+mod playlist;
+#[cfg(all(feature = "ui", feature = "export"))]
+mod ply;
+mod post_pass;
+mod power;
+#[cfg(feature = "ui")]
+mod preview;
+#[cfg(feature = "ui")]
+mod print;
+mod replay;
+mod reprojection;
+mod rng;
+#[cfg(feature = "scripting")]
+mod script;
+mod sequencer;
+mod shading_graph;
+mod startup;
+mod statistics;
+#[cfg(all(feature = "ui", feature = "export"))]
+mod stl;
+#[cfg(feature = "ui")]
+mod surface;
+mod test_pattern;
+mod throttle;
+mod time_lapse;
+mod timeline;
+#[cfg(feature = "ui")]
+mod ui;
+#[cfg(feature = "ui")]
+mod units;
+mod usage;
+#[cfg(all(feature = "ui", feature = "export"))]
+mod usd;
+mod validation;
+#[cfg(feature = "ui")]
+mod vertex_colors;
+mod wallpaper;
+mod warmup;
+mod web_bundle;
+
+use acts::ActsPlugin;
+use api::ApiPlugin;
+use args::ArgsPlugin;
+use attract::AttractPlugin;
+#[cfg(feature = "ui")]
+use annotations::AnnotationsPlugin;
+use bindings::BindingsPlugin;
+use brick_map::BrickMapPlugin;
+use compose::{ComposePlugin, ComposeSettings};
+use controls::{ControlsPlugin, MouseSettings};
+use deep_zoom::DeepZoomPlugin;
+#[cfg(feature = "ui")]
+use dimension::DimensionPlugin;
+#[cfg(feature = "ui")]
+use discover::DiscoverPlugin;
+#[cfg(feature = "ui")]
+use dive::DivePlugin;
+use export::ExportPlugin;
+use flare::FlarePlugin;
+use formulas::FormulasPlugin;
+use fractal_compute::FractalComputePlugin;
+#[cfg(feature = "ui")]
+use gallery::GalleryPlugin;
+use geometry_graph::GeometryGraphPlugin;
+use histogram::HistogramPlugin;
+use inspector::InspectorPlugin;
+#[cfg(feature = "ui")]
+use layers::LayersPlugin;
+#[cfg(feature = "ui")]
+use marketplace::MarketplacePlugin;
+use material::{FractalMaterial, MandelbulbMaterial, OUTPUT_MODE_SDR, rotate_all_views};
+#[cfg(feature = "ui")]
+use measure::MeasurePlugin;
+#[cfg(feature = "ui")]
+use mesh_export::MeshExportPlugin;
+use midi::MidiPlugin;
+use navigation::NavigationPlugin;
+use output::OutputPlugin;
+use params::{Param, ParamChanged, ParamStore, ParamsPlugin};
+use particles::ParticlesPlugin;
+use playlist::PlaylistPlugin;
+use power::{
+    ECO_SETTLE_FRAMES, ECO_WAIT, KeepAwake, LOW_POWER_FPS, LowPower, LowPowerPlugin, keep_awake,
+};
+use replay::ReplayPlugin;
+use reprojection::ReprojectionPlugin;
+use rng::RngPlugin;
+use sequencer::{Sequencer, SequencerPlugin};
+use shading_graph::ShadingGraphPlugin;
+use startup::StartupPlugin;
+use statistics::StatisticsPlugin;
+use test_pattern::TestPatternPlugin;
+use throttle::ThrottlePlugin;
+use time_lapse::TimeLapsePlugin;
+use timeline::TimelinePlugin;
+#[cfg(feature = "ui")]
+use ui::ui_controls;
+#[cfg(feature = "ui")]
+use units::UnitsPlugin;
+use usage::UsagePlugin;
+use validation::ValidationPlugin;
+use wallpaper::WallpaperPlugin;
+use warmup::WarmupPlugin;
+use web_bundle::WebBundlePlugin;
+
 fn main() {
-    App::new()
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins);
+    // without the ui feature the parameters are driven through the api module and the command
+    // line only
+    #[cfg(feature = "ui")]
+    app.add_plugins(EguiPlugin::default())
+        .add_systems(EguiPrimaryContextPass, ui_controls);
+    app.add_plugins(Material2dPlugin::<MandelbulbMaterial>::default())
+        .add_plugins((
+            FormulasPlugin,
+            GeometryGraphPlugin::default(),
+            ParamsPlugin,
+            ActsPlugin,
+            BindingsPlugin,
+            ComposePlugin,
+            ExportPlugin,
+            MidiPlugin,
+            OutputPlugin,
+            PlaylistPlugin,
+            SequencerPlugin,
+            ShadingGraphPlugin::default(),
+            TestPatternPlugin,
+            TimelinePlugin,
+            WarmupPlugin,
+        ))
+        .add_plugins((
+            ArgsPlugin,
+            BrickMapPlugin,
+            FractalComputePlugin,
+            HistogramPlugin,
+            LowPowerPlugin,
+            NavigationPlugin,
+            ParticlesPlugin,
+            ReprojectionPlugin,
+            StatisticsPlugin,
+            ThrottlePlugin,
+        ))
         .add_plugins((
-            DefaultPlugins,
-            EguiPlugin::default(),
-            Material2dPlugin::<MandelbulbMaterial>::default(),
+            ApiPlugin,
+            AttractPlugin,
+            ControlsPlugin,
+            DeepZoomPlugin,
+            FlarePlugin,
+            InspectorPlugin,
+            ReplayPlugin,
+            RngPlugin,
+            StartupPlugin,
+            TimeLapsePlugin,
+            UsagePlugin,
+            ValidationPlugin,
+            WallpaperPlugin,
+            WebBundlePlugin,
         ))
         .init_resource::<SimSettings>()
+        .register_asset_reflect::<MandelbulbMaterial>()
         .insert_resource(WinitSettings::desktop_app())
         .add_systems(Startup, setup)
         .add_systems(
@@ -24,123 +237,123 @@ fn main() {
                 mouse_controls,
                 keyboard_controls,
                 manage_rendering_mode,
+                keep_awake.run_if(|sim_settings: Res<SimSettings>, store: Res<ParamStore>| {
+                    sim_settings.is_animating() || is_breathing(&store)
+                }),
                 resize_mesh,
+                sync_hdr_camera,
             ),
-        )
-        .add_systems(EguiPrimaryContextPass, ui_controls)
-        .run();
+        );
+    // tools that are only reached through their windows, and how the panels show numbers
+    #[cfg(feature = "ui")]
+    app.add_plugins((
+        AnnotationsPlugin,
+        DimensionPlugin,
+        DiscoverPlugin,
+        DivePlugin,
+        GalleryPlugin,
+        LayersPlugin,
+        MarketplacePlugin,
+        MeasurePlugin,
+        MeshExportPlugin,
+        UnitsPlugin,
+    ));
+    app.run();
 }
 
 fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<MandelbulbMaterial>>,
-    window: Query<&Window>,
+    window: Query<&Window, With<PrimaryWindow>>,
 ) {
-    let win = window.single().unwrap();
-
-    commands.spawn((Camera2d::default(),));
-
-    let material_handle = materials.add(MandelbulbMaterial {
-        resolution: Vec2::new(win.width(), win.height()),
-        power: 8.0,
-        ray_steps: 220,
-        mandel_iters: 10,
-        max_dist: 20.0,
-        hit_threshold: 0.0025,
-        camera_zoom: 2.5,
-        camera_position: Vec3::ZERO,
-        camera_rotation: Vec4::from(Quat::IDENTITY),
-        palette_id: 0,
-        light_pos_x: 8.0,
-        light_pos_y: 10.0,
-        background_glow_intensity: 0.0,
-        color_scale: 0.95, // Start with 1.0
-        color_offset: 0.05,
-        ao_strength: 1.2,
-        rim_strength: 0.1,
-        fog_density: 0.05,
-        julia: Vec4::new(0.35, 0.35, -0.35, 0.0), // last value 0, not used initially
-        supersampling_enabled: 0,
-
-    });
+    // fall back to a default size if there is no primary window (yet)
+    let resolution = window
+        .single()
+        .map(|win| Vec2::new(win.width(), win.height()))
+        .unwrap_or(Vec2::new(1280.0, 720.0));
 
+    commands.spawn((Camera2d::default(), MainCamera));
+
+    let material = MandelbulbMaterial::new(resolution);
+    commands.insert_resource(ParamStore::capture(&material));
+    let material_handle = materials.add(material);
+
+    commands.insert_resource(FractalMaterial(material_handle.clone()));
     commands.spawn((
         Mesh2d(meshes.add(Rectangle::default())),
         MeshMaterial2d(material_handle),
-        Transform::default().with_scale(Vec3::new(win.width(), win.height(), 1.0)),
+        Transform::default().with_scale(resolution.extend(1.0)),
+        FractalQuad,
     ));
 }
 
-#[derive(Asset, TypePath, AsBindGroup, Clone)]
-struct MandelbulbMaterial {
-    #[uniform(0)]
-    resolution: Vec2, // 8 bytes (Aligned)
-    #[uniform(0)]
-    power: f32, // 4 bytes
-    #[uniform(0)]
-    ray_steps: u32, // 4 bytes (Integers are fine in Uniforms)
-    #[uniform(0)]
-    mandel_iters: u32, // 4 bytes
-    #[uniform(0)]
-    max_dist: f32, // 4 bytes
-    #[uniform(0)]
-    hit_threshold: f32, // 4 bytes
-    #[uniform(0)]
-    camera_zoom: f32, // 4 bytes
-    #[uniform(0)]
-    camera_position: Vec3,
-    #[uniform(0)]
-    camera_rotation: Vec4,
-    #[uniform(0)]
-    palette_id: u32,
-    #[uniform(0)]
-    light_pos_x: f32,
-    #[uniform(0)]
-    light_pos_y: f32,
-    #[uniform(0)]
-    background_glow_intensity: f32,
-    #[uniform(0)]
-    color_scale: f32,
-    #[uniform(0)]
-    color_offset: f32,
-    #[uniform(0)]
-    ao_strength: f32,
-    #[uniform(0)]
-    rim_strength: f32,
-    #[uniform(0)]
-    fog_density: f32,
-    #[uniform(0)]
-    julia: Vec4,
-    #[uniform(0)]
-    supersampling_enabled: u32,
-}
+/// The camera that draws to the primary window, as opposed to offscreen cameras used for
+/// exports, warm-up and pre-rendering
+#[derive(Component)]
+struct MainCamera;
 
-impl Material2d for MandelbulbMaterial {
-    fn fragment_shader() -> ShaderRef {
-        "shaders/mandelbulb.wgsl".into()
-    }
-}
+/// The fullscreen quad the main fractal is drawn on
+#[derive(Component)]
+struct FractalQuad;
 
-// System to update the time uniform every frame
+/// Advances animations and keeps the resolution in sync. Materials are only borrowed mutably
+/// when one of their values actually changes, since every mutable access re-uploads the uniforms
+/// and an idle scene should not touch the GPU.
 fn update_material(
     time: Res<Time>,
-    window: Query<&Window>,
+    frame_count: Res<FrameCount>,
+    window: Query<&Window, With<PrimaryWindow>>,
     mut materials: ResMut<Assets<MandelbulbMaterial>>,
     settings: Res<SimSettings>,
+    compose: Res<ComposeSettings>,
+    store: Res<ParamStore>,
+    sequencer: Res<Sequencer>,
+    mut changes: MessageWriter<ParamChanged>,
 ) {
-    let win = window.single().unwrap();
-    for (_, material) in materials.iter_mut() {
-        material.resolution = Vec2::new(win.width(), win.height());
-
-        // Animate the power parameter over time, goes 1->16->1 and loops
-        if settings.animate_power {
-            // normalized 0.0 to 1.0 sine
-            let t = (0.5
-                + 0.5 * (time.elapsed_secs_f64() * 0.1 * settings.power_speed as f64).sin())
-                as f32;
-            // Exponentially mapped because the power parameter has an exponential effect on the shape
-            material.power = 16.0_f32.powf(t);
+    let Ok(win) = window.single() else {
+        return;
+    };
+    let resolution = compose.frame_size(Vec2::new(win.width(), win.height()));
+    let animating =
+        settings.is_animating() || test_pattern::is_showing(&store) || is_breathing(&store);
+
+    // Animate the power parameter over time, goes 1->16->1 and loops. Like zoom below it goes
+    // through the parameter store, so share codes and the panel see the value being shown.
+    if settings.animate_power {
+        // normalized 0.0 to 1.0 sine
+        let t = (0.5 + 0.5 * (time.elapsed_secs_f64() * 0.1 * settings.power_speed as f64).sin())
+            as f32;
+        // Exponentially mapped because the power parameter has an exponential effect on the shape
+        changes.write(ParamChanged {
+            param: Param::Power,
+            value: 16.0_f32.powf(t),
+        });
+    }
+    if settings.animate_zoom {
+        changes.write(ParamChanged {
+            param: Param::Zoom,
+            value: 2.75
+                + ((time.elapsed_secs_f64() * settings.zoom_speed as f64).sin() as f32) * 0.25,
+        });
+    }
+
+    let ids: Vec<_> = materials.ids().collect();
+    for id in ids {
+        let Some(material) = materials.get(id) else {
+            continue;
+        };
+        let mut camera = material.camera;
+        let shape = &material.shape;
+        let mut animation = material.animation;
+
+        camera.resolution = resolution;
+
+        // the clock only matters while something is animating
+        if animating {
+            animation.time = time.elapsed_secs();
+            animation.delta_time = time.delta_secs();
+            animation.frame = frame_count.0;
         }
 
         if settings.rotation_speed > 0.0 {
@@ -150,24 +363,70 @@ fn update_material(
                 Quat::from_rotation_x(settings.rotation_speed * time.delta_secs());
 
             let new_rotation =
-                delta_rotation_y * delta_rotation_x * Quat::from_vec4(material.camera_rotation);
-            material.camera_rotation = Vec4::from(new_rotation.normalize());
+                delta_rotation_y * delta_rotation_x * Quat::from_vec4(camera.rotation);
+            camera.rotation = Vec4::from(new_rotation.normalize());
+        }
+
+        // the phase advances rather than following the clock, so speed changes don't jump
+        if shape.breath_amplitude > 0.0 {
+            let breaths_per_sec = if settings.sync_breathing {
+                shape.breath_speed * sequencer.bpm / 60.0
+            } else {
+                shape.breath_speed
+            };
+            animation.breath_phase =
+                (animation.breath_phase + breaths_per_sec * time.delta_secs()).fract();
         }
 
-        if settings.animate_zoom {
-            material.camera_zoom =
-                2.75 + ((time.elapsed_secs_f64() * settings.zoom_speed as f64).sin() as f32) * 0.25;
+        if camera != material.camera || animation != material.animation {
+            let material = materials.get_mut(id).unwrap();
+            material.camera = camera;
+            material.animation = animation;
         }
     }
 }
 
+/// Keeps the camera's render target in a floating point format while an HDR output mode is
+/// selected, so values above 1.0 are not clipped before the output pass encodes them.
+fn sync_hdr_camera(
+    mut commands: Commands,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    cameras: Query<(Entity, Has<Hdr>), With<MainCamera>>,
+) {
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let wants_hdr = mat.shading.output_mode != OUTPUT_MODE_SDR;
+
+    for (entity, has_hdr) in cameras.iter() {
+        if wants_hdr && !has_hdr {
+            commands.entity(entity).insert((Hdr, Tonemapping::None));
+        } else if !wants_hdr && has_hdr {
+            commands.entity(entity).remove::<Hdr>();
+        }
+    }
+}
+
+/// Rescales the quad to cover the primary window, or the locked-aspect frame inside it, whenever
+/// either changes
 fn resize_mesh(
-    window: Query<&Window>,
-    mut transforms: Query<&mut Transform, With<MeshMaterial2d<MandelbulbMaterial>>>,
+    mut resized: MessageReader<WindowResized>,
+    window: Query<(Entity, &Window), With<PrimaryWindow>>,
+    compose: Res<ComposeSettings>,
+    mut transforms: Query<&mut Transform, With<FractalQuad>>,
 ) {
-    let win = window.single().unwrap();
+    let Ok((primary, win)) = window.single() else {
+        return;
+    };
+    let primary_resized = resized.read().any(|ev| ev.window == primary);
+    if !primary_resized && !compose.is_changed() {
+        return;
+    }
+
+    let size = compose.frame_size(Vec2::new(win.width(), win.height()));
     for mut transform in transforms.iter_mut() {
-        transform.scale = Vec3::new(win.width(), win.height(), 1.0);
+        transform.scale = size.extend(1.0);
     }
 }
 
@@ -182,10 +441,6 @@ fn keyboard_controls(
     keys: Res<ButtonInput<KeyCode>>,
     time: Res<Time>,
 ) {
-    if keys.get_pressed().next().is_none() {
-        return;
-    }
-
     let speed = 2.0 * time.delta_secs();
     let rotation_speed = 1.5 * time.delta_secs();
 
@@ -228,8 +483,13 @@ fn keyboard_controls(
         pitch += rotation_speed; // Look down
     }
 
+    // other keys, like hotkeys or typing into a panel, leave the materials untouched
+    if move_input == Vec3::ZERO && yaw == 0.0 && pitch == 0.0 {
+        return;
+    }
+
     for (_, mat) in materials.iter_mut() {
-        let rotation = Quat::from_vec4(mat.camera_rotation);
+        let rotation = Quat::from_vec4(mat.camera.rotation);
 
         // Handle movement
         if move_input != Vec3::ZERO {
@@ -242,7 +502,7 @@ fn keyboard_controls(
             let up = inv_rotation.mul_vec3(Vec3::new(0.0, 1.0, 0.0));
 
             let movement = forward * move_input.z + right * move_input.x + up * move_input.y;
-            mat.camera_position += movement;
+            mat.camera.position += movement;
         }
 
         // Handle rotation
@@ -251,61 +511,107 @@ fn keyboard_controls(
             let delta_pitch = Quat::from_rotation_x(pitch);
 
             let new_rotation = delta_yaw * delta_pitch * rotation;
-            mat.camera_rotation = Vec4::from(new_rotation.normalize());
+            mat.camera.rotation = Vec4::from(new_rotation.normalize());
         }
     }
 }
 
-/// Handles mouse input for rotating the camera when the left mouse button is held down.
-/// The rotation is applied about the origin
+/// Handles mouse input for rotating the camera when the left mouse button is held down, and
+/// zooming with the wheel. The rotation is applied about the origin
 fn mouse_controls(
     mut materials: ResMut<Assets<MandelbulbMaterial>>,
     buttons: Res<ButtonInput<MouseButton>>,
     mut motion_evr: MessageReader<MouseMotion>,
-    mut contexts: EguiContexts,
+    mut wheel_evr: MessageReader<MouseWheel>,
+    mouse: Res<MouseSettings>,
+    store: Res<ParamStore>,
+    mut param_changes: MessageWriter<ParamChanged>,
+    #[cfg(feature = "ui")] mut contexts: EguiContexts,
 ) {
     // If the mouse is over an egui area, don't rotate
-    let ctx = contexts.ctx_mut().unwrap();
-    if ctx.is_pointer_over_area() || ctx.wants_pointer_input() {
-        return;
+    #[cfg(feature = "ui")]
+    {
+        let ctx = contexts.ctx_mut().unwrap();
+        if ctx.is_pointer_over_area() || ctx.wants_pointer_input() {
+            return;
+        }
     }
 
     // On left mouse button drag, rotate the fractal
     if buttons.pressed(MouseButton::Left) {
         for ev in motion_evr.read() {
-            let sensitivity = 0.005;
-
-            for (_, mat) in materials.iter_mut() {
-                let current_quat = Quat::from_vec4(mat.camera_rotation);
-
-                let delta_yaw = Quat::from_rotation_y(-ev.delta.x * sensitivity);
-                let delta_pitch = Quat::from_rotation_x(ev.delta.y * sensitivity);
-                let new_quat = delta_yaw *  delta_pitch * current_quat;
-                mat.camera_rotation = Vec4::from(new_quat.normalize());
-            }
+            let (yaw, pitch) = mouse.drag_rotation(ev.delta);
+            rotate_all_views(
+                &mut materials,
+                Quat::from_rotation_y(yaw) * Quat::from_rotation_x(pitch),
+            );
         }
     }
+
+    // Wheel zoom, through the parameter so the slider follows
+    let current = store.get(Param::Zoom);
+    let zoom = wheel_evr
+        .read()
+        .fold(current, |zoom, ev| mouse.scrolled_zoom(zoom, ev));
+    if zoom != current {
+        param_changes.write(ParamChanged {
+            param: Param::Zoom,
+            value: zoom,
+        });
+    }
 }
 
 fn manage_rendering_mode(
     mut winit_settings: ResMut<WinitSettings>,
-    sim_settings: Res<SimSettings>,
+    mut keep_awake: ResMut<KeepAwake>,
+    low_power: Res<LowPower>,
+    mut material_events: MessageReader<AssetEvent<MandelbulbMaterial>>,
+    mut unchanged_frames: Local<u32>,
 ) {
+    // low power caps the frame rate, also while animating
+    let frame_time = if low_power.active() {
+        1.0 / LOW_POWER_FPS
+    } else {
+        1.0 / 60.0
+    };
+    let capped = UpdateMode::Reactive {
+        wait: Duration::from_secs_f64(frame_time),
+        react_to_device_events: false,
+        react_to_user_events: false,
+        react_to_window_events: false,
+    };
+
     // Check if anything requires continuous updates
-    let is_animating = sim_settings.animate_zoom
-        || sim_settings.animate_power
-        || sim_settings.rotation_speed > 0.0;
+    if keep_awake.take() {
+        material_events.clear();
+        *unchanged_frames = 0;
 
-    if is_animating {
         // If animating, render every frame
-        winit_settings.focused_mode = UpdateMode::Continuous;
-        winit_settings.unfocused_mode = UpdateMode::Continuous;
+        let mode = if low_power.active() {
+            capped
+        } else {
+            UpdateMode::Continuous
+        };
+        winit_settings.focused_mode = mode;
+        winit_settings.unfocused_mode = mode;
     } else {
-        winit_settings.focused_mode = UpdateMode::Reactive {
-            wait: Duration::from_secs_f64(1.0 / 60.0), // if focused, check 60 times per second
-            react_to_device_events: false,
-            react_to_user_events: false,
-            react_to_window_events: false,
+        // once nothing has changed for a few frames, the last frame stays on screen and nothing
+        // is rendered until input arrives
+        if material_events.read().count() > 0 {
+            *unchanged_frames = 0;
+        } else {
+            *unchanged_frames = unchanged_frames.saturating_add(1);
+        }
+        winit_settings.focused_mode = if *unchanged_frames < ECO_SETTLE_FRAMES {
+            // check 60 times per second, or at the low power rate
+            capped
+        } else {
+            UpdateMode::Reactive {
+                wait: ECO_WAIT,
+                react_to_device_events: false,
+                react_to_user_events: true,
+                react_to_window_events: true,
+            }
         };
 
         winit_settings.unfocused_mode = UpdateMode::Reactive {
@@ -317,13 +623,26 @@ fn manage_rendering_mode(
     }
 }
 
-#[derive(Resource)]
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
 struct SimSettings {
     rotation_speed: f32,
     animate_zoom: bool,
     zoom_speed: f32,
     animate_power: bool,
     power_speed: f32,
+    /// Edit the power as a whole number and a fraction rather than with one slider
+    #[cfg(feature = "ui")]
+    split_power: bool,
+    /// Count the breathing speed in breaths per beat of the sequencer's tempo
+    sync_breathing: bool,
+}
+
+impl SimSettings {
+    /// Whether anything changes the material on its own, without user input
+    fn is_animating(&self) -> bool {
+        self.animate_zoom || self.animate_power || self.rotation_speed > 0.0
+    }
 }
 
 impl Default for SimSettings {
@@ -334,176 +653,14 @@ impl Default for SimSettings {
             zoom_speed: 1.0,
             animate_power: false,
             power_speed: 1.0,
+            #[cfg(feature = "ui")]
+            split_power: false,
+            sync_breathing: false,
         }
     }
 }
 
-fn ui_controls(
-    mut contexts: EguiContexts,
-    mut materials: ResMut<Assets<MandelbulbMaterial>>,
-    mut settings: ResMut<SimSettings>,
-) {
-    let ctx = contexts.ctx_mut().unwrap();
-
-    egui::Window::new("Mandelbulb Settings")
-        .default_width(300.0)
-        .show(ctx, |ui| {
-            ui.heading("Fractal Parameters");
-
-            for (_, mat) in materials.iter_mut() {
-                // SHAPE SETTINGS
-                ui.separator();
-                ui.label("Shape");
-
-                ui.add_enabled(
-                    !settings.animate_power,
-                    egui::Slider::new(&mut mat.power, -2.0..=16.0).text("Power"),
-                );
-
-                let mut iters = mat.mandel_iters as f32;
-                if ui
-                    .add(egui::Slider::new(&mut iters, 1.0..=50.0).text("Iterations"))
-                    .changed()
-                {
-                    mat.mandel_iters = iters as u32;
-                }
-
-                // RENDERING SETTINGS
-                ui.separator();
-                ui.label("Rendering Quality");
-                let mut steps = mat.ray_steps as f32;
-                if ui
-                    .add(egui::Slider::new(&mut steps, 10.0..=300.0).text("Ray Steps"))
-                    .changed()
-                {
-                    mat.ray_steps = steps as u32;
-                }
-                ui.add(
-                    egui::Slider::new(&mut mat.hit_threshold, 0.0001..=0.01)
-                        .text("Threshold")
-                        .logarithmic(true),
-                );
-                ui.add(egui::Slider::new(&mut mat.max_dist, 10.0..=100.0).text("Max Dist"));
-
-                // CAMERA SETTINGS
-                ui.separator();
-                ui.label("Camera");
-
-                ui.add_enabled(
-                    !settings.animate_zoom,
-                    egui::Slider::new(&mut mat.camera_zoom, 0.1..=10.0).text("Zoom"),
-                );
-
-                ui.add(
-                    egui::Slider::new(&mut settings.rotation_speed, 0.0..=1.0)
-                        .text("Rotation Speed"),
-                );
-
-                // ANIMATION SETTINGS
-                ui.separator();
-                ui.heading("Animations");
-
-                ui.checkbox(&mut settings.animate_power, "Auto-Animate Power");
-                if settings.animate_power {
-                    ui.indent("power_speed", |ui| {
-                        ui.add(
-                            egui::Slider::new(&mut settings.power_speed, 0.01..=4.0)
-                                .text("Power Speed"),
-                        );
-                    });
-                }
-
-                ui.checkbox(&mut settings.animate_zoom, "Auto-Animate Zoom");
-                if settings.animate_zoom {
-                    ui.indent("zoom_speed", |ui| {
-                        ui.add(
-                            egui::Slider::new(&mut settings.zoom_speed, 0.1..=5.0)
-                                .text("Zoom Speed"),
-                        );
-                    });
-                }
-
-                // VISUAL STYLE
-                ui.separator();
-                ui.heading("Visual Style");
-
-                ui.add(
-                    egui::Slider::new(&mut mat.background_glow_intensity, 0.0..=5.0).text("Background Brightness"),
-                );
-
-                ui.horizontal(|ui| {
-                    ui.label("Color Palette");
-                    egui::ComboBox::from_id_salt("palette_combo")
-                        .selected_text(match mat.palette_id {
-                            0 => "Standard",
-                            1 => "Fire (Red/Yellow)",
-                            2 => "Neon (Purple/Green)",
-                            _ => "Unknown",
-                        })
-                        .show_ui(ui, |ui| {
-                            ui.selectable_value(&mut mat.palette_id, 0, "Standard");
-                            ui.selectable_value(&mut mat.palette_id, 2, "Fire");
-                            ui.selectable_value(&mut mat.palette_id, 3, "Neon");
-                        });
-                });
-
-                ui.add(
-                    egui::Slider::new(&mut mat.color_scale, 0.1..=3.0)
-                        .text("Color Scale")
-                        .step_by(0.01),
-                );
-                ui.add(
-                    egui::Slider::new(&mut mat.color_offset, 0.0..=1.0)
-                        .text("Color Offset")
-                        .step_by(0.005),
-                );
-
-                ui.separator();
-                ui.heading("Lighting");
-                ui.add(egui::Slider::new(&mut mat.light_pos_x, -10.0..=10.0).text("Light X"));
-                ui.add(egui::Slider::new(&mut mat.light_pos_y, -10.0..=10.0).text("Light Y"));
-                ui.add(
-                    egui::Slider::new(&mut mat.ao_strength, 0.0..=5.0)
-                        .text("Ambient Occlusion")
-                        .step_by(0.01),
-                );
-                ui.add(
-                    egui::Slider::new(&mut mat.rim_strength, 0.0..=2.0)
-                        .text("Rim Lighting")
-                        .step_by(0.01),
-                );
-                ui.add(
-                    egui::Slider::new(&mut mat.fog_density, 0.0..=1.0)
-                        .text("Fog Density")
-                        .step_by(0.01),
-                );
-
-                // JULIA FOLDING CONTROLS
-                ui.separator();
-                ui.heading("Julia Folding");
-
-                // enable/disable toggle
-                let mut is_julia = mat.julia.w > 0.5;
-                if ui.checkbox(&mut is_julia, "Enable Julia Mode").changed() {
-                    mat.julia.w = if is_julia { 1.0 } else { 0.0 };
-                }
-
-                // coordinate Sliders
-                if is_julia {
-                    ui.indent("julia_controls", |ui| {
-                        ui.label("Constant K");
-                        ui.add(egui::Slider::new(&mut mat.julia.x, -2.0..=2.0).step_by(0.005).text("X"));
-                        ui.add(egui::Slider::new(&mut mat.julia.y, -2.0..=2.0).step_by(0.005).text("Y"));
-                        ui.add(egui::Slider::new(&mut mat.julia.z, -2.0..=2.0).step_by(0.005).text("Z"));
-                    });
-                }
-
-                ui.separator();
-                ui.heading("Performance");
-                let mut ss_enabled = mat.supersampling_enabled > 0;
-                if ui.checkbox(&mut ss_enabled, "Supersampling (2x2)").changed() {
-                    mat.supersampling_enabled = if ss_enabled { 1 } else { 0 };
-                }
-            }
-        });
+/// Whether the shape breathes, which redraws every frame
+fn is_breathing(store: &ParamStore) -> bool {
+    store.get(Param::BreathAmplitude) > 0.0
 }
@@ -0,0 +1,335 @@
+use crate::formulas::FORMULAS_DIR;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use bevy::tasks::{IoTaskPool, Task, block_on, poll_once};
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Environment variable that sets the default index URL
+const INDEX_URL_VAR: &str = "FRACTAL3D_FORMULA_INDEX";
+
+/// Files a formula in the index may install, anything else in its file list is rejected
+const ALLOWED_FILES: [&str; 3] = ["formula.ron", "de.wgsl", "de.frac"];
+
+pub struct MarketplacePlugin;
+
+impl Plugin for MarketplacePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Marketplace>()
+            .add_systems(Update, poll_downloads)
+            .add_systems(EguiPrimaryContextPass, marketplace_window);
+    }
+}
+
+/// An index is a JSON document listing formulas shared by the community:
+///
+/// ```json
+/// { "formulas": [{
+///     "id": "menger_sponge",
+///     "name": "Menger Sponge",
+///     "description": "...",
+///     "author": "...",
+///     "screenshot": "https://.../menger.png",
+///     "files": { "formula.ron": "https://...", "de.frac": "https://..." }
+/// }] }
+/// ```
+#[derive(Deserialize)]
+struct Index {
+    formulas: Vec<IndexEntry>,
+}
+
+#[derive(Deserialize, Clone)]
+struct IndexEntry {
+    /// Folder name under [`FORMULAS_DIR`]
+    id: String,
+    name: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    author: String,
+    /// PNG shown next to the entry
+    #[serde(default)]
+    screenshot: Option<String>,
+    /// File name to download URL
+    files: BTreeMap<String, String>,
+}
+
+enum Download<T> {
+    Pending(Task<Result<T, String>>),
+    Done(T),
+    Failed(String),
+}
+
+impl<T> Download<T> {
+    fn start(fetch: impl FnOnce() -> Result<T, String> + Send + 'static) -> Self
+    where
+        T: Send + 'static,
+    {
+        Download::Pending(IoTaskPool::get().spawn(async move { fetch() }))
+    }
+
+    /// Moves a finished task into `Done` or `Failed`
+    fn poll(&mut self) {
+        if let Download::Pending(task) = self
+            && let Some(result) = block_on(poll_once(task))
+        {
+            *self = match result {
+                Ok(value) => Download::Done(value),
+                Err(err) => Download::Failed(err),
+            };
+        }
+    }
+}
+
+/// In-app browser for formula plugins and scripts from a community index. Installed formulas are
+/// written to [`FORMULAS_DIR`] and show up in the formula list after a restart.
+#[derive(Resource)]
+pub struct Marketplace {
+    pub open: bool,
+    pub index_url: String,
+    index: Option<Download<Vec<IndexEntry>>>,
+    screenshots: HashMap<String, Download<egui::ColorImage>>,
+    textures: HashMap<String, egui::TextureHandle>,
+    installs: HashMap<String, Download<()>>,
+}
+
+impl Default for Marketplace {
+    fn default() -> Self {
+        Self {
+            open: false,
+            index_url: std::env::var(INDEX_URL_VAR).unwrap_or_default(),
+            index: None,
+            screenshots: HashMap::default(),
+            textures: HashMap::default(),
+            installs: HashMap::default(),
+        }
+    }
+}
+
+impl Marketplace {
+    fn refresh(&mut self) {
+        let url = self.index_url.trim().to_string();
+        self.screenshots.clear();
+        self.textures.clear();
+        self.index = Some(Download::start(move || {
+            let body = fetch(&url)?;
+            let index: Index = serde_json::from_slice(&body).map_err(|e| e.to_string())?;
+            Ok(index.formulas)
+        }));
+    }
+
+    fn install(&mut self, entry: &IndexEntry) {
+        let entry = entry.clone();
+        self.installs.insert(
+            entry.id.clone(),
+            Download::start(move || install_formula(&entry)),
+        );
+    }
+}
+
+#[cfg(feature = "network")]
+fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    ureq::get(url)
+        .call()
+        .and_then(|mut response| response.body_mut().read_to_vec())
+        .map_err(|e| format!("{url}: {e}"))
+}
+
+#[cfg(not(feature = "network"))]
+fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    Err(format!("{url}: built without the network feature"))
+}
+
+fn is_installed(id: &str) -> bool {
+    Path::new(FORMULAS_DIR).join(id).is_dir()
+}
+
+/// Downloads every file of the entry, then writes them into its folder. Nothing is written if
+/// any download fails or the entry asks for a file outside its folder.
+fn install_formula(entry: &IndexEntry) -> Result<(), String> {
+    let valid_id = !entry.id.is_empty()
+        && entry
+            .id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if !valid_id {
+        return Err(format!("invalid formula id {:?}", entry.id));
+    }
+    if let Some(name) = entry
+        .files
+        .keys()
+        .find(|f| !ALLOWED_FILES.contains(&f.as_str()))
+    {
+        return Err(format!("unexpected file {name:?}"));
+    }
+    if !entry.files.contains_key("formula.ron") {
+        return Err("missing formula.ron".into());
+    }
+
+    let mut files = Vec::new();
+    for (name, url) in &entry.files {
+        files.push((name, fetch(url)?));
+    }
+
+    let folder = Path::new(FORMULAS_DIR).join(&entry.id);
+    std::fs::create_dir_all(&folder).map_err(|e| e.to_string())?;
+    for (name, bytes) in files {
+        std::fs::write(folder.join(name), bytes).map_err(|e| e.to_string())?;
+    }
+    info!("Installed formula {} into {}", entry.name, folder.display());
+    Ok(())
+}
+
+fn fetch_screenshot(url: &str) -> Result<egui::ColorImage, String> {
+    let bytes = fetch(url)?;
+    let image = image::load_from_memory(&bytes)
+        .map_err(|e| e.to_string())?
+        .thumbnail(256, 256)
+        .to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+    Ok(egui::ColorImage::from_rgba_unmultiplied(
+        size,
+        image.as_raw(),
+    ))
+}
+
+fn poll_downloads(mut marketplace: ResMut<Marketplace>) {
+    let marketplace = &mut *marketplace;
+    if let Some(index) = &mut marketplace.index {
+        index.poll();
+    }
+    for download in marketplace.screenshots.values_mut() {
+        download.poll();
+    }
+    for download in marketplace.installs.values_mut() {
+        download.poll();
+    }
+
+    // start loading screenshots once the index is in
+    if let Some(Download::Done(entries)) = &marketplace.index {
+        for entry in entries {
+            if let Some(url) = &entry.screenshot
+                && !marketplace.screenshots.contains_key(&entry.id)
+            {
+                let url = url.clone();
+                marketplace.screenshots.insert(
+                    entry.id.clone(),
+                    Download::start(move || fetch_screenshot(&url)),
+                );
+            }
+        }
+    }
+}
+
+fn marketplace_window(mut contexts: EguiContexts, mut marketplace: ResMut<Marketplace>) {
+    if !marketplace.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let marketplace = &mut *marketplace;
+
+    // upload finished screenshots to egui once
+    for (id, download) in &marketplace.screenshots {
+        if let Download::Done(image) = download
+            && !marketplace.textures.contains_key(id)
+        {
+            let texture = ctx.load_texture(id, image.clone(), egui::TextureOptions::LINEAR);
+            marketplace.textures.insert(id.clone(), texture);
+        }
+    }
+
+    let mut open = marketplace.open;
+    let mut install = None;
+    egui::Window::new("Formula Browser")
+        .open(&mut open)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Index URL");
+                ui.text_edit_singleline(&mut marketplace.index_url);
+                let loading = matches!(marketplace.index, Some(Download::Pending(_)));
+                if ui
+                    .add_enabled(
+                        !loading && !marketplace.index_url.trim().is_empty(),
+                        egui::Button::new("Refresh"),
+                    )
+                    .clicked()
+                {
+                    marketplace.refresh();
+                }
+            });
+            ui.separator();
+
+            let entries = match &marketplace.index {
+                None => {
+                    ui.label("Enter the URL of a formula index and press Refresh.");
+                    return;
+                }
+                Some(Download::Pending(_)) => {
+                    ui.spinner();
+                    return;
+                }
+                Some(Download::Failed(err)) => {
+                    ui.colored_label(egui::Color32::LIGHT_RED, err.as_str());
+                    return;
+                }
+                Some(Download::Done(entries)) => entries,
+            };
+            if entries.is_empty() {
+                ui.label("The index lists no formulas.");
+            }
+
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for entry in entries {
+                    ui.horizontal(|ui| {
+                        match marketplace.textures.get(&entry.id) {
+                            Some(texture) => {
+                                ui.add(
+                                    egui::Image::new(texture)
+                                        .fit_to_exact_size(egui::vec2(96.0, 96.0)),
+                                );
+                            }
+                            None => {
+                                ui.allocate_space(egui::vec2(96.0, 96.0));
+                            }
+                        }
+                        ui.vertical(|ui| {
+                            ui.strong(entry.name.as_str());
+                            if !entry.author.is_empty() {
+                                ui.small(format!("by {}", entry.author));
+                            }
+                            ui.label(entry.description.as_str());
+
+                            match marketplace.installs.get(&entry.id) {
+                                Some(Download::Pending(_)) => {
+                                    ui.spinner();
+                                }
+                                Some(Download::Done(())) => {
+                                    ui.label("Installed, restart to use it");
+                                }
+                                Some(Download::Failed(err)) => {
+                                    ui.colored_label(egui::Color32::LIGHT_RED, err.as_str());
+                                }
+                                None if is_installed(&entry.id) => {
+                                    ui.label("Installed");
+                                }
+                                None => {
+                                    if ui.button("Install").clicked() {
+                                        install = Some(entry.clone());
+                                    }
+                                }
+                            }
+                        });
+                    });
+                    ui.separator();
+                }
+            });
+        });
+
+    if let Some(entry) = install {
+        marketplace.install(&entry);
+    }
+    marketplace.open = open;
+}
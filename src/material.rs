@@ -0,0 +1,535 @@
+use crate::brick_map::BRICK_MAP_IMAGE;
+use crate::flare::LensFlare;
+use crate::formulas;
+use crate::geometry_graph::GEOMETRY_GRAPH_SHADER;
+use crate::histogram::HISTOGRAM_BUFFER;
+use crate::palette::CosinePalette;
+use crate::reprojection::{UNUSED_DEPTH, UNUSED_PREVIOUS_DEPTH};
+use bevy::mesh::MeshVertexBufferLayoutRef;
+use bevy::prelude::*;
+use bevy::render::render_resource::{
+    AsBindGroup, RenderPipelineDescriptor, ShaderType, SpecializedMeshPipelineError,
+};
+use bevy::render::storage::ShaderStorageBuffer;
+use bevy::shader::{ShaderDefVal, ShaderRef};
+use bevy::sprite_render::{Material2d, Material2dKey};
+
+// Uniform blocks. Every block is laid out in 16 byte rows with explicit padding so the Rust and
+// WGSL declarations can be compared line by line, and a new field either fills a padding slot or
+// starts a new row. Keep `assets/shaders/mandelbulb.wgsl` in sync when changing these.
+
+/// Where the camera is and what it is looking at
+#[derive(ShaderType, Reflect, Clone, Copy, PartialEq, Debug)]
+pub struct CameraUniform {
+    pub position: Vec3,
+    pub zoom: f32,      // distance from the orbit center
+    pub rotation: Vec4, // quaternion (x, y, z, w)
+    pub resolution: Vec2,
+    pub pixel_aspect: f32, // width / height of one pixel, above 1.0 for anamorphic squeeze
+    pub focal_length: f32, // distance to the image plane, smaller is a wider field of view
+    pub fov_fit: u32, // 0=vertical extent fixed, 1=horizontal extent fixed, 2=shorter side fixed
+    pub _padding0: f32,
+    pub _padding1: f32,
+    pub _padding2: f32,
+}
+
+impl Default for CameraUniform {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            zoom: 2.5,
+            rotation: Vec4::from(Quat::IDENTITY),
+            resolution: Vec2::new(1280.0, 720.0),
+            pixel_aspect: 1.0,
+            focal_length: 1.5,
+            fov_fit: 0,
+            _padding0: 0.0,
+            _padding1: 0.0,
+            _padding2: 0.0,
+        }
+    }
+}
+
+impl CameraUniform {
+    /// Ray origin in world space, the same for every pixel
+    pub fn ray_origin(&self) -> Vec3 {
+        self.position + self.orientation().inverse() * Vec3::new(0.0, 0.0, -self.zoom)
+    }
+
+    /// World space ray through a [0, 1] screen uv, as (origin, direction). Mirrors the shader's
+    /// `image_plane_uv` and ray setup.
+    #[cfg(feature = "ui")]
+    pub fn ray(&self, screen_uv: Vec2) -> (Vec3, Vec3) {
+        let mut uv = screen_uv * 2.0 - 1.0;
+        if self.fit_horizontal() {
+            uv.y /= self.aspect();
+        } else {
+            uv.x *= self.aspect();
+        }
+        let dir = self.orientation().inverse() * uv.extend(self.focal_length).normalize();
+        (self.ray_origin(), dir)
+    }
+
+    /// Screen uv where `p` appears, the inverse of [`Self::ray`]. `None` behind the camera.
+    #[cfg(feature = "ui")]
+    pub fn project(&self, p: Vec3) -> Option<Vec2> {
+        let local = self.orientation() * (p - self.ray_origin());
+        if local.z <= 0.0 {
+            return None;
+        }
+        let mut uv = local.truncate() * self.focal_length / local.z;
+        if self.fit_horizontal() {
+            uv.y *= self.aspect();
+        } else {
+            uv.x /= self.aspect();
+        }
+        Some((uv + 1.0) * 0.5)
+    }
+
+    fn orientation(&self) -> Quat {
+        Quat::from_vec4(self.rotation)
+    }
+
+    /// Display aspect, corrected for non-square pixels
+    #[cfg(feature = "ui")]
+    fn aspect(&self) -> f32 {
+        self.resolution.x / self.resolution.y * self.pixel_aspect
+    }
+
+    #[cfg(feature = "ui")]
+    fn fit_horizontal(&self) -> bool {
+        self.fov_fit == 1 || (self.fov_fit == 2 && self.aspect() < 1.0)
+    }
+}
+
+/// The fractal formula parameters and the ray marcher's quality settings
+#[derive(ShaderType, Reflect, Clone, Copy, PartialEq, Debug)]
+pub struct ShapeUniform {
+    pub julia: Vec4, // xyz are the constant, w is the enabled flag
+    pub power: f32,
+    pub mandel_iters: u32,
+    pub ray_steps: u32,
+    pub max_dist: f32,
+    pub hit_threshold: f32,
+    pub stall_limit: u32, // steps near the surface without progress before a ray stops, 0=off
+    pub breath_amplitude: f32, // how far the breathing displaces the domain, 0=off
+    pub breath_speed: f32, // breaths per second, or per beat with tempo sync, read on the CPU
+
+    pub plugin_params: [Vec4; 2], // parameters of a plugin formula, read with plugin_param(i)
+}
+
+impl Default for ShapeUniform {
+    fn default() -> Self {
+        Self {
+            julia: Vec4::new(0.35, 0.35, -0.35, 0.0), // last value 0, not used initially
+            power: 8.0,
+            mandel_iters: 10,
+            ray_steps: 220,
+            max_dist: 20.0,
+            hit_threshold: 0.0025,
+            stall_limit: 0,
+            breath_amplitude: 0.0,
+            breath_speed: 0.2,
+            plugin_params: [Vec4::ZERO; 2],
+        }
+    }
+}
+
+/// Coloring, lighting and output settings
+#[derive(ShaderType, Reflect, Clone, Copy, PartialEq, Debug)]
+pub struct ShadingUniform {
+    pub light_pos_x: f32,
+    pub light_pos_y: f32,
+    pub palette_id: u32, // index into the palette table, the shader reads `palette`
+    pub background_glow_intensity: f32,
+
+    pub color_scale: f32,
+    pub color_offset: f32,
+    pub ao_strength: f32,
+    pub rim_strength: f32,
+
+    pub fog_density: f32,
+    pub supersampling_enabled: u32,
+    pub output_mode: u32,      // see OUTPUT_MODE_SDR and the HDR modes
+    pub paper_white_nits: f32, // brightness of diffuse white in HDR modes
+
+    pub test_pattern: u32, // 0=off, otherwise a calibration pattern replaces the fractal
+    pub streamline_density: f32, // noise frequency of the flow lines, higher is finer
+    pub streamline_length: f32, // world units traced on each side of a point
+    pub isoline_mode: u32, // 0=off, 1=contours on the surface, 2=shells in empty space
+
+    pub isoline_spacing: f32, // distance estimate between neighbouring isolines
+    pub isoline_intensity: f32,
+    pub slice_mode: u32, // 0=off, 1=inset, 2=fullscreen heatmap of the DE on a plane
+    pub slice_axis: u32, // 0=XY, 1=XZ, 2=YZ plane
+
+    pub slice_offset: f32, // position of the plane along its normal axis
+    pub slice_extent: f32, // half the width of the plane area shown
+    pub show_stalled: u32, // 1=color rays stopped by stall_limit instead of shading them
+    pub transparent_background: u32, // 1=rays that miss are transparent, see LayersPlugin
+
+    pub light_color: Vec4,   // linear RGB of the key light, w unused
+    pub ambient_color: Vec4, // linear RGB of the light reaching every surface, w unused
+    pub rim_color: Vec4,     // linear RGB of the rim light, w unused
+
+    pub specular_intensity: f32, // brightness of the key light's highlight, 0 is matte
+    pub shininess: f32,          // Blinn-Phong exponent, higher is a smaller, sharper highlight
+    pub rim_direction: u32,      // 0=every silhouette edge, 1=only edges facing the light
+    pub lens_strength: f32, // pull bending rays toward the origin like a black hole, 0=straight rays
+
+    pub lens_distortion: f32, // radial distortion of the image, positive is barrel, negative pincushion
+    pub chromatic_aberration: f32, // extra distortion of red and less of blue, 0=off
+    pub grain: f32,           // strength of the film grain, 0=off
+    pub noise_seed: u32,      // hashed into the grain, see NoiseLock
+
+    pub palette: [Vec4; 4], // cosine palette coefficients a, b, c and d of palette_id
+}
+
+impl Default for ShadingUniform {
+    fn default() -> Self {
+        Self {
+            light_pos_x: 8.0,
+            light_pos_y: 10.0,
+            palette_id: 0,
+            background_glow_intensity: 0.0,
+            color_scale: 0.95,
+            color_offset: 0.05,
+            ao_strength: 1.2,
+            rim_strength: 0.1,
+            fog_density: 0.05,
+            supersampling_enabled: 0,
+            output_mode: OUTPUT_MODE_SDR,
+            paper_white_nits: 203.0,
+            test_pattern: 0,
+            streamline_density: 120.0,
+            streamline_length: 0.02,
+            isoline_mode: 0,
+            isoline_spacing: 0.02,
+            isoline_intensity: 0.5,
+            slice_mode: 0,
+            slice_axis: 0,
+            slice_offset: 0.0,
+            slice_extent: 1.5,
+            show_stalled: 0,
+            transparent_background: 0,
+            light_color: Vec4::new(1.0, 0.9, 0.8, 0.0),
+            ambient_color: Vec4::new(0.1, 0.1, 0.1, 0.0),
+            rim_color: Vec4::new(0.0, 0.5, 1.0, 0.0),
+            specular_intensity: 0.8,
+            shininess: 32.0,
+            rim_direction: 0,
+            lens_strength: 0.0,
+            lens_distortion: 0.0,
+            chromatic_aberration: 0.0,
+            grain: 0.0,
+            noise_seed: 0,
+            palette: CosinePalette::STANDARD.uniform(),
+        }
+    }
+}
+
+/// Clock values for time based shader effects
+#[derive(ShaderType, Reflect, Clone, Copy, PartialEq, Debug, Default)]
+pub struct AnimationUniform {
+    pub time: f32,
+    pub delta_time: f32,
+    pub frame: u32,
+    pub breath_phase: f32, // position in the current breath, 0 to 1
+}
+
+/// The camera of the frame in the previous distance texture, to find where a pixel's ray was
+/// then. See `ReprojectionPlugin`.
+#[derive(ShaderType, Reflect, Clone, Copy, PartialEq, Debug, Default)]
+pub struct ReprojectionUniform {
+    pub previous: CameraUniform,
+    pub valid: u32, // 0 when the previous distances can't be reused, e.g. the shape changed
+    pub _padding0: f32,
+    pub _padding1: f32,
+    pub _padding2: f32,
+}
+
+#[derive(Asset, Reflect, AsBindGroup, Clone, PartialEq)]
+#[reflect(Default)]
+#[bind_group_data(MandelbulbMaterialKey)]
+pub struct MandelbulbMaterial {
+    #[uniform(0)]
+    pub camera: CameraUniform,
+    #[uniform(1)]
+    pub shape: ShapeUniform,
+    #[uniform(2)]
+    pub shading: ShadingUniform,
+    #[uniform(3)]
+    pub animation: AnimationUniform,
+    /// Distribution of the frame's coloring values, filled by `HistogramPlugin`
+    #[storage(4, read_only)]
+    pub histogram: Handle<ShaderStorageBuffer>,
+    /// Distance bounds for empty space skipping, baked by `BrickMapPlugin`
+    #[texture(5, dimension = "3d", sample_type = "float", filterable = false)]
+    pub brick_texture: Handle<Image>,
+    #[uniform(6)]
+    pub previous_frame: ReprojectionUniform,
+    /// Distances along each pixel's ray from the previous frame, read with temporal reuse on
+    #[texture(7, sample_type = "float", filterable = false)]
+    pub previous_depth: Handle<Image>,
+    /// Distances along each pixel's ray, written with temporal reuse on
+    #[storage_texture(8, image_format = R32Float, access = WriteOnly, visibility(fragment))]
+    pub depth: Handle<Image>,
+
+    // Compile-time options, each combination is a separate shader variant (see `specialize`)
+    pub formula: Formula,
+    /// Use the node graph from `geometry_graph` as the distance estimator, overriding `formula`
+    pub geometry_graph: bool,
+    pub coloring: ColoringMode,
+    pub soft_shadows: bool,
+    /// Trace flow lines of the distance estimator's gradient over the surface
+    pub streamlines: bool,
+    /// Color surfaces with the node graph from `shading_graph` instead of the palette
+    pub custom_shading: bool,
+    /// Skip empty space with the baked brick map instead of estimating the distance every step
+    pub brick_map: bool,
+    /// Start marching at the distances reprojected from the previous frame
+    pub reprojection: bool,
+    /// Output raw march data instead of colors, for the data export. Never set on the main
+    /// material.
+    pub data_output: bool,
+
+    /// Drawn by a pass of its own on the camera rather than by this material, see `FlarePlugin`
+    pub flare: LensFlare,
+}
+
+impl Default for MandelbulbMaterial {
+    fn default() -> Self {
+        Self {
+            camera: default(),
+            shape: default(),
+            shading: default(),
+            animation: default(),
+            histogram: HISTOGRAM_BUFFER,
+            brick_texture: BRICK_MAP_IMAGE,
+            previous_frame: default(),
+            previous_depth: UNUSED_PREVIOUS_DEPTH,
+            depth: UNUSED_DEPTH,
+            formula: default(),
+            geometry_graph: false,
+            coloring: default(),
+            soft_shadows: false,
+            streamlines: false,
+            custom_shading: false,
+            brick_map: false,
+            reprojection: false,
+            data_output: false,
+            flare: default(),
+        }
+    }
+}
+
+impl MandelbulbMaterial {
+    /// Default scene rendered at the given resolution
+    pub fn new(resolution: Vec2) -> Self {
+        Self::default().with_camera(CameraUniform {
+            resolution,
+            ..default()
+        })
+    }
+
+    pub fn with_camera(mut self, camera: CameraUniform) -> Self {
+        self.camera = camera;
+        self
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Formula {
+    #[default]
+    Mandelbulb,
+    Mandelbox,
+    /// Index into [`formulas::plugins`]
+    Plugin(u8),
+}
+
+impl Formula {
+    pub const BUILT_IN: [Formula; 2] = [Formula::Mandelbulb, Formula::Mandelbox];
+
+    /// The built-in formulas followed by every loaded plugin
+    pub fn all() -> impl Iterator<Item = Formula> {
+        let plugins = (0..formulas::plugins().len()).map(|i| Formula::Plugin(i as u8));
+        Formula::BUILT_IN.into_iter().chain(plugins)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Formula::Mandelbulb => "Mandelbulb",
+            Formula::Mandelbox => "Mandelbox",
+            Formula::Plugin(i) => formulas::plugins()
+                .get(i as usize)
+                .map_or("Missing Plugin", |plugin| plugin.name),
+        }
+    }
+}
+
+#[derive(Reflect, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum ColoringMode {
+    /// Minimum orbit radius combined with the step count
+    #[default]
+    OrbitTrap,
+    /// Surface orientation
+    Normal,
+    /// Orbit trap mapped through the frame's histogram, so every color gets an equal share of
+    /// the surface
+    Equalized,
+}
+
+impl ColoringMode {
+    pub const ALL: [ColoringMode; 3] = [
+        ColoringMode::OrbitTrap,
+        ColoringMode::Normal,
+        ColoringMode::Equalized,
+    ];
+}
+
+/// The part of the material that selects a shader variant rather than a uniform value
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MandelbulbMaterialKey {
+    formula: Formula,
+    geometry_graph: bool,
+    coloring: ColoringMode,
+    soft_shadows: bool,
+    streamlines: bool,
+    custom_shading: bool,
+    brick_map: bool,
+    reprojection: bool,
+    data_output: bool,
+}
+
+impl From<&MandelbulbMaterial> for MandelbulbMaterialKey {
+    fn from(material: &MandelbulbMaterial) -> Self {
+        Self {
+            formula: material.formula,
+            geometry_graph: material.geometry_graph,
+            coloring: material.coloring,
+            soft_shadows: material.soft_shadows,
+            streamlines: material.streamlines,
+            custom_shading: material.custom_shading,
+            brick_map: material.brick_map,
+            reprojection: material.reprojection,
+            data_output: material.data_output,
+        }
+    }
+}
+
+/// Standard dynamic range, gamma encoded for an 8-bit sRGB surface
+pub const OUTPUT_MODE_SDR: u32 = 0;
+
+/// Linear scRGB, Rec.709 primaries where 1.0 is 80 nits
+pub const OUTPUT_MODE_SCRGB: u32 = 1;
+
+/// HDR10, Rec.2020 primaries with the PQ curve
+pub const OUTPUT_MODE_HDR10: u32 = 2;
+
+/// Handle to the material shown in the main view. Other instances may exist temporarily, e.g.
+/// while the playlist pre-renders its next entry.
+#[derive(Resource)]
+pub struct FractalMaterial(pub Handle<MandelbulbMaterial>);
+
+/// Moves the camera of every view, the main one and any pre-rendered or layered instance, so
+/// they stay aligned while the user steers
+pub fn move_all_views(
+    materials: &mut Assets<MandelbulbMaterial>,
+    mut move_camera: impl FnMut(&mut CameraUniform),
+) {
+    for (_, mat) in materials.iter_mut() {
+        move_camera(&mut mat.camera);
+    }
+}
+
+/// Points every view like `camera`, for moving the main view to a whole other state along with
+/// [`ParamChanged::all`](crate::params::ParamChanged::all)
+pub fn aim_all_views(materials: &mut Assets<MandelbulbMaterial>, camera: &CameraUniform) {
+    move_all_views(materials, |view| {
+        view.position = camera.position;
+        view.rotation = camera.rotation;
+    });
+}
+
+/// Turns every view by `delta`, applied before the current rotation
+pub fn rotate_all_views(materials: &mut Assets<MandelbulbMaterial>, delta: Quat) {
+    move_all_views(materials, |camera| {
+        let rotation = Quat::from_vec4(camera.rotation);
+        camera.rotation = Vec4::from((delta * rotation).normalize());
+    });
+}
+
+impl Material2d for MandelbulbMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/mandelbulb.wgsl".into()
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let Some(fragment) = descriptor.fragment.as_mut() else {
+            return Ok(());
+        };
+        let key = key.bind_group_data;
+
+        if let Some(shader) =
+            estimator_shader(key.formula, key.geometry_graph, &mut fragment.shader_defs)
+        {
+            fragment.shader = shader;
+        }
+        match key.coloring {
+            ColoringMode::OrbitTrap => {}
+            ColoringMode::Normal => fragment.shader_defs.push("COLOR_BY_NORMAL".into()),
+            ColoringMode::Equalized => fragment.shader_defs.push("COLOR_EQUALIZED".into()),
+        }
+        if key.soft_shadows {
+            fragment.shader_defs.push("SOFT_SHADOWS".into());
+        }
+        if key.streamlines {
+            fragment.shader_defs.push("STREAMLINES".into());
+        }
+        if key.custom_shading {
+            fragment.shader_defs.push("CUSTOM_SHADING".into());
+        }
+        if key.brick_map {
+            fragment.shader_defs.push("BRICK_MAP".into());
+        }
+        if key.reprojection {
+            fragment.shader_defs.push("REPROJECTION".into());
+        }
+        if key.data_output {
+            fragment.shader_defs.push("DATA_OUTPUT".into());
+        }
+        Ok(())
+    }
+}
+
+/// Selects the distance estimator in the fractal shader by pushing its shader defs. Returns the
+/// shader to use instead of `shaders/mandelbulb.wgsl`, for estimators appended to a copy of it.
+/// Shared by every pipeline that evaluates the estimator, so they all see the same fractal.
+pub fn estimator_shader(
+    formula: Formula,
+    geometry_graph: bool,
+    shader_defs: &mut Vec<ShaderDefVal>,
+) -> Option<Handle<Shader>> {
+    if geometry_graph {
+        // like plugins, the graph's shader is the fractal shader with an estimator appended
+        shader_defs.push("GEOMETRY_GRAPH".into());
+        return Some(GEOMETRY_GRAPH_SHADER);
+    }
+    match formula {
+        Formula::Mandelbulb => None,
+        Formula::Mandelbox => {
+            shader_defs.push("FORMULA_MANDELBOX".into());
+            None
+        }
+        Formula::Plugin(i) => {
+            // plugin shaders are the fractal shader with the plugin's estimator appended
+            let plugin = formulas::plugins().get(i as usize)?;
+            shader_defs.push("FORMULA_PLUGIN".into());
+            Some(plugin.shader.clone())
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use crate::compose::ComposeSettings;
+use crate::estimator::CpuEstimator;
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use crate::mesh_export::MeshExport;
+use crate::units::{DisplayUnits, Quantity};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// Points along the surface path between the two ends
+const PATH_SEGMENTS: usize = 64;
+
+/// Smoothing passes that pull the surface path taut
+const RELAX_PASSES: usize = 16;
+
+/// Measures distances between two clicked surface points with the CPU estimator
+pub struct MeasurePlugin;
+
+impl Plugin for MeasurePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Measurement>()
+            .add_systems(Update, pick_measure_point)
+            .add_systems(EguiPrimaryContextPass, (measure_window, draw_measurement));
+    }
+}
+
+/// Two surface points and the distances between them. The straight distance is the chord, the
+/// surface distance follows a path pulled taut over the surface, an approximation of the
+/// geodesic that can shortcut across gaps narrower than its segments.
+#[derive(Resource, Default)]
+pub struct Measurement {
+    pub open: bool,
+    /// Clicks on the view pick points while set
+    picking: bool,
+    points: Vec<Vec3>,
+    /// Surface path from the first point to the second, empty until both are picked
+    path: Vec<Vec3>,
+}
+
+impl Measurement {
+    fn straight(&self) -> Option<f32> {
+        match self.points[..] {
+            [a, b] => Some(a.distance(b)),
+            _ => None,
+        }
+    }
+
+    fn along_surface(&self) -> Option<f32> {
+        (!self.path.is_empty()).then(|| self.path.windows(2).map(|w| w[0].distance(w[1])).sum())
+    }
+}
+
+/// Starts from the chord between the ends projected onto the surface, then repeatedly moves
+/// each point to the middle of its neighbours and back onto the surface
+fn surface_path(estimator: &CpuEstimator, threshold: f32, a: Vec3, b: Vec3) -> Vec<Vec3> {
+    let mut path: Vec<Vec3> = (0..=PATH_SEGMENTS)
+        .map(|i| a.lerp(b, i as f32 / PATH_SEGMENTS as f32))
+        .map(|p| estimator.project_to_surface(p, threshold))
+        .collect();
+    for _ in 0..RELAX_PASSES {
+        for i in 1..PATH_SEGMENTS {
+            let middle = (path[i - 1] + path[i + 1]) * 0.5;
+            path[i] = estimator.project_to_surface(middle, threshold);
+        }
+    }
+    path
+}
+
+fn pick_measure_point(
+    mut measurement: ResMut<Measurement>,
+    buttons: Res<ButtonInput<MouseButton>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    compose: Res<ComposeSettings>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    mut contexts: EguiContexts,
+) {
+    if !measurement.picking || !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    if ctx.is_pointer_over_area() || ctx.wants_pointer_input() {
+        return;
+    }
+    let (Ok(window), Some(mat)) = (window.single(), materials.get(&fractal.0)) else {
+        return;
+    };
+    let Some(estimator) = CpuEstimator::new(mat) else {
+        measurement.picking = false;
+        return;
+    };
+    let Some(uv) = compose.cursor_uv(window) else {
+        return;
+    };
+    let (origin, dir) = mat.camera.ray(uv);
+    let Some(t) = estimator.trace(&mat.shape, origin, dir) else {
+        return;
+    };
+
+    // a third click starts a new measurement
+    if measurement.points.len() == 2 {
+        measurement.points.clear();
+        measurement.path.clear();
+    }
+    measurement.points.push(origin + dir * t);
+    if let [a, b] = measurement.points[..] {
+        measurement.path = surface_path(&estimator, mat.shape.hit_threshold, a, b);
+    }
+}
+
+fn measure_window(
+    mut contexts: EguiContexts,
+    mut measurement: ResMut<Measurement>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    units: Res<DisplayUnits>,
+    mesh_export: Res<MeshExport>,
+) {
+    if !measurement.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let supported = materials
+        .get(&fractal.0)
+        .and_then(CpuEstimator::new)
+        .is_some();
+    let measurement = &mut *measurement;
+    let format = units.format((mesh_export.unit, mesh_export.scale));
+
+    let mut open = measurement.open;
+    egui::Window::new("Measure")
+        .open(&mut open)
+        .default_width(280.0)
+        .show(ctx, |ui| {
+            if !supported {
+                ui.label("Not available for plugin formulas and the geometry graph.");
+                return;
+            }
+            ui.horizontal(|ui| {
+                ui.toggle_value(&mut measurement.picking, "Pick Points")
+                    .on_hover_text("Click two points on the surface");
+                if ui.button("Clear").clicked() {
+                    measurement.points.clear();
+                    measurement.path.clear();
+                }
+            });
+            ui.separator();
+
+            for (i, p) in measurement.points.iter().enumerate() {
+                ui.label(format!(
+                    "Point {}: {}",
+                    i + 1,
+                    format.show_point(Quantity::Distance, *p, 4)
+                ));
+            }
+            match (measurement.straight(), measurement.along_surface()) {
+                (Some(straight), Some(surface)) => {
+                    let (straight, surface) = (
+                        format.show(Quantity::Distance, straight, 5),
+                        format.show(Quantity::Distance, surface, 5),
+                    );
+                    ui.label(format!("Straight: {straight}"));
+                    ui.label(format!("Along Surface: ~{surface}"));
+                }
+                _ if measurement.picking => {
+                    ui.small("Click a point on the fractal.");
+                }
+                _ => {}
+            }
+        });
+    measurement.open = open;
+}
+
+/// Draws the picked points, the chord and the surface path over the view
+fn draw_measurement(
+    mut contexts: EguiContexts,
+    measurement: Res<Measurement>,
+    compose: Res<ComposeSettings>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    if !measurement.open || measurement.points.is_empty() {
+        return;
+    }
+    let (Ok(win), Some(mat)) = (window.single(), materials.get(&fractal.0)) else {
+        return;
+    };
+    let to_screen = |p: Vec3| {
+        let pos = compose.uv_to_window(win, mat.camera.project(p)?);
+        Some(egui::pos2(pos.x, pos.y))
+    };
+    let ctx = contexts.ctx_mut().unwrap();
+    let painter = ctx.layer_painter(egui::LayerId::background());
+
+    if let [a, b] = measurement.points[..]
+        && let (Some(a), Some(b)) = (to_screen(a), to_screen(b))
+    {
+        let dashed = egui::Shape::dashed_line(
+            &[a, b],
+            egui::Stroke::new(1.0, egui::Color32::from_white_alpha(140)),
+            6.0,
+            4.0,
+        );
+        painter.extend(dashed);
+    }
+    let path: Option<Vec<_>> = measurement.path.iter().map(|p| to_screen(*p)).collect();
+    if let Some(path) = path {
+        painter.line(path, egui::Stroke::new(2.0, egui::Color32::LIGHT_GREEN));
+    }
+    for pos in measurement.points.iter().filter_map(|p| to_screen(*p)) {
+        painter.circle(
+            pos,
+            4.0,
+            egui::Color32::LIGHT_GREEN,
+            egui::Stroke::new(1.0, egui::Color32::BLACK),
+        );
+    }
+}
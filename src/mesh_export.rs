@@ -0,0 +1,726 @@
+use crate::estimator::CpuEstimator;
+use crate::export::{ExportSettings, timestamped_path};
+#[cfg(feature = "export")]
+use crate::gltf::write_glb;
+#[cfg(feature = "export")]
+use crate::material::{CameraUniform, ShadingUniform};
+use crate::material::{Formula, FractalMaterial, MandelbulbMaterial, ShapeUniform};
+use crate::mesh_process::MeshProcessing;
+#[cfg(feature = "export")]
+use crate::obj::write_obj;
+#[cfg(feature = "export")]
+use crate::ply::write_ply;
+use crate::print::{PrintSetup, extract_printable};
+#[cfg(feature = "export")]
+use crate::stl::write_stl;
+use crate::surface::{SurfaceMesh, extract_surface};
+#[cfg(feature = "export")]
+use crate::usd::{write_usda, write_usdz};
+use crate::vertex_colors::VertexColoring;
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, poll_once};
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+#[cfg(feature = "export")]
+use std::f32::consts::PI;
+use std::f32::consts::TAU;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Why exports fail in builds without the mesh writers
+#[cfg(not(feature = "export"))]
+const NO_EXPORT: &str = "Built without the export feature";
+
+/// Grid resolutions offered in the UI, cells per side
+const RESOLUTIONS: [u32; 4] = [64, 128, 256, 384];
+
+/// Exports the surface as a triangle mesh for 3D software and printing, on a background thread
+pub struct MeshExportPlugin;
+
+impl Plugin for MeshExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MeshExport>()
+            .add_systems(Update, poll_mesh_export)
+            .add_systems(EguiPrimaryContextPass, mesh_export_window);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MeshFormat {
+    Gltf,
+    Usda,
+    Usdz,
+    /// A closed solid for 3D printing, without camera or light
+    Stl,
+    Ply,
+}
+
+impl MeshFormat {
+    pub const ALL: [MeshFormat; 5] = [
+        MeshFormat::Gltf,
+        MeshFormat::Usda,
+        MeshFormat::Usdz,
+        MeshFormat::Stl,
+        MeshFormat::Ply,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MeshFormat::Gltf => "glTF (.glb)",
+            MeshFormat::Usda => "USD (.usda)",
+            MeshFormat::Usdz => "USDZ (.usdz)",
+            MeshFormat::Stl => "STL for printing (.stl)",
+            MeshFormat::Ply => "PLY (.ply)",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            MeshFormat::Gltf => "glb",
+            MeshFormat::Usda => "usda",
+            MeshFormat::Usdz => "usdz",
+            MeshFormat::Stl => "stl",
+            MeshFormat::Ply => "ply",
+        }
+    }
+
+    /// Whether the format can hold the view's camera and light
+    fn has_scene(self) -> bool {
+        matches!(self, MeshFormat::Gltf | MeshFormat::Usda | MeshFormat::Usdz)
+    }
+
+    /// Whether baked vertex colors are written
+    fn has_colors(self) -> bool {
+        matches!(self, MeshFormat::Gltf | MeshFormat::Ply)
+    }
+}
+
+/// Physical unit of exported lengths
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Unit {
+    Millimeter,
+    Centimeter,
+    Meter,
+    Inch,
+}
+
+impl Unit {
+    pub const ALL: [Unit; 4] = [Unit::Millimeter, Unit::Centimeter, Unit::Meter, Unit::Inch];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Unit::Millimeter => "mm",
+            Unit::Centimeter => "cm",
+            Unit::Meter => "m",
+            Unit::Inch => "in",
+        }
+    }
+
+    pub fn meters(self) -> f32 {
+        match self {
+            Unit::Millimeter => 0.001,
+            Unit::Centimeter => 0.01,
+            Unit::Meter => 1.0,
+            Unit::Inch => 0.0254,
+        }
+    }
+
+    /// A box's size like "74.2 × 70.1 × 74.2 mm"
+    pub fn format_size(self, size: Vec3) -> String {
+        format!(
+            "{:.1} × {:.1} × {:.1} {}",
+            size.x,
+            size.y,
+            size.z,
+            self.label()
+        )
+    }
+}
+
+/// Resolution of the coarse extraction measuring the fractal for the size preview
+const PREVIEW_RESOLUTION: u32 = 32;
+
+/// How the shape changes over an exported mesh sequence
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Morph {
+    /// From the current power to `end_power`
+    PowerSweep,
+    /// The Julia constant circles the current one in its XY plane, a seamless loop
+    JuliaOrbit,
+}
+
+impl Morph {
+    pub const ALL: [Morph; 2] = [Morph::PowerSweep, Morph::JuliaOrbit];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Morph::PowerSweep => "Power Sweep",
+            Morph::JuliaOrbit => "Julia Orbit",
+        }
+    }
+}
+
+/// The view a mesh was exported from, to place a camera and light in the scene. Both glTF and
+/// USD cameras look down -Z with +Y up.
+#[cfg(feature = "export")]
+#[derive(Clone, Copy)]
+pub struct SceneSetup {
+    pub camera: CameraUniform,
+    pub shading: ShadingUniform,
+    /// File units per scene unit, which the mesh is already scaled by
+    pub scale: f32,
+}
+
+#[cfg(feature = "export")]
+impl SceneSetup {
+    /// The camera's orientation in world space. The renderer's camera looks down +Z with +Y down
+    /// the screen, half a turn around X from the exported one.
+    pub fn camera_rotation(&self) -> Quat {
+        Quat::from_vec4(self.camera.rotation).inverse() * Quat::from_rotation_x(PI)
+    }
+
+    pub fn camera_position(&self) -> Vec3 {
+        self.camera.ray_origin() * self.scale
+    }
+
+    /// Vertical field of view in radians
+    pub fn yfov(&self) -> f32 {
+        let (_, top) = self.camera.ray(Vec2::new(0.5, 0.0));
+        let (_, bottom) = self.camera.ray(Vec2::new(0.5, 1.0));
+        top.angle_between(bottom)
+    }
+
+    /// Width / height of the picture
+    pub fn aspect(&self) -> f32 {
+        self.camera.resolution.x * self.camera.pixel_aspect / self.camera.resolution.y
+    }
+
+    /// Same placement as `light_pos` in the shader
+    pub fn light_position(&self) -> Vec3 {
+        Vec3::new(self.shading.light_pos_x, self.shading.light_pos_y, -3.0) * self.scale
+    }
+}
+
+#[derive(Resource)]
+pub struct MeshExport {
+    pub open: bool,
+    /// Half the side of the sampled cube, which has to contain the part of the fractal to export
+    pub bounds: f32,
+    pub resolution: u32,
+    pub format: MeshFormat,
+    pub unit: Unit,
+    /// Length in `unit` of one scene unit, the fractal's radius is about 1.1 scene units
+    pub scale: f32,
+    /// Wrap the mesh in a scene with the current camera and light
+    pub scene: bool,
+    /// Bake the on-screen coloring into vertex colors
+    pub colors: bool,
+    /// Smoothing, decimation and cleanup applied to every exported mesh
+    pub processing: MeshProcessing,
+    /// Hollowing, base cut and island checks of STL exports
+    pub print: PrintSetup,
+    pub morph: Morph,
+    pub frames: u32,
+    /// Power at the last frame of a power sweep
+    pub end_power: f32,
+    /// Radius of the Julia orbit
+    pub orbit_radius: f32,
+    task: Option<Task<Result<String, String>>>,
+    /// Frames written and total frames of a running sequence export
+    sequence_progress: Option<(Arc<AtomicU32>, u32)>,
+    /// Outcome of the last export
+    status: Option<Result<String, String>>,
+    /// Size of the surface in scene units, None without a surface, and what it was measured for
+    preview: Option<((Formula, ShapeUniform, f32), Option<Vec3>)>,
+}
+
+impl Default for MeshExport {
+    fn default() -> Self {
+        Self {
+            open: false,
+            bounds: 1.5,
+            resolution: RESOLUTIONS[1],
+            format: MeshFormat::Gltf,
+            unit: Unit::Millimeter,
+            scale: 50.0,
+            scene: true,
+            colors: true,
+            processing: MeshProcessing::default(),
+            print: PrintSetup::default(),
+            morph: Morph::PowerSweep,
+            frames: 48,
+            end_power: 12.0,
+            orbit_radius: 0.3,
+            task: None,
+            sequence_progress: None,
+            status: None,
+            preview: None,
+        }
+    }
+}
+
+struct MeshJob {
+    estimator: CpuEstimator,
+    level: f32,
+    bounds: f32,
+    resolution: u32,
+    format: MeshFormat,
+    unit: Unit,
+    scale: f32,
+    #[cfg(feature = "export")]
+    scene: Option<SceneSetup>,
+    coloring: Option<VertexColoring>,
+    processing: MeshProcessing,
+    print: PrintSetup,
+    path: PathBuf,
+}
+
+impl MeshJob {
+    fn run(self) -> Result<String, String> {
+        let (mut mesh, report) = if self.format == MeshFormat::Stl {
+            let (mesh, report) = extract_printable(
+                &self.estimator,
+                self.level,
+                self.bounds,
+                self.resolution,
+                &self.print,
+            );
+            (mesh, Some(report))
+        } else {
+            let mesh = extract_surface(&self.estimator, self.level, self.bounds, self.resolution);
+            (mesh, None)
+        };
+        if mesh.indices.is_empty() {
+            return Err("No surface inside the bounds".to_string());
+        }
+        self.processing.apply(&mut mesh, &self.estimator);
+        if let Some(coloring) = &self.coloring {
+            coloring.bake(&mut mesh, &self.estimator);
+        }
+        let (min, max) = mesh.bounds();
+        let size = self.unit.format_size((max - min) * self.scale);
+        // glTF is always in meters, USD declares its unit and STL has none, slicers read mm
+        let (file_scale, meters_per_unit) = match self.format {
+            MeshFormat::Gltf => (self.scale * self.unit.meters(), 1.0),
+            _ => (self.scale, self.unit.meters()),
+        };
+        mesh.scale(file_scale);
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|err| err.to_string())?;
+        }
+        self.write(&mesh, file_scale, meters_per_unit)?;
+        let mut message = format!(
+            "Saved {} triangles, {size}, to {}",
+            mesh.triangle_count(),
+            self.path.display()
+        );
+        if let Some(warning) = report.and_then(|report| report.warning()) {
+            message = format!("{message}\n{warning}");
+        }
+        Ok(message)
+    }
+
+    /// Writes `mesh`, already scaled by `file_scale`, to the job's path in its format
+    #[cfg(feature = "export")]
+    fn write(
+        &self,
+        mesh: &SurfaceMesh,
+        file_scale: f32,
+        meters_per_unit: f32,
+    ) -> Result<(), String> {
+        let scene = self.scene.map(|scene| SceneSetup {
+            scale: file_scale,
+            ..scene
+        });
+        let (path, scene) = (self.path.as_path(), scene.as_ref());
+        match self.format {
+            MeshFormat::Gltf => write_glb(path, mesh, scene),
+            MeshFormat::Usda => std::fs::write(path, write_usda(mesh, scene, meters_per_unit))
+                .map_err(|err| err.to_string()),
+            MeshFormat::Usdz => write_usdz(path, mesh, scene, meters_per_unit),
+            MeshFormat::Stl => write_stl(path, mesh).map_err(|err| err.to_string()),
+            MeshFormat::Ply => write_ply(path, mesh).map_err(|err| err.to_string()),
+        }
+    }
+
+    #[cfg(not(feature = "export"))]
+    fn write(
+        &self,
+        _mesh: &SurfaceMesh,
+        _file_scale: f32,
+        _meters_per_unit: f32,
+    ) -> Result<(), String> {
+        Err(NO_EXPORT.to_string())
+    }
+}
+
+/// One OBJ per frame of a morph, numbered from 1 for the sequence importers of DCCs
+struct SequenceJob {
+    material: MandelbulbMaterial,
+    morph: Morph,
+    end_power: f32,
+    orbit_radius: f32,
+    frames: u32,
+    bounds: f32,
+    resolution: u32,
+    processing: MeshProcessing,
+    scale: f32,
+    directory: PathBuf,
+    progress: Arc<AtomicU32>,
+}
+
+impl SequenceJob {
+    /// The material at `frame`, morphed from the one the export started with
+    fn frame_material(&self, frame: u32) -> MandelbulbMaterial {
+        let mut mat = self.material.clone();
+        match self.morph {
+            Morph::PowerSweep => {
+                let t = frame as f32 / (self.frames - 1).max(1) as f32;
+                mat.shape.power = self.material.shape.power.lerp(self.end_power, t);
+            }
+            Morph::JuliaOrbit => {
+                // the last frame stops short of the first, so the sequence loops
+                let angle = TAU * frame as f32 / self.frames as f32;
+                let offset = Vec2::from_angle(angle) * self.orbit_radius;
+                mat.shape.julia =
+                    (self.material.shape.julia.truncate() + offset.extend(0.0)).extend(1.0);
+            }
+        }
+        mat
+    }
+
+    fn run(self) -> Result<String, String> {
+        std::fs::create_dir_all(&self.directory).map_err(|err| err.to_string())?;
+        for frame in 0..self.frames {
+            let mat = self.frame_material(frame);
+            let estimator = CpuEstimator::new(&mat)
+                .ok_or_else(|| "The formula has no CPU estimator".to_string())?;
+            // frames without a surface are still written, so the numbering has no gaps
+            let mut mesh = extract_surface(
+                &estimator,
+                mat.shape.hit_threshold,
+                self.bounds,
+                self.resolution,
+            );
+            self.processing.apply(&mut mesh, &estimator);
+            mesh.scale(self.scale);
+            let path = self.directory.join(format!("fractal_{:04}.obj", frame + 1));
+            write_frame(&path, &mesh)?;
+            self.progress.store(frame + 1, Ordering::Relaxed);
+        }
+        Ok(format!(
+            "Saved {} frames to {}",
+            self.frames,
+            self.directory.display()
+        ))
+    }
+}
+
+/// Writes one frame of a mesh sequence
+#[cfg(feature = "export")]
+fn write_frame(path: &Path, mesh: &SurfaceMesh) -> Result<(), String> {
+    write_obj(path, mesh).map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "export"))]
+fn write_frame(_path: &Path, _mesh: &SurfaceMesh) -> Result<(), String> {
+    Err(NO_EXPORT.to_string())
+}
+
+fn poll_mesh_export(mut export: ResMut<MeshExport>) {
+    if let Some(task) = &mut export.task
+        && let Some(result) = block_on(poll_once(task))
+    {
+        match &result {
+            Ok(message) => info!("{message}"),
+            Err(err) => error!("Mesh export failed: {err}"),
+        }
+        export.status = Some(result);
+        export.task = None;
+        export.sequence_progress = None;
+    }
+}
+
+fn mesh_export_window(
+    mut contexts: EguiContexts,
+    mut export: ResMut<MeshExport>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    settings: Res<ExportSettings>,
+) {
+    if !export.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let estimator = CpuEstimator::new(mat);
+    let coloring = VertexColoring::new(mat);
+    let export = &mut *export;
+
+    let measured = (mat.formula, mat.shape, export.bounds);
+    if let Some(estimator) = &estimator
+        && export
+            .preview
+            .as_ref()
+            .is_none_or(|(key, _)| *key != measured)
+    {
+        let mesh = extract_surface(
+            estimator,
+            mat.shape.hit_threshold,
+            export.bounds,
+            PREVIEW_RESOLUTION,
+        );
+        let size = (!mesh.indices.is_empty()).then(|| {
+            let (min, max) = mesh.bounds();
+            max - min
+        });
+        export.preview = Some((measured, size));
+    }
+
+    let mut open = export.open;
+    egui::Window::new("Mesh Export")
+        .open(&mut open)
+        .default_width(300.0)
+        .show(ctx, |ui| {
+            let Some(estimator) = estimator else {
+                ui.label("Not available for plugin formulas and the geometry graph.");
+                return;
+            };
+            ui.add(egui::Slider::new(&mut export.bounds, 0.5..=10.0).text("Bounds"))
+                .on_hover_text("Half the side of the sampled cube, the mesh is cut off outside it");
+            ui.horizontal(|ui| {
+                ui.label("Resolution");
+                for resolution in RESOLUTIONS {
+                    ui.selectable_value(
+                        &mut export.resolution,
+                        resolution,
+                        format!("{resolution}³"),
+                    );
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Scale");
+                ui.add(
+                    egui::DragValue::new(&mut export.scale)
+                        .range(0.001..=10_000.0)
+                        .speed(0.1),
+                );
+                egui::ComboBox::from_id_salt("mesh_unit_combo")
+                    .selected_text(export.unit.label())
+                    .show_ui(ui, |ui| {
+                        for unit in Unit::ALL {
+                            ui.selectable_value(&mut export.unit, unit, unit.label());
+                        }
+                    });
+                ui.label("per unit");
+            })
+            .response
+            .on_hover_text("Physical length of one scene unit in every exported file");
+            match export.preview {
+                Some((_, Some(size))) => {
+                    ui.label(format!(
+                        "Size: {}",
+                        export.unit.format_size(size * export.scale)
+                    ))
+                    .on_hover_text("Measured on a coarse grid, before post-processing");
+                }
+                Some((_, None)) => {
+                    ui.weak("No surface inside the bounds");
+                }
+                None => {}
+            }
+            ui.horizontal(|ui| {
+                ui.label("Format");
+                egui::ComboBox::from_id_salt("mesh_format_combo")
+                    .selected_text(export.format.label())
+                    .show_ui(ui, |ui| {
+                        for format in MeshFormat::ALL {
+                            ui.selectable_value(&mut export.format, format, format.label());
+                        }
+                    });
+            });
+            ui.add(
+                egui::Slider::new(&mut export.processing.smooth_passes, 0..=20)
+                    .text("Smoothing Passes"),
+            )
+            .on_hover_text("Rounds off the grid steps of the extraction, without shrinking");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut export.processing.decimate, "Decimate to")
+                    .on_hover_text("Merges vertices where it changes the shape least");
+                ui.add_enabled(
+                    export.processing.decimate,
+                    egui::DragValue::new(&mut export.processing.target_triangles)
+                        .range(1_000..=10_000_000)
+                        .speed(1_000.0)
+                        .suffix(" triangles"),
+                );
+            });
+            if export.format == MeshFormat::Stl {
+                let print = &mut export.print;
+                // thicknesses are edited in the export unit, the setup keeps scene units
+                let (scale, unit) = (export.scale, export.unit.label());
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut print.hollow, "Hollow, walls");
+                    let mut wall = print.wall_thickness * scale;
+                    ui.add_enabled(
+                        print.hollow,
+                        egui::DragValue::new(&mut wall)
+                            .range(0.005 * scale..=0.5 * scale)
+                            .speed(0.001 * scale)
+                            .suffix(format!(" {unit}")),
+                    );
+                    print.wall_thickness = wall / scale;
+                });
+                ui.horizontal(|ui| {
+                    ui.checkbox(&mut print.base, "Flat base at height");
+                    let bounds = export.bounds;
+                    ui.add_enabled(
+                        print.base,
+                        egui::DragValue::new(&mut print.base_height)
+                            .range(-bounds..=bounds)
+                            .speed(0.01),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Minimum feature size");
+                    let mut feature = print.min_feature * scale;
+                    ui.add(
+                        egui::DragValue::new(&mut feature)
+                            .range(0.0..=0.5 * scale)
+                            .speed(0.001 * scale)
+                            .suffix(format!(" {unit}")),
+                    );
+                    print.min_feature = feature / scale;
+                })
+                .response
+                .on_hover_text("Disconnected pieces thinner than this are left out");
+            }
+            if export.format.has_scene() {
+                ui.checkbox(&mut export.scene, "Include camera and light")
+                    .on_hover_text(
+                        "Places the current view's camera and light in the exported scene",
+                    );
+            }
+            if export.format.has_colors() {
+                if coloring.is_some() {
+                    ui.checkbox(&mut export.colors, "Bake colors")
+                        .on_hover_text("Writes the on-screen palette coloring as vertex colors");
+                } else {
+                    ui.weak("Colors of the shading graph can't be baked");
+                }
+            }
+
+            ui.horizontal(|ui| {
+                let running = export.task.is_some();
+                if ui
+                    .add_enabled(!running, egui::Button::new("Export"))
+                    .clicked()
+                {
+                    let job = MeshJob {
+                        estimator,
+                        level: mat.shape.hit_threshold,
+                        bounds: export.bounds,
+                        resolution: export.resolution,
+                        format: export.format,
+                        #[cfg(feature = "export")]
+                        scene: (export.scene && export.format.has_scene()).then_some(SceneSetup {
+                            camera: mat.camera,
+                            shading: mat.shading,
+                            scale: 1.0,
+                        }),
+                        coloring: coloring.filter(|_| export.colors && export.format.has_colors()),
+                        unit: export.unit,
+                        scale: export.scale,
+                        processing: export.processing,
+                        print: export.print,
+                        path: timestamped_path(
+                            &settings.directory,
+                            "fractal",
+                            export.format.extension(),
+                        ),
+                    };
+                    export.status = None;
+                    export.task = Some(AsyncComputeTaskPool::get().spawn(async move { job.run() }));
+                }
+                if running && export.sequence_progress.is_none() {
+                    ui.spinner();
+                }
+            });
+
+            ui.separator();
+            ui.label("Sequence");
+            ui.horizontal(|ui| {
+                ui.label("Morph");
+                egui::ComboBox::from_id_salt("morph_combo")
+                    .selected_text(export.morph.label())
+                    .show_ui(ui, |ui| {
+                        for morph in Morph::ALL {
+                            ui.selectable_value(&mut export.morph, morph, morph.label());
+                        }
+                    });
+            });
+            match export.morph {
+                Morph::PowerSweep => {
+                    ui.add(egui::Slider::new(&mut export.end_power, -2.0..=16.0).text("End Power"))
+                        .on_hover_text("The sweep starts at the current power");
+                }
+                Morph::JuliaOrbit => {
+                    ui.add(
+                        egui::Slider::new(&mut export.orbit_radius, 0.01..=1.0)
+                            .text("Orbit Radius"),
+                    )
+                    .on_hover_text("Distance of the Julia constant from the current one");
+                }
+            }
+            ui.add(egui::Slider::new(&mut export.frames, 2..=240).text("Frames"));
+            ui.horizontal(|ui| {
+                let running = export.task.is_some();
+                if ui
+                    .add_enabled(!running, egui::Button::new("Export OBJ Sequence"))
+                    .clicked()
+                {
+                    let progress = Arc::new(AtomicU32::new(0));
+                    // a folder per sequence, named like a single export without the extension
+                    let directory =
+                        timestamped_path(&settings.directory, "fractal_sequence", "obj")
+                            .with_extension("");
+                    let job = SequenceJob {
+                        material: mat.clone(),
+                        morph: export.morph,
+                        end_power: export.end_power,
+                        orbit_radius: export.orbit_radius,
+                        frames: export.frames,
+                        bounds: export.bounds,
+                        resolution: export.resolution,
+                        processing: export.processing,
+                        scale: export.scale,
+                        directory,
+                        progress: progress.clone(),
+                    };
+                    export.status = None;
+                    export.sequence_progress = Some((progress, export.frames));
+                    export.task = Some(AsyncComputeTaskPool::get().spawn(async move { job.run() }));
+                }
+                if let Some((progress, frames)) = &export.sequence_progress {
+                    let done = progress.load(Ordering::Relaxed);
+                    ui.add(
+                        egui::ProgressBar::new(done as f32 / *frames as f32)
+                            .text(format!("{done} / {frames}"))
+                            .animate(true),
+                    );
+                }
+            });
+            match &export.status {
+                Some(Ok(message)) => {
+                    ui.label(message);
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(egui::Color32::LIGHT_RED, err);
+                }
+                None => {}
+            }
+        });
+    export.open = open;
+}
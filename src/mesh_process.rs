@@ -0,0 +1,507 @@
+//! Post-processing of extracted surfaces before export: degenerate triangle cleanup, Taubin
+//! smoothing and quadric error decimation. Vertices on the open border of a mesh cut by the
+//! export bounds never move, so the cut stays where it was.
+
+use crate::estimator::CpuEstimator;
+use crate::surface::SurfaceMesh;
+use bevy::math::DVec3;
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+/// Taubin's shrink free pair of factors, a smoothing step followed by an inflating one
+const SMOOTH_LAMBDA: f32 = 0.5;
+const SMOOTH_MU: f32 = -0.53;
+
+/// Triangles whose area is below this fraction of the mean are dropped as degenerate
+const DEGENERATE_AREA: f32 = 1e-6;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MeshProcessing {
+    /// Taubin smoothing passes, 0 to keep the surface nets vertices
+    pub smooth_passes: u32,
+    /// Collapse edges until the mesh has at most `target_triangles`
+    pub decimate: bool,
+    pub target_triangles: u32,
+}
+
+impl Default for MeshProcessing {
+    fn default() -> Self {
+        Self {
+            smooth_passes: 0,
+            decimate: false,
+            target_triangles: 200_000,
+        }
+    }
+}
+
+impl MeshProcessing {
+    /// Cleans, smooths and decimates `mesh`, then takes its normals from the estimator again so
+    /// they match the surface the renderer shows at the moved vertices
+    pub fn apply(&self, mesh: &mut SurfaceMesh, estimator: &CpuEstimator) {
+        remove_degenerate(mesh);
+        if self.smooth_passes > 0 {
+            smooth(mesh, self.smooth_passes);
+        }
+        if self.decimate && mesh.triangle_count() > self.target_triangles as usize {
+            decimate(mesh, self.target_triangles as usize);
+            remove_degenerate(mesh);
+        }
+        if self.smooth_passes > 0 || self.decimate {
+            mesh.normals = mesh
+                .positions
+                .iter()
+                .map(|p| estimator.normal(*p))
+                .collect();
+        }
+    }
+}
+
+fn triangles(mesh: &SurfaceMesh) -> impl Iterator<Item = [u32; 3]> + '_ {
+    mesh.indices.chunks_exact(3).map(|t| [t[0], t[1], t[2]])
+}
+
+fn area(positions: &[Vec3], [a, b, c]: [u32; 3]) -> f32 {
+    let [a, b, c] = [a, b, c].map(|i| positions[i as usize]);
+    (b - a).cross(c - a).length() * 0.5
+}
+
+/// Drops triangles with repeated or coincident corners and the vertices nothing uses anymore
+pub fn remove_degenerate(mesh: &mut SurfaceMesh) {
+    let count = mesh.triangle_count().max(1) as f32;
+    let mean = triangles(mesh)
+        .map(|t| area(&mesh.positions, t))
+        .sum::<f32>()
+        / count;
+    let kept: Vec<[u32; 3]> = triangles(mesh)
+        .filter(|&[a, b, c]| a != b && b != c && c != a)
+        .filter(|t| area(&mesh.positions, *t) > mean * DEGENERATE_AREA)
+        .collect();
+    rebuild(mesh, &kept);
+}
+
+/// Replaces the triangles and drops unreferenced vertices, keeping the order of the rest
+fn rebuild(mesh: &mut SurfaceMesh, kept: &[[u32; 3]]) {
+    let mut remap = vec![u32::MAX; mesh.positions.len()];
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut colors = Vec::new();
+    for t in kept {
+        for &i in t {
+            if remap[i as usize] == u32::MAX {
+                remap[i as usize] = positions.len() as u32;
+                positions.push(mesh.positions[i as usize]);
+                normals.push(mesh.normals[i as usize]);
+                if let Some(color) = mesh.colors.get(i as usize) {
+                    colors.push(*color);
+                }
+            }
+        }
+    }
+    mesh.indices = kept
+        .iter()
+        .flat_map(|t| t.map(|i| remap[i as usize]))
+        .collect();
+    mesh.positions = positions;
+    mesh.normals = normals;
+    mesh.colors = colors;
+}
+
+/// Vertices on an edge only one triangle uses
+fn border_vertices(mesh: &SurfaceMesh) -> Vec<bool> {
+    let mut edges: HashMap<(u32, u32), u32> = HashMap::default();
+    for [a, b, c] in triangles(mesh) {
+        for (u, v) in [(a, b), (b, c), (c, a)] {
+            *edges.entry((u.min(v), u.max(v))).or_default() += 1;
+        }
+    }
+    let mut border = vec![false; mesh.positions.len()];
+    for ((u, v), uses) in edges {
+        if uses == 1 {
+            border[u as usize] = true;
+            border[v as usize] = true;
+        }
+    }
+    border
+}
+
+fn neighbours(mesh: &SurfaceMesh) -> Vec<Vec<u32>> {
+    let mut neighbours = vec![Vec::new(); mesh.positions.len()];
+    for [a, b, c] in triangles(mesh) {
+        for (u, v) in [(a, b), (b, c), (c, a)] {
+            neighbours[u as usize].push(v);
+            neighbours[v as usize].push(u);
+        }
+    }
+    for list in &mut neighbours {
+        list.sort_unstable();
+        list.dedup();
+    }
+    neighbours
+}
+
+/// Umbrella operator smoothing, alternating shrinking and inflating steps so the fractal doesn't
+/// lose volume with every pass
+pub fn smooth(mesh: &mut SurfaceMesh, passes: u32) {
+    let border = border_vertices(mesh);
+    let neighbours = neighbours(mesh);
+    for _ in 0..passes {
+        for factor in [SMOOTH_LAMBDA, SMOOTH_MU] {
+            let moved: Vec<Vec3> = mesh
+                .positions
+                .iter()
+                .enumerate()
+                .map(|(i, &p)| {
+                    let around = &neighbours[i];
+                    if border[i] || around.is_empty() {
+                        return p;
+                    }
+                    let mean = around
+                        .iter()
+                        .map(|&n| mesh.positions[n as usize])
+                        .sum::<Vec3>()
+                        / around.len() as f32;
+                    p + (mean - p) * factor
+                })
+                .collect();
+            mesh.positions = moved;
+        }
+    }
+}
+
+/// Symmetric 4x4 matrix summing squared distances to planes, stored as its upper triangle
+#[derive(Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    fn plane(normal: DVec3, d: f64) -> Self {
+        let [a, b, c] = normal.to_array();
+        Self([
+            a * a,
+            a * b,
+            a * c,
+            a * d,
+            b * b,
+            b * c,
+            b * d,
+            c * c,
+            c * d,
+            d * d,
+        ])
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(other.0) {
+            *a += b;
+        }
+    }
+
+    fn error(&self, p: Vec3) -> f64 {
+        let [x, y, z] = p.as_dvec3().to_array();
+        let q = &self.0;
+        q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9]
+    }
+}
+
+/// A candidate collapse of `from` into `into`, moving `into` to `position`
+struct Collapse {
+    cost: f64,
+    into: u32,
+    from: u32,
+    position: Vec3,
+    /// Versions of both vertices when the cost was computed, the entry is stale once either
+    /// vertex changed
+    versions: (u32, u32),
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost.total_cmp(&other.cost) == Ordering::Equal
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.cost.total_cmp(&other.cost)
+    }
+}
+
+/// Garland and Heckbert's edge collapse: the cheapest edge by quadric error is collapsed first,
+/// to whichever of its ends or midpoint has the least error. Collapses that would flip a
+/// triangle are skipped, so the normals keep facing out.
+pub fn decimate(mesh: &mut SurfaceMesh, target: usize) {
+    let border = border_vertices(mesh);
+    let mut positions = mesh.positions.clone();
+    let mut tris: Vec<[u32; 3]> = triangles(mesh).collect();
+    let mut alive = vec![true; tris.len()];
+    let mut live = tris.len();
+
+    let mut quadrics = vec![Quadric::default(); positions.len()];
+    let mut vertex_tris = vec![Vec::new(); positions.len()];
+    for (t, &[a, b, c]) in tris.iter().enumerate() {
+        let [pa, pb, pc] = [a, b, c].map(|i| positions[i as usize].as_dvec3());
+        let normal = (pb - pa).cross(pc - pa).normalize_or_zero();
+        let plane = Quadric::plane(normal, -normal.dot(pa));
+        for i in [a, b, c] {
+            quadrics[i as usize].add(&plane);
+            vertex_tris[i as usize].push(t as u32);
+        }
+    }
+
+    let mut versions = vec![0u32; positions.len()];
+    let candidate =
+        |into: u32, from: u32, positions: &[Vec3], quadrics: &[Quadric], versions: &[u32]| {
+            let mut q = quadrics[into as usize];
+            q.add(&quadrics[from as usize]);
+            let (a, b) = (positions[into as usize], positions[from as usize]);
+            let (cost, position) = [a, b, (a + b) * 0.5]
+                .into_iter()
+                .map(|p| (q.error(p), p))
+                .min_by(|x, y| x.0.total_cmp(&y.0))
+                .unwrap();
+            Reverse(Collapse {
+                cost,
+                into,
+                from,
+                position,
+                versions: (versions[into as usize], versions[from as usize]),
+            })
+        };
+
+    let mut heap = BinaryHeap::new();
+    for &[a, b, c] in &tris {
+        for (u, v) in [(a, b), (b, c), (c, a)] {
+            // each interior edge is shared by two triangles, queue it from one of them
+            if u < v && !border[u as usize] && !border[v as usize] {
+                heap.push(candidate(u, v, &positions, &quadrics, &versions));
+            }
+        }
+    }
+
+    while live > target
+        && let Some(Reverse(collapse)) = heap.pop()
+    {
+        let (into, from) = (collapse.into as usize, collapse.from as usize);
+        if (versions[into], versions[from]) != collapse.versions {
+            continue;
+        }
+        let flips = [into, from].into_iter().any(|v| {
+            vertex_tris[v].iter().any(|&t| {
+                let tri = tris[t as usize];
+                if !alive[t as usize]
+                    || tri.contains(&(into as u32)) && tri.contains(&(from as u32))
+                {
+                    return false;
+                }
+                let corners = tri.map(|i| positions[i as usize]);
+                let moved = tri.map(|i| {
+                    if i as usize == into || i as usize == from {
+                        collapse.position
+                    } else {
+                        positions[i as usize]
+                    }
+                });
+                let normal = |[a, b, c]: [Vec3; 3]| (b - a).cross(c - a);
+                normal(corners).dot(normal(moved)) <= 0.0
+            })
+        });
+        if flips {
+            continue;
+        }
+
+        positions[into] = collapse.position;
+        let q = quadrics[from];
+        quadrics[into].add(&q);
+        versions[into] += 1;
+        versions[from] += 1;
+        for t in std::mem::take(&mut vertex_tris[from]) {
+            if !alive[t as usize] {
+                continue;
+            }
+            let tri = &mut tris[t as usize];
+            if tri.contains(&(into as u32)) {
+                alive[t as usize] = false;
+                live -= 1;
+            } else {
+                for i in tri.iter_mut() {
+                    if *i as usize == from {
+                        *i = into as u32;
+                    }
+                }
+                vertex_tris[into].push(t);
+            }
+        }
+        vertex_tris[into].retain(|&t| alive[t as usize]);
+
+        let mut around: Vec<u32> = vertex_tris[into]
+            .iter()
+            .flat_map(|&t| tris[t as usize])
+            .filter(|&i| i as usize != into && !border[i as usize])
+            .collect();
+        around.sort_unstable();
+        around.dedup();
+        for other in around {
+            heap.push(candidate(
+                into as u32,
+                other,
+                &positions,
+                &quadrics,
+                &versions,
+            ));
+        }
+    }
+
+    let kept: Vec<[u32; 3]> = tris
+        .into_iter()
+        .zip(alive)
+        .filter_map(|(t, alive)| alive.then_some(t))
+        .collect();
+    mesh.positions = positions;
+    rebuild(mesh, &kept);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit sphere subdivided from an icosahedron, closed and wound counterclockwise seen from
+    /// outside
+    fn icosphere(subdivisions: u32) -> SurfaceMesh {
+        let t = (1.0 + 5f32.sqrt()) / 2.0;
+        let mut positions: Vec<Vec3> = [
+            (-1.0, t, 0.0),
+            (1.0, t, 0.0),
+            (-1.0, -t, 0.0),
+            (1.0, -t, 0.0),
+            (0.0, -1.0, t),
+            (0.0, 1.0, t),
+            (0.0, -1.0, -t),
+            (0.0, 1.0, -t),
+            (t, 0.0, -1.0),
+            (t, 0.0, 1.0),
+            (-t, 0.0, -1.0),
+            (-t, 0.0, 1.0),
+        ]
+        .map(|(x, y, z)| Vec3::new(x, y, z).normalize())
+        .to_vec();
+        let mut tris: Vec<[u32; 3]> = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+        for _ in 0..subdivisions {
+            let mut midpoints = HashMap::<(u32, u32), u32>::default();
+            let mut midpoint = |u: u32, v: u32| {
+                *midpoints.entry((u.min(v), u.max(v))).or_insert_with(|| {
+                    let p = (positions[u as usize] + positions[v as usize]).normalize();
+                    positions.push(p);
+                    positions.len() as u32 - 1
+                })
+            };
+            tris = tris
+                .into_iter()
+                .flat_map(|[a, b, c]| {
+                    let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                    [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+        SurfaceMesh {
+            normals: positions.clone(),
+            positions,
+            colors: Vec::new(),
+            indices: tris.into_iter().flatten().collect(),
+        }
+    }
+
+    /// Whether every triangle of a mesh around the origin faces away from it
+    fn faces_out(mesh: &SurfaceMesh) -> bool {
+        triangles(mesh).all(|t| {
+            let [a, b, c] = t.map(|i| mesh.positions[i as usize]);
+            (b - a).cross(c - a).dot(a + b + c) > 0.0
+        })
+    }
+
+    #[test]
+    fn the_icosphere_is_closed_and_faces_out() {
+        let mesh = icosphere(2);
+        assert_eq!((mesh.positions.len(), mesh.triangle_count()), (162, 320));
+        assert!(border_vertices(&mesh).iter().all(|border| !border));
+        assert!(faces_out(&mesh));
+    }
+
+    #[test]
+    fn smoothing_keeps_the_vertices_and_the_volume() {
+        let mut mesh = icosphere(2);
+        let indices = mesh.indices.clone();
+        smooth(&mut mesh, 5);
+        assert_eq!(mesh.positions.len(), 162);
+        assert_eq!(mesh.indices, indices);
+        for p in &mesh.positions {
+            assert!((p.length() - 1.0).abs() < 0.05, "{p} moved off the sphere");
+        }
+    }
+
+    #[test]
+    fn degenerate_triangles_are_dropped_with_their_vertices() {
+        let mut mesh = icosphere(1);
+        let (vertices, count) = (mesh.positions.len(), mesh.triangle_count());
+        // a triangle with a repeated corner, and one whose corners coincide
+        let twin = mesh.positions.len() as u32;
+        mesh.positions.push(mesh.positions[0]);
+        mesh.normals.push(mesh.normals[0]);
+        mesh.indices.extend([0, 0, 1, 0, twin, 1]);
+        remove_degenerate(&mut mesh);
+        assert_eq!(
+            (mesh.positions.len(), mesh.triangle_count()),
+            (vertices, count)
+        );
+        assert!(faces_out(&mesh));
+    }
+
+    #[test]
+    fn decimation_reaches_the_target_without_flipping() {
+        let mut mesh = icosphere(3);
+        assert_eq!(mesh.triangle_count(), 1280);
+        decimate(&mut mesh, 200);
+        assert_eq!(mesh.triangle_count(), 200);
+        assert_eq!(mesh.normals.len(), mesh.positions.len());
+        assert!(border_vertices(&mesh).iter().all(|border| !border));
+        assert!(faces_out(&mesh));
+    }
+}
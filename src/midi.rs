@@ -0,0 +1,139 @@
+use bevy::prelude::*;
+#[cfg(feature = "midi")]
+use std::sync::Mutex;
+#[cfg(feature = "midi")]
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+/// Environment variable selecting the MIDI input whose name contains its value. Without it the
+/// first input is used.
+#[cfg(feature = "midi")]
+const MIDI_PORT_VAR: &str = "FRACTAL3D_MIDI_PORT";
+
+/// Listens to a MIDI controller and forwards its key presses as [`MidiNote`] and its clock as
+/// [`MidiClock`] messages. Without the `midi` feature nothing is received, but the messages still
+/// exist for the tools reacting to them.
+pub struct MidiPlugin;
+
+impl Plugin for MidiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<MidiNote>().add_message::<MidiClock>();
+        #[cfg(feature = "midi")]
+        app.add_systems(Startup, connect_midi)
+            .add_systems(PreUpdate, forward_midi_messages);
+    }
+}
+
+/// A Note On received from the MIDI input, on any channel
+#[derive(Message, Clone, Copy, Debug)]
+pub struct MidiNote {
+    pub note: u8,
+}
+
+/// MIDI clock and transport messages
+#[cfg_attr(not(feature = "midi"), allow(dead_code))]
+#[derive(Message, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MidiClock {
+    /// 24 per quarter note
+    Tick,
+    Start,
+    Stop,
+}
+
+/// Number of [`MidiClock::Tick`]s per quarter note
+#[cfg(feature = "ui")]
+pub const TICKS_PER_BEAT: u32 = 24;
+
+#[cfg(feature = "midi")]
+enum MidiEvent {
+    Note(MidiNote),
+    Clock(MidiClock),
+}
+
+/// Messages received on the MIDI thread since the last frame
+#[cfg(feature = "midi")]
+#[derive(Resource)]
+struct MidiInbox(Mutex<Receiver<MidiEvent>>);
+
+#[cfg(feature = "midi")]
+fn connect_midi(mut commands: Commands) {
+    let (sender, receiver) = channel();
+    commands.insert_resource(MidiInbox(Mutex::new(receiver)));
+
+    // the connection lives on its own thread, which keeps it open for the rest of the run
+    std::thread::spawn(move || match open_input(sender) {
+        Ok(_connection) => loop {
+            std::thread::park();
+        },
+        Err(err) => info!("No MIDI input: {err}"),
+    });
+}
+
+#[cfg(feature = "midi")]
+fn open_input(
+    sender: Sender<MidiEvent>,
+) -> Result<midir::MidiInputConnection<()>, Box<dyn std::error::Error>> {
+    let input = midir::MidiInput::new("fractal3D")?;
+    let filter = std::env::var(MIDI_PORT_VAR).unwrap_or_default();
+    let port = input
+        .ports()
+        .into_iter()
+        .find(|port| {
+            input
+                .port_name(port)
+                .is_ok_and(|name| name.contains(filter.as_str()))
+        })
+        .ok_or("no matching MIDI input")?;
+    let name = input.port_name(&port)?;
+
+    let connection = input.connect(
+        &port,
+        "fractal3D input",
+        move |_stamp, message, _| {
+            let event = match *message {
+                // Note On with velocity 0 is a Note Off
+                [status, note, velocity] if status & 0xF0 == 0x90 && velocity > 0 => {
+                    MidiEvent::Note(MidiNote { note })
+                }
+                [0xF8] => MidiEvent::Clock(MidiClock::Tick),
+                // Continue resumes from the song position, which is not tracked
+                [0xFA] | [0xFB] => MidiEvent::Clock(MidiClock::Start),
+                [0xFC] => MidiEvent::Clock(MidiClock::Stop),
+                _ => return,
+            };
+            let _ = sender.send(event);
+        },
+        (),
+    )?;
+    info!("Listening to MIDI input {name}");
+    Ok(connection)
+}
+
+#[cfg(feature = "midi")]
+fn forward_midi_messages(
+    inbox: Res<MidiInbox>,
+    mut notes: MessageWriter<MidiNote>,
+    mut clock: MessageWriter<MidiClock>,
+) {
+    let Ok(receiver) = inbox.0.lock() else {
+        return;
+    };
+    for event in receiver.try_iter() {
+        match event {
+            MidiEvent::Note(note) => {
+                notes.write(note);
+            }
+            MidiEvent::Clock(message) => {
+                clock.write(message);
+            }
+        }
+    }
+}
+
+/// Name of a note number, e.g. 60 is C4
+#[cfg(feature = "ui")]
+pub fn note_name(note: u8) -> String {
+    const NAMES: [&str; 12] = [
+        "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+    ];
+    format!("{}{}", NAMES[note as usize % 12], note as i32 / 12 - 1)
+}
@@ -0,0 +1,596 @@
+use crate::args;
+use crate::formulas;
+#[cfg(feature = "ui")]
+use crate::material::FractalMaterial;
+use crate::material::{CameraUniform, Formula, MandelbulbMaterial, move_all_views};
+use crate::params::{Param, ParamChanged, ParamStore};
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// Version of the share code format written by [`View::share_code`]. Codes without a `version`
+/// key are version 0. Bump it with a step added to [`MIGRATIONS`] whenever a parameter is added,
+/// renamed or changes meaning, so codes saved before keep opening as they looked.
+pub const VIEW_VERSION: u32 = 1;
+
+/// Upgrade of a code's `key=value` pairs by one version
+type Migration = fn(&mut Vec<(String, String)>);
+
+/// `MIGRATIONS[n]` takes a code of version `n` to `n + 1`
+const MIGRATIONS: [Migration; VIEW_VERSION as usize] = [add_version_1_params];
+
+/// The parameters of the first share codes, before they had a version
+const VERSION_0_PARAMS: [&str; 41] = [
+    "formula",
+    "geometry_graph",
+    "power",
+    "iterations",
+    "ray_steps",
+    "hit_threshold",
+    "max_dist",
+    "zoom",
+    "focal_length",
+    "fov_fit",
+    "pixel_aspect",
+    "background_glow",
+    "palette",
+    "coloring",
+    "custom_shading",
+    "color_scale",
+    "color_offset",
+    "light_x",
+    "light_y",
+    "soft_shadows",
+    "ao_strength",
+    "rim_strength",
+    "fog_density",
+    "streamlines",
+    "streamline_density",
+    "streamline_length",
+    "isolines",
+    "isoline_spacing",
+    "isoline_intensity",
+    "slice",
+    "slice_axis",
+    "slice_offset",
+    "slice_extent",
+    "julia_enabled",
+    "julia_x",
+    "julia_y",
+    "julia_z",
+    "supersampling",
+    "output_mode",
+    "paper_white",
+    "test_pattern",
+];
+
+/// Version 0 codes were written while parameters were still being added, so any added since the
+/// first codes may be missing. They get their defaults, which is how those codes rendered, rather
+/// than whatever the session has.
+fn add_version_1_params(pairs: &mut Vec<(String, String)>) {
+    let defaults = MandelbulbMaterial::default();
+    for param in Param::ALL {
+        let name = param.name();
+        if !VERSION_0_PARAMS.contains(&name) && !pairs.iter().any(|(key, _)| key == name) {
+            pairs.push((name.to_string(), param.get(&defaults).to_string()));
+        }
+    }
+}
+
+/// Shows where the camera is in world coordinates and jumps to typed or pasted coordinates, for
+/// sharing exact locations
+pub struct NavigationPlugin;
+
+impl Plugin for NavigationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            open_startup_view.run_if(resource_exists::<StartupView>),
+        );
+        #[cfg(feature = "ui")]
+        app.init_resource::<Navigation>()
+            .add_systems(EguiPrimaryContextPass, navigation_window);
+
+        match view_argument() {
+            Some(Ok(view)) => {
+                app.insert_resource(StartupView(view));
+            }
+            Some(Err(err)) => error!("Ignoring --view: {err}"),
+            None => {}
+        }
+    }
+}
+
+/// The code passed as `--view <code>`, parsed
+fn view_argument() -> Option<Result<View, String>> {
+    args::value("--view").map(|code| code.and_then(|code| View::parse(&code)))
+}
+
+/// A camera location: the orbit target, the orientation as yaw, pitch and roll in degrees, and
+/// the distance from the target
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+struct Location {
+    target: Vec3,
+    angles: Vec3,
+    zoom: f32,
+}
+
+impl Location {
+    fn of(camera: &CameraUniform) -> Self {
+        let (yaw, pitch, roll) = Quat::from_vec4(camera.rotation).to_euler(EulerRot::YXZ);
+        Self {
+            target: camera.position,
+            angles: Vec3::new(yaw, pitch, roll) * (180.0 / std::f32::consts::PI),
+            zoom: camera.zoom,
+        }
+    }
+
+    /// Seven comma separated numbers: target x, y, z, yaw, pitch, roll, zoom
+    #[cfg(feature = "ui")]
+    fn to_text(self) -> String {
+        let [x, y, z] = self.target.to_array();
+        let [yaw, pitch, roll] = self.angles.to_array();
+        format!(
+            "{x:.6}, {y:.6}, {z:.6}, {yaw:.3}, {pitch:.3}, {roll:.3}, {:.6}",
+            self.zoom
+        )
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let values = text
+            .split([',', ' ', '\t'])
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<f32>()
+                    .map_err(|_| format!("'{s}' is not a number"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let [x, y, z, yaw, pitch, roll, zoom] = values[..] else {
+            return Err(format!("expected 7 numbers, found {}", values.len()));
+        };
+        Ok(Self {
+            target: Vec3::new(x, y, z),
+            angles: Vec3::new(yaw, pitch, roll),
+            zoom,
+        })
+    }
+}
+
+/// A view to reproduce: where the camera is and the parameters to set. Written as a share code,
+/// `;` separated `key=value` pairs with the camera's `target` and `angles` followed by every
+/// parameter by name, or as the seven numbers of a location, which only move the camera.
+///
+/// A share code also lists under `requires` what it needs beyond the built-in formulas, such as
+/// `plugin:Kleinian` for a formula plugin, since plugins are numbered in the order they load.
+/// Opened where something is missing, the view falls back to what this build has and says so
+/// in [`View::compatibility_note`] instead of rendering a different shape without a word.
+#[derive(Clone, Debug, Default)]
+pub struct View {
+    target: Vec3,
+    angles: Vec3,
+    params: Vec<ParamChanged>,
+    /// What the view needs that this build lacks, each with what was done instead
+    missing: Vec<String>,
+}
+
+impl View {
+    pub fn share_code(camera: &CameraUniform, store: &ParamStore) -> String {
+        let location = Location::of(camera);
+        let [x, y, z] = location.target.to_array();
+        let [yaw, pitch, roll] = location.angles.to_array();
+        let mut parts = vec![
+            format!("version={VIEW_VERSION}"),
+            format!("target={x},{y},{z}"),
+            format!("angles={yaw},{pitch},{roll}"),
+        ];
+        if let Some(plugin @ Formula::Plugin(_)) =
+            Formula::all().nth(store.get(Param::Formula) as usize)
+        {
+            parts.push(format!("requires=plugin:{}", plugin.label()));
+        }
+        parts.extend(
+            Param::ALL
+                .iter()
+                .map(|param| format!("{}={}", param.name(), store.get(*param))),
+        );
+        parts.join(";")
+    }
+
+    pub fn parse(code: &str) -> Result<Self, String> {
+        let code = code.trim();
+        if !code.contains('=') {
+            let location = Location::parse(code)?;
+            return Ok(Self {
+                target: location.target,
+                angles: location.angles,
+                params: vec![ParamChanged {
+                    param: Param::Zoom,
+                    value: location.zoom,
+                }],
+                ..default()
+            });
+        }
+
+        let mut pairs = code
+            .split(';')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|part| {
+                part.split_once('=')
+                    .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+                    .ok_or_else(|| format!("'{part}' is not key=value"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut view = Self::default();
+        let version = match pairs.iter().position(|(key, _)| key == "version") {
+            Some(i) => {
+                let (_, value) = pairs.remove(i);
+                value
+                    .parse::<u32>()
+                    .map_err(|_| format!("'{value}' is not a version"))?
+            }
+            None => 0,
+        };
+        if version > VIEW_VERSION {
+            view.missing.push(format!(
+                "version {version} of the share code format, this build reads up to \
+                 {VIEW_VERSION}"
+            ));
+        }
+        for migrate in MIGRATIONS.iter().skip(version as usize) {
+            migrate(&mut pairs);
+        }
+
+        let mut requires = Vec::new();
+        let mut unknown = Vec::new();
+        for (key, value) in &pairs {
+            let (key, value) = (key.as_str(), value.as_str());
+            match key {
+                "target" => view.target = parse_vec3(value)?,
+                "angles" => view.angles = parse_vec3(value)?,
+                "requires" => requires.extend(value.split(',').map(str::trim)),
+                name => {
+                    let value = value
+                        .parse()
+                        .map_err(|_| format!("'{value}' is not a number"))?;
+                    match Param::from_name(name) {
+                        Some(param) => view.params.push(ParamChanged { param, value }),
+                        // from a newer build, the rest of the view still opens
+                        None => unknown.push(name),
+                    }
+                }
+            }
+        }
+        if view.params.is_empty() && !unknown.is_empty() {
+            return Err(format!("unknown key '{}'", unknown[0]));
+        }
+        if !unknown.is_empty() {
+            view.missing.push(format!(
+                "unknown parameters {}, left unchanged",
+                unknown.join(", ")
+            ));
+        }
+        for requirement in requires.into_iter().filter(|r| !r.is_empty()) {
+            view.require(requirement);
+        }
+        Ok(view)
+    }
+
+    /// Checks one `requires` entry against this build, pointing the formula at the plugin by
+    /// name or falling back to the default formula when it isn't loaded
+    fn require(&mut self, requirement: &str) {
+        let Some(name) = requirement.strip_prefix("plugin:") else {
+            self.missing
+                .push(format!("'{requirement}', not known to this version"));
+            return;
+        };
+        let index = formulas::plugins()
+            .iter()
+            .position(|plugin| plugin.name == name)
+            .map(|i| Formula::BUILT_IN.len() + i);
+        let Some(formula) = self
+            .params
+            .iter_mut()
+            .find(|change| change.param == Param::Formula)
+        else {
+            return;
+        };
+        match index {
+            Some(index) => formula.value = index as f32,
+            None => {
+                formula.value = 0.0;
+                self.missing.push(format!(
+                    "the formula plugin '{name}' is not installed, showing the {} instead",
+                    Formula::default().label()
+                ));
+            }
+        }
+    }
+
+    /// One line naming everything missing, `None` if the view opens as it was saved
+    pub fn compatibility_note(&self) -> Option<String> {
+        (!self.missing.is_empty()).then(|| format!("This view needs {}", self.missing.join("; ")))
+    }
+
+    fn rotation(&self) -> Quat {
+        let [yaw, pitch, roll] = self.angles.to_array().map(f32::to_radians);
+        Quat::from_euler(EulerRot::YXZ, yaw, pitch, roll)
+    }
+
+    /// Moves every view's camera and sets the parameters
+    pub fn open(
+        &self,
+        materials: &mut Assets<MandelbulbMaterial>,
+        param_changes: &mut MessageWriter<ParamChanged>,
+    ) {
+        if let Some(note) = self.compatibility_note() {
+            warn!("{note}");
+        }
+        move_all_views(materials, |camera| {
+            camera.position = self.target;
+            camera.rotation = Vec4::from(self.rotation());
+        });
+        param_changes.write_batch(self.params.iter().copied());
+    }
+
+    /// Sets the camera and parameters of a single material, one that isn't on screen
+    pub fn apply(&self, mat: &mut MandelbulbMaterial) {
+        mat.camera.position = self.target;
+        mat.camera.rotation = Vec4::from(self.rotation());
+        for change in &self.params {
+            change.param.set(mat, change.value);
+        }
+    }
+}
+
+fn parse_vec3(text: &str) -> Result<Vec3, String> {
+    let values = text
+        .split(',')
+        .map(|s| {
+            s.trim()
+                .parse::<f32>()
+                .map_err(|_| format!("'{s}' is not a number"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let [x, y, z] = values[..] else {
+        return Err(format!("expected 3 numbers in '{text}'"));
+    };
+    Ok(Vec3::new(x, y, z))
+}
+
+/// The view from `--view` or the startup scene, opened once the scene exists
+#[derive(Resource)]
+pub struct StartupView(pub View);
+
+fn open_startup_view(
+    mut commands: Commands,
+    view: Res<StartupView>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    mut param_changes: MessageWriter<ParamChanged>,
+) {
+    view.0.open(&mut materials, &mut param_changes);
+    commands.remove_resource::<StartupView>();
+}
+
+#[cfg(feature = "ui")]
+#[derive(Resource, Default)]
+pub struct Navigation {
+    pub open: bool,
+    /// Location being edited, applied with "Go"
+    edit: Location,
+    /// Location text being typed or pasted
+    text: String,
+    error: Option<String>,
+}
+
+#[cfg(feature = "ui")]
+fn navigation_window(
+    mut contexts: EguiContexts,
+    mut navigation: ResMut<Navigation>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    store: Res<ParamStore>,
+    mut param_changes: MessageWriter<ParamChanged>,
+) {
+    if !navigation.open {
+        return;
+    }
+    let Some(camera) = materials.get(&fractal.0).map(|mat| mat.camera) else {
+        return;
+    };
+    let ctx = contexts.ctx_mut().unwrap();
+    let current = Location::of(&camera);
+    let navigation = &mut *navigation;
+
+    let mut open = navigation.open;
+    let mut go_to = None;
+    egui::Window::new("Navigation")
+        .open(&mut open)
+        .default_width(320.0)
+        .show(ctx, |ui| {
+            let eye = camera.ray_origin();
+            ui.label(format!("Eye: {:.5}, {:.5}, {:.5}", eye.x, eye.y, eye.z));
+            ui.label(format!("Location: {}", current.to_text()));
+            ui.horizontal(|ui| {
+                if ui.button("Copy").clicked() {
+                    ui.ctx().copy_text(current.to_text());
+                }
+                if ui
+                    .button("Copy Share Code")
+                    .on_hover_text("The location and every parameter, for --view")
+                    .clicked()
+                {
+                    ui.ctx().copy_text(View::share_code(&camera, &store));
+                }
+                if ui.button("Edit Current").clicked() {
+                    navigation.edit = current;
+                }
+            });
+            ui.separator();
+
+            let edit = &mut navigation.edit;
+            egui::Grid::new("go_to_grid").num_columns(4).show(ui, |ui| {
+                ui.label("Target");
+                for value in edit.target.as_mut() {
+                    ui.add(egui::DragValue::new(value).speed(0.001).max_decimals(6));
+                }
+                ui.end_row();
+                ui.label("Yaw Pitch Roll");
+                for value in edit.angles.as_mut() {
+                    ui.add(egui::DragValue::new(value).speed(0.1).suffix("°"));
+                }
+                ui.end_row();
+                ui.label("Zoom");
+                let range = Param::Zoom.range();
+                ui.add(
+                    egui::DragValue::new(&mut edit.zoom)
+                        .speed(0.001)
+                        .range(range),
+                );
+                ui.end_row();
+            });
+            if ui.button("Go").clicked() {
+                go_to = Some(View {
+                    target: edit.target,
+                    angles: edit.angles,
+                    params: vec![ParamChanged {
+                        param: Param::Zoom,
+                        value: edit.zoom,
+                    }],
+                    ..default()
+                });
+            }
+            ui.separator();
+
+            ui.label("Paste a location or share code:");
+            ui.horizontal(|ui| {
+                ui.add(
+                    egui::TextEdit::singleline(&mut navigation.text)
+                        .hint_text("x, y, z, yaw, pitch, roll, zoom"),
+                );
+                if ui.button("Go").clicked() {
+                    match View::parse(&navigation.text) {
+                        Ok(view) => {
+                            navigation.error = view.compatibility_note();
+                            go_to = Some(view);
+                        }
+                        Err(err) => navigation.error = Some(err),
+                    }
+                }
+            });
+            if let Some(err) = &navigation.error {
+                ui.colored_label(egui::Color32::LIGHT_RED, err);
+            }
+        });
+    navigation.open = open;
+
+    if let Some(view) = go_to {
+        view.open(&mut materials, &mut param_changes);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(view: &View) -> Vec<(Param, f32)> {
+        view.params
+            .iter()
+            .map(|change| (change.param, change.value))
+            .collect()
+    }
+
+    #[test]
+    fn share_codes_round_trip() {
+        let mut material = MandelbulbMaterial::default();
+        material.shape.power = 6.5;
+        material.camera.position = Vec3::new(1.0, -2.0, 0.25);
+        material.camera.rotation = Vec4::from(Quat::from_euler(EulerRot::YXZ, 0.3, -0.2, 0.1));
+        let store = ParamStore::capture(&material);
+
+        let view = View::parse(&View::share_code(&material.camera, &store)).unwrap();
+        assert_eq!(view.target, material.camera.position);
+        assert!(
+            view.rotation()
+                .abs_diff_eq(Quat::from_vec4(material.camera.rotation), 1e-5)
+        );
+        assert_eq!(view.params.len(), Param::ALL.len());
+        for change in &view.params {
+            assert_eq!(change.value, store.get(change.param), "{:?}", change.param);
+        }
+        assert_eq!(view.compatibility_note(), None);
+    }
+
+    #[test]
+    fn unversioned_codes_still_open() {
+        let view = View::parse("target=1,2,3;angles=90,0,0;power=6").unwrap();
+        assert_eq!(view.target, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(view.angles, Vec3::new(90.0, 0.0, 0.0));
+        assert_eq!(changes(&view)[0], (Param::Power, 6.0));
+        assert_eq!(view.compatibility_note(), None);
+    }
+
+    #[test]
+    fn unversioned_codes_get_the_defaults_of_later_parameters() {
+        let defaults = MandelbulbMaterial::default();
+        let mut session = MandelbulbMaterial::default();
+        for param in [
+            Param::Specular,
+            Param::LightRed,
+            Param::Grain,
+            Param::FlareIntensity,
+        ] {
+            param.set(&mut session, param.get(&defaults) + 0.25);
+        }
+        session.shape.breath_amplitude = 0.1;
+
+        // written before specular, colored lights, grain, flares and breathing existed
+        let view =
+            View::parse("target=0,0,0;angles=0,0,0;power=6;light_x=0.5;rim_strength=0.3").unwrap();
+        view.apply(&mut session);
+        assert_eq!(session.shape.power, 6.0);
+        assert_eq!(Param::LightX.get(&session), 0.5);
+        for param in Param::ALL {
+            if !VERSION_0_PARAMS.contains(&param.name()) {
+                assert_eq!(param.get(&session), param.get(&defaults), "{param:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn versioned_codes_keep_what_they_leave_out() {
+        let view = View::parse("version=1;power=6").unwrap();
+        assert_eq!(changes(&view), [(Param::Power, 6.0)]);
+    }
+
+    #[test]
+    fn locations_only_move_the_camera() {
+        let view = View::parse("1, 2, 3, 90, 0, 0, 4").unwrap();
+        assert_eq!(view.target, Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(changes(&view), [(Param::Zoom, 4.0)]);
+    }
+
+    #[test]
+    fn newer_versions_open_with_a_note() {
+        let view = View::parse(&format!("version={};power=6", VIEW_VERSION + 1)).unwrap();
+        assert_eq!(view.params.len(), 1);
+        let note = view.compatibility_note().unwrap();
+        assert!(
+            note.contains(&format!("version {}", VIEW_VERSION + 1)),
+            "{note}"
+        );
+    }
+
+    #[test]
+    fn unknown_parameters_are_noted_not_fatal() {
+        let view = View::parse("version=1;power=6;sparkle=1").unwrap();
+        assert!(view.compatibility_note().unwrap().contains("sparkle"));
+        assert!(View::parse("version=1;sparkle=1").is_err());
+    }
+
+    #[test]
+    fn the_replay_fixture_parses() {
+        let view = View::parse(include_str!("../tests/bulb.view")).unwrap();
+        assert_eq!(view.compatibility_note(), None);
+    }
+}
@@ -0,0 +1,537 @@
+//! Node graphs that compile to WGSL, and the editor window shared by every graph. A graph type
+//! implements [`NodeKind`] for its nodes and is added with [`NodeGraphPlugin`].
+//!
+//! Each graph owns one shader asset with a fixed handle. Replacing it recompiles every pipeline
+//! that uses it, the same way shader hot reloading does.
+
+#[cfg(feature = "ui")]
+use crate::palette::{CosinePalette, paint_strip};
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use std::fmt::Write;
+use std::marker::PhantomData;
+
+/// Seconds without edits before a graph is recompiled, so dragging a value does not rebuild the
+/// pipeline every frame
+const RECOMPILE_DELAY: f64 = 0.3;
+
+#[cfg(feature = "ui")]
+const NODE_WIDTH: f32 = 150.0;
+#[cfg(feature = "ui")]
+const TITLE_HEIGHT: f32 = 24.0;
+#[cfg(feature = "ui")]
+const ROW_HEIGHT: f32 = 22.0;
+#[cfg(feature = "ui")]
+const PORT_RADIUS: f32 = 5.0;
+
+/// What flows along a link. Links can only connect ports of the same type.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PortType {
+    /// `vec3<f32>`, scalars are splatted
+    Value,
+    /// `vec3<f32>` position, an unconnected point input is the sample point `p`
+    Point,
+    /// `vec2<f32>` distance estimate and orbit trap
+    Distance,
+}
+
+impl PortType {
+    /// Expression for an unconnected input
+    fn constant(self, value: f32) -> String {
+        match self {
+            PortType::Value => format!("vec3<f32>({value:?})"),
+            PortType::Point => "p".into(),
+            PortType::Distance => format!("vec2<f32>({value:?}, 0.0)"),
+        }
+    }
+}
+
+pub struct Port {
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub name: &'static str,
+    pub ty: PortType,
+    /// Used while the input is not connected
+    pub default: f32,
+}
+
+pub const fn port(name: &'static str, ty: PortType, default: f32) -> Port {
+    Port { name, ty, default }
+}
+
+/// The node types of one kind of graph, and how the graph becomes a shader
+pub trait NodeKind: Copy + PartialEq + Send + Sync + 'static {
+    /// Title of the editor window
+    const TITLE: &'static str;
+    /// Kinds offered in the Add menu, there is always exactly one output node
+    #[cfg(feature = "ui")]
+    const ADDABLE: &'static [Self];
+    const OUTPUT: Self;
+    /// The shader asset the compiled graph is written to
+    const SHADER: Handle<Shader>;
+
+    #[cfg(feature = "ui")]
+    fn label(self) -> &'static str;
+    fn inputs(self) -> &'static [Port];
+    /// `None` for the output node
+    #[cfg(feature = "ui")]
+    fn output(self) -> Option<PortType>;
+    /// WGSL expression computing the node from its input expressions
+    fn code(self, node: &Node<Self>, inputs: &[String]) -> String;
+    /// Whether the node is edited with a color picker, see [`Node::color`]
+    #[cfg(feature = "ui")]
+    fn has_color(self) -> bool {
+        false
+    }
+    /// Colors the node shows as a strip under its inputs, for nodes that look up a palette
+    #[cfg(feature = "ui")]
+    fn gradient(_node: &Node<Self>) -> Option<CosinePalette> {
+        None
+    }
+    /// Graph the app starts with, node links refer to positions in the list
+    fn default_nodes() -> Vec<Node<Self>>;
+    /// Complete shader source around the compiled body, which ends by returning the output
+    fn shader_source(body: &str) -> String;
+}
+
+pub struct NodeGraphPlugin<K>(PhantomData<K>);
+
+impl<K> Default for NodeGraphPlugin<K> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<K: NodeKind> Plugin for NodeGraphPlugin<K> {
+    fn build(&self, app: &mut App) {
+        let graph = Graph::<K>::default();
+        let body = graph.compile().expect("the default graph compiles");
+        let _ = app
+            .world_mut()
+            .resource_mut::<Assets<Shader>>()
+            .insert(&K::SHADER, shader::<K>(&body));
+
+        app.insert_resource(graph)
+            .add_systems(Update, recompile_graph::<K>);
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, graph_editor::<K>);
+    }
+}
+
+fn shader<K: NodeKind>(body: &str) -> Shader {
+    let path = format!("{}.wgsl", K::TITLE.to_lowercase().replace(' ', "_"));
+    Shader::from_wgsl(K::shader_source(body), path)
+}
+
+pub struct Node<K> {
+    pub kind: K,
+    /// Top left corner relative to the canvas
+    #[cfg_attr(not(feature = "ui"), allow(dead_code))]
+    pub pos: Vec2,
+    /// Node feeding each input, if any
+    pub links: Vec<Option<usize>>,
+    /// Values of unconnected inputs
+    pub values: Vec<f32>,
+    /// Only used by kinds with [`NodeKind::has_color`]
+    pub color: [f32; 3],
+}
+
+impl<K: NodeKind> Node<K> {
+    pub fn new(kind: K, pos: Vec2) -> Self {
+        Self {
+            kind,
+            pos,
+            links: vec![None; kind.inputs().len()],
+            values: kind.inputs().iter().map(|port| port.default).collect(),
+            color: [1.0, 0.5, 0.2],
+        }
+    }
+
+    pub fn linked(mut self, input: usize, source: usize) -> Self {
+        self.links[input] = Some(source);
+        self
+    }
+
+    #[cfg(feature = "ui")]
+    fn rect(&self, origin: egui::Pos2) -> egui::Rect {
+        let strip = K::gradient(self).is_some() as usize;
+        let rows = (self.links.len() + strip).max(1) as f32;
+        egui::Rect::from_min_size(
+            origin + egui::vec2(self.pos.x, self.pos.y),
+            egui::vec2(NODE_WIDTH, TITLE_HEIGHT + rows * ROW_HEIGHT),
+        )
+    }
+
+    #[cfg(feature = "ui")]
+    fn input_port(&self, origin: egui::Pos2, input: usize) -> egui::Pos2 {
+        let rect = self.rect(origin);
+        egui::pos2(
+            rect.left(),
+            rect.top() + TITLE_HEIGHT + (input as f32 + 0.5) * ROW_HEIGHT,
+        )
+    }
+
+    #[cfg(feature = "ui")]
+    fn output_port(&self, origin: egui::Pos2) -> egui::Pos2 {
+        let rect = self.rect(origin);
+        egui::pos2(rect.right(), rect.top() + TITLE_HEIGHT / 2.0)
+    }
+}
+
+#[derive(Resource)]
+pub struct Graph<K> {
+    #[cfg(feature = "ui")]
+    pub open: bool,
+    /// Indexed by node id, removed nodes leave a `None` so ids stay stable
+    nodes: Vec<Option<Node<K>>>,
+    /// Node whose output is being dragged to an input
+    #[cfg(feature = "ui")]
+    dragging: Option<usize>,
+    edited_at: Option<f64>,
+    #[cfg(feature = "ui")]
+    error: Option<String>,
+}
+
+impl<K: NodeKind> Default for Graph<K> {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "ui")]
+            open: false,
+            nodes: K::default_nodes().into_iter().map(Some).collect(),
+            #[cfg(feature = "ui")]
+            dragging: None,
+            edited_at: None,
+            #[cfg(feature = "ui")]
+            error: None,
+        }
+    }
+}
+
+impl<K: NodeKind> Graph<K> {
+    fn node(&self, id: usize) -> Option<&Node<K>> {
+        self.nodes.get(id).and_then(Option::as_ref)
+    }
+
+    #[cfg(feature = "ui")]
+    fn remove(&mut self, id: usize) {
+        self.nodes[id] = None;
+        for node in self.nodes.iter_mut().flatten() {
+            for link in &mut node.links {
+                if *link == Some(id) {
+                    *link = None;
+                }
+            }
+        }
+    }
+
+    /// Generates the function body, one `let` per node ending with a `return` of the output.
+    /// Fails if the graph has a cycle.
+    pub fn compile(&self) -> Result<String, String> {
+        let output = self
+            .nodes
+            .iter()
+            .position(|n| n.as_ref().is_some_and(|n| n.kind == K::OUTPUT))
+            .ok_or("the graph has no output")?;
+
+        let mut body = String::new();
+        let mut state = vec![Visit::New; self.nodes.len()];
+        self.emit(output, &mut state, &mut body)?;
+        Ok(body)
+    }
+
+    fn emit(&self, id: usize, state: &mut [Visit], body: &mut String) -> Result<(), String> {
+        match state[id] {
+            Visit::Done => return Ok(()),
+            Visit::Active => return Err("the graph has a cycle".into()),
+            Visit::New => {}
+        }
+        state[id] = Visit::Active;
+
+        let node = self.node(id).ok_or("link to a removed node")?;
+        let mut inputs = Vec::new();
+        for ((link, value), port) in node.links.iter().zip(&node.values).zip(node.kind.inputs()) {
+            match link {
+                Some(source) => {
+                    self.emit(*source, state, body)?;
+                    inputs.push(format!("n{source}"));
+                }
+                None => inputs.push(port.ty.constant(*value)),
+            }
+        }
+
+        let code = node.kind.code(node, &inputs);
+        if node.kind == K::OUTPUT {
+            let _ = writeln!(body, "    return {code};");
+        } else {
+            let _ = writeln!(body, "    let n{id} = {code};");
+        }
+        state[id] = Visit::Done;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Visit {
+    New,
+    Active,
+    Done,
+}
+
+/// Replaces the shader once the graph has been left alone for a moment
+fn recompile_graph<K: NodeKind>(
+    time: Res<Time>,
+    mut graph: ResMut<Graph<K>>,
+    mut shaders: ResMut<Assets<Shader>>,
+) {
+    let Some(edited_at) = graph.edited_at else {
+        return;
+    };
+    if time.elapsed_secs_f64() - edited_at < RECOMPILE_DELAY {
+        return;
+    }
+    graph.edited_at = None;
+    let compiled = graph.compile();
+    if let Ok(body) = &compiled {
+        let _ = shaders.insert(&K::SHADER, shader::<K>(body));
+    }
+    #[cfg(feature = "ui")]
+    {
+        graph.error = compiled.err();
+    }
+}
+
+/// Edits made while drawing the graph, applied afterwards
+#[cfg(feature = "ui")]
+enum Edit<K> {
+    Add(K),
+    Remove(usize),
+    Link {
+        from: usize,
+        to: usize,
+        input: usize,
+    },
+    Unlink {
+        node: usize,
+        input: usize,
+    },
+}
+
+#[cfg(feature = "ui")]
+fn graph_editor<K: NodeKind>(
+    mut contexts: EguiContexts,
+    mut graph: ResMut<Graph<K>>,
+    time: Res<Time>,
+) {
+    if !graph.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let graph = &mut *graph;
+
+    let mut open = graph.open;
+    let mut edits = Vec::new();
+    let mut changed = false;
+    egui::Window::new(K::TITLE)
+        .open(&mut open)
+        .default_size([640.0, 360.0])
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.menu_button("Add Node", |ui| {
+                    for kind in K::ADDABLE {
+                        if ui.button(kind.label()).clicked() {
+                            edits.push(Edit::Add(*kind));
+                            ui.close();
+                        }
+                    }
+                });
+                match &graph.error {
+                    Some(err) => {
+                        ui.colored_label(egui::Color32::LIGHT_RED, err.as_str());
+                    }
+                    None => {
+                        ui.weak("Drag from an output to an input, right-click an input to unlink");
+                    }
+                }
+            });
+
+            let (canvas, painter) = ui.allocate_painter(ui.available_size(), egui::Sense::hover());
+            let origin = canvas.rect.min;
+            painter.rect_filled(canvas.rect, 0.0, egui::Color32::from_gray(20));
+            let painter = painter.with_clip_rect(canvas.rect);
+
+            let link_stroke = egui::Stroke::new(2.0, egui::Color32::from_rgb(120, 170, 255));
+            let draw_link = |from: egui::Pos2, to: egui::Pos2| {
+                let dx = egui::vec2(((to.x - from.x).abs() * 0.5).max(30.0), 0.0);
+                painter.add(egui::epaint::CubicBezierShape::from_points_stroke(
+                    [from, from + dx, to - dx, to],
+                    false,
+                    egui::Color32::TRANSPARENT,
+                    link_stroke,
+                ));
+            };
+
+            for node in graph.nodes.iter().flatten() {
+                for (input, link) in node.links.iter().enumerate() {
+                    if let Some(source) = link.and_then(|id| graph.node(id)) {
+                        draw_link(source.output_port(origin), node.input_port(origin, input));
+                    }
+                }
+            }
+
+            for (id, slot) in graph.nodes.iter_mut().enumerate() {
+                let Some(node) = slot else {
+                    continue;
+                };
+                let rect = node.rect(origin);
+                painter.rect_filled(rect, 4.0, egui::Color32::from_gray(45));
+                painter.text(
+                    rect.min + egui::vec2(8.0, TITLE_HEIGHT / 2.0),
+                    egui::Align2::LEFT_CENTER,
+                    node.kind.label(),
+                    egui::FontId::proportional(14.0),
+                    egui::Color32::WHITE,
+                );
+
+                let title =
+                    egui::Rect::from_min_size(rect.min, egui::vec2(NODE_WIDTH, TITLE_HEIGHT));
+                let drag = ui.interact(title, ui.id().with(("node", id)), egui::Sense::drag());
+                if drag.dragged() {
+                    let delta = drag.drag_delta();
+                    node.pos += Vec2::new(delta.x, delta.y);
+                }
+                if node.kind != K::OUTPUT {
+                    let close = egui::Rect::from_center_size(
+                        egui::pos2(rect.right() - 24.0, title.center().y),
+                        egui::vec2(16.0, 16.0),
+                    );
+                    if ui.put(close, egui::Button::new("x").small()).clicked() {
+                        edits.push(Edit::Remove(id));
+                    }
+                }
+
+                if node.kind.output().is_some() {
+                    let port = node.output_port(origin);
+                    painter.circle_filled(port, PORT_RADIUS, egui::Color32::LIGHT_GRAY);
+                    let response = ui.interact(
+                        egui::Rect::from_center_size(port, egui::Vec2::splat(PORT_RADIUS * 3.0)),
+                        ui.id().with(("output", id)),
+                        egui::Sense::drag(),
+                    );
+                    if response.drag_started() {
+                        graph.dragging = Some(id);
+                    }
+                }
+
+                if node.kind.has_color() {
+                    let row = egui::Rect::from_min_size(
+                        rect.min + egui::vec2(8.0, TITLE_HEIGHT),
+                        egui::vec2(NODE_WIDTH - 16.0, ROW_HEIGHT),
+                    );
+                    changed |= ui
+                        .put(row, |ui: &mut egui::Ui| {
+                            ui.color_edit_button_rgb(&mut node.color)
+                        })
+                        .changed();
+                }
+
+                if let Some(palette) = K::gradient(node) {
+                    let strip = egui::Rect::from_min_size(
+                        rect.left_bottom() + egui::vec2(8.0, 4.0 - ROW_HEIGHT),
+                        egui::vec2(NODE_WIDTH - 16.0, ROW_HEIGHT - 8.0),
+                    );
+                    paint_strip(&painter, strip, &palette);
+                }
+
+                for (input, port_info) in node.kind.inputs().iter().enumerate() {
+                    let port = node.input_port(origin, input);
+                    painter.circle_filled(port, PORT_RADIUS, egui::Color32::LIGHT_GRAY);
+                    painter.text(
+                        port + egui::vec2(10.0, 0.0),
+                        egui::Align2::LEFT_CENTER,
+                        port_info.name,
+                        egui::FontId::proportional(13.0),
+                        egui::Color32::LIGHT_GRAY,
+                    );
+                    let response = ui.interact(
+                        egui::Rect::from_center_size(port, egui::Vec2::splat(PORT_RADIUS * 3.0)),
+                        ui.id().with(("input", id, input)),
+                        egui::Sense::click(),
+                    );
+                    if response.secondary_clicked() {
+                        edits.push(Edit::Unlink { node: id, input });
+                    }
+                    // points default to the sample point, there is nothing to edit
+                    if node.links[input].is_none() && port_info.ty != PortType::Point {
+                        let value = egui::Rect::from_min_size(
+                            egui::pos2(rect.right() - 70.0, port.y - ROW_HEIGHT / 2.0 + 2.0),
+                            egui::vec2(62.0, ROW_HEIGHT - 4.0),
+                        );
+                        changed |= ui
+                            .put(
+                                value,
+                                egui::DragValue::new(&mut node.values[input]).speed(0.01),
+                            )
+                            .changed();
+                    }
+                }
+            }
+
+            // a link being dragged, connected to the input it is released over
+            if let Some(from) = graph.dragging
+                && let Some(pointer) = ui.input(|i| i.pointer.latest_pos())
+            {
+                let source = graph.node(from);
+                if let Some(source) = source {
+                    draw_link(source.output_port(origin), pointer);
+                }
+                if ui.input(|i| i.pointer.any_released()) {
+                    let ty = source.and_then(|source| source.kind.output());
+                    let target = graph.nodes.iter().enumerate().find_map(|(id, node)| {
+                        let node = node.as_ref()?;
+                        node.kind
+                            .inputs()
+                            .iter()
+                            .enumerate()
+                            .find(|(input, port)| {
+                                Some(port.ty) == ty
+                                    && node.input_port(origin, *input).distance(pointer)
+                                        < PORT_RADIUS * 2.0
+                            })
+                            .map(|(input, _)| (id, input))
+                    });
+                    if let Some((to, input)) = target
+                        && to != from
+                    {
+                        edits.push(Edit::Link { from, to, input });
+                    }
+                    graph.dragging = None;
+                }
+            }
+        });
+
+    for edit in edits {
+        changed = true;
+        match edit {
+            Edit::Add(kind) => {
+                graph
+                    .nodes
+                    .push(Some(Node::new(kind, Vec2::new(20.0, 20.0))));
+            }
+            Edit::Remove(id) => graph.remove(id),
+            Edit::Link { from, to, input } => {
+                if let Some(Some(node)) = graph.nodes.get_mut(to) {
+                    node.links[input] = Some(from);
+                }
+            }
+            Edit::Unlink { node, input } => {
+                if let Some(Some(node)) = graph.nodes.get_mut(node) {
+                    node.links[input] = None;
+                }
+            }
+        }
+    }
+    if changed {
+        graph.edited_at = Some(time.elapsed_secs_f64());
+    }
+    graph.open = open;
+}
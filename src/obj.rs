@@ -0,0 +1,22 @@
+//! Wavefront OBJ writer for extracted surfaces, the format every DCC reads as a mesh sequence
+
+use crate::surface::SurfaceMesh;
+use std::io::{BufWriter, Write as _};
+use std::path::Path;
+
+pub fn write_obj(path: &Path, mesh: &SurfaceMesh) -> std::io::Result<()> {
+    let mut out = BufWriter::new(std::fs::File::create(path)?);
+    writeln!(out, "# fractal3D")?;
+    for p in &mesh.positions {
+        writeln!(out, "v {} {} {}", p.x, p.y, p.z)?;
+    }
+    for n in &mesh.normals {
+        writeln!(out, "vn {} {} {}", n.x, n.y, n.z)?;
+    }
+    // indices are 1-based, every vertex has the normal with the same index
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+        writeln!(out, "f {a}//{a} {b}//{b} {c}//{c}")?;
+    }
+    out.flush()
+}
@@ -0,0 +1,176 @@
+use crate::MainCamera;
+use crate::material::{FractalMaterial, MandelbulbMaterial, OUTPUT_MODE_HDR10, OUTPUT_MODE_SCRGB};
+use crate::post_pass::PostPass;
+use bevy::core_pipeline::FullscreenShader;
+use bevy::core_pipeline::core_2d::graph::{Core2d, Node2d};
+use bevy::ecs::query::QueryItem;
+use bevy::prelude::*;
+use bevy::render::extract_component::{
+    DynamicUniformIndex, ExtractComponent, ExtractComponentPlugin, UniformComponentPlugin,
+};
+use bevy::render::render_graph::{
+    NodeRunError, RenderGraphContext, RenderGraphExt, RenderLabel, ViewNode, ViewNodeRunner,
+};
+use bevy::render::render_resource::{
+    CachedRenderPipelineId, PipelineCache, ShaderType, TextureFormat,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::view::ViewTarget;
+use bevy::render::{RenderApp, RenderStartup};
+
+const SHADER_PATH: &str = "shaders/output.wgsl";
+
+/// The output transfer function. Everything before it, the fractal, offscreen layers and
+/// crossfades, flares and particles, works in linear light, and this fullscreen pass at the end
+/// of the post chain encodes the frame once for the selected output mode.
+///
+/// Bevy picks the window's surface format itself and prefers 8-bit sRGB, so the window can't show
+/// the HDR modes. On such a surface the pass previews them with the SDR encoding instead of
+/// writing PQ or scRGB values the display would misread. Float targets, like still exports, get
+/// the selected mode's full range.
+pub struct OutputPlugin;
+
+impl Plugin for OutputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            ExtractComponentPlugin::<OutputEncoding>::default(),
+            UniformComponentPlugin::<OutputEncoding>::default(),
+        ))
+        .add_systems(Update, sync_main_camera_encoding);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .add_systems(RenderStartup, init_output_pipeline)
+            .add_render_graph_node::<ViewNodeRunner<OutputNode>>(Core2d, OutputLabel)
+            .add_render_graph_edges(
+                Core2d,
+                (
+                    Node2d::Tonemapping,
+                    OutputLabel,
+                    Node2d::EndMainPassPostProcessing,
+                ),
+            );
+    }
+}
+
+/// Output settings of a camera, see `OutputUniform` in `shaders/output.wgsl`. Taken from the
+/// main material's shading, where share codes and the parameter system see them.
+#[derive(Component, ExtractComponent, ShaderType, Clone, Copy, PartialEq, Debug)]
+pub struct OutputEncoding {
+    pub mode: u32,             // see OUTPUT_MODE_SDR and the HDR modes
+    pub paper_white_nits: f32, // brightness of diffuse white in HDR modes
+}
+
+impl OutputEncoding {
+    pub fn of(material: &MandelbulbMaterial) -> Self {
+        Self {
+            mode: material.shading.output_mode,
+            paper_white_nits: material.shading.paper_white_nits,
+        }
+    }
+}
+
+/// Gives the main camera the main material's output settings
+fn sync_main_camera_encoding(
+    mut commands: Commands,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    cameras: Query<(Entity, Option<&OutputEncoding>), With<MainCamera>>,
+) {
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let wanted = OutputEncoding::of(mat);
+    for (entity, current) in cameras.iter() {
+        if current != Some(&wanted) {
+            commands.entity(entity).insert(wanted);
+        }
+    }
+}
+
+/// Whether a final target can hold the values an output mode produces. scRGB goes above 1.0 and
+/// needs floats, HDR10's PQ signal fits 10-bit.
+fn can_show(mode: u32, format: TextureFormat) -> bool {
+    let float = matches!(
+        format,
+        TextureFormat::Rgba16Float | TextureFormat::Rgba32Float
+    );
+    match mode {
+        OUTPUT_MODE_SCRGB => float,
+        OUTPUT_MODE_HDR10 => float || format == TextureFormat::Rgb10a2Unorm,
+        _ => true,
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct OutputLabel;
+
+/// An SDR pipeline per view target format, and one that encodes the HDR modes for float targets
+#[derive(Resource)]
+struct OutputPipeline {
+    pass: PostPass<OutputEncoding>,
+    sdr: CachedRenderPipelineId,
+    hdr_preview: CachedRenderPipelineId,
+    hdr: CachedRenderPipelineId,
+}
+
+fn init_output_pipeline(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    asset_server: Res<AssetServer>,
+    fullscreen_shader: Res<FullscreenShader>,
+    pipeline_cache: Res<PipelineCache>,
+) {
+    let pass = PostPass::new("output", asset_server.load(SHADER_PATH), &render_device);
+    let queue = |format: TextureFormat, sdr_surface: bool| {
+        let shader_defs = if sdr_surface {
+            vec!["SDR_SURFACE".into()]
+        } else {
+            Vec::new()
+        };
+        pass.queue(&pipeline_cache, &fullscreen_shader, format, shader_defs)
+    };
+    let sdr = queue(TextureFormat::bevy_default(), true);
+    let hdr_preview = queue(ViewTarget::TEXTURE_FORMAT_HDR, true);
+    let hdr = queue(ViewTarget::TEXTURE_FORMAT_HDR, false);
+    commands.insert_resource(OutputPipeline {
+        pass,
+        sdr,
+        hdr_preview,
+        hdr,
+    });
+}
+
+#[derive(Default)]
+struct OutputNode;
+
+impl ViewNode for OutputNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static OutputEncoding,
+        &'static DynamicUniformIndex<OutputEncoding>,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (target, encoding, index): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let output = world.resource::<OutputPipeline>();
+        let pipeline = if !target.is_hdr() {
+            output.sdr
+        } else if can_show(encoding.mode, target.out_texture_format()) {
+            output.hdr
+        } else {
+            output.hdr_preview
+        };
+        // reads the linear frame and writes it encoded
+        output
+            .pass
+            .run(render_context, world, target, pipeline, index)
+    }
+}
@@ -0,0 +1,186 @@
+//! The color palettes, one table shared by the palette selector, share codes, which store a
+//! palette by its index, the shader, which gets the selected palette's coefficients, and every
+//! CPU port of the coloring. The built-in palettes come first so their indices never change,
+//! followed by the user's own from `palettes.ron` in the config directory:
+//!
+//! ```ron
+//! [
+//!     (name: "Ocean", a: (0.2, 0.4, 0.6), b: (0.2, 0.3, 0.4), c: (1.0, 1.0, 1.0), d: (0.0, 0.1, 0.2)),
+//! ]
+//! ```
+
+use crate::config::config_file;
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::egui;
+use serde::Deserialize;
+#[cfg(feature = "ui")]
+use std::f32::consts::TAU;
+use std::sync::OnceLock;
+
+const PALETTES_FILE: &str = "palettes.ron";
+
+/// Quads along a drawn strip, the palettes are smooth enough that more don't show
+#[cfg(feature = "ui")]
+const STRIP_SEGMENTS: u32 = 32;
+
+static PALETTES: OnceLock<Vec<NamedPalette>> = OnceLock::new();
+
+/// Inigo Quilez's cosine palette, `a + b * cos(2π (c t + d))` per channel, see `palette` in the
+/// fractal shader
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CosinePalette {
+    pub a: Vec3,
+    pub b: Vec3,
+    pub c: Vec3,
+    pub d: Vec3,
+}
+
+impl CosinePalette {
+    pub const STANDARD: Self = Self {
+        a: Vec3::splat(0.5),
+        b: Vec3::splat(0.5),
+        c: Vec3::ONE,
+        d: Vec3::new(0.263, 0.416, 0.557),
+    };
+
+    /// The coefficients as the shader reads them, a to d with w unused
+    pub fn uniform(&self) -> [Vec4; 4] {
+        [self.a, self.b, self.c, self.d].map(|v| v.extend(0.0))
+    }
+
+    /// Linear color at `t`
+    #[cfg(feature = "ui")]
+    pub fn color(&self, t: f32) -> Vec3 {
+        let phase = (self.c * t + self.d) * TAU;
+        self.a + self.b * Vec3::new(phase.x.cos(), phase.y.cos(), phase.z.cos())
+    }
+
+    /// The same colors with `t` moved by `shift`
+    #[cfg(feature = "ui")]
+    pub fn shifted(self, shift: f32) -> Self {
+        Self {
+            d: self.d + self.c * shift,
+            ..self
+        }
+    }
+}
+
+pub struct NamedPalette {
+    pub name: String,
+    pub palette: CosinePalette,
+}
+
+/// An entry of [`PALETTES_FILE`]
+#[derive(Deserialize)]
+struct UserPalette {
+    name: String,
+    a: [f32; 3],
+    b: [f32; 3],
+    c: [f32; 3],
+    d: [f32; 3],
+}
+
+const BUILT_IN: [(&str, CosinePalette); 3] = [
+    ("Standard", CosinePalette::STANDARD),
+    (
+        "Fire",
+        CosinePalette {
+            a: Vec3::new(0.5, 0.5, 0.0),
+            b: Vec3::new(0.5, 0.5, 0.0),
+            c: Vec3::new(0.1, 0.5, 0.0),
+            d: Vec3::ZERO,
+        },
+    ),
+    (
+        "Neon",
+        CosinePalette {
+            a: Vec3::splat(0.5),
+            b: Vec3::splat(0.5),
+            c: Vec3::new(2.0, 1.0, 0.0),
+            d: Vec3::new(0.5, 0.2, 0.25),
+        },
+    ),
+];
+
+/// Every palette, indexed by `palette_id`
+pub fn palettes() -> &'static [NamedPalette] {
+    PALETTES.get_or_init(|| {
+        let mut palettes: Vec<_> = BUILT_IN
+            .into_iter()
+            .map(|(name, palette)| NamedPalette {
+                name: name.to_string(),
+                palette,
+            })
+            .collect();
+        palettes.extend(load_user_palettes().into_iter().map(|user| NamedPalette {
+            name: user.name,
+            palette: CosinePalette {
+                a: Vec3::from_array(user.a),
+                b: Vec3::from_array(user.b),
+                c: Vec3::from_array(user.c),
+                d: Vec3::from_array(user.d),
+            },
+        }));
+        palettes
+    })
+}
+
+fn load_user_palettes() -> Vec<UserPalette> {
+    let path = config_file(PALETTES_FILE);
+    match std::fs::read_to_string(&path) {
+        Ok(text) => ron::from_str(&text).unwrap_or_else(|err| {
+            warn!("Ignoring {}: {err}", path.display());
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// The palette for `palette_id`, the standard one for ids not in the table, like a share code
+/// using a palette this user doesn't have
+pub fn palette(id: u32) -> CosinePalette {
+    palettes()
+        .get(id as usize)
+        .map_or(CosinePalette::STANDARD, |entry| entry.palette)
+}
+
+/// Name of a `palette_id`
+pub fn palette_label(id: u32) -> &'static str {
+    palettes()
+        .get(id as usize)
+        .map_or("Unknown", |entry| entry.name.as_str())
+}
+
+/// Paints `t` from 0 to 1 left to right over `rect`
+#[cfg(feature = "ui")]
+pub fn paint_strip(painter: &egui::Painter, rect: egui::Rect, palette: &CosinePalette) {
+    let mut mesh = egui::Mesh::default();
+    for i in 0..=STRIP_SEGMENTS {
+        let t = i as f32 / STRIP_SEGMENTS as f32;
+        let [r, g, b] = palette.color(t).clamp(Vec3::ZERO, Vec3::ONE).to_array();
+        // the shader's output is linear and encoded by the sRGB surface, egui wants it encoded
+        let color = egui::Color32::from(egui::Rgba::from_rgb(r, g, b));
+        let x = rect.left() + rect.width() * t;
+        mesh.colored_vertex(egui::pos2(x, rect.top()), color);
+        mesh.colored_vertex(egui::pos2(x, rect.bottom()), color);
+        if i > 0 {
+            let k = 2 * i;
+            mesh.add_triangle(k - 2, k - 1, k);
+            mesh.add_triangle(k - 1, k + 1, k);
+        }
+    }
+    painter.add(mesh);
+}
+
+/// A strip of `size` in the layout
+#[cfg(feature = "ui")]
+pub fn gradient_strip(
+    ui: &mut egui::Ui,
+    palette: &CosinePalette,
+    size: egui::Vec2,
+) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+    paint_strip(ui.painter(), rect, palette);
+    response
+}
@@ -0,0 +1,838 @@
+use crate::formulas::{self, MAX_PLUGIN_PARAMS};
+use crate::material::{
+    CameraUniform, ColoringMode, Formula, FractalMaterial, MandelbulbMaterial, ShapeUniform,
+};
+use crate::palette::{palette, palettes};
+#[cfg(feature = "ui")]
+use crate::units::Quantity;
+use crate::validation::{self, Validation};
+use bevy::platform::collections::HashMap;
+use bevy::prelude::*;
+use std::ops::RangeInclusive;
+
+pub struct ParamsPlugin;
+
+impl Plugin for ParamsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<ParamChanged>()
+            .add_systems(Update, apply_param_changes);
+    }
+}
+
+/// Every user-editable value of the fractal. The UI and other input sources never write the
+/// material directly, they send a [`ParamChanged`] for one of these and [`apply_param_changes`]
+/// updates the [`ParamStore`] and the material.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Param {
+    Formula,
+    GeometryGraph,
+    Power,
+    Iterations,
+    RaySteps,
+    HitThreshold,
+    MaxDist,
+    StallLimit,
+    ShowStalled,
+    Zoom,
+    FocalLength,
+    FovFit,
+    PixelAspect,
+    BackgroundGlow,
+    Palette,
+    Coloring,
+    CustomShading,
+    ColorScale,
+    ColorOffset,
+    LightX,
+    LightY,
+    LightRed,
+    LightGreen,
+    LightBlue,
+    AmbientRed,
+    AmbientGreen,
+    AmbientBlue,
+    Specular,
+    Shininess,
+    SoftShadows,
+    AoStrength,
+    RimStrength,
+    RimRed,
+    RimGreen,
+    RimBlue,
+    RimDirection,
+    LensStrength,
+    LensDistortion,
+    ChromaticAberration,
+    Grain,
+    BreathAmplitude,
+    BreathSpeed,
+    FlareIntensity,
+    FlareThreshold,
+    FlareLength,
+    FlareStarburst,
+    FlareRed,
+    FlareGreen,
+    FlareBlue,
+    FogDensity,
+    Streamlines,
+    StreamlineDensity,
+    StreamlineLength,
+    Isolines,
+    IsolineSpacing,
+    IsolineIntensity,
+    Slice,
+    SliceAxis,
+    SliceOffset,
+    SliceExtent,
+    JuliaEnabled,
+    JuliaX,
+    JuliaY,
+    JuliaZ,
+    Supersampling,
+    BrickMap,
+    Reprojection,
+    OutputMode,
+    PaperWhite,
+    TestPattern,
+    /// Parameter slot of a plugin formula, see [`formulas::PluginParam`]
+    PluginSlot(u8),
+}
+
+/// How a parameter's value is interpreted and edited
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParamKind {
+    Scalar,
+    Integer,
+    /// 0.0 or 1.0
+    Toggle,
+    /// Index into [`Param::choices`]
+    Choice,
+}
+
+impl Param {
+    pub const ALL: [Param; 78] = [
+        Param::Formula,
+        Param::GeometryGraph,
+        Param::Power,
+        Param::Iterations,
+        Param::RaySteps,
+        Param::HitThreshold,
+        Param::MaxDist,
+        Param::StallLimit,
+        Param::ShowStalled,
+        Param::Zoom,
+        Param::FocalLength,
+        Param::FovFit,
+        Param::PixelAspect,
+        Param::BackgroundGlow,
+        Param::Palette,
+        Param::Coloring,
+        Param::CustomShading,
+        Param::ColorScale,
+        Param::ColorOffset,
+        Param::LightX,
+        Param::LightY,
+        Param::LightRed,
+        Param::LightGreen,
+        Param::LightBlue,
+        Param::AmbientRed,
+        Param::AmbientGreen,
+        Param::AmbientBlue,
+        Param::Specular,
+        Param::Shininess,
+        Param::SoftShadows,
+        Param::AoStrength,
+        Param::RimStrength,
+        Param::RimRed,
+        Param::RimGreen,
+        Param::RimBlue,
+        Param::RimDirection,
+        Param::LensStrength,
+        Param::LensDistortion,
+        Param::ChromaticAberration,
+        Param::Grain,
+        Param::BreathAmplitude,
+        Param::BreathSpeed,
+        Param::FlareIntensity,
+        Param::FlareThreshold,
+        Param::FlareLength,
+        Param::FlareStarburst,
+        Param::FlareRed,
+        Param::FlareGreen,
+        Param::FlareBlue,
+        Param::FogDensity,
+        Param::Streamlines,
+        Param::StreamlineDensity,
+        Param::StreamlineLength,
+        Param::Isolines,
+        Param::IsolineSpacing,
+        Param::IsolineIntensity,
+        Param::Slice,
+        Param::SliceAxis,
+        Param::SliceOffset,
+        Param::SliceExtent,
+        Param::JuliaEnabled,
+        Param::JuliaX,
+        Param::JuliaY,
+        Param::JuliaZ,
+        Param::Supersampling,
+        Param::BrickMap,
+        Param::Reprojection,
+        Param::OutputMode,
+        Param::PaperWhite,
+        Param::TestPattern,
+        Param::PluginSlot(0),
+        Param::PluginSlot(1),
+        Param::PluginSlot(2),
+        Param::PluginSlot(3),
+        Param::PluginSlot(4),
+        Param::PluginSlot(5),
+        Param::PluginSlot(6),
+        Param::PluginSlot(7),
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Param::Formula => "Formula",
+            Param::GeometryGraph => "Use Geometry Graph",
+            Param::Power => "Power",
+            Param::Iterations => "Iterations",
+            Param::RaySteps => "Ray Steps",
+            Param::HitThreshold => "Threshold",
+            Param::MaxDist => "Max Dist",
+            Param::StallLimit => "Stall Limit",
+            Param::ShowStalled => "Highlight Stalled Rays",
+            Param::Zoom => "Zoom",
+            Param::FocalLength => "Focal Length",
+            Param::FovFit => "Fit",
+            Param::PixelAspect => "Pixel Aspect",
+            Param::BackgroundGlow => "Background Brightness",
+            Param::Palette => "Color Palette",
+            Param::Coloring => "Coloring",
+            Param::CustomShading => "Use Shading Graph",
+            Param::ColorScale => "Color Scale",
+            Param::ColorOffset => "Color Offset",
+            Param::LightX => "Light X",
+            Param::LightY => "Light Y",
+            Param::LightRed => "Light Red",
+            Param::LightGreen => "Light Green",
+            Param::LightBlue => "Light Blue",
+            Param::AmbientRed => "Ambient Red",
+            Param::AmbientGreen => "Ambient Green",
+            Param::AmbientBlue => "Ambient Blue",
+            Param::Specular => "Specular",
+            Param::Shininess => "Shininess",
+            Param::SoftShadows => "Soft Shadows",
+            Param::AoStrength => "Ambient Occlusion",
+            Param::RimStrength => "Rim Lighting",
+            Param::RimRed => "Rim Red",
+            Param::RimGreen => "Rim Green",
+            Param::RimBlue => "Rim Blue",
+            Param::RimDirection => "Rim Direction",
+            Param::LensStrength => "Gravity Lens",
+            Param::LensDistortion => "Lens Distortion",
+            Param::ChromaticAberration => "Chromatic Aberration",
+            Param::Grain => "Film Grain",
+            Param::BreathAmplitude => "Breathing",
+            Param::BreathSpeed => "Breathing Speed",
+            Param::FlareIntensity => "Flare",
+            Param::FlareThreshold => "Flare Threshold",
+            Param::FlareLength => "Flare Length",
+            Param::FlareStarburst => "Starburst",
+            Param::FlareRed => "Flare Red",
+            Param::FlareGreen => "Flare Green",
+            Param::FlareBlue => "Flare Blue",
+            Param::FogDensity => "Fog Density",
+            Param::Streamlines => "Flow Lines",
+            Param::StreamlineDensity => "Line Density",
+            Param::StreamlineLength => "Line Length",
+            Param::Isolines => "Isolines",
+            Param::IsolineSpacing => "Isoline Spacing",
+            Param::IsolineIntensity => "Isoline Intensity",
+            Param::Slice => "Slice View",
+            Param::SliceAxis => "Plane",
+            Param::SliceOffset => "Offset",
+            Param::SliceExtent => "Extent",
+            Param::JuliaEnabled => "Enable Julia Mode",
+            Param::JuliaX => "X",
+            Param::JuliaY => "Y",
+            Param::JuliaZ => "Z",
+            Param::Supersampling => "Supersampling (2x2)",
+            Param::BrickMap => "Empty Space Skipping",
+            Param::Reprojection => "Temporal Reuse",
+            Param::OutputMode => "Output",
+            Param::PaperWhite => "Paper White (nits)",
+            Param::TestPattern => "Test Pattern",
+            Param::PluginSlot(_) => "Formula Parameter",
+        }
+    }
+
+    /// Identifier used in text, e.g. timeline expressions. Unlike the label it is unique and does
+    /// not change with the UI wording.
+    pub fn name(self) -> &'static str {
+        match self {
+            Param::Formula => "formula",
+            Param::GeometryGraph => "geometry_graph",
+            Param::Power => "power",
+            Param::Iterations => "iterations",
+            Param::RaySteps => "ray_steps",
+            Param::HitThreshold => "hit_threshold",
+            Param::MaxDist => "max_dist",
+            Param::StallLimit => "stall_limit",
+            Param::ShowStalled => "show_stalled",
+            Param::Zoom => "zoom",
+            Param::FocalLength => "focal_length",
+            Param::FovFit => "fov_fit",
+            Param::PixelAspect => "pixel_aspect",
+            Param::BackgroundGlow => "background_glow",
+            Param::Palette => "palette",
+            Param::Coloring => "coloring",
+            Param::CustomShading => "custom_shading",
+            Param::ColorScale => "color_scale",
+            Param::ColorOffset => "color_offset",
+            Param::LightX => "light_x",
+            Param::LightY => "light_y",
+            Param::LightRed => "light_r",
+            Param::LightGreen => "light_g",
+            Param::LightBlue => "light_b",
+            Param::AmbientRed => "ambient_r",
+            Param::AmbientGreen => "ambient_g",
+            Param::AmbientBlue => "ambient_b",
+            Param::Specular => "specular",
+            Param::Shininess => "shininess",
+            Param::SoftShadows => "soft_shadows",
+            Param::AoStrength => "ao_strength",
+            Param::RimStrength => "rim_strength",
+            Param::RimRed => "rim_r",
+            Param::RimGreen => "rim_g",
+            Param::RimBlue => "rim_b",
+            Param::RimDirection => "rim_direction",
+            Param::LensStrength => "lens_strength",
+            Param::LensDistortion => "lens_distortion",
+            Param::ChromaticAberration => "chromatic_aberration",
+            Param::Grain => "grain",
+            Param::BreathAmplitude => "breath_amplitude",
+            Param::BreathSpeed => "breath_speed",
+            Param::FlareIntensity => "flare_intensity",
+            Param::FlareThreshold => "flare_threshold",
+            Param::FlareLength => "flare_length",
+            Param::FlareStarburst => "flare_starburst",
+            Param::FlareRed => "flare_r",
+            Param::FlareGreen => "flare_g",
+            Param::FlareBlue => "flare_b",
+            Param::FogDensity => "fog_density",
+            Param::Streamlines => "streamlines",
+            Param::StreamlineDensity => "streamline_density",
+            Param::StreamlineLength => "streamline_length",
+            Param::Isolines => "isolines",
+            Param::IsolineSpacing => "isoline_spacing",
+            Param::IsolineIntensity => "isoline_intensity",
+            Param::Slice => "slice",
+            Param::SliceAxis => "slice_axis",
+            Param::SliceOffset => "slice_offset",
+            Param::SliceExtent => "slice_extent",
+            Param::JuliaEnabled => "julia_enabled",
+            Param::JuliaX => "julia_x",
+            Param::JuliaY => "julia_y",
+            Param::JuliaZ => "julia_z",
+            Param::Supersampling => "supersampling",
+            Param::BrickMap => "brick_map",
+            Param::Reprojection => "reprojection",
+            Param::OutputMode => "output_mode",
+            Param::PaperWhite => "paper_white",
+            Param::TestPattern => "test_pattern",
+            Param::PluginSlot(i) => {
+                const NAMES: [&str; MAX_PLUGIN_PARAMS] = [
+                    "formula_param_0",
+                    "formula_param_1",
+                    "formula_param_2",
+                    "formula_param_3",
+                    "formula_param_4",
+                    "formula_param_5",
+                    "formula_param_6",
+                    "formula_param_7",
+                ];
+                NAMES[i as usize]
+            }
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Param> {
+        Param::ALL.into_iter().find(|param| param.name() == name)
+    }
+
+    pub fn kind(self) -> ParamKind {
+        match self {
+            Param::Iterations | Param::RaySteps | Param::StallLimit | Param::Palette => {
+                ParamKind::Integer
+            }
+            Param::SoftShadows
+            | Param::GeometryGraph
+            | Param::CustomShading
+            | Param::Streamlines
+            | Param::JuliaEnabled
+            | Param::Supersampling
+            | Param::BrickMap
+            | Param::Reprojection
+            | Param::ShowStalled => ParamKind::Toggle,
+            Param::Formula
+            | Param::Coloring
+            | Param::FovFit
+            | Param::OutputMode
+            | Param::Isolines
+            | Param::Slice
+            | Param::SliceAxis
+            | Param::RimDirection
+            | Param::TestPattern => ParamKind::Choice,
+            _ => ParamKind::Scalar,
+        }
+    }
+
+    /// Labels for [`ParamKind::Choice`] parameters, indexed by value
+    pub fn choices(self) -> &'static [&'static str] {
+        match self {
+            Param::Formula => formulas::formula_labels(),
+            Param::Coloring => &["Orbit Trap", "Normal", "Equalized"],
+            Param::FovFit => &["Vertical", "Horizontal", "Shorter Side"],
+            Param::OutputMode => &["SDR", "HDR (scRGB)", "HDR10 (PQ)"],
+            Param::Isolines => &["Off", "On Surface", "Shells in Space"],
+            Param::Slice => &["Off", "Inset", "Fullscreen"],
+            Param::SliceAxis => &["XY", "XZ", "YZ"],
+            Param::RimDirection => &["Camera", "Light"],
+            Param::TestPattern => &["Off", "Color Bars", "Gradient Ramps", "Resolution Chart"],
+            _ => &[],
+        }
+    }
+
+    /// Range offered by the UI
+    pub fn range(self) -> RangeInclusive<f32> {
+        match self {
+            Param::Power => -2.0..=16.0,
+            Param::Iterations => 1.0..=50.0,
+            Param::RaySteps => 10.0..=300.0,
+            Param::HitThreshold => 0.0001..=0.01,
+            Param::MaxDist => 10.0..=100.0,
+            Param::StallLimit => 0.0..=64.0,
+            Param::Zoom => 0.1..=10.0,
+            Param::FocalLength => 0.3..=6.0,
+            Param::PixelAspect => 0.5..=2.0,
+            Param::BackgroundGlow => 0.0..=5.0,
+            Param::ColorScale => 0.1..=3.0,
+            Param::ColorOffset => 0.0..=1.0,
+            Param::LightX | Param::LightY => -10.0..=10.0,
+            Param::LightRed
+            | Param::LightGreen
+            | Param::LightBlue
+            | Param::AmbientRed
+            | Param::AmbientGreen
+            | Param::AmbientBlue
+            | Param::RimRed
+            | Param::RimGreen
+            | Param::RimBlue
+            | Param::FlareRed
+            | Param::FlareGreen
+            | Param::FlareBlue => 0.0..=1.0,
+            Param::AoStrength => 0.0..=5.0,
+            Param::RimStrength | Param::Specular => 0.0..=2.0,
+            Param::Shininess => 1.0..=256.0,
+            Param::LensStrength => 0.0..=1.0,
+            Param::LensDistortion => -0.5..=0.5,
+            Param::ChromaticAberration => 0.0..=0.05,
+            Param::Grain => 0.0..=0.5,
+            Param::BreathAmplitude => 0.0..=0.05,
+            Param::BreathSpeed => 0.01..=4.0,
+            Param::FlareIntensity | Param::FlareStarburst => 0.0..=2.0,
+            Param::FlareThreshold => 0.0..=4.0,
+            Param::FlareLength => 0.05..=1.0,
+            Param::FogDensity => 0.0..=1.0,
+            Param::StreamlineDensity => 10.0..=500.0,
+            Param::StreamlineLength => 0.001..=0.1,
+            Param::IsolineSpacing => 0.001..=0.2,
+            Param::IsolineIntensity => 0.0..=1.0,
+            Param::SliceOffset => -2.0..=2.0,
+            Param::SliceExtent => 0.05..=4.0,
+            Param::JuliaX | Param::JuliaY | Param::JuliaZ => -2.0..=2.0,
+            Param::PaperWhite => 80.0..=400.0,
+            Param::Palette => 0.0..=(palettes().len() - 1) as f32,
+            _ => 0.0..=(self.choices().len().max(2) - 1) as f32,
+        }
+    }
+
+    /// Slider increment, `None` for continuous
+    #[cfg(feature = "ui")]
+    pub fn step(self) -> Option<f64> {
+        match self {
+            Param::ColorScale
+            | Param::AoStrength
+            | Param::RimStrength
+            | Param::Specular
+            | Param::FogDensity
+            | Param::FlareIntensity
+            | Param::FlareThreshold
+            | Param::FlareLength
+            | Param::FlareStarburst => Some(0.01),
+            Param::LensStrength | Param::LensDistortion | Param::Grain => Some(0.005),
+            Param::ChromaticAberration | Param::BreathAmplitude => Some(0.001),
+            Param::ColorOffset | Param::JuliaX | Param::JuliaY | Param::JuliaZ => Some(0.005),
+            _ => None,
+        }
+    }
+
+    /// What the value measures, deciding the unit the UI shows it in. Formula parameters are
+    /// described by their plugin.
+    #[cfg(feature = "ui")]
+    pub fn quantity(self) -> Quantity {
+        match self {
+            Param::HitThreshold
+            | Param::MaxDist
+            | Param::LightX
+            | Param::LightY
+            | Param::StreamlineLength
+            | Param::IsolineSpacing
+            | Param::SliceOffset
+            | Param::SliceExtent
+            | Param::BreathAmplitude => Quantity::Distance,
+            Param::ColorOffset | Param::IsolineIntensity => Quantity::Fraction,
+            _ => Quantity::Plain,
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn logarithmic(self) -> bool {
+        matches!(
+            self,
+            Param::HitThreshold
+                | Param::StreamlineDensity
+                | Param::StreamlineLength
+                | Param::IsolineSpacing
+                | Param::SliceExtent
+                | Param::Shininess
+                | Param::BreathSpeed
+        )
+    }
+
+    pub fn get(self, m: &MandelbulbMaterial) -> f32 {
+        match self {
+            Param::Formula => Formula::all().position(|f| f == m.formula).unwrap_or(0) as f32,
+            Param::Power => m.shape.power,
+            Param::Iterations => m.shape.mandel_iters as f32,
+            Param::RaySteps => m.shape.ray_steps as f32,
+            Param::HitThreshold => m.shape.hit_threshold,
+            Param::MaxDist => m.shape.max_dist,
+            Param::StallLimit => m.shape.stall_limit as f32,
+            Param::ShowStalled => m.shading.show_stalled as f32,
+            Param::Zoom => m.camera.zoom,
+            Param::FocalLength => m.camera.focal_length,
+            Param::FovFit => m.camera.fov_fit as f32,
+            Param::PixelAspect => m.camera.pixel_aspect,
+            Param::BackgroundGlow => m.shading.background_glow_intensity,
+            Param::Palette => m.shading.palette_id as f32,
+            Param::Coloring => ColoringMode::ALL
+                .iter()
+                .position(|c| *c == m.coloring)
+                .unwrap() as f32,
+            Param::ColorScale => m.shading.color_scale,
+            Param::ColorOffset => m.shading.color_offset,
+            Param::LightX => m.shading.light_pos_x,
+            Param::LightY => m.shading.light_pos_y,
+            Param::LightRed => m.shading.light_color.x,
+            Param::LightGreen => m.shading.light_color.y,
+            Param::LightBlue => m.shading.light_color.z,
+            Param::AmbientRed => m.shading.ambient_color.x,
+            Param::AmbientGreen => m.shading.ambient_color.y,
+            Param::AmbientBlue => m.shading.ambient_color.z,
+            Param::Specular => m.shading.specular_intensity,
+            Param::Shininess => m.shading.shininess,
+            Param::SoftShadows => m.soft_shadows as u32 as f32,
+            Param::GeometryGraph => m.geometry_graph as u32 as f32,
+            Param::CustomShading => m.custom_shading as u32 as f32,
+            Param::AoStrength => m.shading.ao_strength,
+            Param::RimStrength => m.shading.rim_strength,
+            Param::RimRed => m.shading.rim_color.x,
+            Param::RimGreen => m.shading.rim_color.y,
+            Param::RimBlue => m.shading.rim_color.z,
+            Param::RimDirection => m.shading.rim_direction as f32,
+            Param::LensStrength => m.shading.lens_strength,
+            Param::LensDistortion => m.shading.lens_distortion,
+            Param::ChromaticAberration => m.shading.chromatic_aberration,
+            Param::Grain => m.shading.grain,
+            Param::BreathAmplitude => m.shape.breath_amplitude,
+            Param::BreathSpeed => m.shape.breath_speed,
+            Param::FlareIntensity => m.flare.intensity,
+            Param::FlareThreshold => m.flare.threshold,
+            Param::FlareLength => m.flare.length,
+            Param::FlareStarburst => m.flare.starburst,
+            Param::FlareRed => m.flare.color.x,
+            Param::FlareGreen => m.flare.color.y,
+            Param::FlareBlue => m.flare.color.z,
+            Param::FogDensity => m.shading.fog_density,
+            Param::Streamlines => m.streamlines as u32 as f32,
+            Param::StreamlineDensity => m.shading.streamline_density,
+            Param::StreamlineLength => m.shading.streamline_length,
+            Param::Isolines => m.shading.isoline_mode as f32,
+            Param::IsolineSpacing => m.shading.isoline_spacing,
+            Param::IsolineIntensity => m.shading.isoline_intensity,
+            Param::Slice => m.shading.slice_mode as f32,
+            Param::SliceAxis => m.shading.slice_axis as f32,
+            Param::SliceOffset => m.shading.slice_offset,
+            Param::SliceExtent => m.shading.slice_extent,
+            Param::JuliaEnabled => m.shape.julia.w,
+            Param::JuliaX => m.shape.julia.x,
+            Param::JuliaY => m.shape.julia.y,
+            Param::JuliaZ => m.shape.julia.z,
+            Param::Supersampling => m.shading.supersampling_enabled as f32,
+            Param::BrickMap => m.brick_map as u32 as f32,
+            Param::Reprojection => m.reprojection as u32 as f32,
+            Param::OutputMode => m.shading.output_mode as f32,
+            Param::PaperWhite => m.shading.paper_white_nits,
+            Param::TestPattern => m.shading.test_pattern as f32,
+            Param::PluginSlot(i) => m.shape.plugin_params[i as usize / 4][i as usize % 4],
+        }
+    }
+
+    pub fn set(self, m: &mut MandelbulbMaterial, value: f32) {
+        let index = value.round().max(0.0) as usize;
+        let flag = value > 0.5;
+        match self {
+            Param::Formula => m.formula = Formula::all().nth(index).unwrap_or_default(),
+            Param::Power => m.shape.power = value,
+            Param::Iterations => m.shape.mandel_iters = index as u32,
+            Param::RaySteps => m.shape.ray_steps = index as u32,
+            Param::HitThreshold => m.shape.hit_threshold = value,
+            Param::MaxDist => m.shape.max_dist = value,
+            Param::StallLimit => m.shape.stall_limit = index as u32,
+            Param::ShowStalled => m.shading.show_stalled = flag as u32,
+            Param::Zoom => m.camera.zoom = value,
+            Param::FocalLength => m.camera.focal_length = value,
+            Param::FovFit => m.camera.fov_fit = index as u32,
+            Param::PixelAspect => m.camera.pixel_aspect = value,
+            Param::BackgroundGlow => m.shading.background_glow_intensity = value,
+            Param::Palette => {
+                m.shading.palette_id = index as u32;
+                m.shading.palette = palette(index as u32).uniform();
+            }
+            Param::Coloring => {
+                m.coloring = ColoringMode::ALL[index.min(ColoringMode::ALL.len() - 1)]
+            }
+            Param::ColorScale => m.shading.color_scale = value,
+            Param::ColorOffset => m.shading.color_offset = value,
+            Param::LightX => m.shading.light_pos_x = value,
+            Param::LightY => m.shading.light_pos_y = value,
+            Param::LightRed => m.shading.light_color.x = value,
+            Param::LightGreen => m.shading.light_color.y = value,
+            Param::LightBlue => m.shading.light_color.z = value,
+            Param::AmbientRed => m.shading.ambient_color.x = value,
+            Param::AmbientGreen => m.shading.ambient_color.y = value,
+            Param::AmbientBlue => m.shading.ambient_color.z = value,
+            Param::Specular => m.shading.specular_intensity = value,
+            Param::Shininess => m.shading.shininess = value,
+            Param::SoftShadows => m.soft_shadows = flag,
+            Param::GeometryGraph => m.geometry_graph = flag,
+            Param::CustomShading => m.custom_shading = flag,
+            Param::AoStrength => m.shading.ao_strength = value,
+            Param::RimStrength => m.shading.rim_strength = value,
+            Param::RimRed => m.shading.rim_color.x = value,
+            Param::RimGreen => m.shading.rim_color.y = value,
+            Param::RimBlue => m.shading.rim_color.z = value,
+            Param::RimDirection => m.shading.rim_direction = index as u32,
+            Param::LensStrength => m.shading.lens_strength = value,
+            Param::LensDistortion => m.shading.lens_distortion = value,
+            Param::ChromaticAberration => m.shading.chromatic_aberration = value,
+            Param::Grain => m.shading.grain = value,
+            Param::BreathAmplitude => m.shape.breath_amplitude = value,
+            Param::BreathSpeed => m.shape.breath_speed = value,
+            Param::FlareIntensity => m.flare.intensity = value,
+            Param::FlareThreshold => m.flare.threshold = value,
+            Param::FlareLength => m.flare.length = value,
+            Param::FlareStarburst => m.flare.starburst = value,
+            Param::FlareRed => m.flare.color.x = value,
+            Param::FlareGreen => m.flare.color.y = value,
+            Param::FlareBlue => m.flare.color.z = value,
+            Param::FogDensity => m.shading.fog_density = value,
+            Param::Streamlines => m.streamlines = flag,
+            Param::StreamlineDensity => m.shading.streamline_density = value,
+            Param::StreamlineLength => m.shading.streamline_length = value,
+            Param::Isolines => m.shading.isoline_mode = index as u32,
+            Param::IsolineSpacing => m.shading.isoline_spacing = value,
+            Param::IsolineIntensity => m.shading.isoline_intensity = value,
+            Param::Slice => m.shading.slice_mode = index as u32,
+            Param::SliceAxis => m.shading.slice_axis = index as u32,
+            Param::SliceOffset => m.shading.slice_offset = value,
+            Param::SliceExtent => m.shading.slice_extent = value,
+            Param::JuliaEnabled => m.shape.julia.w = flag as u32 as f32,
+            Param::JuliaX => m.shape.julia.x = value,
+            Param::JuliaY => m.shape.julia.y = value,
+            Param::JuliaZ => m.shape.julia.z = value,
+            Param::Supersampling => m.shading.supersampling_enabled = flag as u32,
+            Param::BrickMap => m.brick_map = flag,
+            Param::Reprojection => m.reprojection = flag,
+            Param::OutputMode => m.shading.output_mode = index as u32,
+            Param::PaperWhite => m.shading.paper_white_nits = value,
+            Param::TestPattern => m.shading.test_pattern = index as u32,
+            Param::PluginSlot(i) => m.shape.plugin_params[i as usize / 4][i as usize % 4] = value,
+        }
+    }
+}
+
+/// Smallest change worth sending. Anything that drives a parameter every frame, like a timeline
+/// track or a binding, skips smaller ones so holding still doesn't touch the material.
+pub const EPSILON: f32 = 1e-6;
+
+/// A parameter was set to a new value. Sent by anything that edits parameters and read by
+/// anything that needs to react to edits.
+#[derive(Message, Clone, Copy, Debug)]
+pub struct ParamChanged {
+    pub param: Param,
+    pub value: f32,
+}
+
+/// The current value of every [`Param`]
+#[derive(Resource, Clone, Debug)]
+pub struct ParamStore {
+    values: HashMap<Param, f32>,
+}
+
+impl ParamStore {
+    pub fn capture(material: &MandelbulbMaterial) -> Self {
+        Self {
+            values: Param::ALL
+                .iter()
+                .map(|param| (*param, param.get(material)))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, param: Param) -> f32 {
+        self.values.get(&param).copied().unwrap_or_default()
+    }
+
+    #[cfg(feature = "ui")]
+    pub fn flag(&self, param: Param) -> bool {
+        self.get(param) > 0.5
+    }
+
+    /// Writes the stored values into a copy of the main material, which may hold the reduced
+    /// values of low power mode rather than the ones the user set
+    #[cfg(feature = "ui")]
+    pub fn restore(&self, material: &mut MandelbulbMaterial) {
+        for param in Param::ALL {
+            param.set(material, self.get(param));
+        }
+    }
+}
+
+impl ParamChanged {
+    /// Every parameter of `material`, for moving the main view to a whole other state. The
+    /// formula comes first, so its defaults don't override the rest.
+    pub fn all(material: &MandelbulbMaterial) -> impl Iterator<Item = ParamChanged> + '_ {
+        Param::ALL.into_iter().map(move |param| ParamChanged {
+            param,
+            value: param.get(material),
+        })
+    }
+}
+
+/// Applies parameter changes to the store and the main material
+pub fn apply_param_changes(
+    mut changes: MessageReader<ParamChanged>,
+    mut store: ResMut<ParamStore>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    mut validation: ResMut<Validation>,
+) {
+    if changes.is_empty() {
+        return;
+    }
+    let Some(material) = materials.get_mut(&fractal.0) else {
+        return;
+    };
+    for change in changes.read() {
+        let label = change.param.label();
+        let value = match validation::clamp(change.param, change.value) {
+            Some((value, None)) => value,
+            Some((value, Some(reason))) => {
+                validation.note(format!(
+                    "{label} raised from {} to {value}: {reason}",
+                    change.value
+                ));
+                value
+            }
+            None => {
+                validation.note(format!("{label} ignored a value that is not a number"));
+                continue;
+            }
+        };
+        let formula = material.formula;
+        store.values.insert(change.param, value);
+        change.param.set(material, value);
+
+        // a new formula starts from its own defaults and camera distance rather than values
+        // tuned for the previous one. Changes after it in the same batch, like the rest of a
+        // share code, still apply on top.
+        if change.param == Param::Formula && material.formula != formula {
+            let zoom = ParamChanged {
+                param: Param::Zoom,
+                value: default_zoom(material.formula),
+            };
+            for default in shape_defaults(material.formula).into_iter().chain([zoom]) {
+                store.values.insert(default.param, default.value);
+                default.param.set(material, default.value);
+            }
+        }
+    }
+}
+
+/// Shape parameters a formula looks right with: power, iterations, no Julia folding and a
+/// plugin's declared parameter defaults. What "Reset Shape" applies, leaving camera and
+/// lighting alone.
+pub fn shape_defaults(formula: Formula) -> Vec<ParamChanged> {
+    let shape = ShapeUniform::default();
+    let (power, iterations) = match formula {
+        Formula::Mandelbulb => (shape.power, shape.mandel_iters),
+        // the "amazing box", a negative scale keeps it compact and detailed
+        Formula::Mandelbox => (-1.5, 15),
+        Formula::Plugin(index) => (
+            shape.power,
+            formulas::plugins()
+                .get(index as usize)
+                .and_then(|plugin| plugin.iterations)
+                .unwrap_or(shape.mandel_iters),
+        ),
+    };
+    let mut defaults = vec![
+        (Param::Power, power),
+        (Param::Iterations, iterations as f32),
+        (Param::JuliaEnabled, 0.0),
+        (Param::JuliaX, shape.julia.x),
+        (Param::JuliaY, shape.julia.y),
+        (Param::JuliaZ, shape.julia.z),
+    ];
+    if let Formula::Plugin(index) = formula
+        && let Some(plugin) = formulas::plugins().get(index as usize)
+    {
+        defaults.extend(
+            plugin
+                .params
+                .iter()
+                .enumerate()
+                .map(|(i, param)| (Param::PluginSlot(i as u8), param.default)),
+        );
+    }
+    defaults
+        .into_iter()
+        .map(|(param, value)| ParamChanged { param, value })
+        .collect()
+}
+
+/// Camera distance that fits the formula's shape in frame
+fn default_zoom(formula: Formula) -> f32 {
+    let zoom = CameraUniform::default().zoom;
+    match formula {
+        Formula::Mandelbulb => zoom,
+        Formula::Mandelbox => 6.0,
+        Formula::Plugin(index) => formulas::plugins()
+            .get(index as usize)
+            .and_then(|plugin| plugin.zoom)
+            .unwrap_or(zoom),
+    }
+}
@@ -0,0 +1,454 @@
+use crate::FractalQuad;
+use crate::fractal_compute::{
+    EstimatorKey, FractalBindGroups, FractalComputeLayouts, FractalUniforms,
+};
+use crate::material::{CameraUniform, FractalMaterial, MandelbulbMaterial};
+use crate::power::keep_awake;
+use crate::rng::Rng;
+use bevy::asset::RenderAssetUsages;
+use bevy::camera::visibility::NoFrustumCulling;
+use bevy::mesh::{MeshVertexBufferLayoutRef, PrimitiveTopology};
+use bevy::prelude::*;
+use bevy::render::extract_resource::{ExtractResource, ExtractResourcePlugin};
+use bevy::render::graph::CameraDriverLabel;
+use bevy::render::render_asset::RenderAssets;
+use bevy::render::render_graph::{self, RenderGraph, RenderLabel};
+use bevy::render::render_resource::binding_types::{storage_buffer_sized, uniform_buffer};
+use bevy::render::render_resource::{
+    AsBindGroup, BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries,
+    BlendComponent, BlendFactor, BlendOperation, BlendState, CachedComputePipelineId,
+    ComputePassDescriptor, ComputePipelineDescriptor, PipelineCache, RenderPipelineDescriptor,
+    ShaderStages, ShaderType, SpecializedComputePipeline, SpecializedComputePipelines,
+    SpecializedMeshPipelineError, UniformBuffer,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice, RenderQueue};
+use bevy::render::storage::{GpuShaderStorageBuffer, ShaderStorageBuffer};
+use bevy::render::{Render, RenderApp, RenderStartup, RenderSystems};
+use bevy::shader::ShaderRef;
+use bevy::sprite_render::{AlphaMode2d, Material2d, Material2dKey, Material2dPlugin};
+
+/// Bytes per particle in the storage buffer, see `Particle` in `shaders/mandelbulb.wgsl`
+const PARTICLE_SIZE: usize = 32;
+
+/// Must match `@workgroup_size` of `update_particles`
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Sparks and dust seeded on the fractal surface. Particles are simulated by a compute pass that
+/// evaluates the same distance estimator as the material, and drawn additively over the render.
+pub struct ParticlesPlugin;
+
+impl Plugin for ParticlesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Particles>()
+            .init_resource::<ParticleFrame>()
+            .insert_resource(ParticleRng(Rng::stream("particles")))
+            .add_plugins((
+                ExtractResourcePlugin::<ParticleFrame>::default(),
+                Material2dPlugin::<ParticleMaterial>::default(),
+            ))
+            .add_systems(
+                Update,
+                (
+                    sync_particles,
+                    keep_awake.run_if(|particles: Res<Particles>| particles.enabled),
+                ),
+            );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<SpecializedComputePipelines<ParticlePipeline>>()
+            .add_systems(RenderStartup, init_particle_pipeline)
+            .add_systems(
+                Render,
+                prepare_particles.in_set(RenderSystems::PrepareBindGroups),
+            );
+        let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
+        graph.add_node(ParticleLabel, ParticleNode);
+        graph.add_node_edge(ParticleLabel, CameraDriverLabel);
+    }
+}
+
+/// How particles move once spawned
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParticleMotion {
+    /// Away from the surface along the distance estimator's gradient
+    Gradient,
+    /// Swirling through a curl-noise field
+    #[cfg(feature = "ui")]
+    CurlNoise,
+}
+
+impl ParticleMotion {
+    #[cfg(feature = "ui")]
+    pub const ALL: [ParticleMotion; 2] = [ParticleMotion::Gradient, ParticleMotion::CurlNoise];
+
+    #[cfg(feature = "ui")]
+    pub fn label(self) -> &'static str {
+        match self {
+            ParticleMotion::Gradient => "Gradient",
+            ParticleMotion::CurlNoise => "Curl Noise",
+        }
+    }
+}
+
+/// User settings of the particle system
+#[derive(Resource)]
+pub struct Particles {
+    pub enabled: bool,
+    pub count: u32,
+    pub motion: ParticleMotion,
+    /// World units per second
+    pub speed: f32,
+    /// Longest life of a particle in seconds, each one lives between half of this and all of it
+    pub lifetime: f32,
+    /// Radius around the origin in which spawn points are sampled before being pulled onto the
+    /// surface
+    pub spawn_radius: f32,
+    /// Sprite size in pixels at distance 1 from the camera
+    pub size: f32,
+    pub color: [f32; 3],
+    pub intensity: f32,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            count: 16384,
+            motion: ParticleMotion::Gradient,
+            speed: 0.1,
+            lifetime: 3.0,
+            spawn_radius: 1.5,
+            size: 4.0,
+            color: [1.0, 0.7, 0.3],
+            intensity: 1.0,
+        }
+    }
+}
+
+/// Where particles spawn, one stream for the whole run so a `--seed` repeats the same sequence
+#[derive(Resource, Deref, DerefMut)]
+struct ParticleRng(Rng);
+
+/// Mirrors `ParticleSettings` in `shaders/mandelbulb.wgsl`
+#[derive(ShaderType, Clone, Copy, Default, Debug)]
+struct ParticleSettingsUniform {
+    count: u32,
+    motion: u32, // 0=along the DE gradient, 1=curl noise
+    speed: f32,
+    lifetime: f32,
+    spawn_radius: f32,
+    delta_time: f32,
+    /// Offsets where particles spawn, drawn from the `particles` stream every frame
+    seed: f32,
+}
+
+/// Mirrors `ParticleLook` in `shaders/particles.wgsl`
+#[derive(ShaderType, Clone, Copy, PartialEq, Default, Debug)]
+struct ParticleLook {
+    color: Vec4, // rgb and intensity
+    size: f32,   // pixels at distance 1
+    lifetime: f32,
+    _padding0: f32,
+    _padding1: f32,
+}
+
+/// Draws the particle buffer as one quad per particle
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+struct ParticleMaterial {
+    #[storage(0, read_only, visibility(vertex))]
+    particles: Handle<ShaderStorageBuffer>,
+    #[uniform(1)]
+    camera: CameraUniform,
+    #[uniform(2)]
+    look: ParticleLook,
+}
+
+impl Material2d for ParticleMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/particles.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/particles.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode2d {
+        AlphaMode2d::Blend
+    }
+
+    fn specialize(
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: Material2dKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // additive, so overlapping sparks glow
+        if let Some(target) = descriptor
+            .fragment
+            .as_mut()
+            .and_then(|fragment| fragment.targets[0].as_mut())
+        {
+            target.blend = Some(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// The entity drawing the particles, with the count its buffers were sized for
+#[derive(Component)]
+struct ParticleLayer {
+    count: u32,
+}
+
+/// What the compute pass needs from the main world besides the fractal, copied each frame
+#[derive(Resource, Clone, Default, ExtractResource)]
+struct ParticleFrame {
+    enabled: bool,
+    buffer: Handle<ShaderStorageBuffer>,
+    settings: ParticleSettingsUniform,
+}
+
+/// Spawns, respawns and removes the particle layer to match the settings, and hands the settings
+/// to the compute pass
+fn sync_particles(
+    mut commands: Commands,
+    time: Res<Time>,
+    particles: Res<Particles>,
+    mut frame: ResMut<ParticleFrame>,
+    mut rng: ResMut<ParticleRng>,
+    fractal: Res<FractalMaterial>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    mut particle_materials: ResMut<Assets<ParticleMaterial>>,
+    mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    layers: Query<(Entity, &ParticleLayer, &MeshMaterial2d<ParticleMaterial>)>,
+    quads: Query<&Transform, (With<FractalQuad>, Without<ParticleLayer>)>,
+) {
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let wanted = particles.enabled.then_some(particles.count);
+
+    let mut layer = None;
+    for (entity, existing, material) in &layers {
+        if Some(existing.count) == wanted {
+            layer = Some(material.0.clone());
+        } else {
+            commands.entity(entity).despawn();
+        }
+    }
+    if layer.is_none()
+        && let Some(count) = wanted
+    {
+        // zeroed, so every particle starts dead and spawns on the first dispatch
+        let buffer = buffers.add(ShaderStorageBuffer::with_size(
+            count as usize * PARTICLE_SIZE,
+            RenderAssetUsages::RENDER_WORLD,
+        ));
+        let material = particle_materials.add(ParticleMaterial {
+            particles: buffer.clone(),
+            camera: mat.camera,
+            look: ParticleLook::default(),
+        });
+        let scale = quads.iter().next().map_or(Vec3::ONE, |t| t.scale);
+        commands.spawn((
+            Mesh2d(meshes.add(particle_mesh(count))),
+            MeshMaterial2d(material.clone()),
+            // in front of the fractal, behind crossfade overlays; scaled with the quad
+            Transform::from_xyz(0.0, 0.0, 0.5).with_scale(scale),
+            FractalQuad,
+            ParticleLayer { count },
+            NoFrustumCulling,
+        ));
+        layer = Some(material);
+    }
+
+    let Some(layer) = layer else {
+        frame.enabled = false;
+        return;
+    };
+    let look = ParticleLook {
+        color: Vec3::from(particles.color).extend(particles.intensity),
+        size: particles.size,
+        lifetime: particles.lifetime,
+        ..default()
+    };
+    let Some(material) = particle_materials.get(&layer) else {
+        return;
+    };
+    let buffer = material.particles.clone();
+    let stale = material.camera != mat.camera || material.look != look;
+    if stale && let Some(material) = particle_materials.get_mut(&layer) {
+        material.camera = mat.camera;
+        material.look = look;
+    }
+
+    *frame = ParticleFrame {
+        enabled: true,
+        buffer,
+        settings: ParticleSettingsUniform {
+            count: particles.count,
+            motion: particles.motion as u32,
+            speed: particles.speed,
+            lifetime: particles.lifetime,
+            spawn_radius: particles.spawn_radius,
+            delta_time: time.delta_secs(),
+            seed: rng.next_f32() * 1000.0,
+        },
+    };
+}
+
+/// Six vertices per particle, each holding its quad corner in xy and its particle index in z
+fn particle_mesh(count: u32) -> Mesh {
+    const CORNERS: [[f32; 2]; 6] = [
+        [-1.0, -1.0],
+        [1.0, -1.0],
+        [1.0, 1.0],
+        [-1.0, -1.0],
+        [1.0, 1.0],
+        [-1.0, 1.0],
+    ];
+    let positions: Vec<[f32; 3]> = (0..count)
+        .flat_map(|i| CORNERS.map(|[x, y]| [x, y, i as f32]))
+        .collect();
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+}
+
+#[derive(Resource)]
+struct ParticlePipeline {
+    fractal: FractalComputeLayouts,
+    layout: BindGroupLayout,
+}
+
+impl SpecializedComputePipeline for ParticlePipeline {
+    type Key = EstimatorKey;
+
+    fn specialize(&self, key: Self::Key) -> ComputePipelineDescriptor {
+        self.fractal
+            .descriptor(key, "PARTICLE_COMPUTE", "update_particles", &self.layout)
+    }
+}
+
+fn init_particle_pipeline(
+    mut commands: Commands,
+    render_device: Res<RenderDevice>,
+    fractal: Res<FractalComputeLayouts>,
+) {
+    let layout = render_device.create_bind_group_layout(
+        "particle_layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::COMPUTE,
+            (
+                storage_buffer_sized(false, None),
+                uniform_buffer::<ParticleSettingsUniform>(false),
+            ),
+        ),
+    );
+    commands.insert_resource(ParticlePipeline {
+        fractal: fractal.clone(),
+        layout,
+    });
+    commands.insert_resource(ParticleSettingsBuffer::default());
+}
+
+#[derive(Resource, Default)]
+struct ParticleSettingsBuffer(UniformBuffer<ParticleSettingsUniform>);
+
+/// A dispatch ready for the render graph node, absent while particles are off
+#[derive(Resource)]
+struct ParticleDispatch {
+    pipeline: CachedComputePipelineId,
+    bind_group: BindGroup,
+    count: u32,
+}
+
+fn prepare_particles(
+    mut commands: Commands,
+    frame: Res<ParticleFrame>,
+    fractal: Res<FractalUniforms>,
+    pipeline: Res<ParticlePipeline>,
+    mut pipelines: ResMut<SpecializedComputePipelines<ParticlePipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    mut settings: ResMut<ParticleSettingsBuffer>,
+    gpu_buffers: Res<RenderAssets<GpuShaderStorageBuffer>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let storage = gpu_buffers.get(&frame.buffer);
+    let (true, Some(storage)) = (frame.enabled, storage) else {
+        commands.remove_resource::<ParticleDispatch>();
+        return;
+    };
+
+    settings.0.set(frame.settings);
+    settings.0.write_buffer(&render_device, &render_queue);
+    let Some(settings) = settings.0.binding() else {
+        return;
+    };
+    let bind_group = render_device.create_bind_group(
+        "particle_bind_group",
+        &pipeline.layout,
+        &BindGroupEntries::sequential((storage.buffer.as_entire_buffer_binding(), settings)),
+    );
+
+    commands.insert_resource(ParticleDispatch {
+        pipeline: pipelines.specialize(&pipeline_cache, &pipeline, fractal.key),
+        bind_group,
+        count: frame.settings.count,
+    });
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct ParticleLabel;
+
+/// Advances the simulation once per frame, before any camera draws the particles
+struct ParticleNode;
+
+impl render_graph::Node for ParticleNode {
+    fn run(
+        &self,
+        _graph: &mut render_graph::RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), render_graph::NodeRunError> {
+        let (Some(dispatch), Some(fractal)) = (
+            world.get_resource::<ParticleDispatch>(),
+            world.get_resource::<FractalBindGroups>(),
+        ) else {
+            return Ok(());
+        };
+        // still compiling, particles start moving once it is ready
+        let Some(pipeline) = world
+            .resource::<PipelineCache>()
+            .get_compute_pipeline(dispatch.pipeline)
+        else {
+            return Ok(());
+        };
+
+        let mut pass =
+            render_context
+                .command_encoder()
+                .begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("particles"),
+                    ..default()
+                });
+        pass.set_pipeline(pipeline);
+        fractal.set(&mut pass);
+        pass.set_bind_group(3, &dispatch.bind_group, &[]);
+        pass.dispatch_workgroups(dispatch.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        Ok(())
+    }
+}
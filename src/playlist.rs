@@ -0,0 +1,503 @@
+use crate::crossfade::Crossfade;
+use crate::easing::Easing;
+#[cfg(feature = "ui")]
+use crate::easing::easing_combo;
+#[cfg(feature = "ui")]
+use crate::material::FractalMaterial;
+use crate::material::{MandelbulbMaterial, aim_all_views, move_all_views};
+#[cfg(feature = "ui")]
+use crate::navigation::View;
+#[cfg(feature = "ui")]
+use crate::params::ParamStore;
+use crate::params::{Param, ParamChanged, ParamKind, apply_param_changes};
+use crate::power::keep_awake;
+#[cfg(feature = "ui")]
+use crate::preview::smart_thumbnail;
+use crate::validation;
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on, poll_once};
+use bevy::window::PrimaryWindow;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use serde::{Deserialize, Serialize};
+
+/// How long before a crossfade starts the next entry begins rendering offscreen. This gives its
+/// pipeline time to be specialized and its first frames to settle, so the fade itself never
+/// shows a blank or stalled frame.
+const PREROLL_SECS: f32 = 0.75;
+
+/// File the editor saves to and loads from until another is entered, relative to the working
+/// directory like the screenshots folder
+#[cfg(feature = "ui")]
+const DEFAULT_PLAYLIST_FILE: &str = "playlist.ron";
+
+/// Width of the entry thumbnails in the editor, they are 4:3
+#[cfg(feature = "ui")]
+const THUMBNAIL_WIDTH: u32 = 64;
+
+pub struct PlaylistPlugin;
+
+impl Plugin for PlaylistPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Playlist>().add_systems(
+            Update,
+            (
+                // the main view takes over an entry in the frame its preroll goes away
+                advance_playlist.before(apply_param_changes),
+                keep_awake.run_if(|playlist: Res<Playlist>| playlist.playing),
+            ),
+        );
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, playlist_window);
+    }
+}
+
+/// How the playlist goes from an entry to the next one
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum TransitionKind {
+    /// Switches at once, the transition length is ignored
+    Cut,
+    /// Blends the two rendered images
+    #[default]
+    Crossfade,
+    /// Interpolates the camera and the continuous parameters, so the shape itself changes.
+    /// Choices such as the formula switch at the end.
+    Morph,
+}
+
+#[cfg(feature = "ui")]
+impl TransitionKind {
+    const ALL: [TransitionKind; 3] = [
+        TransitionKind::Cut,
+        TransitionKind::Crossfade,
+        TransitionKind::Morph,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            TransitionKind::Cut => "Cut",
+            TransitionKind::Crossfade => "Crossfade",
+            TransitionKind::Morph => "Morph",
+        }
+    }
+}
+
+pub struct PlaylistEntry {
+    #[cfg(feature = "ui")]
+    pub name: String,
+    pub material: MandelbulbMaterial,
+    /// Seconds the entry is shown before the transition to the next one starts
+    pub hold_secs: f32,
+    pub transition: TransitionKind,
+    /// Length of the transition into the next entry
+    pub transition_secs: f32,
+    /// Pace of the crossfade or morph into the next entry
+    pub easing: Easing,
+    #[cfg(feature = "ui")]
+    thumbnail: Thumbnail,
+}
+
+/// The entry's look in the editor, rendered in the background with a framing and exposure of
+/// its own so thumbnails are comparable however the view was captured
+#[cfg(feature = "ui")]
+enum Thumbnail {
+    Rendering(Task<Option<egui::ColorImage>>),
+    Ready(egui::TextureHandle),
+    Unavailable,
+}
+
+impl PlaylistEntry {
+    #[cfg(feature = "ui")]
+    pub fn new(name: String, material: MandelbulbMaterial) -> Self {
+        #[cfg(feature = "ui")]
+        let framed = material.clone();
+        Self {
+            name,
+            material,
+            hold_secs: 10.0,
+            transition: TransitionKind::Crossfade,
+            transition_secs: 2.0,
+            easing: Easing::Smoothstep,
+            #[cfg(feature = "ui")]
+            thumbnail: Thumbnail::Rendering(
+                AsyncComputeTaskPool::get()
+                    .spawn(async move { smart_thumbnail(&framed, THUMBNAIL_WIDTH) }),
+            ),
+        }
+    }
+
+    /// Seconds from the end of the hold to the next entry taking over
+    fn transition_len(&self) -> f32 {
+        match self.transition {
+            TransitionKind::Cut => 0.0,
+            TransitionKind::Crossfade | TransitionKind::Morph => self.transition_secs,
+        }
+    }
+}
+
+/// An entry as saved, the view as a share code so the file stays readable and survives changes
+/// to the material's layout
+#[cfg(feature = "ui")]
+#[derive(Serialize, Deserialize)]
+struct SavedEntry {
+    name: String,
+    view: String,
+    hold_secs: f32,
+    transition: TransitionKind,
+    transition_secs: f32,
+    #[serde(default)]
+    easing: Easing,
+}
+
+#[derive(Resource)]
+pub struct Playlist {
+    #[cfg(feature = "ui")]
+    pub open: bool,
+    pub entries: Vec<PlaylistEntry>,
+    pub playing: bool,
+    pub current: usize,
+    /// Seconds spent on the current entry, including its outgoing transition
+    elapsed: f32,
+    /// The next entry rendering in the background, overlaid during a crossfade
+    preroll: Option<Crossfade>,
+    /// Path typed into the editor for saving and loading
+    #[cfg(feature = "ui")]
+    file: String,
+    #[cfg(feature = "ui")]
+    status: Option<Result<String, String>>,
+}
+
+impl Default for Playlist {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "ui")]
+            open: false,
+            entries: Vec::new(),
+            playing: false,
+            current: 0,
+            elapsed: 0.0,
+            preroll: None,
+            #[cfg(feature = "ui")]
+            file: DEFAULT_PLAYLIST_FILE.to_string(),
+            #[cfg(feature = "ui")]
+            status: None,
+        }
+    }
+}
+
+impl Playlist {
+    #[cfg(feature = "ui")]
+    pub fn play(&mut self) {
+        self.playing = true;
+        self.elapsed = 0.0;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    #[cfg(feature = "ui")]
+    fn save(&self) -> Result<(), String> {
+        let saved: Vec<SavedEntry> = self
+            .entries
+            .iter()
+            .map(|entry| SavedEntry {
+                name: entry.name.clone(),
+                view: View::share_code(
+                    &entry.material.camera,
+                    &ParamStore::capture(&entry.material),
+                ),
+                hold_secs: entry.hold_secs,
+                transition: entry.transition,
+                transition_secs: entry.transition_secs,
+                easing: entry.easing,
+            })
+            .collect();
+        let text = ron::ser::to_string_pretty(&saved, default()).map_err(|err| err.to_string())?;
+        std::fs::write(&self.file, text).map_err(|err| err.to_string())
+    }
+
+    /// Replaces the entries with the saved ones, each view applied on top of `base` for the
+    /// parts a share code doesn't hold
+    #[cfg(feature = "ui")]
+    fn load(&mut self, base: &MandelbulbMaterial) -> Result<(), String> {
+        let text = std::fs::read_to_string(&self.file).map_err(|err| err.to_string())?;
+        let saved: Vec<SavedEntry> = ron::from_str(&text).map_err(|err| err.to_string())?;
+        let entries = saved
+            .into_iter()
+            .map(|entry| {
+                let mut material = base.clone();
+                View::parse(&entry.view)
+                    .map_err(|err| format!("{}: {err}", entry.name))?
+                    .apply(&mut material);
+                Ok(PlaylistEntry {
+                    hold_secs: entry.hold_secs,
+                    transition: entry.transition,
+                    transition_secs: entry.transition_secs,
+                    easing: entry.easing,
+                    ..PlaylistEntry::new(entry.name, material)
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        self.stop();
+        self.entries = entries;
+        self.current = 0;
+        Ok(())
+    }
+}
+
+fn advance_playlist(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut playlist: ResMut<Playlist>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut sprites: Query<&mut Sprite>,
+    mut param_changes: MessageWriter<ParamChanged>,
+    window: Query<&Window, With<PrimaryWindow>>,
+) {
+    let playlist = &mut *playlist;
+    if !playlist.playing || playlist.entries.len() < 2 {
+        if let Some(preroll) = playlist.preroll.take() {
+            preroll.despawn(&mut commands);
+        }
+        return;
+    }
+
+    let Ok(win) = window.single() else {
+        return;
+    };
+    playlist.current = playlist.current.min(playlist.entries.len() - 1);
+    playlist.elapsed += time.delta_secs();
+
+    let entry = &playlist.entries[playlist.current];
+    let transition_start = entry.hold_secs;
+    let transition_end = entry.hold_secs + entry.transition_len();
+    let progress = entry
+        .easing
+        .apply((playlist.elapsed - transition_start) / entry.transition_len().max(f32::EPSILON));
+    let next = (playlist.current + 1) % playlist.entries.len();
+
+    if playlist.elapsed < transition_end {
+        match entry.transition {
+            TransitionKind::Crossfade => {
+                // start rendering the next entry offscreen ahead of the fade
+                if playlist.preroll.is_none() && playlist.elapsed >= transition_start - PREROLL_SECS
+                {
+                    playlist.preroll = Some(Crossfade::spawn(
+                        &mut commands,
+                        &mut materials,
+                        &mut meshes,
+                        &mut images,
+                        win,
+                        playlist.entries[next].material.clone(),
+                    ));
+                }
+                if let Some(preroll) = &playlist.preroll {
+                    preroll.set_alpha(&mut sprites, progress);
+                }
+            }
+            TransitionKind::Morph if playlist.elapsed >= transition_start => {
+                morph(
+                    &entry.material,
+                    &playlist.entries[next].material,
+                    progress,
+                    &mut materials,
+                    &mut param_changes,
+                );
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    // transition finished, the main quad takes over the new entry and the preroll is torn down
+    let entry = &playlist.entries[next].material;
+    aim_all_views(&mut materials, &entry.camera);
+    param_changes.write_batch(ParamChanged::all(entry));
+    playlist.current = next;
+    playlist.elapsed = 0.0;
+    if let Some(preroll) = playlist.preroll.take() {
+        preroll.despawn(&mut commands);
+    }
+}
+
+/// Moves the main view from `from` towards `to` by `t`. The camera and scalar parameters are
+/// interpolated, everything else stays at `from` until the next entry takes over. Easings that
+/// overshoot push `t` past 1, so parameters are kept to the values the renderer accepts.
+fn morph(
+    from: &MandelbulbMaterial,
+    to: &MandelbulbMaterial,
+    t: f32,
+    materials: &mut Assets<MandelbulbMaterial>,
+    param_changes: &mut MessageWriter<ParamChanged>,
+) {
+    let position = from.camera.position.lerp(to.camera.position, t);
+    let rotation = Quat::from_vec4(from.camera.rotation)
+        .normalize()
+        .slerp(Quat::from_vec4(to.camera.rotation).normalize(), t);
+    move_all_views(materials, |camera| {
+        camera.position = position;
+        camera.rotation = Vec4::from(rotation);
+    });
+    param_changes.write_batch(Param::ALL.into_iter().map(|param| {
+        let (a, b) = (param.get(from), param.get(to));
+        let value = if param.kind() == ParamKind::Scalar {
+            validation::clamp(param, a + (b - a) * t).map_or(b, |(value, _)| value)
+        } else {
+            a
+        };
+        ParamChanged { param, value }
+    }));
+}
+
+#[cfg(feature = "ui")]
+fn playlist_window(
+    mut contexts: EguiContexts,
+    mut playlist: ResMut<Playlist>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    let ctx = contexts.ctx_mut().unwrap();
+    for (index, entry) in playlist.entries.iter_mut().enumerate() {
+        if let Thumbnail::Rendering(task) = &mut entry.thumbnail
+            && let Some(image) = block_on(poll_once(task))
+        {
+            entry.thumbnail = match image {
+                Some(image) => Thumbnail::Ready(ctx.load_texture(
+                    format!("playlist_{index}"),
+                    image,
+                    egui::TextureOptions::LINEAR,
+                )),
+                None => Thumbnail::Unavailable,
+            };
+        }
+    }
+    if !playlist.open {
+        return;
+    }
+    let playlist = &mut *playlist;
+
+    let mut open = playlist.open;
+    let mut moved = None;
+    let mut removed = None;
+    egui::Window::new("Playlist")
+        .open(&mut open)
+        .default_width(540.0)
+        .show(ctx, |ui| {
+            // indices shift when entries move, so not while playing
+            let editable = !playlist.playing;
+            let count = playlist.entries.len();
+            for (index, entry) in playlist.entries.iter_mut().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.add_enabled_ui(editable, |ui| {
+                            if ui
+                                .add_enabled(index > 0, egui::Button::new("↑").small())
+                                .clicked()
+                            {
+                                moved = Some((index, index - 1));
+                            }
+                            if ui
+                                .add_enabled(index + 1 < count, egui::Button::new("↓").small())
+                                .clicked()
+                            {
+                                moved = Some((index, index + 1));
+                            }
+                        });
+                        let size =
+                            egui::vec2(THUMBNAIL_WIDTH as f32, THUMBNAIL_WIDTH as f32 * 0.75);
+                        match &entry.thumbnail {
+                            Thumbnail::Ready(texture) => {
+                                ui.add(egui::Image::new(texture).fit_to_exact_size(size));
+                            }
+                            Thumbnail::Rendering(_) => {
+                                ui.add_sized(size, egui::Spinner::new());
+                            }
+                            Thumbnail::Unavailable => {
+                                ui.add_sized(size, egui::Label::new("-"));
+                            }
+                        }
+                        ui.add(egui::TextEdit::singleline(&mut entry.name).desired_width(100.0));
+                        ui.add(
+                            egui::DragValue::new(&mut entry.hold_secs)
+                                .range(1.0..=600.0)
+                                .suffix("s hold"),
+                        );
+                        egui::ComboBox::from_id_salt("transition")
+                            .width(80.0)
+                            .selected_text(entry.transition.label())
+                            .show_ui(ui, |ui| {
+                                for kind in TransitionKind::ALL {
+                                    ui.selectable_value(&mut entry.transition, kind, kind.label());
+                                }
+                            });
+                        ui.add_enabled(
+                            entry.transition != TransitionKind::Cut,
+                            egui::DragValue::new(&mut entry.transition_secs)
+                                .range(0.1..=30.0)
+                                .suffix("s"),
+                        );
+                        ui.add_enabled_ui(entry.transition != TransitionKind::Cut, |ui| {
+                            easing_combo(ui, "easing", &mut entry.easing);
+                        });
+                        if ui
+                            .add_enabled(editable, egui::Button::new("x").small())
+                            .clicked()
+                        {
+                            removed = Some(index);
+                        }
+                    });
+                });
+            }
+            if playlist.entries.is_empty() {
+                ui.label("Add views with \"Add Current View\".");
+            }
+            if ui.button("Add Current View").clicked()
+                && let Some(mat) = materials.get(&fractal.0)
+            {
+                let name = format!("Entry {}", playlist.entries.len() + 1);
+                playlist.entries.push(PlaylistEntry::new(name, mat.clone()));
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label("File");
+                ui.text_edit_singleline(&mut playlist.file);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    playlist.status =
+                        Some(playlist.save().map(|()| format!("Saved {}", playlist.file)));
+                }
+                if ui.button("Load").clicked()
+                    && let Some(mat) = materials.get(&fractal.0)
+                {
+                    playlist.status = Some(
+                        playlist
+                            .load(mat)
+                            .map(|()| format!("Loaded {} entries", playlist.entries.len())),
+                    );
+                }
+            });
+            match &playlist.status {
+                Some(Ok(message)) => {
+                    ui.label(message.as_str());
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(egui::Color32::LIGHT_RED, err.as_str());
+                }
+                None => {}
+            }
+        });
+    playlist.open = open;
+
+    if let Some((from, to)) = moved {
+        playlist.entries.swap(from, to);
+    }
+    if let Some(index) = removed {
+        playlist.entries.remove(index);
+    }
+}
@@ -0,0 +1,44 @@
+//! Binary PLY writer for extracted surfaces, with vertex colors when they were baked
+
+use crate::surface::SurfaceMesh;
+use bevy::prelude::*;
+use std::io::{BufWriter, Write as _};
+use std::path::Path;
+
+pub fn write_ply(path: &Path, mesh: &SurfaceMesh) -> std::io::Result<()> {
+    let colored = mesh.colors.len() == mesh.positions.len();
+    let mut out = BufWriter::new(std::fs::File::create(path)?);
+    writeln!(out, "ply")?;
+    writeln!(out, "format binary_little_endian 1.0")?;
+    writeln!(out, "comment fractal3D")?;
+    writeln!(out, "element vertex {}", mesh.positions.len())?;
+    for property in ["x", "y", "z", "nx", "ny", "nz"] {
+        writeln!(out, "property float {property}")?;
+    }
+    if colored {
+        for property in ["red", "green", "blue"] {
+            writeln!(out, "property uchar {property}")?;
+        }
+    }
+    writeln!(out, "element face {}", mesh.triangle_count())?;
+    writeln!(out, "property list uchar uint vertex_indices")?;
+    writeln!(out, "end_header")?;
+
+    for (i, (p, n)) in mesh.positions.iter().zip(&mesh.normals).enumerate() {
+        for component in p.to_array().into_iter().chain(n.to_array()) {
+            out.write_all(&component.to_le_bytes())?;
+        }
+        if colored {
+            // PLY viewers show byte colors as they are, so they are stored in sRGB
+            let [r, g, b] = mesh.colors[i].to_array();
+            out.write_all(&Srgba::from(LinearRgba::rgb(r, g, b)).to_u8_array_no_alpha())?;
+        }
+    }
+    for triangle in mesh.indices.chunks_exact(3) {
+        out.write_all(&[3])?;
+        for index in triangle {
+            out.write_all(&index.to_le_bytes())?;
+        }
+    }
+    out.flush()
+}
@@ -0,0 +1,127 @@
+use bevy::core_pipeline::FullscreenShader;
+use bevy::prelude::*;
+use bevy::render::extract_component::{ComponentUniforms, DynamicUniformIndex};
+use bevy::render::render_graph::NodeRunError;
+use bevy::render::render_resource::binding_types::{sampler, texture_2d, uniform_buffer};
+use bevy::render::render_resource::encase::internal::WriteInto;
+use bevy::render::render_resource::{
+    BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries, CachedRenderPipelineId,
+    ColorTargetState, ColorWrites, FragmentState, Operations, PipelineCache,
+    RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor, Sampler,
+    SamplerBindingType, SamplerDescriptor, ShaderStages, ShaderType, TextureFormat,
+    TextureSampleType,
+};
+use bevy::render::renderer::{RenderContext, RenderDevice};
+use bevy::render::view::ViewTarget;
+use bevy::shader::ShaderDefVal;
+use std::marker::PhantomData;
+
+/// A fullscreen pass over a view's frame so far: its fragment shader samples the frame and reads
+/// the camera's `U` component as a dynamic uniform, and the result replaces the frame. The lens
+/// flare and the output encoding are both one of these, each with its own shader and pipelines
+/// for the target formats it draws to.
+pub struct PostPass<U> {
+    name: &'static str,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    shader: Handle<Shader>,
+    uniform: PhantomData<U>,
+}
+
+impl<U: Component + ShaderType + WriteInto> PostPass<U> {
+    pub fn new(name: &'static str, shader: Handle<Shader>, render_device: &RenderDevice) -> Self {
+        let layout = render_device.create_bind_group_layout(
+            format!("{name}_layout").as_str(),
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    texture_2d(TextureSampleType::Float { filterable: true }),
+                    sampler(SamplerBindingType::Filtering),
+                    uniform_buffer::<U>(true),
+                ),
+            ),
+        );
+        Self {
+            name,
+            layout,
+            sampler: render_device.create_sampler(&SamplerDescriptor::default()),
+            shader,
+            uniform: PhantomData,
+        }
+    }
+
+    /// Queues a pipeline of the pass writing to `format`
+    pub fn queue(
+        &self,
+        pipeline_cache: &PipelineCache,
+        fullscreen_shader: &FullscreenShader,
+        format: TextureFormat,
+        shader_defs: Vec<ShaderDefVal>,
+    ) -> CachedRenderPipelineId {
+        pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+            label: Some(format!("{}_pipeline", self.name).into()),
+            layout: vec![self.layout.clone()],
+            vertex: fullscreen_shader.to_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs,
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+                ..default()
+            }),
+            ..default()
+        })
+    }
+
+    /// Draws the pass over `target` with one of its queued pipelines. Nothing is drawn while the
+    /// pipeline compiles or before the uniforms are uploaded.
+    pub fn run(
+        &self,
+        render_context: &mut RenderContext,
+        world: &World,
+        target: &ViewTarget,
+        pipeline: CachedRenderPipelineId,
+        index: &DynamicUniformIndex<U>,
+    ) -> Result<(), NodeRunError> {
+        let Some(pipeline) = world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(pipeline)
+        else {
+            return Ok(());
+        };
+        let Some(settings) = world
+            .resource::<ComponentUniforms<U>>()
+            .uniforms()
+            .binding()
+        else {
+            return Ok(());
+        };
+
+        // reads the frame so far and writes the pass's result in its place
+        let post_process = target.post_process_write();
+        let bind_group = render_context.render_device().create_bind_group(
+            format!("{}_bind_group", self.name).as_str(),
+            &self.layout,
+            &BindGroupEntries::sequential((post_process.source, &self.sampler, settings)),
+        );
+        let mut pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some(self.name),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: post_process.destination,
+                depth_slice: None,
+                resolve_target: None,
+                ops: Operations::default(),
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_render_pipeline(pipeline);
+        pass.set_bind_group(0, &bind_group, &[index.index()]);
+        pass.draw(0..3, 0..1);
+        Ok(())
+    }
+}
@@ -0,0 +1,305 @@
+use crate::FractalQuad;
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use crate::params::{Param, ParamStore};
+use bevy::camera::RenderTarget;
+use bevy::camera::visibility::RenderLayers;
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::prelude::*;
+use bevy::render::view::{Hdr, ViewTarget};
+use std::time::Duration;
+
+/// Frame rate while animating in low power mode
+pub const LOW_POWER_FPS: f64 = 30.0;
+
+/// Frames the view has to stay unchanged before updates stop until the next input, so the
+/// temporal reuse textures and the panels settle first
+pub const ECO_SETTLE_FRAMES: u32 = 4;
+
+/// Longest wait between updates of an unchanged view while focused. Input and window events wake
+/// the app sooner, this only bounds how late MIDI, network and file changes are picked up.
+pub const ECO_WAIT: Duration = Duration::from_millis(500);
+
+/// Asks for the next frame to be rendered continuously. Anything that animates, above all what
+/// moves without touching the fractal material such as crossfades, a playing playlist or an
+/// export waiting on its readback, requests it every frame it runs, and eco mode only settles
+/// once a frame passes without a request. Registered as `keep_awake.run_if(<condition>)`.
+#[derive(Resource, Default)]
+pub struct KeepAwake {
+    requested: bool,
+}
+
+impl KeepAwake {
+    /// Whether updates were requested since the last call, clearing the request
+    pub fn take(&mut self) -> bool {
+        std::mem::take(&mut self.requested)
+    }
+}
+
+pub fn keep_awake(mut keep_awake: ResMut<KeepAwake>) {
+    keep_awake.requested = true;
+}
+
+/// Ray step limit in low power mode, higher settings are capped to it
+const LOW_POWER_RAY_STEPS: f32 = 120.0;
+
+/// Share of the window's width and height the main view renders at in low power mode
+const LOW_POWER_RENDER_SCALE: f32 = 0.5;
+
+/// Render layer of the main quad while it renders at the reduced scale. Clear of the crossfade,
+/// data export and layers.
+const REDUCED_VIEW_LAYER: usize = 3;
+
+/// How often the power source is checked in automatic mode
+const BATTERY_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Whether the power source can be detected on this platform, see `on_battery`
+#[cfg(feature = "ui")]
+pub const BATTERY_DETECTION: bool = cfg!(target_os = "linux");
+
+/// Trades quality for battery life: caps the frame rate (see `manage_rendering_mode`), renders
+/// the main view at a reduced scale, limits ray steps and turns off the costliest effects. The
+/// parameters themselves are left alone, the main material renders reduced values on top of them
+/// and gets the real ones back when the mode ends.
+pub struct LowPowerPlugin;
+
+impl Plugin for LowPowerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LowPower>()
+            .init_resource::<KeepAwake>()
+            .add_systems(
+                Update,
+                (poll_battery, apply_low_power, apply_render_scale).chain(),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LowPowerMode {
+    Off,
+    #[cfg(feature = "ui")]
+    On,
+    /// On while running on battery. Only detected on Linux, elsewhere this acts as off.
+    Auto,
+}
+
+#[cfg(feature = "ui")]
+impl LowPowerMode {
+    pub const ALL: [LowPowerMode; 3] = [LowPowerMode::Off, LowPowerMode::On, LowPowerMode::Auto];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LowPowerMode::Off => "Off",
+            LowPowerMode::On => "On",
+            LowPowerMode::Auto => "On Battery",
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct LowPower {
+    pub mode: LowPowerMode,
+    /// Last detected power source, `None` when unknown
+    pub on_battery: Option<bool>,
+    /// Reduced quality in response to thermal throttling, see `throttle.rs`. Counts as active
+    /// whatever the mode.
+    pub throttled: bool,
+    poll: Timer,
+    /// Whether the reduced values are currently on the material
+    applied: bool,
+    reduced_view: Option<ReducedView>,
+}
+
+/// The main view rendered offscreen at the reduced scale and stretched over the window
+struct ReducedView {
+    camera: Entity,
+    overlay: Entity,
+    /// Size of the main quad it was made for
+    frame: Vec2,
+}
+
+impl Default for LowPower {
+    fn default() -> Self {
+        Self {
+            mode: LowPowerMode::Off,
+            on_battery: on_battery(),
+            throttled: false,
+            poll: Timer::new(BATTERY_POLL_INTERVAL, TimerMode::Repeating),
+            applied: false,
+            reduced_view: None,
+        }
+    }
+}
+
+impl LowPower {
+    pub fn active(&self) -> bool {
+        if self.throttled {
+            return true;
+        }
+        match self.mode {
+            LowPowerMode::Off => false,
+            #[cfg(feature = "ui")]
+            LowPowerMode::On => true,
+            LowPowerMode::Auto => self.on_battery == Some(true),
+        }
+    }
+}
+
+/// The value the material renders for `param` in low power mode, `None` for parameters that are
+/// not reduced
+fn reduced(param: Param, value: f32) -> Option<f32> {
+    match param {
+        Param::RaySteps => Some(value.min(LOW_POWER_RAY_STEPS)),
+        Param::Supersampling | Param::SoftShadows | Param::Streamlines => Some(0.0),
+        // its textures follow the window's size, not the reduced one the view renders at
+        Param::Reprojection => Some(0.0),
+        _ => None,
+    }
+}
+
+/// Whether the machine runs on battery, from the power supplies the Linux kernel reports. A
+/// machine without a battery or with any mains supply online counts as plugged in.
+#[cfg(target_os = "linux")]
+fn on_battery() -> Option<bool> {
+    let supplies = std::fs::read_dir("/sys/class/power_supply").ok()?;
+    let mut has_battery = false;
+    for supply in supplies.flatten() {
+        let path = supply.path();
+        let read = |name: &str| std::fs::read_to_string(path.join(name)).unwrap_or_default();
+        match read("type").trim() {
+            "Mains" | "USB" if read("online").trim() == "1" => return Some(false),
+            "Battery" => has_battery = true,
+            _ => {}
+        }
+    }
+    Some(has_battery)
+}
+
+/// Other platforms only report their power source through their own APIs, so it stays unknown
+#[cfg(not(target_os = "linux"))]
+fn on_battery() -> Option<bool> {
+    None
+}
+
+fn poll_battery(mut low_power: ResMut<LowPower>, time: Res<Time>) {
+    if low_power.mode != LowPowerMode::Auto || !low_power.poll.tick(time.delta()).just_finished() {
+        return;
+    }
+    let on_battery = on_battery();
+    if low_power.on_battery != on_battery {
+        low_power.on_battery = on_battery;
+    }
+}
+
+/// Keeps the reduced values on the main material while active, since parameter edits, the
+/// playlist and acts all write the material, and restores the stored values once inactive
+fn apply_low_power(
+    mut low_power: ResMut<LowPower>,
+    store: Res<ParamStore>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    let active = low_power.active();
+    if !active && !low_power.applied {
+        return;
+    }
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let wanted: Vec<(Param, f32)> = Param::ALL
+        .into_iter()
+        .filter_map(|param| {
+            let value = store.get(param);
+            let value = if active {
+                reduced(param, value)?
+            } else {
+                reduced(param, value).map(|_| value)?
+            };
+            (param.get(mat) != value).then_some((param, value))
+        })
+        .collect();
+    // only borrow mutably on a difference, every mutable access re-uploads the uniforms
+    if !wanted.is_empty()
+        && let Some(mat) = materials.get_mut(&fractal.0)
+    {
+        for (param, value) in wanted {
+            param.set(mat, value);
+        }
+    }
+    if low_power.applied != active {
+        low_power.applied = active;
+    }
+}
+
+/// Renders the main view at [`LOW_POWER_RENDER_SCALE`] while low power is active. The main quad
+/// moves to a layer of its own, which an offscreen camera draws into a smaller image that a
+/// sprite in its place shows at full size.
+fn apply_render_scale(
+    mut commands: Commands,
+    mut low_power: ResMut<LowPower>,
+    mut images: ResMut<Assets<Image>>,
+    fractal: Res<FractalMaterial>,
+    quads: Query<(Entity, &MeshMaterial2d<MandelbulbMaterial>, &Transform), With<FractalQuad>>,
+) {
+    let Some((quad, _, transform)) = quads
+        .iter()
+        .find(|(_, material, _)| material.0 == fractal.0)
+    else {
+        return;
+    };
+    let frame = transform.scale.truncate();
+    let wanted = low_power.active().then_some(frame);
+    if low_power.reduced_view.as_ref().map(|view| view.frame) == wanted {
+        return;
+    }
+    if let Some(view) = low_power.reduced_view.take() {
+        commands.entity(view.camera).despawn();
+        commands.entity(view.overlay).despawn();
+        commands.entity(quad).remove::<RenderLayers>();
+    }
+    let Some(frame) = wanted else {
+        return;
+    };
+
+    let size = (frame * LOW_POWER_RENDER_SCALE).as_uvec2().max(UVec2::ONE);
+    let target = images.add(Image::new_target_texture(
+        size.x,
+        size.y,
+        ViewTarget::TEXTURE_FORMAT_HDR,
+    ));
+    let layer = RenderLayers::layer(REDUCED_VIEW_LAYER);
+    let camera = commands
+        .spawn((
+            Camera2d,
+            Camera {
+                target: RenderTarget::Image(target.clone().into()),
+                order: -1,
+                ..default()
+            },
+            Hdr,
+            Tonemapping::None,
+            // the quad keeps the window's size, so the camera zooms out to fit the image
+            Projection::Orthographic(OrthographicProjection {
+                scale: frame.x / size.x as f32,
+                ..OrthographicProjection::default_2d()
+            }),
+            layer.clone(),
+        ))
+        .id();
+    commands.entity(quad).insert(layer);
+    // where the quad was, under the layers, particles and overlays
+    let overlay = commands
+        .spawn((
+            Sprite {
+                image: target,
+                custom_size: Some(frame),
+                ..default()
+            },
+            Transform::default(),
+        ))
+        .id();
+    low_power.reduced_view = Some(ReducedView {
+        camera,
+        overlay,
+        frame,
+    });
+}
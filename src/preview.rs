@@ -0,0 +1,178 @@
+//! Small CPU renders of a material for thumbnails and view scoring, with the built-in formulas'
+//! estimators and plain diffuse shading. Far from the renderer's picture, but enough to tell
+//! views apart without a GPU round trip.
+
+use crate::estimator::CpuEstimator;
+use crate::material::MandelbulbMaterial;
+use crate::palette::palette;
+use bevy::prelude::*;
+use bevy_egui::egui;
+
+/// Ray steps of a preview at most, it only needs the rough shape
+const MAX_STEPS: u32 = 160;
+
+/// Directions traced from outside to find the extent of the shape for framing
+const FIT_RAYS: usize = 96;
+
+/// Luminance the bright end of a thumbnail is exposed to
+const TARGET_LUMINANCE: f32 = 0.8;
+
+/// What one pixel of a preview saw
+#[derive(Clone, Copy)]
+pub struct Sample {
+    pub hit: bool,
+    pub depth: f32,
+    pub normal: Vec3,
+    /// Linear color before exposure
+    pub color: Vec3,
+}
+
+pub struct PreviewRender {
+    pub width: usize,
+    pub height: usize,
+    /// Row by row from the top
+    pub samples: Vec<Sample>,
+}
+
+impl PreviewRender {
+    /// Renders `mat` `width` pixels wide, the height following the camera's aspect. `None` for
+    /// plugin formulas and the geometry graph.
+    pub fn new(mat: &MandelbulbMaterial, width: u32) -> Option<Self> {
+        let estimator = CpuEstimator::new(mat)?;
+        let aspect = mat.camera.resolution.x / mat.camera.resolution.y.max(1.0);
+        let height = ((width as f32 / aspect).round() as u32).clamp(16, width * 2);
+        let mut shape = mat.shape;
+        shape.ray_steps = shape.ray_steps.min(MAX_STEPS);
+        // the coarse render can't resolve more than a pixel's worth of detail
+        shape.hit_threshold = shape.hit_threshold.max(0.5 / width as f32);
+        let light =
+            Vec3::new(mat.shading.light_pos_x, mat.shading.light_pos_y, -2.0).normalize_or(Vec3::Z);
+
+        let mut samples = Vec::with_capacity((width * height) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let uv = Vec2::new(
+                    (x as f32 + 0.5) / width as f32,
+                    1.0 - (y as f32 + 0.5) / height as f32,
+                );
+                let (origin, dir) = mat.camera.ray(uv);
+                samples.push(match estimator.trace(&shape, origin, dir) {
+                    Some(t) => {
+                        let p = origin + dir * t;
+                        let normal = estimator.normal(p);
+                        let trap = estimator.orbit_trap(p);
+                        let base = palette(mat.shading.palette_id)
+                            .color(trap * mat.shading.color_scale + mat.shading.color_offset);
+                        let diffuse = normal.dot(light).max(0.0);
+                        Sample {
+                            hit: true,
+                            depth: t,
+                            normal,
+                            color: base * (0.25 + 0.75 * diffuse),
+                        }
+                    }
+                    None => Sample {
+                        hit: false,
+                        depth: shape.max_dist,
+                        normal: Vec3::ZERO,
+                        color: Vec3::splat(0.04),
+                    },
+                });
+            }
+        }
+        Some(Self {
+            width: width as usize,
+            height: height as usize,
+            samples,
+        })
+    }
+
+    /// Gain that brings the 90th percentile of the surface's luminance to a bright but unclipped
+    /// level, so dark palettes and grazing light still give a readable thumbnail
+    pub fn auto_exposure(&self) -> f32 {
+        let mut luminance: Vec<f32> = self
+            .samples
+            .iter()
+            .filter(|s| s.hit)
+            .map(|s| s.color.dot(Vec3::new(0.2126, 0.7152, 0.0722)))
+            .collect();
+        if luminance.is_empty() {
+            return 1.0;
+        }
+        let index = luminance.len() * 9 / 10;
+        let (_, bright, _) = luminance.select_nth_unstable_by(index, f32::total_cmp);
+        (TARGET_LUMINANCE / bright.max(1e-3)).clamp(0.5, 4.0)
+    }
+
+    /// The render as an sRGB image, colors multiplied by `exposure`
+    pub fn image(&self, exposure: f32) -> egui::ColorImage {
+        let pixels: Vec<u8> = self
+            .samples
+            .iter()
+            .flat_map(|s| {
+                let [r, g, b] = (s.color * exposure).to_array();
+                Srgba::from(LinearRgba::rgb(r, g, b)).to_u8_array_no_alpha()
+            })
+            .collect();
+        egui::ColorImage::from_rgb([self.width, self.height], &pixels)
+    }
+}
+
+/// `mat` seen from a three-quarter angle above, orbiting the middle of the shape at the
+/// distance that just fits it in frame. Gives thumbnails of saved views a consistent framing
+/// whatever the camera was doing. `None` for formulas without a CPU estimator.
+pub fn three_quarter_view(mat: &MandelbulbMaterial) -> Option<MandelbulbMaterial> {
+    let estimator = CpuEstimator::new(mat)?;
+    // surface points hit by rays from a sphere around the shape towards its middle, spread
+    // evenly with a Fibonacci lattice
+    let outer = 4.0;
+    let mut shape = mat.shape;
+    shape.max_dist = outer * 2.0;
+    let points: Vec<Vec3> = (0..FIT_RAYS)
+        .filter_map(|i| {
+            let z = 1.0 - 2.0 * (i as f32 + 0.5) / FIT_RAYS as f32;
+            let angle = i as f32 * std::f32::consts::PI * (3.0 - 5f32.sqrt());
+            let r = (1.0 - z * z).sqrt();
+            let dir = Vec3::new(r * angle.cos(), r * angle.sin(), z);
+            let origin = dir * outer;
+            estimator
+                .trace(&shape, origin, -dir)
+                .map(|t| origin - dir * t)
+        })
+        .collect();
+    if points.is_empty() {
+        return None;
+    }
+    let min = points.iter().copied().fold(Vec3::MAX, Vec3::min);
+    let max = points.iter().copied().fold(Vec3::MIN, Vec3::max);
+    let center = (min + max) * 0.5;
+    let radius = points
+        .iter()
+        .map(|p| p.distance(center))
+        .fold(0.0, f32::max);
+
+    let mut framed = mat.clone();
+    let camera = &mut framed.camera;
+    camera.position = center;
+    camera.rotation = Vec4::from(Quat::from_euler(
+        EulerRot::YXZ,
+        35f32.to_radians(),
+        -25f32.to_radians(),
+        0.0,
+    ));
+    // the sphere around the shape touches the edges of the image plane's shorter extent
+    let focal_length = camera.focal_length.max(0.1);
+    camera.zoom = radius * (1.0 + focal_length * focal_length).sqrt() * 1.05;
+    camera.fov_fit = 2;
+    Some(framed)
+}
+
+/// A thumbnail of `mat` framed by [`three_quarter_view`] and exposed by
+/// [`PreviewRender::auto_exposure`]
+pub fn smart_thumbnail(mat: &MandelbulbMaterial, width: u32) -> Option<egui::ColorImage> {
+    let mut framed = three_quarter_view(mat)?;
+    // thumbnails are 4:3 whatever the window's shape
+    framed.camera.resolution = Vec2::new(4.0, 3.0);
+    let render = PreviewRender::new(&framed, width)?;
+    Some(render.image(render.auto_exposure()))
+}
@@ -0,0 +1,200 @@
+//! Printable solids for STL export. The fractal is sampled on the extraction grid and edited
+//! there before the surface is extracted: closed at the bounds, cut flat at the base, hollowed
+//! to a wall thickness and cleared of islands too thin to print. Editing the sampled field
+//! rather than the mesh keeps the result watertight.
+
+use crate::estimator::CpuEstimator;
+use crate::surface::{Grid, SurfaceMesh, extract_field};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PrintSetup {
+    pub hollow: bool,
+    /// Thickness of the walls left around the cavity of a hollowed solid
+    pub wall_thickness: f32,
+    /// Cut everything below `base_height` off, leaving a flat face to stand on
+    pub base: bool,
+    pub base_height: f32,
+    /// Islands thinner than this everywhere are dropped, the printer can't reproduce them
+    pub min_feature: f32,
+}
+
+impl Default for PrintSetup {
+    fn default() -> Self {
+        Self {
+            hollow: false,
+            wall_thickness: 0.05,
+            base: true,
+            base_height: -0.8,
+            min_feature: 0.01,
+        }
+    }
+}
+
+/// What the printability checks found
+#[derive(Clone, Copy, Default, Debug)]
+pub struct PrintReport {
+    /// Disconnected pieces left in the solid
+    pub islands: usize,
+    /// Pieces dropped for being thinner than the minimum feature size
+    pub dropped: usize,
+}
+
+impl PrintReport {
+    /// A warning for the export status, when the solid won't print in one piece
+    pub fn warning(&self) -> Option<String> {
+        let dropped = match self.dropped {
+            0 => String::new(),
+            n => format!(", dropped {n} below the minimum feature size"),
+        };
+        (self.islands > 1 || self.dropped > 0).then(|| {
+            format!(
+                "{} disconnected islands{dropped}. Floating pieces need supports.",
+                self.islands
+            )
+        })
+    }
+}
+
+/// The closed, printable surface at `level` within the cube of half size `bounds`
+pub fn extract_printable(
+    estimator: &CpuEstimator,
+    level: f32,
+    bounds: f32,
+    resolution: u32,
+    setup: &PrintSetup,
+) -> (SurfaceMesh, PrintReport) {
+    let grid = Grid::new(bounds, resolution);
+    let mut values = grid.sample(|p| {
+        let d = estimator.distance(p) - level;
+        if setup.base {
+            d.max(setup.base_height - p.y)
+        } else {
+            d
+        }
+    });
+    // the outermost samples are outside, capping the solid where the bounds cut it
+    let last = grid.samples - 1;
+    for z in 0..grid.samples {
+        for y in 0..grid.samples {
+            for x in 0..grid.samples {
+                if [x, y, z].iter().any(|&i| i == 0 || i == last) {
+                    let i = grid.index(x, y, z);
+                    values[i] = values[i].max(grid.step * 0.5);
+                }
+            }
+        }
+    }
+
+    let depth = depth(&grid, &values);
+    let mut report = PrintReport::default();
+    for island in islands(&grid, &values) {
+        let thickest = island.iter().map(|&i| depth[i]).fold(0.0, f32::max);
+        if 2.0 * thickest < setup.min_feature {
+            for i in island {
+                values[i] = grid.step * 0.5;
+            }
+            report.dropped += 1;
+        } else {
+            report.islands += 1;
+        }
+    }
+
+    if setup.hollow {
+        for (value, depth) in values.iter_mut().zip(&depth) {
+            // deeper inside than the wall thickness becomes the cavity
+            *value = value.max(depth - setup.wall_thickness);
+        }
+    }
+    (
+        extract_field(&grid, &values, |p| estimator.normal(p)),
+        report,
+    )
+}
+
+/// The 26 neighbours of a sample as offsets with their distance in cells
+fn neighbour_offsets() -> impl Iterator<Item = ([isize; 3], f32)> {
+    (-1isize..=1).flat_map(|z| {
+        (-1isize..=1).flat_map(move |y| {
+            (-1isize..=1).filter_map(move |x| {
+                let steps = x.abs() + y.abs() + z.abs();
+                (steps > 0).then(|| ([x, y, z], (steps as f32).sqrt()))
+            })
+        })
+    })
+}
+
+/// Approximate distance from every inside sample to the nearest outside one, 0 outside.
+/// Two chamfer passes over the grid with the 26 neighbour distances.
+fn depth(grid: &Grid, values: &[f32]) -> Vec<f32> {
+    let n = grid.samples as isize;
+    let mut depth: Vec<f32> = values
+        .iter()
+        .map(|&v| if v < 0.0 { f32::MAX } else { 0.0 })
+        .collect();
+    let offsets: Vec<_> = neighbour_offsets().collect();
+    let index = |[x, y, z]: [isize; 3]| grid.index(x as usize, y as usize, z as usize);
+    let relax = |at: [isize; 3], depth: &mut Vec<f32>| {
+        let i = index(at);
+        if depth[i] == 0.0 {
+            return;
+        }
+        for &(offset, length) in &offsets {
+            let next = [at[0] + offset[0], at[1] + offset[1], at[2] + offset[2]];
+            if next.iter().all(|&c| (0..n).contains(&c)) {
+                depth[i] = depth[i].min(depth[index(next)] + length * grid.step);
+            }
+        }
+    };
+    for z in 0..n {
+        for y in 0..n {
+            for x in 0..n {
+                relax([x, y, z], &mut depth);
+            }
+        }
+    }
+    for z in (0..n).rev() {
+        for y in (0..n).rev() {
+            for x in (0..n).rev() {
+                relax([x, y, z], &mut depth);
+            }
+        }
+    }
+    depth
+}
+
+/// Inside samples grouped into face connected pieces
+fn islands(grid: &Grid, values: &[f32]) -> Vec<Vec<usize>> {
+    let n = grid.samples;
+    let mut seen = vec![false; values.len()];
+    let mut islands = Vec::new();
+    for start in 0..values.len() {
+        if seen[start] || values[start] >= 0.0 {
+            continue;
+        }
+        seen[start] = true;
+        let mut island = vec![start];
+        let mut queue = VecDeque::from([start]);
+        while let Some(i) = queue.pop_front() {
+            let (x, y, z) = (i % n, i / n % n, i / (n * n));
+            let faces = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < n).then(|| i + 1),
+                (y > 0).then(|| i - n),
+                (y + 1 < n).then(|| i + n),
+                (z > 0).then(|| i - n * n),
+                (z + 1 < n).then(|| i + n * n),
+            ];
+            for next in faces.into_iter().flatten() {
+                if !seen[next] && values[next] < 0.0 {
+                    seen[next] = true;
+                    island.push(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+        islands.push(island);
+    }
+    islands
+}
@@ -0,0 +1,363 @@
+//! Scripted regression runs. `--replay <script>` plays a sequence of interactions through the
+//! command API (see [`crate::api`]) and checks hashes of the resulting state and exported images,
+//! then quits with a failing exit code if any check failed, or at once if the script doesn't
+//! parse. One step per line, `#` starts a comment, as in `tests/bulb.replay`:
+//!
+//! ```text
+//! preset tests/bulb.view
+//! set power 6
+//! rotate 30 -10        # yaw and pitch in degrees
+//! wait 10              # frames
+//! expect state 9c3f0a1b2d4e5f60
+//! export
+//! expect output        # without a hash, logs the current one for writing the script
+//! ```
+//!
+//! The state hash covers the main view's share code, so it holds on any machine. The output hash
+//! covers the bytes of the last exported still and only holds on the GPU and driver it was
+//! recorded with.
+
+use crate::api::{LoadPreset, Rotate, SetParam};
+use crate::args;
+use crate::export::{ExportStill, StillSaved};
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use crate::navigation::View;
+use crate::params::{Param, ParamStore};
+use crate::power::keep_awake;
+use crate::rng::fnv1a;
+use bevy::prelude::*;
+use std::path::PathBuf;
+
+/// Frames after each command for its messages to reach the material
+const SETTLE_FRAMES: u32 = 3;
+
+/// Frames an export may take before the step fails
+const EXPORT_TIMEOUT_FRAMES: u32 = 600;
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        let replay = match replay_argument() {
+            Some(Ok(steps)) => Replay::new(steps),
+            Some(Err(err)) => {
+                // a script that can't run must not pass for one that did
+                error!("--replay: {err}");
+                std::process::exit(1);
+            }
+            None => Replay::default(),
+        };
+        app.insert_resource(replay).add_systems(
+            Update,
+            (
+                run_replay,
+                keep_awake.run_if(|replay: Res<Replay>| replay.is_running()),
+            ),
+        );
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Step {
+    Set(Param, f32),
+    /// Yaw and pitch in degrees
+    Rotate(f32, f32),
+    Preset(PathBuf),
+    Export,
+    Wait(u32),
+    /// `None` logs the hash instead of checking it
+    ExpectState(Option<u64>),
+    ExpectOutput(Option<u64>),
+}
+
+impl Step {
+    fn parse(line: &str) -> Result<Self, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let number = |text: &str| {
+            text.parse::<f32>()
+                .map_err(|_| format!("'{text}' is not a number"))
+        };
+        let hash = |text: Option<&&str>| {
+            text.map(|text| {
+                u64::from_str_radix(text, 16).map_err(|_| format!("'{text}' is not a hash"))
+            })
+            .transpose()
+        };
+        match words[..] {
+            ["set", name, value] => Ok(Step::Set(
+                Param::from_name(name).ok_or_else(|| format!("unknown parameter '{name}'"))?,
+                number(value)?,
+            )),
+            ["rotate", yaw, pitch] => Ok(Step::Rotate(number(yaw)?, number(pitch)?)),
+            ["preset", _, ..] => Ok(Step::Preset(PathBuf::from(
+                line.trim_start()["preset".len()..].trim(),
+            ))),
+            ["export"] => Ok(Step::Export),
+            ["wait", frames] => frames
+                .parse()
+                .map(Step::Wait)
+                .map_err(|_| format!("'{frames}' is not a number of frames")),
+            ["expect", "state", ..] if words.len() <= 3 => {
+                Ok(Step::ExpectState(hash(words.get(2))?))
+            }
+            ["expect", "output", ..] if words.len() <= 3 => {
+                Ok(Step::ExpectOutput(hash(words.get(2))?))
+            }
+            _ => Err(format!("unknown step '{line}'")),
+        }
+    }
+}
+
+/// The script given as `--replay <path>`, parsed
+fn replay_argument() -> Option<Result<Vec<(usize, Step)>, String>> {
+    let path = match args::value("--replay")? {
+        Ok(path) => path,
+        Err(err) => return Some(Err(err)),
+    };
+    Some(
+        std::fs::read_to_string(&path)
+            .map_err(|err| format!("{path}: {err}"))
+            .and_then(|text| parse_script(&text).map_err(|err| format!("{path}:{err}"))),
+    )
+}
+
+/// The steps of a script with their line numbers, or the first line that doesn't parse
+fn parse_script(text: &str) -> Result<Vec<(usize, Step)>, String> {
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.split('#').next().unwrap_or_default().trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(number, line)| {
+            Step::parse(line)
+                .map(|step| (number, step))
+                .map_err(|err| format!("{number}: {err}"))
+        })
+        .collect()
+}
+
+/// A replay in progress, empty when none was asked for
+#[derive(Resource, Default)]
+pub struct Replay {
+    /// Steps with their line numbers, in order
+    steps: Vec<(usize, Step)>,
+    next: usize,
+    /// Frames to wait before the next step
+    waiting: u32,
+    /// Frames spent waiting for the current export
+    exporting: Option<u32>,
+    last_export: Option<PathBuf>,
+    passed: usize,
+    failed: usize,
+    /// Every step ran and the result was reported
+    done: bool,
+}
+
+impl Replay {
+    fn new(steps: Vec<(usize, Step)>) -> Self {
+        Self { steps, ..default() }
+    }
+
+    /// Whether a script is playing, the app renders continuously until it is done
+    pub fn is_running(&self) -> bool {
+        !self.steps.is_empty() && !self.done
+    }
+
+    fn check(&mut self, line: usize, what: &str, expected: Option<u64>, actual: u64) {
+        match expected {
+            None => info!("replay line {line}: {what} hash is {actual:016x}"),
+            Some(expected) if expected == actual => self.passed += 1,
+            Some(expected) => {
+                error!(
+                    "replay line {line}: {what} hash is {actual:016x}, expected {expected:016x}"
+                );
+                self.failed += 1;
+            }
+        }
+    }
+}
+
+fn run_replay(
+    mut replay: ResMut<Replay>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    store: Res<ParamStore>,
+    mut set_param: MessageWriter<SetParam>,
+    mut rotate: MessageWriter<Rotate>,
+    mut presets: MessageWriter<LoadPreset>,
+    mut exports: MessageWriter<ExportStill>,
+    mut saved: MessageReader<StillSaved>,
+    mut exit: MessageWriter<AppExit>,
+) {
+    if !replay.is_running() {
+        return;
+    }
+    let replay = &mut *replay;
+    let saved = saved.read().last().map(|still| still.path.clone());
+
+    if let Some(frames) = &mut replay.exporting {
+        let line = replay.steps[replay.next - 1].0;
+        match saved {
+            Some(path) => {
+                replay.last_export = Some(path);
+                replay.exporting = None;
+                replay.waiting = SETTLE_FRAMES;
+            }
+            None if *frames >= EXPORT_TIMEOUT_FRAMES => {
+                error!("replay line {line}: the export did not finish");
+                replay.failed += 1;
+                replay.exporting = None;
+            }
+            None => {
+                *frames += 1;
+                return;
+            }
+        }
+    }
+    if replay.waiting > 0 {
+        replay.waiting -= 1;
+        return;
+    }
+    if replay.next == replay.steps.len() {
+        info!(
+            "Replay finished: {} check(s) passed, {} failed",
+            replay.passed, replay.failed
+        );
+        exit.write(if replay.failed == 0 {
+            AppExit::Success
+        } else {
+            AppExit::error()
+        });
+        replay.done = true;
+        return;
+    }
+
+    let (line, step) = replay.steps[replay.next].clone();
+    replay.next += 1;
+    match step {
+        Step::Set(param, value) => {
+            set_param.write(SetParam { param, value });
+            replay.waiting = SETTLE_FRAMES;
+        }
+        Step::Rotate(yaw, pitch) => {
+            rotate.write(Rotate {
+                yaw: yaw.to_radians(),
+                pitch: pitch.to_radians(),
+            });
+            replay.waiting = SETTLE_FRAMES;
+        }
+        Step::Preset(path) => {
+            presets.write(LoadPreset(path));
+            replay.waiting = SETTLE_FRAMES;
+        }
+        Step::Export => {
+            exports.write(ExportStill);
+            replay.exporting = Some(0);
+        }
+        Step::Wait(frames) => replay.waiting = frames,
+        Step::ExpectState(expected) => match materials.get(&fractal.0) {
+            Some(mat) => {
+                let code = View::share_code(&mat.camera, &store);
+                replay.check(line, "state", expected, fnv1a(code.as_bytes()));
+            }
+            None => {
+                error!("replay line {line}: there is no view to check");
+                replay.failed += 1;
+            }
+        },
+        Step::ExpectOutput(expected) => match &replay.last_export {
+            Some(path) => match std::fs::read(path) {
+                Ok(bytes) => {
+                    let actual = fnv1a(&bytes);
+                    replay.check(line, "output", expected, actual);
+                }
+                Err(err) => {
+                    error!("replay line {line}: {}: {err}", path.display());
+                    replay.failed += 1;
+                }
+            },
+            None => {
+                error!("replay line {line}: nothing was exported yet");
+                replay.failed += 1;
+            }
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::ApiPlugin;
+    use crate::export::{ExportFrame, ExportSettings, FrameSaved};
+    use crate::params::ParamsPlugin;
+    use crate::validation::Validation;
+
+    /// Plays a script through the command API without a window or a GPU, so only the state
+    /// checks can pass
+    fn play(script: &str) -> Replay {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<MandelbulbMaterial>()
+            .add_plugins((ParamsPlugin, ApiPlugin))
+            .init_resource::<Validation>()
+            .init_resource::<ExportSettings>()
+            .add_message::<ExportStill>()
+            .add_message::<StillSaved>()
+            .add_message::<ExportFrame>()
+            .add_message::<FrameSaved>()
+            .insert_resource(Replay::new(parse_script(script).unwrap()))
+            .add_systems(Update, run_replay);
+        let material = MandelbulbMaterial::default();
+        app.insert_resource(ParamStore::capture(&material));
+        let handle = app
+            .world_mut()
+            .resource_mut::<Assets<MandelbulbMaterial>>()
+            .add(material);
+        app.insert_resource(FractalMaterial(handle));
+        while app.world().resource::<Replay>().is_running() {
+            app.update();
+        }
+        app.world_mut().remove_resource::<Replay>().unwrap()
+    }
+
+    #[test]
+    fn the_sample_script_parses() {
+        let steps = parse_script(include_str!("../tests/bulb.replay")).unwrap();
+        assert_eq!(
+            steps[0],
+            (3, Step::Preset(PathBuf::from("tests/bulb.view")))
+        );
+        assert!(steps.contains(&(6, Step::Set(Param::Power, 6.0))));
+        assert!(steps.contains(&(7, Step::Rotate(30.0, -10.0))));
+        assert!(matches!(
+            steps.last(),
+            Some((9, Step::ExpectState(Some(_))))
+        ));
+    }
+
+    #[test]
+    fn the_sample_script_holds() {
+        let replay = play(include_str!("../tests/bulb.replay"));
+        assert_eq!((replay.passed, replay.failed), (2, 0));
+    }
+
+    #[test]
+    fn a_changed_state_fails() {
+        let replay = play("set power 6\nexpect state 0\nexpect output");
+        assert_eq!((replay.passed, replay.failed), (0, 2));
+    }
+
+    #[test]
+    fn hashes_are_hexadecimal() {
+        assert_eq!(
+            Step::parse("expect state 9c3f0a1b2d4e5f60"),
+            Ok(Step::ExpectState(Some(0x9c3f_0a1b_2d4e_5f60)))
+        );
+        assert!(Step::parse("expect state xyz").is_err());
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = parse_script("wait 3\n\nset nothing 1").unwrap_err();
+        assert!(err.starts_with("3: "), "{err}");
+    }
+}
@@ -0,0 +1,147 @@
+use crate::material::{CameraUniform, FractalMaterial, MandelbulbMaterial, ShapeUniform};
+use bevy::asset::{RenderAssetUsages, uuid_handle};
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+
+/// Bound by materials without temporal reuse, which never read or write them. Kept apart from
+/// the main material's pair so no texture is read and written in the same pass.
+pub const UNUSED_PREVIOUS_DEPTH: Handle<Image> =
+    uuid_handle!("4b1e8f3a-7c2d-4e59-9a60-d3f5b8c1e2a4");
+pub const UNUSED_DEPTH: Handle<Image> = uuid_handle!("a85d2c47-3f1b-4d9e-8c76-1e0b9f4a6d35");
+
+/// The main material's distance textures, swapped every frame the view changes
+const DEPTH_TEXTURES: [Handle<Image>; 2] = [
+    uuid_handle!("62f0c9d8-5a4e-4b13-b7f2-9c8e1d3a5f60"),
+    uuid_handle!("d1a7e3b5-8f26-4c90-a5d4-7b2c6e9f0a18"),
+];
+
+/// Largest camera rotation between frames, in radians, that still reuses the previous frame
+const MAX_ROTATION: f32 = 0.2;
+
+/// Largest eye movement between frames, relative to the zoom distance, that still reuses the
+/// previous frame
+const MAX_MOVEMENT: f32 = 0.1;
+
+/// Temporal reuse while navigating. The main material writes how far each pixel's ray marched,
+/// and the next frame starts its rays near where the previous one stopped, reprojected through
+/// the camera motion in between. Most steps of a ray go to crossing the empty space in front of
+/// the surface, so small camera moves render with a fraction of them. Large moves and shape
+/// changes start from the camera as usual.
+pub struct ReprojectionPlugin;
+
+impl Plugin for ReprojectionPlugin {
+    fn build(&self, app: &mut App) {
+        let mut images = app.world_mut().resource_mut::<Assets<Image>>();
+        for handle in [UNUSED_PREVIOUS_DEPTH, UNUSED_DEPTH]
+            .iter()
+            .chain(&DEPTH_TEXTURES)
+        {
+            let _ = images.insert(handle, depth_image(UVec2::ONE));
+        }
+
+        app.init_resource::<Reprojection>()
+            .add_systems(PostUpdate, (release_copies, swap_depth_textures));
+    }
+}
+
+/// Distances along each pixel's ray. Zero, the value before anything was written, starts at the
+/// camera.
+fn depth_image(size: UVec2) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; 4],
+        TextureFormat::R32Float,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.texture_descriptor.usage |= TextureUsages::STORAGE_BINDING;
+    image
+}
+
+#[derive(Resource, Default)]
+struct Reprojection {
+    /// Camera and shape of the frame being written, `None` while temporal reuse is off
+    current: Option<(CameraUniform, ShapeUniform)>,
+    /// Size of the main material's distance textures
+    size: UVec2,
+}
+
+/// Whether the camera moved little enough for the previous distances to be a good start
+fn slight_motion(from: &CameraUniform, to: &CameraUniform) -> bool {
+    let rotation = Quat::from_vec4(from.rotation).angle_between(Quat::from_vec4(to.rotation));
+    let movement = from.ray_origin().distance(to.ray_origin());
+    rotation <= MAX_ROTATION && movement <= MAX_MOVEMENT * to.zoom
+}
+
+/// Runs after everything that moves the camera. The frame that was written becomes the one that
+/// is read, and the material learns the camera it was written with.
+fn swap_depth_textures(
+    mut reprojection: ResMut<Reprojection>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+    fractal: Res<FractalMaterial>,
+) {
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    if !mat.reprojection {
+        if reprojection.current.is_some() {
+            reprojection.current = None;
+        }
+        return;
+    }
+    let frame = (mat.camera, mat.shape);
+    // an unchanged view renders the same distances again, if it renders at all
+    if reprojection.current == Some(frame) {
+        return;
+    }
+
+    let size = mat.camera.resolution.as_uvec2().max(UVec2::ONE);
+    let resized = size != reprojection.size;
+    if resized {
+        for handle in &DEPTH_TEXTURES {
+            let _ = images.insert(handle, depth_image(size));
+        }
+        reprojection.size = size;
+    }
+    let previous = reprojection.current.replace(frame);
+    let valid = !resized
+        && previous.is_some_and(|(camera, shape)| {
+            shape == mat.shape && slight_motion(&camera, &mat.camera)
+        });
+
+    let Some(mat) = materials.get_mut(&fractal.0) else {
+        return;
+    };
+    let written = usize::from(mat.depth == DEPTH_TEXTURES[1]);
+    mat.previous_depth = DEPTH_TEXTURES[written].clone();
+    mat.depth = DEPTH_TEXTURES[1 - written].clone();
+    mat.previous_frame.valid = valid as u32;
+    if let Some((camera, _)) = previous {
+        mat.previous_frame.previous = camera;
+    }
+}
+
+/// Points copies of the main material, like exports and crossfades, back at the unused
+/// textures. They render elsewhere and would overwrite the main view's distances.
+fn release_copies(
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    let copies: Vec<_> = materials
+        .iter()
+        .filter(|(id, mat)| *id != fractal.0.id() && mat.depth != UNUSED_DEPTH)
+        .map(|(id, _)| id)
+        .collect();
+    for id in copies {
+        if let Some(mat) = materials.get_mut(id) {
+            mat.previous_depth = UNUSED_PREVIOUS_DEPTH;
+            mat.depth = UNUSED_DEPTH;
+            mat.previous_frame.valid = 0;
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! The one source of randomness. Every stochastic effect draws from its own [`Rng`] stream, all
+//! derived from a single seed, so two runs with the same `--seed` make the same random choices
+//! and render the same frames: the views Discover and the wallpaper try, the samples behind the
+//! statistics and where particles spawn. Streams are keyed by name, so a tool drawing more
+//! numbers doesn't shift what the others get. Without `--seed` the seed comes from the clock and
+//! is logged at startup, to reproduce a run afterwards.
+//!
+//! Anti-aliasing, ambient occlusion and the shaders' noise use fixed patterns and hashes of the
+//! position, so they need no seed to be the same every frame. Film grain is the exception, it
+//! hashes a seed from the `noise` stream that the export settings keep or change every frame.
+
+use crate::args;
+use bevy::prelude::*;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static SEED: OnceLock<u64> = OnceLock::new();
+
+pub struct RngPlugin;
+
+impl Plugin for RngPlugin {
+    fn build(&self, _app: &mut App) {
+        let seed = seed();
+        info!("Random seed {seed}, rerun with --seed {seed} to repeat it");
+    }
+}
+
+/// The run's seed, from `--seed` or the clock
+pub fn seed() -> u64 {
+    *SEED.get_or_init(|| {
+        match args::value("--seed").map(|text| text.map(|text| text.parse())) {
+            Some(Ok(Ok(seed))) => return seed,
+            Some(Ok(Err(_))) => error!("Ignoring --seed: not a whole number"),
+            Some(Err(err)) => error!("Ignoring --seed: {err}"),
+            None => {}
+        }
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default()
+    })
+}
+
+/// FNV-1a, stable across builds and platforms unlike the standard library's hasher
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xCBF2_9CE4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01B3)
+    })
+}
+
+/// A stream of random numbers, splitmix64
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    /// The stream of the run's seed for the consumer `name`
+    pub fn stream(name: &str) -> Self {
+        // every consumer starts somewhere else in the sequence
+        Self {
+            state: seed() ^ fnv1a(name.as_bytes()),
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut x = self.state;
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^ (x >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
@@ -0,0 +1,747 @@
+//! Formula scripts, a small language for distance estimators that is translated to WGSL.
+//!
+//! Scripts can be shared and loaded without trusting their author: they can only assign
+//! variables, call a fixed set of math functions and run one loop bounded by the iteration
+//! slider, so they cannot hang the GPU or reach anything outside the estimator.
+//!
+//! ```text
+//! # Menger sponge
+//! param scale = 3.0 [1.5, 4.0]
+//!
+//! loop {
+//!     z = abs(z)
+//!     if z.x < z.y { z = vec3(z.y, z.x, z.z) }
+//!     z = z * scale - (scale - 1.0)
+//!     dr = dr * scale
+//!     trap = min(trap, length(z))
+//!     break if length(z) > 100.0
+//! }
+//! distance = (length(z) - 1.0) / dr
+//! ```
+//!
+//! Built-in variables are `p` (the sample point, read-only), `z` (starts at `p`), `dr` (starts
+//! at 1), `trap` (starts large, used for coloring), `i` (the iteration, inside the loop) and
+//! `distance`, which the script has to assign. New variables are declared by assigning them.
+
+use crate::formulas::{MAX_PLUGIN_PARAMS, PluginParam};
+#[cfg(feature = "ui")]
+use crate::units::Quantity;
+use std::fmt;
+
+/// A formula script translated to WGSL
+pub struct CompiledScript {
+    /// Defines `plugin_de`, ready to be appended to the fractal shader
+    pub wgsl: String,
+    pub params: Vec<PluginParam>,
+}
+
+#[derive(Debug)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+type Result<T> = std::result::Result<T, ScriptError>;
+
+pub fn compile(source: &str) -> Result<CompiledScript> {
+    let tokens = tokenize(source)?;
+    let mut compiler = Compiler {
+        tokens,
+        pos: 0,
+        params: Vec::new(),
+        vars: vec![
+            ("p".into(), Ty::Vec3),
+            ("z".into(), Ty::Vec3),
+            ("dr".into(), Ty::Scalar),
+            ("trap".into(), Ty::Scalar),
+            ("distance".into(), Ty::Scalar),
+        ],
+        in_loop: false,
+        has_loop: false,
+        assigns_distance: false,
+    };
+
+    let mut body = String::new();
+    while compiler.peek().is_some() {
+        compiler.statement(&mut body, 1)?;
+    }
+    if !compiler.assigns_distance {
+        return Err(compiler.error("the script never assigns distance"));
+    }
+
+    let wgsl = format!(
+        "fn plugin_de(p: vec3<f32>) -> vec2<f32> {{\n    \
+         var z = p;\n    var dr = 1.0;\n    var trap = 1e20;\n    var distance = 0.0;\n\
+         {body}    return vec2<f32>(distance, trap);\n}}\n"
+    );
+    Ok(CompiledScript {
+        wgsl,
+        params: compiler.params,
+    })
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Num(f32),
+    Ident(String),
+    Sym(&'static str),
+}
+
+const SYMBOLS: [&str; 18] = [
+    "<=", ">=", "==", "!=", "<", ">", "=", "+", "-", "*", "/", "(", ")", "{", "}", "[", "]", ",",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>> {
+    let mut tokens = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        let line_no = index + 1;
+        let code = line.split('#').next().unwrap_or_default();
+        let mut rest = code.trim_start();
+        while !rest.is_empty() {
+            let c = rest.chars().next().unwrap();
+            let len = if c.is_ascii_digit()
+                || (c == '.' && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+            {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                    .unwrap_or(rest.len());
+                let value = rest[..len].parse().map_err(|_| ScriptError {
+                    line: line_no,
+                    message: format!("invalid number {}", &rest[..len]),
+                })?;
+                tokens.push((Token::Num(value), line_no));
+                len
+            } else if c.is_ascii_alphabetic() || c == '_' {
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                tokens.push((Token::Ident(rest[..len].into()), line_no));
+                len
+            } else if c == '.' {
+                tokens.push((Token::Sym("."), line_no));
+                1
+            } else if let Some(sym) = SYMBOLS.iter().find(|s| rest.starts_with(**s)) {
+                tokens.push((Token::Sym(sym), line_no));
+                sym.len()
+            } else {
+                return Err(ScriptError {
+                    line: line_no,
+                    message: format!("unexpected character '{c}'"),
+                });
+            };
+            rest = rest[len..].trim_start();
+        }
+    }
+    Ok(tokens)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Ty {
+    Scalar,
+    Vec3,
+    Bool,
+}
+
+impl Ty {
+    fn wgsl(self) -> &'static str {
+        match self {
+            Ty::Scalar => "f32",
+            Ty::Vec3 => "vec3<f32>",
+            Ty::Bool => "bool",
+        }
+    }
+}
+
+/// A translated expression
+struct Expr {
+    code: String,
+    ty: Ty,
+}
+
+impl Expr {
+    /// The expression as a vector, scalars are splatted
+    fn as_vec3(&self) -> String {
+        match self.ty {
+            Ty::Scalar => format!("vec3<f32>({})", self.code),
+            _ => self.code.clone(),
+        }
+    }
+}
+
+/// Names that are translated as-is. Everything the script declares is prefixed, so it can never
+/// collide with WGSL keywords or the rest of the shader.
+const BUILT_INS: [&str; 5] = ["p", "z", "dr", "trap", "distance"];
+
+struct Compiler {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    params: Vec<PluginParam>,
+    /// Variables in scope, by script name
+    vars: Vec<(String, Ty)>,
+    in_loop: bool,
+    has_loop: bool,
+    assigns_distance: bool,
+}
+
+impl Compiler {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |(_, line)| *line)
+    }
+
+    fn error(&self, message: impl Into<String>) -> ScriptError {
+        ScriptError {
+            line: self.line(),
+            message: message.into(),
+        }
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| self.error("unexpected end of script"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, sym: &'static str) -> bool {
+        if self.peek() == Some(&Token::Sym(sym)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, sym: &'static str) -> Result<()> {
+        if self.eat(sym) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{sym}'")))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next()? {
+            Token::Ident(name) => Ok(name),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected a name"))
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<f32> {
+        let negative = self.eat("-");
+        match self.next()? {
+            Token::Num(value) => Ok(if negative { -value } else { value }),
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected a number"))
+            }
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Ty> {
+        self.vars
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, ty)| *ty)
+    }
+
+    fn statement(&mut self, out: &mut String, depth: usize) -> Result<()> {
+        let indent = "    ".repeat(depth);
+        match self.next()? {
+            Token::Ident(keyword) if keyword == "param" => {
+                if depth != 1 {
+                    return Err(self.error("params must be declared at the top level"));
+                }
+                let name = self.ident()?;
+                if self.lookup(&name).is_some() || self.params.iter().any(|p| p.name == name) {
+                    return Err(self.error(format!("{name} is already defined")));
+                }
+                if self.params.len() == MAX_PLUGIN_PARAMS {
+                    return Err(self.error(format!("at most {MAX_PLUGIN_PARAMS} params")));
+                }
+                self.expect("=")?;
+                let default = self.number()?;
+                self.expect("[")?;
+                let min = self.number()?;
+                self.expect(",")?;
+                let max = self.number()?;
+                self.expect("]")?;
+                if !(min..=max).contains(&default) {
+                    return Err(self.error(format!("{name}'s default is outside [{min}, {max}]")));
+                }
+                self.params.push(PluginParam {
+                    name,
+                    default,
+                    #[cfg(feature = "ui")]
+                    min,
+                    #[cfg(feature = "ui")]
+                    max,
+                    #[cfg(feature = "ui")]
+                    quantity: Quantity::Plain,
+                });
+            }
+            Token::Ident(keyword) if keyword == "loop" => {
+                if self.has_loop || depth != 1 {
+                    return Err(self.error("a script has one loop, at the top level"));
+                }
+                self.has_loop = true;
+                self.in_loop = true;
+                out.push_str(&format!(
+                    "{indent}for (var i = 0u; i < shape.mandel_iters; i++) {{\n"
+                ));
+                self.block(out, depth + 1)?;
+                out.push_str(&format!("{indent}}}\n"));
+                self.in_loop = false;
+            }
+            Token::Ident(keyword) if keyword == "if" => {
+                let condition = self.expr()?;
+                if condition.ty != Ty::Bool {
+                    return Err(self.error("if needs a comparison"));
+                }
+                out.push_str(&format!("{indent}if ({}) {{\n", condition.code));
+                self.block(out, depth + 1)?;
+                out.push_str(&format!("{indent}}}\n"));
+            }
+            Token::Ident(keyword) if keyword == "break" => {
+                if !self.in_loop {
+                    return Err(self.error("break outside of the loop"));
+                }
+                let Token::Ident(keyword) = self.next()? else {
+                    return Err(self.error("expected 'break if'"));
+                };
+                if keyword != "if" {
+                    return Err(self.error("expected 'break if'"));
+                }
+                let condition = self.expr()?;
+                if condition.ty != Ty::Bool {
+                    return Err(self.error("break if needs a comparison"));
+                }
+                out.push_str(&format!("{indent}if ({}) {{ break; }}\n", condition.code));
+            }
+            Token::Ident(name) => {
+                if name == "p" || name == "i" || self.params.iter().any(|p| p.name == name) {
+                    return Err(self.error(format!("{name} is read-only")));
+                }
+                self.expect("=")?;
+                let value = self.expr()?;
+                if value.ty == Ty::Bool {
+                    return Err(self.error("comparisons can only be used in if"));
+                }
+                if name == "distance" {
+                    if depth != 1 {
+                        return Err(self.error("distance must be assigned at the top level"));
+                    }
+                    self.assigns_distance = true;
+                }
+                match self.lookup(&name) {
+                    Some(ty) if ty == value.ty => {
+                        out.push_str(&format!("{indent}{} = {};\n", var_name(&name), value.code));
+                    }
+                    Some(Ty::Vec3) => {
+                        out.push_str(&format!(
+                            "{indent}{} = {};\n",
+                            var_name(&name),
+                            value.as_vec3()
+                        ));
+                    }
+                    Some(_) => {
+                        return Err(self.error(format!("{name} is a number, not a vector")));
+                    }
+                    None => {
+                        out.push_str(&format!(
+                            "{indent}var {}: {} = {};\n",
+                            var_name(&name),
+                            value.ty.wgsl(),
+                            value.code
+                        ));
+                        self.vars.push((name, value.ty));
+                    }
+                }
+            }
+            _ => {
+                self.pos -= 1;
+                return Err(self.error("expected a statement"));
+            }
+        }
+        Ok(())
+    }
+
+    /// `{ statement* }`, variables declared inside go out of scope at the end
+    fn block(&mut self, out: &mut String, depth: usize) -> Result<()> {
+        self.expect("{")?;
+        let scope = self.vars.len();
+        while !self.eat("}") {
+            if self.peek().is_none() {
+                return Err(self.error("missing '}'"));
+            }
+            self.statement(out, depth)?;
+        }
+        self.vars.truncate(scope);
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let lhs = self.sum()?;
+        for op in ["<=", ">=", "==", "!=", "<", ">"] {
+            if self.eat(op) {
+                let rhs = self.sum()?;
+                if lhs.ty != Ty::Scalar || rhs.ty != Ty::Scalar {
+                    return Err(self.error("only numbers can be compared"));
+                }
+                return Ok(Expr {
+                    code: format!("{} {op} {}", lhs.code, rhs.code),
+                    ty: Ty::Bool,
+                });
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn sum(&mut self) -> Result<Expr> {
+        let mut lhs = self.product()?;
+        loop {
+            let op = if self.eat("+") {
+                "+"
+            } else if self.eat("-") {
+                "-"
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.product()?;
+            lhs = arithmetic(lhs, op, rhs);
+        }
+    }
+
+    fn product(&mut self) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.eat("*") {
+                "*"
+            } else if self.eat("/") {
+                "/"
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = self.unary()?;
+            lhs = arithmetic(lhs, op, rhs);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat("-") {
+            let inner = self.unary()?;
+            return Ok(Expr {
+                code: format!("(-{})", inner.code),
+                ty: inner.ty,
+            });
+        }
+        let mut expr = self.primary()?;
+        while self.eat(".") {
+            let field = self.ident()?;
+            if expr.ty != Ty::Vec3 || !matches!(field.as_str(), "x" | "y" | "z") {
+                return Err(self.error(format!("no component .{field}")));
+            }
+            expr = Expr {
+                code: format!("({}).{field}", expr.code),
+                ty: Ty::Scalar,
+            };
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.next()? {
+            Token::Num(value) => Ok(Expr {
+                code: format!("{value:?}"),
+                ty: Ty::Scalar,
+            }),
+            Token::Sym("(") => {
+                let inner = self.sum()?;
+                self.expect(")")?;
+                Ok(Expr {
+                    code: format!("({})", inner.code),
+                    ty: inner.ty,
+                })
+            }
+            Token::Ident(name) if self.eat("(") => {
+                let mut args = Vec::new();
+                if !self.eat(")") {
+                    loop {
+                        args.push(self.sum()?);
+                        if self.eat(")") {
+                            break;
+                        }
+                        self.expect(",")?;
+                    }
+                }
+                self.call(&name, args)
+            }
+            Token::Ident(name) => {
+                if name == "i" {
+                    if !self.in_loop {
+                        return Err(self.error("i is only defined inside the loop"));
+                    }
+                    return Ok(Expr {
+                        code: "f32(i)".into(),
+                        ty: Ty::Scalar,
+                    });
+                }
+                if let Some(index) = self.params.iter().position(|p| p.name == name) {
+                    return Ok(Expr {
+                        code: format!("plugin_param({index}u)"),
+                        ty: Ty::Scalar,
+                    });
+                }
+                match self.lookup(&name) {
+                    Some(ty) => Ok(Expr {
+                        code: var_name(&name),
+                        ty,
+                    }),
+                    None => Err(self.error(format!("unknown variable {name}"))),
+                }
+            }
+            _ => {
+                self.pos -= 1;
+                Err(self.error("expected an expression"))
+            }
+        }
+    }
+
+    fn call(&self, name: &str, args: Vec<Expr>) -> Result<Expr> {
+        let arity = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err(self.error(format!("{name} takes {n} argument(s)")))
+            }
+        };
+        let any_vec = args.iter().any(|a| a.ty == Ty::Vec3);
+        let ty = if any_vec { Ty::Vec3 } else { Ty::Scalar };
+        // arguments of mixed type are splatted to vectors, like the arithmetic operators
+        let joined = || {
+            args.iter()
+                .map(|a| if any_vec { a.as_vec3() } else { a.code.clone() })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        match name {
+            "abs" | "sin" | "cos" | "tan" | "sqrt" | "exp" | "log" | "floor" | "fract" | "sign" => {
+                arity(1)?;
+                Ok(Expr {
+                    code: format!("{name}({})", args[0].code),
+                    ty: args[0].ty,
+                })
+            }
+            "asin" | "acos" | "atan" => {
+                arity(1)?;
+                self.scalars(name, &args)?;
+                Ok(Expr {
+                    code: format!("{name}({})", args[0].code),
+                    ty: Ty::Scalar,
+                })
+            }
+            "atan2" => {
+                arity(2)?;
+                self.scalars(name, &args)?;
+                Ok(Expr {
+                    code: format!("atan2({}, {})", args[0].code, args[1].code),
+                    ty: Ty::Scalar,
+                })
+            }
+            "min" | "max" | "pow" | "clamp" | "mix" => {
+                arity(if matches!(name, "clamp" | "mix") {
+                    3
+                } else {
+                    2
+                })?;
+                Ok(Expr {
+                    code: format!("{name}({})", joined()),
+                    ty,
+                })
+            }
+            "length" | "normalize" => {
+                arity(1)?;
+                if args[0].ty != Ty::Vec3 {
+                    return Err(self.error(format!("{name} needs a vector")));
+                }
+                Ok(Expr {
+                    code: format!("{name}({})", args[0].code),
+                    ty: if name == "length" {
+                        Ty::Scalar
+                    } else {
+                        Ty::Vec3
+                    },
+                })
+            }
+            "dot" | "cross" => {
+                arity(2)?;
+                self.vectors(name, &args)?;
+                Ok(Expr {
+                    code: format!("{name}({}, {})", args[0].code, args[1].code),
+                    ty: if name == "dot" { Ty::Scalar } else { Ty::Vec3 },
+                })
+            }
+            "vec3" => {
+                if args.len() != 1 && args.len() != 3 {
+                    return Err(self.error("vec3 takes 1 or 3 arguments"));
+                }
+                self.scalars(name, &args)?;
+                Ok(Expr {
+                    code: format!("vec3<f32>({})", joined()),
+                    ty: Ty::Vec3,
+                })
+            }
+            _ => Err(self.error(format!("unknown function {name}"))),
+        }
+    }
+
+    fn scalars(&self, name: &str, args: &[Expr]) -> Result<()> {
+        if args.iter().all(|a| a.ty == Ty::Scalar) {
+            Ok(())
+        } else {
+            Err(self.error(format!("{name} takes numbers")))
+        }
+    }
+
+    fn vectors(&self, name: &str, args: &[Expr]) -> Result<()> {
+        if args.iter().all(|a| a.ty == Ty::Vec3) {
+            Ok(())
+        } else {
+            Err(self.error(format!("{name} takes vectors")))
+        }
+    }
+}
+
+fn arithmetic(lhs: Expr, op: &str, rhs: Expr) -> Expr {
+    let ty = if lhs.ty == Ty::Vec3 || rhs.ty == Ty::Vec3 {
+        Ty::Vec3
+    } else {
+        Ty::Scalar
+    };
+    Expr {
+        code: format!("{} {op} {}", lhs.code, rhs.code),
+        ty,
+    }
+}
+
+fn var_name(name: &str) -> String {
+    if BUILT_INS.contains(&name) {
+        name.into()
+    } else {
+        format!("s_{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MENGER: &str = "\
+# Menger sponge
+param scale = 3.0 [1.5, 4.0]
+
+loop {
+    z = abs(z)
+    if z.x < z.y { z = vec3(z.y, z.x, z.z) }
+    z = z * scale - (scale - 1.0)
+    dr = dr * scale
+    trap = min(trap, length(z))
+    break if length(z) > 100.0
+}
+distance = (length(z) - 1.0) / dr
+";
+
+    fn error(source: &str) -> String {
+        match compile(source) {
+            Ok(_) => panic!("{source} compiled"),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn the_documented_script_compiles() {
+        let script = compile(MENGER).unwrap();
+        assert_eq!(script.params.len(), 1);
+        assert_eq!(script.params[0].name, "scale");
+        assert_eq!(script.params[0].default, 3.0);
+        assert!(
+            script
+                .wgsl
+                .starts_with("fn plugin_de(p: vec3<f32>) -> vec2<f32> {")
+        );
+        assert!(
+            script
+                .wgsl
+                .contains("for (var i = 0u; i < shape.mandel_iters; i++) {")
+        );
+        assert!(script.wgsl.contains("if (length(z) > 100.0) { break; }"));
+        assert!(script.wgsl.contains("z = vec3<f32>((z).y, (z).x, (z).z);"));
+        assert!(script.wgsl.contains("dr = dr * plugin_param(0u);"));
+        assert!(script.wgsl.contains("return vec2<f32>(distance, trap);"));
+    }
+
+    #[test]
+    fn emitted_wgsl() {
+        let wgsl = |body: &str| compile(&format!("{body}\ndistance = 0.0")).unwrap().wgsl;
+        // new variables are declared with their type and prefixed
+        assert!(wgsl("k = 2.0").contains("var s_k: f32 = 2.0;"));
+        assert!(wgsl("w = cross(z, p)").contains("var s_w: vec3<f32> = cross(z, p);"));
+        // numbers assigned to vectors are splatted
+        assert!(wgsl("z = 1.0").contains("z = vec3<f32>(1.0);"));
+        assert!(wgsl("dr = dot(z, z)").contains("dr = dot(z, z);"));
+        assert!(wgsl("z = max(z, 0.5)").contains("z = max(z, vec3<f32>(0.5));"));
+        assert!(wgsl("dr = -dr").contains("dr = (-dr);"));
+    }
+
+    #[test]
+    fn type_errors() {
+        assert!(error("distance = dot(1.0, 2.0)").contains("dot takes vectors"));
+        assert!(error("z = cross(z, 1.0)\ndistance = 0.0").contains("cross takes vectors"));
+        assert!(error("distance = length(1.0)").contains("length needs a vector"));
+        assert!(error("z = vec3(z, 1.0, 1.0)\ndistance = 0.0").contains("vec3 takes numbers"));
+        assert!(error("dr = z\ndistance = 0.0").contains("dr is a number, not a vector"));
+        assert!(error("distance = 1.0 < 2.0").contains("comparisons can only be used in if"));
+        assert!(error("if z > 1.0 { dr = 1.0 }\ndistance = 0.0").contains("only numbers"));
+        assert!(error("distance = atan2(z, 1.0)").contains("atan2 takes numbers"));
+        assert!(error("distance = min(1.0)").contains("min takes 2 argument(s)"));
+    }
+
+    #[test]
+    fn sandbox_errors() {
+        assert!(error("dr = 1.0").contains("never assigns distance"));
+        assert!(error("p = z\ndistance = 0.0").contains("p is read-only"));
+        assert!(error("distance = system(1.0)").contains("unknown function system"));
+        assert!(error("break if dr > 1.0\ndistance = 0.0").contains("break outside of the loop"));
+        assert!(error("loop { }\nloop { }\ndistance = 0.0").contains("one loop"));
+        assert!(error("distance = k").contains("unknown variable k"));
+        assert!(error("param k = 5.0 [0.0, 1.0]\ndistance = k").contains("outside [0, 1]"));
+        assert!(error("distance = 1.0 $ 2.0").contains("unexpected character '$'"));
+    }
+
+    #[test]
+    fn variables_leave_scope_with_their_block() {
+        let source = "loop {\n    k = 1.0\n}\ndistance = k";
+        assert_eq!(error(source), "line 4: unknown variable k");
+    }
+}
@@ -0,0 +1,306 @@
+use crate::midi::MidiClock;
+#[cfg(feature = "ui")]
+use crate::midi::TICKS_PER_BEAT;
+use crate::params::{Param, ParamChanged, ParamStore};
+use crate::power::keep_awake;
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// Pattern lengths offered in the UI
+const LENGTHS: [usize; 2] = [16, 32];
+
+/// Steps per beat, so each step is a sixteenth note
+const STEPS_PER_BEAT: u32 = 4;
+
+#[cfg(feature = "ui")]
+const CELL_SIZE: f32 = 16.0;
+
+pub struct SequencerPlugin;
+
+impl Plugin for SequencerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Sequencer>().add_systems(
+            Update,
+            (
+                run_sequencer,
+                keep_awake.run_if(|sequencer: Res<Sequencer>| sequencer.playing),
+            ),
+        );
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, sequencer_window);
+    }
+}
+
+/// Where the sequencer takes its tempo from
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TempoSource {
+    Internal,
+    /// Follows the clock and start/stop of the MIDI input
+    #[cfg(feature = "ui")]
+    MidiClock,
+}
+
+/// A parameter and the value it jumps to on each step. Steps without a value leave the
+/// parameter where it is.
+pub struct Row {
+    pub param: Param,
+    pub steps: Vec<Option<f32>>,
+    /// Slide from each value to the next set step instead of jumping
+    pub glide: bool,
+}
+
+impl Row {
+    #[cfg(feature = "ui")]
+    fn new(param: Param, length: usize) -> Self {
+        Self {
+            param,
+            steps: vec![None; length],
+            glide: false,
+        }
+    }
+
+    /// Value at a position in steps, `None` if no step is set
+    fn value_at(&self, position: f32) -> Option<f32> {
+        let len = self.steps.len();
+        let step = position as usize % len;
+        // the last set step at or before this one, wrapping around the pattern
+        let (from_step, from) = (0..len)
+            .map(|back| (step + len - back) % len)
+            .find_map(|i| self.steps[i].map(|value| (i, value)))?;
+        if !self.glide {
+            return Some(from);
+        }
+        let (to_step, to) = (1..=len)
+            .map(|ahead| (from_step + ahead) % len)
+            .find_map(|i| self.steps[i].map(|value| (i, value)))?;
+        let span = ((to_step + len - from_step - 1) % len + 1) as f32;
+        let into = (position - from_step as f32).rem_euclid(len as f32);
+        Some(from + (to - from) * (into / span).min(1.0))
+    }
+}
+
+/// Step sequencer, each row drives one parameter in time with the tempo
+#[derive(Resource)]
+pub struct Sequencer {
+    #[cfg(feature = "ui")]
+    pub open: bool,
+    pub playing: bool,
+    pub bpm: f32,
+    pub source: TempoSource,
+    /// Steps in the pattern, one of [`LENGTHS`]
+    pub length: usize,
+    pub rows: Vec<Row>,
+    /// Playhead in steps
+    position: f32,
+    /// Step being edited in the UI, as (row, step)
+    #[cfg(feature = "ui")]
+    selected: Option<(usize, usize)>,
+    #[cfg(feature = "ui")]
+    new_row: Param,
+}
+
+impl Default for Sequencer {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "ui")]
+            open: false,
+            playing: false,
+            bpm: 120.0,
+            source: TempoSource::Internal,
+            length: LENGTHS[0],
+            rows: Vec::new(),
+            position: 0.0,
+            #[cfg(feature = "ui")]
+            selected: None,
+            #[cfg(feature = "ui")]
+            new_row: Param::Power,
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+impl Sequencer {
+    fn set_length(&mut self, length: usize) {
+        self.length = length;
+        for row in &mut self.rows {
+            row.steps.resize(length, None);
+        }
+        self.position %= length as f32;
+        self.selected = self.selected.filter(|(_, step)| *step < length);
+    }
+}
+
+fn run_sequencer(
+    time: Res<Time>,
+    mut clock: MessageReader<MidiClock>,
+    mut sequencer: ResMut<Sequencer>,
+    store: Res<ParamStore>,
+    mut changes: MessageWriter<ParamChanged>,
+) {
+    let sequencer = &mut *sequencer;
+    let mut advance = 0.0;
+    match sequencer.source {
+        TempoSource::Internal => {
+            if sequencer.playing {
+                advance = time.delta_secs() * sequencer.bpm / 60.0 * STEPS_PER_BEAT as f32;
+            }
+            clock.clear();
+        }
+        #[cfg(feature = "ui")]
+        TempoSource::MidiClock => {
+            let ticks_per_step = (TICKS_PER_BEAT / STEPS_PER_BEAT) as f32;
+            for message in clock.read() {
+                match message {
+                    MidiClock::Start => {
+                        sequencer.playing = true;
+                        sequencer.position = 0.0;
+                        advance = 0.0;
+                    }
+                    MidiClock::Stop => sequencer.playing = false,
+                    MidiClock::Tick if sequencer.playing => advance += 1.0 / ticks_per_step,
+                    MidiClock::Tick => {}
+                }
+            }
+        }
+    }
+    if !sequencer.playing {
+        return;
+    }
+    sequencer.position = (sequencer.position + advance) % sequencer.length as f32;
+
+    for row in &sequencer.rows {
+        if let Some(value) = row.value_at(sequencer.position)
+            && value != store.get(row.param)
+        {
+            changes.write(ParamChanged {
+                param: row.param,
+                value,
+            });
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+fn sequencer_window(
+    mut contexts: EguiContexts,
+    mut sequencer: ResMut<Sequencer>,
+    store: Res<ParamStore>,
+) {
+    if !sequencer.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let sequencer = &mut *sequencer;
+
+    let mut open = sequencer.open;
+    let mut remove = None;
+    egui::Window::new("Sequencer")
+        .open(&mut open)
+        .default_width(640.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let label = if sequencer.playing { "Stop" } else { "Play" };
+                if ui.button(label).clicked() {
+                    sequencer.playing = !sequencer.playing;
+                    sequencer.position = 0.0;
+                }
+                ui.selectable_value(&mut sequencer.source, TempoSource::Internal, "Internal");
+                ui.selectable_value(&mut sequencer.source, TempoSource::MidiClock, "MIDI Clock");
+                ui.add_enabled(
+                    sequencer.source == TempoSource::Internal,
+                    egui::DragValue::new(&mut sequencer.bpm)
+                        .range(20.0..=300.0)
+                        .suffix(" BPM"),
+                );
+                let mut length = sequencer.length;
+                for option in LENGTHS {
+                    ui.selectable_value(&mut length, option, format!("{option} steps"));
+                }
+                if length != sequencer.length {
+                    sequencer.set_length(length);
+                }
+            });
+            ui.separator();
+
+            let playhead = sequencer.playing.then_some(sequencer.position as usize);
+            for (r, row) in sequencer.rows.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.add_sized([110.0, CELL_SIZE], egui::Label::new(row.param.name()));
+                    let range = row.param.range();
+                    for (s, step) in row.steps.iter_mut().enumerate() {
+                        // brightness shows the value within the parameter's range
+                        let fill = match step {
+                            Some(value) => {
+                                let t = (*value - range.start()) / (range.end() - range.start());
+                                let level = 80 + (t.clamp(0.0, 1.0) * 175.0) as u8;
+                                egui::Color32::from_rgb(level / 3, level / 2, level)
+                            }
+                            None if s % STEPS_PER_BEAT as usize == 0 => {
+                                egui::Color32::from_gray(50)
+                            }
+                            None => egui::Color32::from_gray(35),
+                        };
+                        let mut button = egui::Button::new("")
+                            .fill(fill)
+                            .min_size(egui::Vec2::splat(CELL_SIZE));
+                        if playhead == Some(s) {
+                            button = button.stroke(egui::Stroke::new(1.5, egui::Color32::WHITE));
+                        } else if sequencer.selected == Some((r, s)) {
+                            button = button.stroke(egui::Stroke::new(1.5, egui::Color32::YELLOW));
+                        }
+                        let response = ui
+                            .add(button)
+                            .on_hover_text("Click to set and select, right-click to clear");
+                        if response.clicked() {
+                            step.get_or_insert(store.get(row.param));
+                            sequencer.selected = Some((r, s));
+                        }
+                        if response.secondary_clicked() {
+                            *step = None;
+                        }
+                    }
+                    ui.checkbox(&mut row.glide, "Glide");
+                    if ui.small_button("x").clicked() {
+                        remove = Some(r);
+                    }
+                });
+            }
+
+            // value of the selected step
+            if let Some((r, s)) = sequencer.selected
+                && let Some(row) = sequencer.rows.get_mut(r)
+                && let Some(value) = &mut row.steps[s]
+            {
+                let range = row.param.range();
+                ui.add(
+                    egui::Slider::new(value, *range.start()..=*range.end()).text(format!(
+                        "{} step {}",
+                        row.param.name(),
+                        s + 1
+                    )),
+                );
+            }
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_salt("sequencer_param")
+                    .selected_text(sequencer.new_row.name())
+                    .show_ui(ui, |ui| {
+                        for param in Param::ALL {
+                            ui.selectable_value(&mut sequencer.new_row, param, param.name());
+                        }
+                    });
+                if ui.button("Add Row").clicked() {
+                    let row = Row::new(sequencer.new_row, sequencer.length);
+                    sequencer.rows.push(row);
+                }
+            });
+        });
+
+    if let Some(index) = remove {
+        sequencer.rows.remove(index);
+        sequencer.selected = None;
+    }
+    sequencer.open = open;
+}
@@ -0,0 +1,175 @@
+//! Node graph for surface color. The graph is compiled to the `fractal3d::shading_graph` shader
+//! module, which the fractal shader imports when the material's `custom_shading` flag is set.
+
+#[cfg(feature = "ui")]
+use crate::node_graph::Graph;
+use crate::node_graph::{Node, NodeGraphPlugin, NodeKind, Port, PortType, port};
+#[cfg(feature = "ui")]
+use crate::palette::CosinePalette;
+use bevy::asset::uuid_handle;
+use bevy::prelude::*;
+
+/// Imported by the fractal shader as `fractal3d::shading_graph`
+const SHADING_GRAPH_SHADER: Handle<Shader> = uuid_handle!("6c1f3b9e-2f4a-4f7e-9d3c-5a8e1b2c7d40");
+
+/// Start of the generated module, helpers used by the nodes
+const GRAPH_PRELUDE: &str = r#"#define_import_path fractal3d::shading_graph
+
+fn graph_hash(p: vec3<f32>) -> f32 {
+    return fract(sin(dot(p, vec3<f32>(127.1, 311.7, 74.7))) * 43758.5453);
+}
+
+// trilinear value noise in [0, 1]
+fn graph_noise(p: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    let x00 = mix(graph_hash(i), graph_hash(i + vec3<f32>(1.0, 0.0, 0.0)), u.x);
+    let x10 = mix(graph_hash(i + vec3<f32>(0.0, 1.0, 0.0)), graph_hash(i + vec3<f32>(1.0, 1.0, 0.0)), u.x);
+    let x01 = mix(graph_hash(i + vec3<f32>(0.0, 0.0, 1.0)), graph_hash(i + vec3<f32>(1.0, 0.0, 1.0)), u.x);
+    let x11 = mix(graph_hash(i + vec3<f32>(0.0, 1.0, 1.0)), graph_hash(i + vec3<f32>(1.0)), u.x);
+    return mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z);
+}
+"#;
+
+#[cfg(feature = "ui")]
+pub type ShadingGraph = Graph<ShadingNode>;
+
+pub type ShadingGraphPlugin = NodeGraphPlugin<ShadingNode>;
+
+// only the default graph's nodes are built until the editor adds the others
+#[cfg_attr(not(feature = "ui"), allow(dead_code))]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ShadingNode {
+    Orbit,
+    Normal,
+    Depth,
+    Steps,
+    Position,
+    Color,
+    Noise,
+    Palette,
+    Add,
+    Multiply,
+    Mix,
+    Output,
+}
+
+const fn value(name: &'static str, default: f32) -> Port {
+    port(name, PortType::Value, default)
+}
+
+impl NodeKind for ShadingNode {
+    const TITLE: &'static str = "Shading Graph";
+    #[cfg(feature = "ui")]
+    const ADDABLE: &'static [Self] = &[
+        ShadingNode::Orbit,
+        ShadingNode::Normal,
+        ShadingNode::Depth,
+        ShadingNode::Steps,
+        ShadingNode::Position,
+        ShadingNode::Color,
+        ShadingNode::Noise,
+        ShadingNode::Palette,
+        ShadingNode::Add,
+        ShadingNode::Multiply,
+        ShadingNode::Mix,
+    ];
+    const OUTPUT: Self = ShadingNode::Output;
+    const SHADER: Handle<Shader> = SHADING_GRAPH_SHADER;
+
+    #[cfg(feature = "ui")]
+    fn label(self) -> &'static str {
+        match self {
+            ShadingNode::Orbit => "Orbit Trap",
+            ShadingNode::Normal => "Normal",
+            ShadingNode::Depth => "Depth",
+            ShadingNode::Steps => "Step Count",
+            ShadingNode::Position => "Position",
+            ShadingNode::Color => "Color",
+            ShadingNode::Noise => "Noise",
+            ShadingNode::Palette => "Palette",
+            ShadingNode::Add => "Add",
+            ShadingNode::Multiply => "Multiply",
+            ShadingNode::Mix => "Mix",
+            ShadingNode::Output => "Surface Color",
+        }
+    }
+
+    fn inputs(self) -> &'static [Port] {
+        match self {
+            ShadingNode::Noise => const { &[value("Position", 0.0), value("Scale", 4.0)] },
+            ShadingNode::Palette => const { &[value("T", 0.0), value("Shift", 0.0)] },
+            ShadingNode::Add => const { &[value("A", 0.0), value("B", 0.0)] },
+            ShadingNode::Multiply => const { &[value("A", 1.0), value("B", 1.0)] },
+            ShadingNode::Mix => const { &[value("A", 0.0), value("B", 1.0), value("T", 0.5)] },
+            ShadingNode::Output => const { &[value("Color", 0.5)] },
+            _ => &[],
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    fn output(self) -> Option<PortType> {
+        (self != ShadingNode::Output).then_some(PortType::Value)
+    }
+
+    #[cfg(feature = "ui")]
+    fn has_color(self) -> bool {
+        self == ShadingNode::Color
+    }
+
+    /// The standard palette, shifted when the shift is a constant
+    #[cfg(feature = "ui")]
+    fn gradient(node: &Node<Self>) -> Option<CosinePalette> {
+        if node.kind != ShadingNode::Palette {
+            return None;
+        }
+        let shift = if node.links[1].is_none() {
+            node.values[1]
+        } else {
+            0.0
+        };
+        Some(CosinePalette::STANDARD.shifted(shift))
+    }
+
+    fn code(self, node: &Node<Self>, inputs: &[String]) -> String {
+        let [r, g, b] = node.color;
+        match self {
+            ShadingNode::Orbit => "vec3<f32>(orbit)".to_string(),
+            ShadingNode::Normal => "normal * 0.5 + 0.5".to_string(),
+            ShadingNode::Depth => "vec3<f32>(depth)".to_string(),
+            ShadingNode::Steps => "vec3<f32>(steps)".to_string(),
+            ShadingNode::Position => "position".to_string(),
+            ShadingNode::Color => format!("vec3<f32>({r:?}, {g:?}, {b:?})"),
+            ShadingNode::Noise => {
+                format!("vec3<f32>(graph_noise({} * {}.x))", inputs[0], inputs[1])
+            }
+            // the standard cosine palette of the fractal shader
+            ShadingNode::Palette => format!(
+                "0.5 + 0.5 * cos(6.28318 * ({}.x + vec3<f32>(0.263, 0.416, 0.557) + {}.x))",
+                inputs[0], inputs[1]
+            ),
+            ShadingNode::Add => format!("{} + {}", inputs[0], inputs[1]),
+            ShadingNode::Multiply => format!("{} * {}", inputs[0], inputs[1]),
+            ShadingNode::Mix => format!("mix({}, {}, {}.x)", inputs[0], inputs[1], inputs[2]),
+            ShadingNode::Output => inputs[0].clone(),
+        }
+    }
+
+    /// Orbit trap through a palette, close to the built-in coloring
+    fn default_nodes() -> Vec<Node<Self>> {
+        vec![
+            Node::new(ShadingNode::Orbit, Vec2::new(20.0, 40.0)),
+            Node::new(ShadingNode::Palette, Vec2::new(200.0, 40.0)).linked(0, 0),
+            Node::new(ShadingNode::Output, Vec2::new(400.0, 40.0)).linked(0, 1),
+        ]
+    }
+
+    fn shader_source(body: &str) -> String {
+        format!(
+            "{GRAPH_PRELUDE}\n\
+             fn graph_color(orbit: f32, normal: vec3<f32>, depth: f32, steps: f32, \
+             position: vec3<f32>) -> vec3<f32> {{\n{body}}}\n"
+        )
+    }
+}
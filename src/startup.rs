@@ -0,0 +1,225 @@
+//! What the viewer opens with: the built-in defaults, the view the last session ended on, a saved
+//! view or a random one from a folder of them. Views given on the command line with `--view` or
+//! `--preset` always win.
+
+use crate::args;
+use crate::config::{config_file, write_config};
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use crate::navigation::{StartupView, View};
+use crate::params::ParamStore;
+use crate::rng::Rng;
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::egui;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+const STARTUP_FILE: &str = "startup.ron";
+
+/// The share code of the view on screen when the app last closed
+const LAST_SESSION_FILE: &str = "last_session.view";
+
+pub struct StartupPlugin;
+
+impl Plugin for StartupPlugin {
+    fn build(&self, app: &mut App) {
+        let scene = StartupScene::load();
+        let given = args::value("--view").is_some() || args::value("--preset").is_some();
+        if !given {
+            match scene.view() {
+                Some(Ok(view)) => {
+                    app.insert_resource(StartupView(view));
+                }
+                Some(Err(err)) => warn!("Starting with the defaults: {err}"),
+                None => {}
+            }
+        }
+        app.insert_resource(scene)
+            // after the window closing systems, which send the exit
+            .add_systems(Last, save_last_session);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum StartupKind {
+    /// The built-in Mandelbulb
+    #[default]
+    Defaults,
+    /// Where the last session left off
+    LastSession,
+    /// A `.view` file, like the ones saved next to stills
+    Preset,
+    /// A random `.view` file from a folder
+    RandomPreset,
+}
+
+#[cfg(feature = "ui")]
+impl StartupKind {
+    pub const ALL: [StartupKind; 4] = [
+        StartupKind::Defaults,
+        StartupKind::LastSession,
+        StartupKind::Preset,
+        StartupKind::RandomPreset,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            StartupKind::Defaults => "Defaults",
+            StartupKind::LastSession => "Last Session",
+            StartupKind::Preset => "Saved View",
+            StartupKind::RandomPreset => "Random Saved View",
+        }
+    }
+}
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct StartupScene {
+    pub kind: StartupKind,
+    /// The `.view` file opened by [`StartupKind::Preset`]
+    pub preset: String,
+    /// The folder [`StartupKind::RandomPreset`] picks from
+    pub folder: String,
+}
+
+impl Default for StartupScene {
+    fn default() -> Self {
+        Self {
+            kind: StartupKind::Defaults,
+            preset: String::new(),
+            folder: "screenshots".to_string(),
+        }
+    }
+}
+
+impl StartupScene {
+    fn load() -> Self {
+        let path = config_file(STARTUP_FILE);
+        match std::fs::read_to_string(&path) {
+            Ok(text) => ron::from_str(&text).unwrap_or_else(|err| {
+                warn!("Ignoring {}: {err}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    #[cfg(feature = "ui")]
+    fn save(&self) {
+        let path = config_file(STARTUP_FILE);
+        let result = ron::ser::to_string_pretty(self, default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| write_config(&path, &text));
+        if let Err(err) = result {
+            error!("Failed to save {}: {err}", path.display());
+        }
+    }
+
+    /// The view to open, `None` for the defaults
+    fn view(&self) -> Option<Result<View, String>> {
+        let path = match self.kind {
+            StartupKind::Defaults => return None,
+            StartupKind::LastSession => config_file(LAST_SESSION_FILE),
+            StartupKind::Preset => self.preset.clone().into(),
+            StartupKind::RandomPreset => match random_view_file(Path::new(&self.folder)) {
+                Ok(path) => path,
+                Err(err) => return Some(Err(err)),
+            },
+        };
+        // no last session yet is the one expected gap
+        if self.kind == StartupKind::LastSession && !path.exists() {
+            return None;
+        }
+        Some(
+            std::fs::read_to_string(&path)
+                .map_err(|err| format!("{}: {err}", path.display()))
+                .and_then(|code| View::parse(&code))
+                .inspect(|_| info!("Opening {}", path.display())),
+        )
+    }
+
+    /// Every saved view the scene can open, whichever it picks this time: the last session, the
+    /// view file and each one in the random folder. Files that don't parse are left out.
+    pub fn saved_views(&self) -> Vec<View> {
+        let folder = view_files(Path::new(&self.folder)).unwrap_or_default();
+        [config_file(LAST_SESSION_FILE), PathBuf::from(&self.preset)]
+            .into_iter()
+            .chain(folder)
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .filter_map(|code| View::parse(&code).ok())
+            .collect()
+    }
+
+    /// Choices for the settings window, saved as they change
+    #[cfg(feature = "ui")]
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        let before = self.clone();
+        egui::ComboBox::from_label("On Launch")
+            .selected_text(self.kind.label())
+            .show_ui(ui, |ui| {
+                for kind in StartupKind::ALL {
+                    ui.selectable_value(&mut self.kind, kind, kind.label());
+                }
+            });
+        match self.kind {
+            StartupKind::Preset => {
+                ui.horizontal(|ui| {
+                    ui.label("View File");
+                    ui.text_edit_singleline(&mut self.preset)
+                        .on_hover_text("A .view file, like the ones saved next to stills");
+                });
+            }
+            StartupKind::RandomPreset => {
+                ui.horizontal(|ui| {
+                    ui.label("Folder");
+                    ui.text_edit_singleline(&mut self.folder)
+                        .on_hover_text("Opens one of the .view files in it");
+                });
+            }
+            StartupKind::Defaults | StartupKind::LastSession => {}
+        }
+        if *self != before {
+            self.save();
+        }
+    }
+}
+
+/// One of the `.view` files in `folder`, from the run's random seed
+fn random_view_file(folder: &Path) -> Result<PathBuf, String> {
+    let mut views = view_files(folder).map_err(|err| format!("{}: {err}", folder.display()))?;
+    if views.is_empty() {
+        return Err(format!("no .view files in {}", folder.display()));
+    }
+    let i = Rng::stream("startup").next_u64() % views.len() as u64;
+    Ok(views.swap_remove(i as usize))
+}
+
+/// The `.view` files in `folder`, sorted by name. Directory order differs between systems, a seed
+/// should pick the same file everywhere.
+fn view_files(folder: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut views: Vec<_> = std::fs::read_dir(folder)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "view"))
+        .collect();
+    views.sort();
+    Ok(views)
+}
+
+/// Keeps the main view on exit, for starting with the last session
+fn save_last_session(
+    mut exit: MessageReader<AppExit>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    store: Res<ParamStore>,
+) {
+    if exit.read().count() == 0 {
+        return;
+    }
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let path = config_file(LAST_SESSION_FILE);
+    if let Err(err) = write_config(&path, &View::share_code(&mat.camera, &store)) {
+        error!("Failed to save {}: {err}", path.display());
+    }
+}
@@ -0,0 +1,211 @@
+use crate::estimator::CpuEstimator;
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use crate::power::keep_awake;
+use crate::rng::Rng;
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// Points evaluated per frame while sampling
+const SAMPLES_PER_FRAME: u32 = 20_000;
+
+/// z-score of a 95% confidence interval
+#[cfg(feature = "ui")]
+const Z_95: f64 = 1.96;
+
+/// Monte Carlo estimates of the fractal's volume and surface area, using the CPU estimator
+pub struct StatisticsPlugin;
+
+impl Plugin for StatisticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Statistics>().add_systems(
+            Update,
+            (
+                sample_statistics,
+                keep_awake.run_if(|statistics: Res<Statistics>| statistics.running),
+            ),
+        );
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, statistics_window);
+    }
+}
+
+/// Uniform samples in a cube around the origin, counted as inside the solid when the estimate
+/// is below the hit threshold, like a ray would stop there, and as on the surface when they lie
+/// in a thin shell just outside it. Volume is the inside fraction of the cube, area is the shell
+/// volume divided by its thickness. Both describe the fractal at the threshold's resolution, a
+/// finer threshold finds more surface.
+#[derive(Resource)]
+pub struct Statistics {
+    #[cfg(feature = "ui")]
+    pub open: bool,
+    pub running: bool,
+    /// Half the side of the sampled cube, which has to contain the whole fractal
+    pub bounds: f32,
+    /// Thickness of the surface shell
+    pub shell: f32,
+    samples: u64,
+    inside: u64,
+    on_surface: u64,
+    /// What the counts were sampled from, they restart when it changes
+    sampled: Option<(CpuEstimator, f32, f32, f32)>,
+    rng: Rng,
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "ui")]
+            open: false,
+            running: false,
+            bounds: 2.0,
+            shell: 0.005,
+            samples: 0,
+            inside: 0,
+            on_surface: 0,
+            sampled: None,
+            rng: Rng::stream("statistics"),
+        }
+    }
+}
+
+/// An estimate and the half width of its 95% confidence interval
+#[cfg(feature = "ui")]
+struct Estimate {
+    value: f64,
+    error: f64,
+}
+
+impl Statistics {
+    fn reset(&mut self) {
+        self.samples = 0;
+        self.inside = 0;
+        self.on_surface = 0;
+    }
+
+    /// Scales the fraction of samples in a region to a measure, with the binomial standard
+    /// error of the fraction
+    #[cfg(feature = "ui")]
+    fn estimate(&self, count: u64, scale: f64) -> Option<Estimate> {
+        if self.samples == 0 {
+            return None;
+        }
+        let n = self.samples as f64;
+        let fraction = count as f64 / n;
+        let std_error = (fraction * (1.0 - fraction) / n).sqrt();
+        Some(Estimate {
+            value: fraction * scale,
+            error: Z_95 * std_error * scale,
+        })
+    }
+
+    #[cfg(feature = "ui")]
+    fn cube_volume(&self) -> f64 {
+        (2.0 * self.bounds as f64).powi(3)
+    }
+
+    #[cfg(feature = "ui")]
+    fn volume(&self) -> Option<Estimate> {
+        self.estimate(self.inside, self.cube_volume())
+    }
+
+    #[cfg(feature = "ui")]
+    fn area(&self) -> Option<Estimate> {
+        self.estimate(self.on_surface, self.cube_volume() / self.shell as f64)
+    }
+}
+
+fn sample_statistics(
+    mut stats: ResMut<Statistics>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    if !stats.running {
+        return;
+    }
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let Some(estimator) = CpuEstimator::new(mat) else {
+        stats.running = false;
+        return;
+    };
+    let threshold = mat.shape.hit_threshold;
+    let sampled = Some((estimator, threshold, stats.bounds, stats.shell));
+    if stats.sampled != sampled {
+        stats.sampled = sampled;
+        stats.reset();
+    }
+
+    for _ in 0..SAMPLES_PER_FRAME {
+        let p = Vec3::new(
+            stats.rng.next_f32(),
+            stats.rng.next_f32(),
+            stats.rng.next_f32(),
+        ) * 2.0
+            - 1.0;
+        let d = estimator.distance(p * stats.bounds);
+        if d < threshold {
+            stats.inside += 1;
+        } else if d < threshold + stats.shell {
+            stats.on_surface += 1;
+        }
+    }
+    stats.samples += SAMPLES_PER_FRAME as u64;
+}
+
+#[cfg(feature = "ui")]
+fn statistics_window(
+    mut contexts: EguiContexts,
+    mut stats: ResMut<Statistics>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    if !stats.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let supported = materials
+        .get(&fractal.0)
+        .and_then(CpuEstimator::new)
+        .is_some();
+
+    let mut open = stats.open;
+    egui::Window::new("Statistics")
+        .open(&mut open)
+        .default_width(300.0)
+        .show(ctx, |ui| {
+            if !supported {
+                ui.label("Not available for plugin formulas and the geometry graph.");
+                return;
+            }
+            ui.horizontal(|ui| {
+                let label = if stats.running { "Stop" } else { "Start" };
+                if ui.button(label).clicked() {
+                    stats.running = !stats.running;
+                }
+                if ui.button("Reset").clicked() {
+                    stats.reset();
+                }
+            });
+            ui.add(egui::Slider::new(&mut stats.bounds, 0.5..=10.0).text("Bounds"))
+                .on_hover_text("Half the side of the sampled cube, it must contain the fractal");
+            ui.add(
+                egui::Slider::new(&mut stats.shell, 0.0005..=0.05)
+                    .logarithmic(true)
+                    .text("Shell"),
+            )
+            .on_hover_text("Thickness of the layer counted as surface");
+            ui.separator();
+
+            ui.label(format!("Samples: {}", stats.samples));
+            let show = |ui: &mut egui::Ui, name: &str, estimate: Option<Estimate>| match estimate {
+                Some(e) => ui.label(format!("{name}: {:.4} ± {:.4} (95%)", e.value, e.error)),
+                None => ui.label(format!("{name}: -")),
+            };
+            show(ui, "Volume", stats.volume());
+            show(ui, "Surface Area", stats.area());
+            ui.small("Measured at the current hit threshold, a finer one finds more surface.");
+        });
+    stats.open = open;
+}
@@ -0,0 +1,24 @@
+//! Binary STL writer for printable surfaces
+
+use crate::surface::SurfaceMesh;
+use std::path::Path;
+
+pub fn write_stl(path: &Path, mesh: &SurfaceMesh) -> std::io::Result<()> {
+    let mut stl = Vec::with_capacity(84 + mesh.triangle_count() * 50);
+    let mut header = [0u8; 80];
+    header[..9].copy_from_slice(b"fractal3D");
+    stl.extend_from_slice(&header);
+    stl.extend_from_slice(&(mesh.triangle_count() as u32).to_le_bytes());
+    for triangle in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| mesh.positions[triangle[i] as usize]);
+        // slicers expect the facet normal, which the winding defines
+        let normal = (b - a).cross(c - a).normalize_or_zero();
+        for v in [normal, a, b, c] {
+            for component in v.to_array() {
+                stl.extend_from_slice(&component.to_le_bytes());
+            }
+        }
+        stl.extend_from_slice(&0u16.to_le_bytes());
+    }
+    std::fs::write(path, stl)
+}
@@ -0,0 +1,206 @@
+//! Triangle meshes of the fractal surface, extracted from the CPU distance estimator with
+//! surface nets: one vertex per grid cell the surface passes through, placed at the average of
+//! the crossings on the cell's edges, and one quad per grid edge the surface crosses.
+
+use crate::estimator::CpuEstimator;
+use bevy::prelude::*;
+
+/// An indexed triangle mesh, counter-clockwise seen from outside
+#[derive(Clone, Default)]
+pub struct SurfaceMesh {
+    pub positions: Vec<Vec3>,
+    pub normals: Vec<Vec3>,
+    /// Linear RGB per vertex, empty unless colors were baked
+    pub colors: Vec<Vec3>,
+    pub indices: Vec<u32>,
+}
+
+impl SurfaceMesh {
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Scales every vertex around the origin
+    pub fn scale(&mut self, factor: f32) {
+        for p in &mut self.positions {
+            *p *= factor;
+        }
+    }
+
+    /// Smallest box containing every vertex, as (min, max)
+    pub fn bounds(&self) -> (Vec3, Vec3) {
+        self.positions.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), p| (min.min(*p), max.max(*p)),
+        )
+    }
+}
+
+/// The level set at `level` within the cube of half size `bounds` around the origin, sampled
+/// on a grid of `resolution` cells per side. Pass the hit threshold as the level to get the
+/// surface the renderer shows.
+pub fn extract_surface(
+    estimator: &CpuEstimator,
+    level: f32,
+    bounds: f32,
+    resolution: u32,
+) -> SurfaceMesh {
+    let grid = Grid::new(bounds, resolution);
+    let values = grid.sample(|p| estimator.distance(p) - level);
+    extract_field(&grid, &values, |p| estimator.normal(p))
+}
+
+/// Sample points of the extraction grid, `samples` per side spanning the cube of half size
+/// `bounds`, stored x first
+#[derive(Clone, Copy, Debug)]
+pub struct Grid {
+    pub bounds: f32,
+    pub cells: usize,
+    pub samples: usize,
+    pub step: f32,
+}
+
+impl Grid {
+    pub fn new(bounds: f32, resolution: u32) -> Self {
+        let cells = resolution.max(1) as usize;
+        Self {
+            bounds,
+            cells,
+            samples: cells + 1,
+            step: 2.0 * bounds / cells as f32,
+        }
+    }
+
+    pub fn point(&self, x: usize, y: usize, z: usize) -> Vec3 {
+        Vec3::new(x as f32, y as f32, z as f32) * self.step - Vec3::splat(self.bounds)
+    }
+
+    pub fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        x + self.samples * (y + self.samples * z)
+    }
+
+    /// `field` at every sample, in index order
+    pub fn sample(&self, field: impl Fn(Vec3) -> f32) -> Vec<f32> {
+        let mut values = Vec::with_capacity(self.samples.pow(3));
+        for z in 0..self.samples {
+            for y in 0..self.samples {
+                for x in 0..self.samples {
+                    values.push(field(self.point(x, y, z)));
+                }
+            }
+        }
+        values
+    }
+}
+
+/// The zero level set of sampled `values`, negative inside
+pub fn extract_field(grid: &Grid, values: &[f32], normal: impl Fn(Vec3) -> Vec3) -> SurfaceMesh {
+    let (cells, samples) = (grid.cells, grid.samples);
+    let point = |x: usize, y: usize, z: usize| grid.point(x, y, z);
+    let sample_index = |x: usize, y: usize, z: usize| grid.index(x, y, z);
+    let cell_index = |x: usize, y: usize, z: usize| x + cells * (y + cells * z);
+
+    const CORNERS: [(usize, usize, usize); 8] = [
+        (0, 0, 0),
+        (1, 0, 0),
+        (0, 1, 0),
+        (1, 1, 0),
+        (0, 0, 1),
+        (1, 0, 1),
+        (0, 1, 1),
+        (1, 1, 1),
+    ];
+    const EDGES: [(usize, usize); 12] = [
+        (0, 1),
+        (2, 3),
+        (4, 5),
+        (6, 7),
+        (0, 2),
+        (1, 3),
+        (4, 6),
+        (5, 7),
+        (0, 4),
+        (1, 5),
+        (2, 6),
+        (3, 7),
+    ];
+
+    let mut mesh = SurfaceMesh::default();
+    let mut cell_vertex = vec![u32::MAX; cells * cells * cells];
+    for z in 0..cells {
+        for y in 0..cells {
+            for x in 0..cells {
+                let corner = |i: usize| {
+                    let (dx, dy, dz) = CORNERS[i];
+                    (
+                        point(x + dx, y + dy, z + dz),
+                        values[sample_index(x + dx, y + dy, z + dz)],
+                    )
+                };
+                let mut sum = Vec3::ZERO;
+                let mut crossings = 0;
+                for (a, b) in EDGES {
+                    let ((pa, va), (pb, vb)) = (corner(a), corner(b));
+                    if (va < 0.0) != (vb < 0.0) {
+                        sum += pa.lerp(pb, va / (va - vb));
+                        crossings += 1;
+                    }
+                }
+                if crossings == 0 {
+                    continue;
+                }
+                let p = sum / crossings as f32;
+                cell_vertex[cell_index(x, y, z)] = mesh.positions.len() as u32;
+                mesh.positions.push(p);
+                mesh.normals.push(normal(p));
+            }
+        }
+    }
+
+    // every edge between samples the surface crosses is surrounded by four cells with vertices
+    for z in 0..samples {
+        for y in 0..samples {
+            for x in 0..samples {
+                let start = [x, y, z];
+                let inside = values[sample_index(x, y, z)] < 0.0;
+                for axis in 0..3 {
+                    let mut end = start;
+                    end[axis] += 1;
+                    if end[axis] == samples {
+                        continue;
+                    }
+                    if inside == (values[sample_index(end[0], end[1], end[2])] < 0.0) {
+                        continue;
+                    }
+                    // the other two axes, in cyclic order so the quad faces along `axis`
+                    let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+                    if start[b] == 0 || start[c] == 0 {
+                        continue;
+                    }
+                    let cell = |db: usize, dc: usize| {
+                        let mut at = start;
+                        at[b] -= db;
+                        at[c] -= dc;
+                        (at[0] < cells && at[1] < cells && at[2] < cells)
+                            .then(|| cell_vertex[cell_index(at[0], at[1], at[2])])
+                            .filter(|v| *v != u32::MAX)
+                    };
+                    let (Some(v0), Some(v1), Some(v2), Some(v3)) =
+                        (cell(0, 0), cell(1, 0), cell(1, 1), cell(0, 1))
+                    else {
+                        continue;
+                    };
+                    // outside lies towards the end of the edge when it starts inside
+                    let quad = if inside {
+                        [v0, v1, v2, v3]
+                    } else {
+                        [v0, v3, v2, v1]
+                    };
+                    mesh.indices
+                        .extend([quad[0], quad[1], quad[2], quad[0], quad[2], quad[3]]);
+                }
+            }
+        }
+    }
+    mesh
+}
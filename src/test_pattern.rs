@@ -0,0 +1,40 @@
+use crate::params::{Param, ParamChanged, ParamStore};
+use crate::power::keep_awake;
+use bevy::prelude::*;
+
+pub struct TestPatternPlugin;
+
+impl Plugin for TestPatternPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                test_pattern_hotkey,
+                keep_awake.run_if(|store: Res<ParamStore>| is_showing(&store)),
+            ),
+        );
+    }
+}
+
+/// F9 cycles through the calibration patterns and back to the fractal, so a projector or LED
+/// wall can be checked right before a show without touching the settings window
+fn test_pattern_hotkey(
+    keys: Res<ButtonInput<KeyCode>>,
+    store: Res<ParamStore>,
+    mut changes: MessageWriter<ParamChanged>,
+) {
+    if !keys.just_pressed(KeyCode::F9) {
+        return;
+    }
+    let count = Param::TestPattern.choices().len() as f32;
+    changes.write(ParamChanged {
+        param: Param::TestPattern,
+        value: (store.get(Param::TestPattern) + 1.0) % count,
+    });
+}
+
+/// Whether a test pattern is shown. Its A/V sync marker is driven by the animation clock, so the
+/// view has to redraw every frame while it is on.
+pub fn is_showing(store: &ParamStore) -> bool {
+    store.get(Param::TestPattern) > 0.0
+}
@@ -0,0 +1,156 @@
+use crate::params::ParamStore;
+use crate::power::LowPower;
+use bevy::prelude::*;
+use bevy::window::WindowResized;
+use bevy::winit::{UpdateMode, WinitSettings};
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use std::collections::VecDeque;
+
+/// Frames averaged into one frame time measurement
+const WINDOW_FRAMES: usize = 120;
+
+/// Average frame time, relative to the best seen for the same scene, that counts as slow
+const SLOW_RATIO: f32 = 1.4;
+
+/// Seconds frames have to stay slow before it counts as throttling rather than a busy moment
+const SUSTAINED_SECS: f32 = 15.0;
+
+/// Watches for frames getting slower while nothing about the scene changed, which on laptops and
+/// small form factor machines usually means the GPU is throttling because of heat. The user is
+/// told about it and can have the quality stepped down, automatically if they opt in.
+pub struct ThrottlePlugin;
+
+impl Plugin for ThrottlePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ThrottleMonitor>()
+            .add_systems(Update, monitor_frame_times);
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, throttle_notice);
+    }
+}
+
+#[derive(Resource)]
+pub struct ThrottleMonitor {
+    /// Step quality down without asking when throttling is detected
+    pub auto_reduce: bool,
+    frame_times: VecDeque<f32>,
+    /// Best average frame time since the scene last changed
+    baseline: Option<f32>,
+    /// Seconds the average has been slow for
+    slow_for: f32,
+    /// Throttling detected and not yet dismissed, as (baseline, current) frame times
+    notice: Option<(f32, f32)>,
+}
+
+impl Default for ThrottleMonitor {
+    fn default() -> Self {
+        Self {
+            auto_reduce: false,
+            frame_times: VecDeque::with_capacity(WINDOW_FRAMES),
+            baseline: None,
+            slow_for: 0.0,
+            notice: None,
+        }
+    }
+}
+
+impl ThrottleMonitor {
+    /// Forgets the measurements, for when the scene or quality changes and frame times are
+    /// expected to change with it
+    fn restart(&mut self) {
+        self.frame_times.clear();
+        self.baseline = None;
+        self.slow_for = 0.0;
+    }
+}
+
+fn monitor_frame_times(
+    mut monitor: ResMut<ThrottleMonitor>,
+    mut low_power: ResMut<LowPower>,
+    time: Res<Time>,
+    winit_settings: Res<WinitSettings>,
+    store: Res<ParamStore>,
+    mut resized: MessageReader<WindowResized>,
+) {
+    let resized = resized.read().count() > 0;
+    // frame times only say something about the GPU while rendering as fast as possible
+    let continuous = matches!(winit_settings.focused_mode, UpdateMode::Continuous);
+    if !continuous || resized || store.is_changed() || low_power.is_changed() {
+        monitor.restart();
+        return;
+    }
+
+    let monitor = &mut *monitor;
+    if monitor.frame_times.len() == WINDOW_FRAMES {
+        monitor.frame_times.pop_front();
+    }
+    monitor.frame_times.push_back(time.delta_secs());
+    if monitor.frame_times.len() < WINDOW_FRAMES {
+        return;
+    }
+    let average = monitor.frame_times.iter().sum::<f32>() / WINDOW_FRAMES as f32;
+    let baseline = *monitor.baseline.get_or_insert(average);
+    monitor.baseline = Some(baseline.min(average));
+
+    if average < baseline * SLOW_RATIO {
+        monitor.slow_for = 0.0;
+        return;
+    }
+    monitor.slow_for += time.delta_secs();
+    if monitor.slow_for >= SUSTAINED_SECS && monitor.notice.is_none() && !low_power.throttled {
+        warn!(
+            "Frames slowed from {:.1} ms to {:.1} ms, the GPU may be throttling",
+            baseline * 1000.0,
+            average * 1000.0
+        );
+        monitor.notice = Some((baseline, average));
+        if monitor.auto_reduce {
+            low_power.throttled = true;
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+fn throttle_notice(
+    mut contexts: EguiContexts,
+    mut monitor: ResMut<ThrottleMonitor>,
+    mut low_power: ResMut<LowPower>,
+) {
+    let Some((baseline, current)) = monitor.notice else {
+        return;
+    };
+    let ctx = contexts.ctx_mut().unwrap();
+    let mut dismissed = false;
+    egui::Window::new("Performance Dropped")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "Frames take {:.1} ms, up from {:.1} ms, without the scene changing.",
+                current * 1000.0,
+                baseline * 1000.0
+            ));
+            ui.label("The GPU is probably throttling because it got too hot.");
+            ui.horizontal(|ui| {
+                if low_power.throttled {
+                    ui.label("Quality was reduced.");
+                    if ui.button("Restore Quality").clicked() {
+                        low_power.throttled = false;
+                        dismissed = true;
+                    }
+                } else if ui.button("Reduce Quality").clicked() {
+                    low_power.throttled = true;
+                    dismissed = true;
+                }
+                if ui.button("Dismiss").clicked() {
+                    dismissed = true;
+                }
+            });
+            ui.checkbox(&mut monitor.auto_reduce, "Reduce automatically next time");
+        });
+    if dismissed {
+        monitor.notice = None;
+    }
+}
@@ -0,0 +1,273 @@
+//! Time-lapses of a working session: while capturing, a small copy of the whole window, panels
+//! included, is saved every few seconds, and on demand ffmpeg assembles the frames into a video
+//! of how the fractal came about.
+
+use crate::export::ExportSettings;
+use bevy::prelude::*;
+use bevy::render::view::screenshot::{Screenshot, ScreenshotCaptured};
+#[cfg(feature = "ui")]
+use bevy::tasks::AsyncComputeTaskPool;
+use bevy::tasks::{Task, block_on, poll_once};
+use bevy::window::PrimaryWindow;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use image::imageops::FilterType;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "ui")]
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub struct TimeLapsePlugin;
+
+impl Plugin for TimeLapsePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TimeLapse>()
+            .add_systems(Update, (capture_time_lapse, poll_time_lapse_video));
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, time_lapse_window);
+    }
+}
+
+#[derive(Resource)]
+pub struct TimeLapse {
+    #[cfg(feature = "ui")]
+    pub open: bool,
+    pub capturing: bool,
+    /// Seconds between frames
+    pub interval_secs: f32,
+    /// Width of the frames, the height follows the window
+    pub width: u32,
+    /// Frame rate of the assembled video
+    #[cfg(feature = "ui")]
+    pub fps: u32,
+    /// The session's frame folder, frames keep going to it when capturing resumes
+    directory: Option<PathBuf>,
+    /// Every frame of a session has the size of the first, as the video needs
+    size: Option<UVec2>,
+    frames: u32,
+    since_capture: f32,
+    task: Option<Task<Result<String, String>>>,
+    /// Outcome of the last video
+    #[cfg(feature = "ui")]
+    status: Option<Result<String, String>>,
+}
+
+impl Default for TimeLapse {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "ui")]
+            open: false,
+            capturing: false,
+            interval_secs: 5.0,
+            width: 480,
+            #[cfg(feature = "ui")]
+            fps: 24,
+            directory: None,
+            size: None,
+            frames: 0,
+            since_capture: 0.0,
+            task: None,
+            #[cfg(feature = "ui")]
+            status: None,
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+impl TimeLapse {
+    /// Forgets the current session, the next capture starts a new folder
+    fn new_session(&mut self) {
+        self.directory = None;
+        self.size = None;
+        self.frames = 0;
+        self.status = None;
+    }
+}
+
+/// Takes a screenshot of the window every interval while capturing. The app only redraws when
+/// something changes, so idle stretches are skipped rather than filling the video with still
+/// frames.
+fn capture_time_lapse(
+    mut commands: Commands,
+    time: Res<Time<Real>>,
+    mut lapse: ResMut<TimeLapse>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    settings: Res<ExportSettings>,
+) {
+    if !lapse.capturing {
+        return;
+    }
+    lapse.since_capture += time.delta_secs();
+    if lapse.frames > 0 && lapse.since_capture < lapse.interval_secs {
+        return;
+    }
+    let Ok(window) = window.single() else {
+        return;
+    };
+    lapse.since_capture = 0.0;
+
+    let width = lapse.width;
+    let size = *lapse.size.get_or_insert_with(|| {
+        // even sides, which the video encoder needs
+        let height = (width as f32 / window.width() * window.height()) as u32;
+        UVec2::new(width & !1, (height & !1).max(2))
+    });
+    let directory = lapse
+        .directory
+        .get_or_insert_with(|| {
+            settings.directory.join(format!(
+                "timelapse_{}",
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|d| d.as_millis())
+                    .unwrap_or_default()
+            ))
+        })
+        .clone();
+    let path = directory.join(format!("frame_{:05}.png", lapse.frames));
+    lapse.frames += 1;
+
+    commands.spawn(Screenshot::primary_window()).observe(
+        move |captured: On<ScreenshotCaptured>| {
+            if let Err(err) = save_frame(&captured.image, size, &directory, &path) {
+                error!("Failed to save {}: {err}", path.display());
+            }
+        },
+    );
+}
+
+/// Shrinks a screenshot to the session's frame size and writes it as PNG
+fn save_frame(
+    screenshot: &Image,
+    size: UVec2,
+    directory: &Path,
+    path: &Path,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let frame = screenshot
+        .clone()
+        .try_into_dynamic()?
+        .resize_exact(size.x, size.y, FilterType::Triangle)
+        .to_rgb8();
+    std::fs::create_dir_all(directory)?;
+    frame.save(path)?;
+    Ok(())
+}
+
+/// Encodes the session's frames into an MP4 next to its folder, on a background thread
+#[cfg(feature = "ui")]
+fn make_video(directory: PathBuf, fps: u32) -> Task<Result<String, String>> {
+    AsyncComputeTaskPool::get().spawn(async move {
+        let video = directory.with_extension("mp4");
+        let output = Command::new("ffmpeg")
+            .args(["-y", "-loglevel", "error", "-framerate", &fps.to_string()])
+            .arg("-i")
+            .arg(directory.join("frame_%05d.png"))
+            .args(["-c:v", "libx264", "-pix_fmt", "yuv420p"])
+            .arg(&video)
+            .output()
+            .map_err(|err| format!("couldn't run ffmpeg, is it installed? {err}"))?;
+        if output.status.success() {
+            Ok(format!("Saved {}", video.display()))
+        } else {
+            Err(format!(
+                "ffmpeg failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    })
+}
+
+fn poll_time_lapse_video(mut lapse: ResMut<TimeLapse>) {
+    if let Some(task) = &mut lapse.task
+        && let Some(result) = block_on(poll_once(task))
+    {
+        match &result {
+            Ok(message) => info!("{message}"),
+            Err(err) => error!("Time-lapse video failed: {err}"),
+        }
+        lapse.task = None;
+        #[cfg(feature = "ui")]
+        {
+            lapse.status = Some(result);
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+fn time_lapse_window(mut contexts: EguiContexts, mut lapse: ResMut<TimeLapse>) {
+    if !lapse.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let lapse = &mut *lapse;
+
+    let mut open = lapse.open;
+    egui::Window::new("Time-Lapse")
+        .open(&mut open)
+        .default_width(300.0)
+        .show(ctx, |ui| {
+            ui.checkbox(&mut lapse.capturing, "Capture")
+                .on_hover_text("Saves a small copy of the window every interval");
+            ui.add(
+                egui::Slider::new(&mut lapse.interval_secs, 1.0..=60.0)
+                    .suffix(" s")
+                    .text("Interval"),
+            );
+            // the frames of a session all have the same size
+            ui.add_enabled(
+                lapse.frames == 0,
+                egui::Slider::new(&mut lapse.width, 240..=1280)
+                    .suffix(" px")
+                    .text("Width"),
+            );
+            ui.add(egui::Slider::new(&mut lapse.fps, 10..=60).text("Video Frames per Second"));
+
+            ui.separator();
+            match &lapse.directory {
+                Some(directory) => ui.label(format!(
+                    "{} frames in {}, {:.1} s of video",
+                    lapse.frames,
+                    directory.display(),
+                    lapse.frames as f32 / lapse.fps as f32
+                )),
+                None => ui.label("No frames yet"),
+            };
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        lapse.frames >= 2 && lapse.task.is_none(),
+                        egui::Button::new("Make Video"),
+                    )
+                    .on_hover_text("Encodes the frames with ffmpeg, which has to be installed")
+                    .clicked()
+                    && let Some(directory) = &lapse.directory
+                {
+                    lapse.task = Some(make_video(directory.clone(), lapse.fps));
+                    lapse.status = None;
+                }
+                if ui
+                    .add_enabled(lapse.directory.is_some(), egui::Button::new("New Session"))
+                    .on_hover_text("Keeps these frames and starts a new folder")
+                    .clicked()
+                {
+                    lapse.new_session();
+                }
+            });
+            if lapse.task.is_some() {
+                ui.horizontal(|ui| {
+                    ui.spinner();
+                    ui.label("Encoding...");
+                });
+            }
+            match &lapse.status {
+                Some(Ok(message)) => {
+                    ui.label(message);
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(egui::Color32::LIGHT_RED, err);
+                }
+                None => {}
+            }
+        });
+    lapse.open = open;
+}
@@ -0,0 +1,409 @@
+use crate::easing::Easing;
+#[cfg(feature = "ui")]
+use crate::easing::easing_combo;
+use crate::expr::{Expression, Vars};
+use crate::params::{EPSILON, Param, ParamChanged, ParamStore};
+use crate::power::keep_awake;
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+pub struct TimelinePlugin;
+
+impl Plugin for TimelinePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Timeline>().add_systems(
+            Update,
+            (
+                play_timeline,
+                keep_awake.run_if(|timeline: Res<Timeline>| timeline.playing),
+            ),
+        );
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, timeline_window);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    pub time: f32,
+    pub value: f32,
+    /// Pace of the segment from this keyframe to the next
+    pub easing: Easing,
+}
+
+/// Animates one parameter. The value is the keyframes interpolated at the current time, the
+/// expression evaluated at the current time, or a blend of both.
+pub struct Track {
+    pub param: Param,
+    /// Sorted by time
+    keyframes: Vec<Keyframe>,
+    pub expression: Option<Expression>,
+    /// Text being edited, parsed into `expression` while it is valid
+    #[cfg(feature = "ui")]
+    expression_text: String,
+    #[cfg(feature = "ui")]
+    expression_error: Option<String>,
+    /// 0 plays the keyframes, 1 the expression, values in between mix the two
+    pub blend: f32,
+    /// Value of the parameter when the track was added, `key` while there are no keyframes
+    base: f32,
+}
+
+impl Track {
+    #[cfg(feature = "ui")]
+    pub fn new(param: Param, base: f32) -> Self {
+        Self {
+            param,
+            keyframes: Vec::new(),
+            expression: None,
+            #[cfg(feature = "ui")]
+            expression_text: String::new(),
+            #[cfg(feature = "ui")]
+            expression_error: None,
+            blend: 0.0,
+            base,
+        }
+    }
+
+    /// Replaces the expression, an empty text removes it
+    #[cfg(feature = "ui")]
+    pub fn set_expression(&mut self, text: &str) {
+        self.expression_text = text.to_string();
+        if text.trim().is_empty() {
+            self.expression = None;
+            self.expression_error = None;
+            return;
+        }
+        match Expression::parse(text) {
+            Ok(expression) => {
+                self.expression = Some(expression);
+                self.expression_error = None;
+            }
+            Err(err) => self.expression_error = Some(err.to_string()),
+        }
+    }
+
+    /// Adds a keyframe, replacing the value of one at the same time
+    #[cfg(feature = "ui")]
+    pub fn set_key(&mut self, time: f32, value: f32) {
+        let index = self.keyframes.partition_point(|k| k.time < time);
+        match self.keyframes.get_mut(index) {
+            Some(key) if (key.time - time).abs() < EPSILON => key.value = value,
+            _ => self.keyframes.insert(
+                index,
+                Keyframe {
+                    time,
+                    value,
+                    easing: Easing::Linear,
+                },
+            ),
+        }
+    }
+
+    /// Interpolation between keyframes with each segment's easing, holding the first and last
+    /// value outside them
+    fn keyed(&self, time: f32) -> Option<f32> {
+        let index = self.keyframes.partition_point(|k| k.time <= time);
+        match (
+            index.checked_sub(1).map(|i| self.keyframes[i]),
+            self.keyframes.get(index).copied(),
+        ) {
+            (Some(a), Some(b)) => {
+                let t = (time - a.time) / (b.time - a.time).max(EPSILON);
+                Some(a.value + (b.value - a.value) * a.easing.apply(t))
+            }
+            (Some(key), None) | (None, Some(key)) => Some(key.value),
+            (None, None) => None,
+        }
+    }
+
+    /// Value at `time`, the parameter's value from before the track stands in for the keyframes
+    /// while there are none. Not the current value, which the track itself wrote last frame and
+    /// an expression reading `key` would build on. `None` if the track has neither keyframes
+    /// nor an expression.
+    pub fn sample(&self, time: f32, store: &ParamStore) -> Option<f32> {
+        let keyed = self.keyed(time);
+        let Some(expression) = &self.expression else {
+            return keyed;
+        };
+        let key = keyed.unwrap_or(self.base);
+        let procedural = expression.eval(Vars {
+            t: time,
+            key,
+            store,
+        });
+        Some(key + (procedural - key) * self.blend)
+    }
+}
+
+/// Keyframed and procedural parameter animation
+#[derive(Resource)]
+pub struct Timeline {
+    #[cfg(feature = "ui")]
+    pub open: bool,
+    pub playing: bool,
+    pub looping: bool,
+    /// Playhead in seconds
+    pub time: f32,
+    pub duration: f32,
+    pub tracks: Vec<Track>,
+    /// Set when the playhead was moved while paused, so the tracks are applied once
+    scrubbed: bool,
+    /// Contents of the add-track field
+    #[cfg(feature = "ui")]
+    new_track: String,
+    #[cfg(feature = "ui")]
+    new_track_error: Option<String>,
+}
+
+impl Default for Timeline {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "ui")]
+            open: false,
+            playing: false,
+            looping: true,
+            time: 0.0,
+            duration: 10.0,
+            tracks: Vec::new(),
+            scrubbed: false,
+            #[cfg(feature = "ui")]
+            new_track: String::new(),
+            #[cfg(feature = "ui")]
+            new_track_error: None,
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+impl Timeline {
+    /// Adds a track from `param` or `param = expression`, e.g. `power = 4 + 2*sin(t*0.5)`. A
+    /// track given an expression starts fully procedural.
+    pub fn add_track(&mut self, text: &str, store: &ParamStore) -> Result<(), String> {
+        let (name, expression) = match text.split_once('=') {
+            Some((name, expression)) => (name.trim(), Some(expression.trim())),
+            None => (text.trim(), None),
+        };
+        let param = Param::from_name(name).ok_or_else(|| format!("unknown parameter '{name}'"))?;
+        if self.tracks.iter().any(|track| track.param == param) {
+            return Err(format!("{name} already has a track"));
+        }
+
+        let mut track = Track::new(param, store.get(param));
+        if let Some(expression) = expression {
+            track.set_expression(expression);
+            if let Some(err) = track.expression_error.take() {
+                return Err(err);
+            }
+            track.blend = 1.0;
+        }
+        self.tracks.push(track);
+        Ok(())
+    }
+}
+
+fn play_timeline(
+    time: Res<Time>,
+    mut timeline: ResMut<Timeline>,
+    store: Res<ParamStore>,
+    mut changes: MessageWriter<ParamChanged>,
+) {
+    if !timeline.playing && !timeline.scrubbed {
+        return;
+    }
+    let timeline = &mut *timeline;
+    timeline.scrubbed = false;
+
+    if timeline.playing {
+        timeline.time += time.delta_secs();
+        if timeline.time > timeline.duration {
+            if timeline.looping {
+                timeline.time %= timeline.duration.max(EPSILON);
+            } else {
+                timeline.time = timeline.duration;
+                timeline.playing = false;
+            }
+        }
+    }
+
+    for track in &timeline.tracks {
+        let current = store.get(track.param);
+        if let Some(value) = track.sample(timeline.time, &store)
+            && value.is_finite()
+            && (value - current).abs() > EPSILON
+        {
+            changes.write(ParamChanged {
+                param: track.param,
+                value,
+            });
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+fn timeline_window(
+    mut contexts: EguiContexts,
+    mut timeline: ResMut<Timeline>,
+    store: Res<ParamStore>,
+) {
+    if !timeline.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let timeline = &mut *timeline;
+
+    let mut open = timeline.open;
+    let mut remove = None;
+    egui::Window::new("Timeline")
+        .open(&mut open)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let label = if timeline.playing { "Pause" } else { "Play" };
+                if ui.button(label).clicked() {
+                    timeline.playing = !timeline.playing;
+                }
+                if ui.button("Rewind").clicked() {
+                    timeline.time = 0.0;
+                    timeline.scrubbed = true;
+                }
+                ui.checkbox(&mut timeline.looping, "Loop");
+                ui.add(
+                    egui::DragValue::new(&mut timeline.duration)
+                        .range(0.1..=3600.0)
+                        .suffix(" s"),
+                );
+            });
+            let scrub = ui.add(
+                egui::Slider::new(&mut timeline.time, 0.0..=timeline.duration)
+                    .text("Time")
+                    .suffix(" s"),
+            );
+            timeline.scrubbed |= scrub.changed();
+            ui.separator();
+
+            for (index, track) in timeline.tracks.iter_mut().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.strong(track.param.name());
+                        ui.label(format!("{} key(s)", track.keyframes.len()));
+                        if ui
+                            .button("Key")
+                            .on_hover_text("Keyframe the current value")
+                            .clicked()
+                        {
+                            track.set_key(timeline.time, store.get(track.param));
+                        }
+                        if ui.button("Clear Keys").clicked() {
+                            track.keyframes.clear();
+                        }
+                        if ui.button("x").clicked() {
+                            remove = Some(index);
+                        }
+                    });
+                    if track.keyframes.len() > 1 {
+                        ui.collapsing("Segments", |ui| {
+                            let count = track.keyframes.len();
+                            for (key_index, key) in
+                                track.keyframes[..count - 1].iter_mut().enumerate()
+                            {
+                                ui.horizontal(|ui| {
+                                    ui.label(format!("{:.2}s", key.time));
+                                    timeline.scrubbed |=
+                                        easing_combo(ui, ("easing", key_index), &mut key.easing);
+                                });
+                            }
+                        });
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("=");
+                        let mut text = track.expression_text.clone();
+                        if ui
+                            .add(
+                                egui::TextEdit::singleline(&mut text)
+                                    .hint_text("expression, e.g. key + sin(t)"),
+                            )
+                            .changed()
+                        {
+                            track.set_expression(&text);
+                            timeline.scrubbed = true;
+                        }
+                    });
+                    if let Some(err) = &track.expression_error {
+                        ui.colored_label(egui::Color32::LIGHT_RED, err.as_str());
+                    }
+                    timeline.scrubbed |= ui
+                        .add(
+                            egui::Slider::new(&mut track.blend, 0.0..=1.0)
+                                .text("Keyframed / Procedural"),
+                        )
+                        .changed();
+                });
+                ui.separator();
+            }
+
+            ui.horizontal(|ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut timeline.new_track)
+                        .hint_text("power = 4 + 2*sin(t*0.5)"),
+                );
+                let submitted =
+                    response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                if ui.button("Add Track").clicked() || submitted {
+                    let text = std::mem::take(&mut timeline.new_track);
+                    match timeline.add_track(&text, &store) {
+                        Ok(()) => timeline.new_track_error = None,
+                        Err(err) => {
+                            timeline.new_track = text;
+                            timeline.new_track_error = Some(err);
+                        }
+                    }
+                }
+            });
+            if let Some(err) = &timeline.new_track_error {
+                ui.colored_label(egui::Color32::LIGHT_RED, err.as_str());
+            }
+        });
+
+    if let Some(index) = remove {
+        timeline.tracks.remove(index);
+    }
+    timeline.open = open;
+}
+
+#[cfg(all(test, feature = "ui"))]
+mod tests {
+    use super::*;
+    use crate::material::MandelbulbMaterial;
+
+    #[test]
+    fn expressions_reading_key_do_not_drift() {
+        let mut mat = MandelbulbMaterial::default();
+        let mut store = ParamStore::capture(&mat);
+        let base = store.get(Param::Power);
+        let mut timeline = Timeline::default();
+        timeline.add_track("power = key + 1", &store).unwrap();
+
+        // the track's own output lands in the store every frame
+        for _ in 0..3 {
+            let value = timeline.tracks[0].sample(0.0, &store).unwrap();
+            Param::Power.set(&mut mat, value);
+            store = ParamStore::capture(&mat);
+        }
+        assert_eq!(store.get(Param::Power), base + 1.0);
+    }
+
+    #[test]
+    fn keyframes_stand_in_for_key() {
+        let store = ParamStore::capture(&MandelbulbMaterial::default());
+        let mut track = Track::new(Param::Power, 8.0);
+        track.set_expression("key * 2");
+        track.blend = 1.0;
+        track.set_key(0.0, 2.0);
+        track.set_key(1.0, 4.0);
+        assert_eq!(track.sample(0.5, &store), Some(6.0));
+        track.blend = 0.5;
+        assert_eq!(track.sample(1.0, &store), Some(6.0));
+    }
+}
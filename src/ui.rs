@@ -0,0 +1,926 @@
+use crate::SimSettings;
+use crate::acts::Show;
+use crate::annotations::Annotations;
+use crate::attract::Attract;
+use crate::bindings::ParamBindings;
+use crate::compose::{ASPECT_PRESETS, ComposeSettings, aspect_label};
+use crate::controls::MouseSettings;
+use crate::deep_zoom::DeepZoom;
+use crate::dimension::Dimension;
+use crate::discover::Discovery;
+use crate::dive::ZoomDive;
+use crate::export::{DataFormat, ExportData, ExportSettings, ExportStill, NoiseLock, StillFormat};
+use crate::expr::Expression;
+use crate::formulas;
+use crate::gallery::Gallery;
+use crate::geometry_graph::GeometryGraph;
+use crate::layers::Layers;
+use crate::marketplace::Marketplace;
+use crate::material::{Formula, FractalMaterial, MandelbulbMaterial, OUTPUT_MODE_SDR};
+use crate::measure::Measurement;
+use crate::mesh_export::MeshExport;
+use crate::navigation::Navigation;
+use crate::palette::{gradient_strip, palette, palette_label, palettes};
+use crate::params::{Param, ParamChanged, ParamKind, ParamStore, shape_defaults};
+use crate::particles::{ParticleMotion, Particles};
+use crate::playlist::{Playlist, PlaylistEntry};
+use crate::power::{BATTERY_DETECTION, LOW_POWER_FPS, LowPower, LowPowerMode};
+use crate::sequencer::Sequencer;
+use crate::shading_graph::ShadingGraph;
+use crate::startup::StartupScene;
+use crate::statistics::Statistics;
+use crate::throttle::ThrottleMonitor;
+use crate::time_lapse::TimeLapse;
+use crate::timeline::Timeline;
+use crate::units::{DisplayUnits, NumberFormat, Quantity};
+use crate::usage::UsageStats;
+use crate::validation::Validation;
+use crate::wallpaper::Wallpaper;
+use crate::web_bundle::ExportWebBundle;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_egui::{EguiContexts, egui};
+
+/// Width of the gradient strips shown with palette names
+const PALETTE_STRIP_WIDTH: f32 = 60.0;
+
+/// What parameter widgets read and write while the settings window is drawn
+struct ParamEditor<'a> {
+    store: &'a ParamStore,
+    bindings: &'a mut ParamBindings,
+    format: NumberFormat,
+    changes: Vec<ParamChanged>,
+}
+
+/// Shows the editing widget for `param` and records a [`ParamChanged`] when the user edits it
+fn param_widget(ui: &mut egui::Ui, editor: &mut ParamEditor, param: Param) {
+    let mut value = editor.store.get(param);
+    let changed = match param.kind() {
+        ParamKind::Scalar | ParamKind::Integer => {
+            let range = param.range();
+            let mut slider = egui::Slider::new(&mut value, *range.start()..=*range.end())
+                .text(param.label())
+                .logarithmic(param.logarithmic());
+            if param.kind() == ParamKind::Integer {
+                slider = slider.integer();
+            }
+            if let Some(step) = param.step() {
+                slider = slider.step_by(step);
+            }
+            let slider = editor.format.slider(slider, param.quantity());
+            ui.horizontal(|ui| {
+                // a bound value is driven by its expression, not the slider
+                let bound = editor.bindings.get(param).is_some();
+                let changed = ui.add_enabled(!bound, slider).changed();
+                let button = if bound { "ƒ=" } else { "ƒ" };
+                ui.menu_button(button, |ui| expression_menu(ui, editor, param))
+                    .response
+                    .on_hover_text("Type an expression");
+                changed
+            })
+            .inner
+        }
+        ParamKind::Toggle => {
+            let mut on = value > 0.5;
+            let changed = ui.checkbox(&mut on, param.label()).changed();
+            value = on as u32 as f32;
+            changed
+        }
+        ParamKind::Choice => {
+            let choices = param.choices();
+            let mut index = value as usize;
+            let before = index;
+            ui.horizontal(|ui| {
+                ui.label(param.label());
+                egui::ComboBox::from_id_salt(param.label())
+                    .selected_text(choices.get(index).copied().unwrap_or("Unknown"))
+                    .show_ui(ui, |ui| {
+                        for (i, choice) in choices.iter().enumerate() {
+                            ui.selectable_value(&mut index, i, *choice);
+                        }
+                    });
+            });
+            value = index as f32;
+            index != before
+        }
+    };
+
+    if changed {
+        editor.changes.push(ParamChanged { param, value });
+    }
+}
+
+/// Shows a color button for three params holding the linear red, green and blue of one color
+fn color_widget(ui: &mut egui::Ui, editor: &mut ParamEditor, label: &str, params: [Param; 3]) {
+    let mut rgb = params.map(|param| editor.store.get(param));
+    ui.horizontal(|ui| {
+        if ui.color_edit_button_rgb(&mut rgb).changed() {
+            for (param, value) in params.into_iter().zip(rgb) {
+                editor.changes.push(ParamChanged { param, value });
+            }
+        }
+        ui.label(label);
+    });
+}
+
+/// The power as a whole number and an offset from it. Whole powers give the fractal its
+/// symmetry and render faster, which a single slider makes hard to land on exactly.
+fn split_power_widget(ui: &mut egui::Ui, editor: &mut ParamEditor) {
+    let range = Param::Power.range();
+    let value = editor.store.get(Param::Power);
+    let mut whole = value.round() as i32;
+    let mut fraction = value - whole as f32;
+    let bound = editor.bindings.get(Param::Power).is_some();
+
+    let mut changed = false;
+    ui.add_enabled_ui(!bound, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Power");
+            changed |= ui
+                .add(
+                    egui::DragValue::new(&mut whole)
+                        .range(*range.start() as i32..=*range.end() as i32)
+                        .speed(0.05),
+                )
+                .changed();
+            if ui
+                .add_enabled(fraction != 0.0, egui::Button::new("Whole").small())
+                .on_hover_text("Drop the fraction")
+                .clicked()
+            {
+                fraction = 0.0;
+                changed = true;
+            }
+        });
+        ui.horizontal(|ui| {
+            let slider = egui::Slider::new(&mut fraction, -0.5..=0.5)
+                .text("Fraction")
+                .step_by(0.001);
+            changed |= ui
+                .add(editor.format.slider(slider, Quantity::Plain))
+                .changed();
+            for step in [-0.01, -0.001, 0.001, 0.01] {
+                if ui.small_button(format!("{step:+}")).clicked() {
+                    fraction += step;
+                    changed = true;
+                }
+            }
+        });
+    });
+
+    if changed {
+        // rounded to the finest step so a whole power stays exact
+        let fraction = (fraction * 1000.0).round() / 1000.0;
+        let value = (whole as f32 + fraction).clamp(*range.start(), *range.end());
+        editor.changes.push(ParamChanged {
+            param: Param::Power,
+            value,
+        });
+    }
+}
+
+/// Explains parameter problems [`Validation`] found, with a button for each fix
+fn validation_notes(ui: &mut egui::Ui, editor: &mut ParamEditor, validation: &mut Validation) {
+    if ui.small_button("Why is my screen black?").clicked() {
+        validation.diagnosis_open = true;
+        validation.diagnosis = None;
+    }
+    for issue in &validation.issues {
+        ui.horizontal_wrapped(|ui| {
+            ui.colored_label(egui::Color32::YELLOW, issue.message.as_str());
+            if let Some((label, fix)) = issue.fix
+                && ui.small_button(label).clicked()
+            {
+                editor.changes.push(fix);
+            }
+        });
+    }
+    if !validation.notes.is_empty() {
+        for note in &validation.notes {
+            ui.colored_label(egui::Color32::YELLOW, note.as_str());
+        }
+        if ui.small_button("Dismiss").clicked() {
+            validation.notes.clear();
+        }
+    }
+}
+
+/// Field for an expression such as `pi/3` or `1/zoom`, applied once or kept as a live binding
+fn expression_menu(ui: &mut egui::Ui, editor: &mut ParamEditor, param: Param) {
+    // the text being typed lives in egui's memory, seeded from the current binding
+    let id = ui.id().with(("expression", param.name()));
+    let mut text = ui
+        .data_mut(|data| data.get_temp::<String>(id))
+        .unwrap_or_else(|| {
+            editor
+                .bindings
+                .get(param)
+                .map(|expression| expression.source().to_string())
+                .unwrap_or_default()
+        });
+
+    ui.add(egui::TextEdit::singleline(&mut text).hint_text("e.g. 1/zoom or 4 + sin(t)"));
+    let parsed = Expression::parse(&text);
+    if let Err(err) = &parsed
+        && !text.trim().is_empty()
+    {
+        ui.colored_label(egui::Color32::LIGHT_RED, err.to_string());
+    }
+
+    ui.horizontal(|ui| {
+        let valid = parsed.is_ok();
+        if ui.add_enabled(valid, egui::Button::new("Set")).clicked()
+            && let Ok(expression) = Expression::parse(&text)
+        {
+            editor.bindings.set_once(param, expression);
+            ui.close();
+        }
+        if ui.add_enabled(valid, egui::Button::new("Bind")).clicked()
+            && let Ok(expression) = Expression::parse(&text)
+        {
+            editor
+                .bindings
+                .bind(param, expression, editor.store.get(param));
+            ui.close();
+        }
+        if editor.bindings.get(param).is_some() && ui.button("Unbind").clicked() {
+            editor.bindings.unbind(param);
+            ui.close();
+        }
+    });
+    ui.data_mut(|data| data.insert_temp(id, text));
+}
+
+/// Windows the settings panel can open
+#[derive(SystemParam)]
+pub struct EditorWindows<'w> {
+    marketplace: ResMut<'w, Marketplace>,
+    shading_graph: ResMut<'w, ShadingGraph>,
+    geometry_graph: ResMut<'w, GeometryGraph>,
+    timeline: ResMut<'w, Timeline>,
+    show: ResMut<'w, Show>,
+    sequencer: ResMut<'w, Sequencer>,
+    analysis: AnalysisWindows<'w>,
+    camera: CameraWindows<'w>,
+    gallery: ResMut<'w, Gallery>,
+    mesh_export: ResMut<'w, MeshExport>,
+    throttle: ResMut<'w, ThrottleMonitor>,
+    wallpaper: ResMut<'w, Wallpaper>,
+    display: DisplaySettings<'w>,
+    validation: ResMut<'w, Validation>,
+    layers: ResMut<'w, Layers>,
+    time_lapse: ResMut<'w, TimeLapse>,
+}
+
+/// Settings of the Display section
+#[derive(SystemParam)]
+pub struct DisplaySettings<'w> {
+    units: ResMut<'w, DisplayUnits>,
+    startup: ResMut<'w, StartupScene>,
+    mouse: ResMut<'w, MouseSettings>,
+    attract: ResMut<'w, Attract>,
+}
+
+/// Windows and settings of the Camera section
+#[derive(SystemParam)]
+pub struct CameraWindows<'w> {
+    deep_zoom: ResMut<'w, DeepZoom>,
+    navigation: ResMut<'w, Navigation>,
+    discovery: ResMut<'w, Discovery>,
+    dive: ResMut<'w, ZoomDive>,
+}
+
+/// Windows opened from the Analysis section
+#[derive(SystemParam)]
+pub struct AnalysisWindows<'w> {
+    statistics: ResMut<'w, Statistics>,
+    dimension: ResMut<'w, Dimension>,
+    annotations: ResMut<'w, Annotations>,
+    measurement: ResMut<'w, Measurement>,
+    usage: ResMut<'w, UsageStats>,
+}
+
+/// Export requests the settings panel sends
+#[derive(SystemParam)]
+pub struct ExportRequests<'w> {
+    still: MessageWriter<'w, ExportStill>,
+    data: MessageWriter<'w, ExportData>,
+    web: MessageWriter<'w, ExportWebBundle>,
+}
+
+pub fn ui_controls(
+    mut contexts: EguiContexts,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    store: Res<ParamStore>,
+    mut param_changes: MessageWriter<ParamChanged>,
+    mut settings: ResMut<SimSettings>,
+    mut compose: ResMut<ComposeSettings>,
+    mut playlist: ResMut<Playlist>,
+    mut export_settings: ResMut<ExportSettings>,
+    mut exports: ExportRequests,
+    mut bindings: ResMut<ParamBindings>,
+    mut particles: ResMut<Particles>,
+    mut low_power: ResMut<LowPower>,
+    mut windows: EditorWindows,
+) {
+    if windows.display.attract.hides_ui() {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let mut editor = ParamEditor {
+        store: &store,
+        bindings: &mut bindings,
+        format: windows
+            .display
+            .units
+            .format((windows.mesh_export.unit, windows.mesh_export.scale)),
+        changes: Vec::new(),
+    };
+
+    egui::Window::new("Mandelbulb Settings")
+        .default_width(300.0)
+        .show(ctx, |ui| {
+            ui.heading("Fractal Parameters");
+            validation_notes(ui, &mut editor, &mut windows.validation);
+
+            // SHAPE SETTINGS
+            ui.separator();
+            ui.label("Shape");
+
+            param_widget(ui, &mut editor, Param::Formula);
+            if let Some(Formula::Plugin(index)) =
+                Formula::all().nth(store.get(Param::Formula) as usize)
+                && let Some(plugin) = formulas::plugins().get(index as usize)
+            {
+                if !plugin.description.is_empty() {
+                    ui.label(plugin.description.as_str());
+                }
+                for (i, descriptor) in plugin.params.iter().enumerate() {
+                    let param = Param::PluginSlot(i as u8);
+                    let mut value = store.get(param);
+                    let slider = egui::Slider::new(&mut value, descriptor.min..=descriptor.max)
+                        .text(descriptor.name.as_str());
+                    if ui
+                        .add(editor.format.slider(slider, descriptor.quantity))
+                        .changed()
+                    {
+                        editor.changes.push(ParamChanged { param, value });
+                    }
+                }
+            }
+            ui.add_enabled_ui(!settings.animate_power, |ui| {
+                if settings.split_power {
+                    split_power_widget(ui, &mut editor);
+                } else {
+                    param_widget(ui, &mut editor, Param::Power);
+                }
+            });
+            ui.checkbox(&mut settings.split_power, "Whole + Fraction")
+                .on_hover_text("Edit the power as a whole number and a fine offset");
+            param_widget(ui, &mut editor, Param::Iterations);
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Reset Shape")
+                    .on_hover_text("The formula's default shape, keeping camera and lighting")
+                    .clicked()
+                {
+                    let formula = Formula::all()
+                        .nth(store.get(Param::Formula) as usize)
+                        .unwrap_or_default();
+                    editor.changes.extend(shape_defaults(formula));
+                }
+                if ui
+                    .add_enabled(
+                        cfg!(feature = "network"),
+                        egui::Button::new("Browse Formulas..."),
+                    )
+                    .on_disabled_hover_text("Built without the network feature")
+                    .clicked()
+                {
+                    windows.marketplace.open = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                param_widget(ui, &mut editor, Param::GeometryGraph);
+                if ui.button("Edit...").clicked() {
+                    windows.geometry_graph.open = true;
+                }
+            });
+
+            // RENDERING SETTINGS
+            ui.separator();
+            ui.label("Rendering Quality");
+            param_widget(ui, &mut editor, Param::RaySteps);
+            param_widget(ui, &mut editor, Param::HitThreshold);
+            param_widget(ui, &mut editor, Param::MaxDist);
+            param_widget(ui, &mut editor, Param::StallLimit);
+            param_widget(ui, &mut editor, Param::ShowStalled);
+
+            // CAMERA SETTINGS
+            ui.separator();
+            ui.label("Camera");
+
+            ui.add_enabled_ui(!settings.animate_zoom, |ui| {
+                param_widget(ui, &mut editor, Param::Zoom);
+            });
+            ui.checkbox(
+                &mut windows.camera.deep_zoom.keep_detail,
+                "Keep Detail When Zooming",
+            )
+            .on_hover_text(
+                "Scales the threshold with the zoom so detail and shading stay the same",
+            );
+            param_widget(ui, &mut editor, Param::FocalLength);
+            param_widget(ui, &mut editor, Param::FovFit);
+            param_widget(ui, &mut editor, Param::PixelAspect);
+            param_widget(ui, &mut editor, Param::LensDistortion);
+            param_widget(ui, &mut editor, Param::ChromaticAberration);
+            param_widget(ui, &mut editor, Param::Grain);
+            param_widget(ui, &mut editor, Param::FlareIntensity);
+            if store.get(Param::FlareIntensity) > 0.0 {
+                ui.indent("flare_controls", |ui| {
+                    param_widget(ui, &mut editor, Param::FlareThreshold);
+                    param_widget(ui, &mut editor, Param::FlareLength);
+                    param_widget(ui, &mut editor, Param::FlareStarburst);
+                    color_widget(
+                        ui,
+                        &mut editor,
+                        "Flare Color",
+                        [Param::FlareRed, Param::FlareGreen, Param::FlareBlue],
+                    );
+                });
+            }
+            ui.horizontal(|ui| {
+                ui.label("Anamorphic");
+                for (label, squeeze) in [("1x", 1.0), ("1.33x", 1.33), ("1.5x", 1.5), ("2x", 2.0)] {
+                    if ui.small_button(label).clicked() {
+                        editor.changes.push(ParamChanged {
+                            param: Param::PixelAspect,
+                            value: squeeze,
+                        });
+                    }
+                }
+            });
+
+            ui.add(
+                egui::Slider::new(&mut settings.rotation_speed, 0.0..=1.0).text("Rotation Speed"),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("Coordinates...").clicked() {
+                    windows.camera.navigation.open = true;
+                }
+                if ui.button("Discover Views...").clicked() {
+                    windows.camera.discovery.open = true;
+                }
+                if ui.button("Zoom Dive...").clicked() {
+                    windows.camera.dive.open = true;
+                }
+            });
+
+            // ANIMATION SETTINGS
+            ui.separator();
+            ui.heading("Animations");
+
+            ui.checkbox(&mut settings.animate_power, "Auto-Animate Power");
+            if settings.animate_power {
+                ui.indent("power_speed", |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut settings.power_speed, 0.01..=4.0)
+                            .text("Power Speed"),
+                    );
+                });
+            }
+
+            ui.checkbox(&mut settings.animate_zoom, "Auto-Animate Zoom");
+            if settings.animate_zoom {
+                ui.indent("zoom_speed", |ui| {
+                    ui.add(
+                        egui::Slider::new(&mut settings.zoom_speed, 0.1..=5.0).text("Zoom Speed"),
+                    );
+                });
+            }
+
+            param_widget(ui, &mut editor, Param::BreathAmplitude);
+            if store.get(Param::BreathAmplitude) > 0.0 {
+                ui.indent("breathing", |ui| {
+                    param_widget(ui, &mut editor, Param::BreathSpeed);
+                    ui.checkbox(&mut settings.sync_breathing, "Sync to Tempo")
+                        .on_hover_text("Speed in breaths per beat of the sequencer's tempo");
+                });
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Timeline...").clicked() {
+                    windows.timeline.open = true;
+                }
+                if ui.button("Sequencer...").clicked() {
+                    windows.sequencer.open = true;
+                }
+            });
+
+            // VISUAL STYLE
+            ui.separator();
+            ui.heading("Visual Style");
+
+            param_widget(ui, &mut editor, Param::BackgroundGlow);
+            param_widget(ui, &mut editor, Param::LensStrength);
+
+            let mut palette_id = store.get(Param::Palette) as u32;
+            ui.horizontal(|ui| {
+                ui.label("Color Palette");
+                gradient_strip(
+                    ui,
+                    &palette(palette_id),
+                    egui::vec2(PALETTE_STRIP_WIDTH, ui.spacing().interact_size.y),
+                );
+                egui::ComboBox::from_id_salt("palette_combo")
+                    .selected_text(palette_label(palette_id))
+                    .show_ui(ui, |ui| {
+                        let before = palette_id;
+                        for (id, entry) in palettes().iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                gradient_strip(
+                                    ui,
+                                    &entry.palette,
+                                    egui::vec2(PALETTE_STRIP_WIDTH, ui.spacing().interact_size.y),
+                                );
+                                ui.selectable_value(&mut palette_id, id as u32, &entry.name);
+                            });
+                        }
+                        if palette_id != before {
+                            editor.changes.push(ParamChanged {
+                                param: Param::Palette,
+                                value: palette_id as f32,
+                            });
+                        }
+                    });
+            });
+
+            param_widget(ui, &mut editor, Param::Coloring);
+            ui.horizontal(|ui| {
+                param_widget(ui, &mut editor, Param::CustomShading);
+                if ui.button("Edit...").clicked() {
+                    windows.shading_graph.open = true;
+                }
+            });
+            param_widget(ui, &mut editor, Param::ColorScale);
+            param_widget(ui, &mut editor, Param::ColorOffset);
+
+            ui.separator();
+            ui.heading("Lighting");
+            param_widget(ui, &mut editor, Param::LightX);
+            param_widget(ui, &mut editor, Param::LightY);
+            color_widget(
+                ui,
+                &mut editor,
+                "Light Color",
+                [Param::LightRed, Param::LightGreen, Param::LightBlue],
+            );
+            color_widget(
+                ui,
+                &mut editor,
+                "Ambient Color",
+                [Param::AmbientRed, Param::AmbientGreen, Param::AmbientBlue],
+            );
+            param_widget(ui, &mut editor, Param::Specular);
+            param_widget(ui, &mut editor, Param::Shininess);
+            param_widget(ui, &mut editor, Param::SoftShadows);
+            param_widget(ui, &mut editor, Param::AoStrength);
+            param_widget(ui, &mut editor, Param::RimStrength);
+            ui.indent("rim_controls", |ui| {
+                color_widget(
+                    ui,
+                    &mut editor,
+                    "Rim Color",
+                    [Param::RimRed, Param::RimGreen, Param::RimBlue],
+                );
+                param_widget(ui, &mut editor, Param::RimDirection);
+            });
+            param_widget(ui, &mut editor, Param::FogDensity);
+            param_widget(ui, &mut editor, Param::Streamlines);
+            if store.flag(Param::Streamlines) {
+                ui.indent("streamline_controls", |ui| {
+                    param_widget(ui, &mut editor, Param::StreamlineDensity);
+                    param_widget(ui, &mut editor, Param::StreamlineLength);
+                });
+            }
+            param_widget(ui, &mut editor, Param::Isolines);
+            if store.get(Param::Isolines) > 0.0 {
+                ui.indent("isoline_controls", |ui| {
+                    param_widget(ui, &mut editor, Param::IsolineSpacing);
+                    param_widget(ui, &mut editor, Param::IsolineIntensity);
+                });
+            }
+
+            // JULIA FOLDING CONTROLS
+            ui.separator();
+            ui.heading("Julia Folding");
+
+            // enable/disable toggle
+            param_widget(ui, &mut editor, Param::JuliaEnabled);
+
+            // coordinate Sliders
+            if store.flag(Param::JuliaEnabled) {
+                ui.indent("julia_controls", |ui| {
+                    ui.label("Constant K");
+                    param_widget(ui, &mut editor, Param::JuliaX);
+                    param_widget(ui, &mut editor, Param::JuliaY);
+                    param_widget(ui, &mut editor, Param::JuliaZ);
+                });
+            }
+
+            ui.separator();
+            ui.heading("Particles");
+            ui.checkbox(&mut particles.enabled, "Enable Particles");
+            if particles.enabled {
+                ui.add(
+                    egui::Slider::new(&mut particles.count, 1024..=262144)
+                        .logarithmic(true)
+                        .text("Count"),
+                );
+                ui.horizontal(|ui| {
+                    ui.label("Motion");
+                    for motion in ParticleMotion::ALL {
+                        ui.selectable_value(&mut particles.motion, motion, motion.label());
+                    }
+                });
+                ui.add(egui::Slider::new(&mut particles.speed, 0.0..=1.0).text("Speed"));
+                ui.add(egui::Slider::new(&mut particles.lifetime, 0.2..=10.0).text("Lifetime"));
+                ui.add(
+                    egui::Slider::new(&mut particles.spawn_radius, 0.5..=6.0).text("Spawn Radius"),
+                );
+                ui.add(egui::Slider::new(&mut particles.size, 0.5..=20.0).text("Size"));
+                ui.horizontal(|ui| {
+                    ui.color_edit_button_rgb(&mut particles.color);
+                    ui.add(
+                        egui::Slider::new(&mut particles.intensity, 0.0..=4.0).text("Intensity"),
+                    );
+                });
+            }
+
+            ui.separator();
+            ui.heading("Analysis");
+            ui.horizontal(|ui| {
+                if ui.button("Statistics...").clicked() {
+                    windows.analysis.statistics.open = true;
+                }
+                if ui.button("Fractal Dimension...").clicked() {
+                    windows.analysis.dimension.open = true;
+                }
+                if ui.button("Annotations...").clicked() {
+                    windows.analysis.annotations.open = true;
+                }
+                if ui.button("Measure...").clicked() {
+                    windows.analysis.measurement.open = true;
+                }
+                if ui.button("Usage...").clicked() {
+                    windows.analysis.usage.open = true;
+                }
+            });
+            param_widget(ui, &mut editor, Param::Slice);
+            if store.get(Param::Slice) > 0.0 {
+                ui.indent("slice_controls", |ui| {
+                    param_widget(ui, &mut editor, Param::SliceAxis);
+                    param_widget(ui, &mut editor, Param::SliceOffset);
+                    param_widget(ui, &mut editor, Param::SliceExtent);
+                });
+            }
+
+            ui.separator();
+            ui.heading("Performance");
+            param_widget(ui, &mut editor, Param::Supersampling);
+            param_widget(ui, &mut editor, Param::BrickMap);
+            param_widget(ui, &mut editor, Param::Reprojection);
+            ui.horizontal(|ui| {
+                ui.label("Low Power");
+                let mut mode = low_power.mode;
+                egui::ComboBox::from_id_salt("low_power_combo")
+                    .selected_text(mode.label())
+                    .show_ui(ui, |ui| {
+                        for option in LowPowerMode::ALL {
+                            // without battery detection the automatic mode never turns on
+                            let available = option != LowPowerMode::Auto || BATTERY_DETECTION;
+                            ui.add_enabled_ui(available, |ui| {
+                                ui.selectable_value(&mut mode, option, option.label());
+                            });
+                        }
+                    })
+                    .response
+                    .on_hover_text(format!(
+                        "Caps the frame rate at {LOW_POWER_FPS} fps, renders at half \
+                         resolution, limits ray steps and turns off supersampling, temporal \
+                         reuse, soft shadows and flow lines"
+                    ));
+                if mode != low_power.mode {
+                    low_power.mode = mode;
+                }
+                if low_power.active() {
+                    ui.label("(active)");
+                }
+            });
+            if !BATTERY_DETECTION {
+                ui.small("Battery detection is only available on Linux.");
+            } else if low_power.mode == LowPowerMode::Auto && low_power.on_battery.is_none() {
+                ui.small("The power source cannot be detected on this system.");
+            }
+            ui.checkbox(
+                &mut windows.throttle.auto_reduce,
+                "Reduce quality when throttling",
+            )
+            .on_hover_text("Step quality down when frames stay slow, e.g. on a hot laptop");
+            if low_power.throttled {
+                ui.horizontal(|ui| {
+                    ui.label("Quality reduced for throttling");
+                    if ui.button("Restore").clicked() {
+                        low_power.throttled = false;
+                    }
+                });
+            }
+
+            ui.separator();
+            ui.heading("Display");
+            param_widget(ui, &mut editor, Param::OutputMode);
+            ui.add_enabled_ui(
+                store.get(Param::OutputMode) as u32 != OUTPUT_MODE_SDR,
+                |ui| {
+                    param_widget(ui, &mut editor, Param::PaperWhite);
+                    // the window surface is 8-bit sRGB, see OutputPlugin
+                    ui.label("The window previews HDR in SDR, stills keep the full range");
+                },
+            );
+            param_widget(ui, &mut editor, Param::TestPattern);
+            ui.label("F9 cycles test patterns");
+            ui.collapsing("Units", |ui| windows.display.units.ui(ui));
+            ui.collapsing("Startup", |ui| windows.display.startup.ui(ui));
+            ui.collapsing("Idle", |ui| windows.display.attract.ui(ui));
+
+            ui.separator();
+            ui.heading("Controls");
+            windows.display.mouse.ui(ui);
+
+            ui.separator();
+            ui.heading("Composition");
+            // edit copies so the resource is only marked changed on an actual edit, which
+            // triggers a quad resize
+            let mut aspect = compose.aspect;
+            ui.horizontal(|ui| {
+                ui.label("Aspect");
+                egui::ComboBox::from_id_salt("aspect_combo")
+                    .selected_text(compose.aspect_label())
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut aspect, None, "Free");
+                        for (label, preset) in ASPECT_PRESETS {
+                            ui.selectable_value(&mut aspect, Some(preset), label);
+                        }
+                    });
+            });
+            if aspect != compose.aspect {
+                compose.aspect = aspect;
+            }
+            // guides don't change the framing, so editing them shouldn't trigger a resize
+            let guides = compose.bypass_change_detection();
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Guides");
+                ui.checkbox(&mut guides.show_safe_areas, "Safe Areas");
+                ui.checkbox(&mut guides.show_thirds, "Thirds");
+                ui.checkbox(&mut guides.show_center, "Center");
+                ui.checkbox(&mut guides.show_golden, "Golden");
+            });
+            ui.horizontal(|ui| {
+                ui.label("Crop Marker");
+                egui::ComboBox::from_id_salt("aspect_marker_combo")
+                    .selected_text(aspect_label(guides.aspect_marker))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut guides.aspect_marker, None, "None");
+                        for (label, preset) in ASPECT_PRESETS {
+                            ui.selectable_value(&mut guides.aspect_marker, Some(preset), label);
+                        }
+                    });
+            });
+            ui.checkbox(&mut guides.guides_in_exports, "Include Guides in Exports");
+            if ui
+                .button("Layers...")
+                .on_hover_text("Composes several fractals over the main view")
+                .clicked()
+            {
+                windows.layers.open = true;
+            }
+
+            ui.separator();
+            ui.heading("Playlist");
+            ui.horizontal(|ui| {
+                if ui.button("Add Current View").clicked()
+                    && let Some(mat) = materials.get(&fractal.0)
+                {
+                    let name = format!("Entry {}", playlist.entries.len() + 1);
+                    playlist.entries.push(PlaylistEntry::new(name, mat.clone()));
+                }
+                if ui.button("Edit...").clicked() {
+                    playlist.open = true;
+                }
+            });
+            ui.label(format!("{} entries", playlist.entries.len()));
+            let mut playing = playlist.playing;
+            if ui
+                .add_enabled(
+                    playlist.entries.len() > 1,
+                    egui::Checkbox::new(&mut playing, "Play"),
+                )
+                .changed()
+            {
+                if playing {
+                    playlist.play();
+                } else {
+                    playlist.stop();
+                }
+            }
+            if ui.button("Show Acts...").clicked() {
+                windows.show.open = true;
+            }
+
+            ui.separator();
+            ui.heading("Export");
+            ui.horizontal(|ui| {
+                ui.label("Format");
+                egui::ComboBox::from_id_salt("still_format_combo")
+                    .selected_text(export_settings.format.label())
+                    .show_ui(ui, |ui| {
+                        for format in StillFormat::ALL {
+                            ui.selectable_value(
+                                &mut export_settings.format,
+                                format,
+                                format.label(),
+                            );
+                        }
+                    });
+            });
+            ui.horizontal(|ui| {
+                ui.label("Noise");
+                egui::ComboBox::from_id_salt("noise_lock_combo")
+                    .selected_text(export_settings.noise.label())
+                    .show_ui(ui, |ui| {
+                        for lock in NoiseLock::ALL {
+                            ui.selectable_value(&mut export_settings.noise, lock, lock.label());
+                        }
+                    });
+            })
+            .response
+            .on_hover_text("Whether film grain holds still or changes every frame");
+            ui.horizontal(|ui| {
+                if ui.button("Save Image (F12)").clicked() {
+                    exports.still.write(ExportStill);
+                }
+                if ui.button("Gallery...").clicked() {
+                    windows.gallery.open = true;
+                }
+                ui.add_enabled_ui(cfg!(feature = "export"), |ui| {
+                    if ui
+                        .button("Export Mesh...")
+                        .on_disabled_hover_text("Built without the export feature")
+                        .clicked()
+                    {
+                        windows.mesh_export.open = true;
+                    }
+                    if ui
+                        .button("Export Web Page")
+                        .on_hover_text(
+                            "A page rendering this view in the browser, with orbit controls",
+                        )
+                        .on_disabled_hover_text("Built without the export feature")
+                        .clicked()
+                    {
+                        exports.web.write(ExportWebBundle);
+                    }
+                });
+                if ui.button("Wallpaper...").clicked() {
+                    windows.wallpaper.open = true;
+                }
+                if ui.button("Time-Lapse...").clicked() {
+                    windows.time_lapse.open = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Data");
+                egui::ComboBox::from_id_salt("data_format_combo")
+                    .selected_text(export_settings.data_format.label())
+                    .show_ui(ui, |ui| {
+                        for format in DataFormat::ALL {
+                            ui.selectable_value(
+                                &mut export_settings.data_format,
+                                format,
+                                format.label(),
+                            );
+                        }
+                    });
+                if ui
+                    .button("Save Data")
+                    .on_hover_text("Per pixel: march steps, orbit trap, depth and hit")
+                    .clicked()
+                {
+                    exports.data.write(ExportData);
+                }
+            });
+        });
+
+    param_changes.write_batch(editor.changes);
+}
@@ -0,0 +1,191 @@
+//! How numbers are shown in the UI. Parameters declare what kind of quantity they are, the user
+//! picks the unit each kind is shown in and the decimal separator, which defaults to the
+//! locale's. Values are stored, saved and shared in scene units, radians and fractions regardless.
+
+use crate::mesh_export::Unit;
+use bevy::prelude::*;
+use bevy_egui::egui;
+use serde::Deserialize;
+use std::ops::RangeInclusive;
+
+pub struct UnitsPlugin;
+
+impl Plugin for UnitsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DisplayUnits>();
+    }
+}
+
+/// What a parameter's value measures, deciding the unit it is shown in
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+pub enum Quantity {
+    #[default]
+    Plain,
+    /// A length in scene units
+    Distance,
+    /// An angle in radians
+    Angle,
+    /// A share between 0 and 1
+    Fraction,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AngleUnit {
+    Degrees,
+    Radians,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DecimalSeparator {
+    Point,
+    Comma,
+}
+
+impl DecimalSeparator {
+    /// The separator of the locale in `LC_ALL`, `LC_NUMERIC` or `LANG`, a point when none is set
+    fn from_locale() -> Self {
+        let locale = ["LC_ALL", "LC_NUMERIC", "LANG"]
+            .into_iter()
+            .filter_map(|key| std::env::var(key).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default();
+        let language = locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default();
+        // languages writing 1,5 rather than 1.5
+        const COMMA: [&str; 28] = [
+            "bg", "ca", "cs", "da", "de", "el", "es", "et", "eu", "fi", "fr", "gl", "hr", "hu",
+            "id", "is", "it", "lt", "lv", "nb", "nl", "nn", "pl", "pt", "ro", "ru", "sv", "uk",
+        ];
+        if COMMA.contains(&language) {
+            DecimalSeparator::Comma
+        } else {
+            DecimalSeparator::Point
+        }
+    }
+}
+
+/// The units the user picked for each kind of quantity
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct DisplayUnits {
+    pub angle: AngleUnit,
+    /// Fractions as percentages rather than 0 to 1
+    pub percent: bool,
+    pub separator: DecimalSeparator,
+    /// Distances in the unit and scale set for mesh export rather than scene units
+    pub physical_lengths: bool,
+}
+
+impl Default for DisplayUnits {
+    fn default() -> Self {
+        Self {
+            angle: AngleUnit::Degrees,
+            percent: true,
+            separator: DecimalSeparator::from_locale(),
+            physical_lengths: false,
+        }
+    }
+}
+
+impl DisplayUnits {
+    /// The formatting for this frame, `length` being the physical unit and the length of one
+    /// scene unit in it
+    pub fn format(self, length: (Unit, f32)) -> NumberFormat {
+        NumberFormat {
+            units: self,
+            length: self.physical_lengths.then_some(length),
+        }
+    }
+
+    /// Choices for the settings window
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Angles");
+            ui.selectable_value(&mut self.angle, AngleUnit::Degrees, "Degrees");
+            ui.selectable_value(&mut self.angle, AngleUnit::Radians, "Radians");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Decimal Separator");
+            ui.selectable_value(&mut self.separator, DecimalSeparator::Point, "1.5");
+            ui.selectable_value(&mut self.separator, DecimalSeparator::Comma, "1,5");
+        });
+        ui.checkbox(&mut self.percent, "Show shares as percentages");
+        ui.checkbox(&mut self.physical_lengths, "Show distances in export units")
+            .on_hover_text("Uses the unit and scale set in Export Mesh");
+    }
+}
+
+/// Converts values to and from their displayed form
+#[derive(Clone, Copy, Debug)]
+pub struct NumberFormat {
+    units: DisplayUnits,
+    length: Option<(Unit, f32)>,
+}
+
+impl NumberFormat {
+    /// Factor from the stored value to the displayed one, and the suffix shown after it
+    fn unit(&self, quantity: Quantity) -> (f64, &'static str) {
+        match quantity {
+            Quantity::Plain => (1.0, ""),
+            Quantity::Distance => match self.length {
+                Some((unit, scale)) => (scale as f64, unit.label()),
+                None => (1.0, ""),
+            },
+            Quantity::Angle => match self.units.angle {
+                AngleUnit::Degrees => (180.0 / std::f64::consts::PI, "°"),
+                AngleUnit::Radians => (1.0, "rad"),
+            },
+            Quantity::Fraction if self.units.percent => (100.0, "%"),
+            Quantity::Fraction => (1.0, ""),
+        }
+    }
+
+    /// `value` in its displayed unit with a fixed number of decimals, e.g. "45,0°"
+    pub fn show(&self, quantity: Quantity, value: f32, decimals: usize) -> String {
+        self.text(quantity, value as f64, decimals..=decimals)
+    }
+
+    /// A point's coordinates, e.g. "0.12, -0.50, 1.00"
+    pub fn show_point(&self, quantity: Quantity, point: Vec3, decimals: usize) -> String {
+        // with a comma separator the coordinates are set apart by semicolons
+        let between = match self.units.separator {
+            DecimalSeparator::Point => ", ",
+            DecimalSeparator::Comma => "; ",
+        };
+        point
+            .to_array()
+            .map(|v| self.show(quantity, v, decimals))
+            .join(between)
+    }
+
+    fn text(&self, quantity: Quantity, value: f64, decimals: RangeInclusive<usize>) -> String {
+        let (factor, suffix) = self.unit(quantity);
+        let mut text = egui::emath::format_with_decimals_in_range(value * factor, decimals);
+        if self.units.separator == DecimalSeparator::Comma {
+            text = text.replace('.', ",");
+        }
+        match suffix {
+            "" => text,
+            "°" | "%" => format!("{text}{suffix}"),
+            _ => format!("{text} {suffix}"),
+        }
+    }
+
+    /// Reads a typed value back, accepting either separator and an optional suffix
+    fn parse(&self, quantity: Quantity, text: &str) -> Option<f64> {
+        let (factor, suffix) = self.unit(quantity);
+        let text = text.trim();
+        let number = text.strip_suffix(suffix).unwrap_or(text).trim();
+        let value: f64 = number.replace(',', ".").parse().ok()?;
+        Some(value / factor)
+    }
+
+    /// Applies the format to a slider over a value of `quantity`
+    pub fn slider<'a>(&self, slider: egui::Slider<'a>, quantity: Quantity) -> egui::Slider<'a> {
+        let (format, parse) = (*self, *self);
+        slider
+            .custom_formatter(move |value, decimals| format.text(quantity, value, decimals))
+            .custom_parser(move |text| parse.parse(quantity, text))
+    }
+}
@@ -0,0 +1,222 @@
+//! Usage statistics kept for the user alone: time spent, frames rendered, exports and which
+//! formulas and palettes get the most screen time. They are written to a file in the user's
+//! config directory and shown in the Usage window, nothing is ever sent anywhere.
+
+use crate::config::{config_file, write_config};
+use crate::export::StillSaved;
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use crate::palette::palette_label;
+use crate::web_bundle::ExportWebBundle;
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const USAGE_FILE: &str = "usage.ron";
+
+/// How much is lost at most when the app doesn't exit cleanly
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Entries listed under each of the most used formulas and palettes
+#[cfg(feature = "ui")]
+const MOST_USED: usize = 5;
+
+pub struct UsagePlugin;
+
+impl Plugin for UsagePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(UsageStats::load())
+            .add_systems(Update, track_usage)
+            // after the window closing systems, which send the exit
+            .add_systems(Last, save_usage);
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, usage_window);
+    }
+}
+
+/// The totals kept between sessions
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+struct Totals {
+    sessions: u64,
+    seconds: f64,
+    frames: u64,
+    exports: u64,
+    /// Seconds in the main view by formula label
+    formulas: BTreeMap<String, f64>,
+    /// Seconds in the main view by palette label
+    palettes: BTreeMap<String, f64>,
+}
+
+#[derive(Resource)]
+pub struct UsageStats {
+    #[cfg(feature = "ui")]
+    pub open: bool,
+    totals: Totals,
+    path: PathBuf,
+    /// Time counted since the totals were last written
+    unsaved: Duration,
+}
+
+impl UsageStats {
+    fn load() -> Self {
+        let path = config_file(USAGE_FILE);
+        let mut totals: Totals = match std::fs::read_to_string(&path) {
+            Ok(text) => ron::from_str(&text).unwrap_or_else(|err| {
+                warn!("Ignoring {}: {err}", path.display());
+                Totals::default()
+            }),
+            Err(_) => Totals::default(),
+        };
+        totals.sessions += 1;
+        Self {
+            #[cfg(feature = "ui")]
+            open: false,
+            totals,
+            path,
+            unsaved: Duration::ZERO,
+        }
+    }
+
+    fn save(&mut self) {
+        self.unsaved = Duration::ZERO;
+        let result = ron::ser::to_string_pretty(&self.totals, default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| write_config(&self.path, &text));
+        if let Err(err) = result {
+            error!("Failed to save {}: {err}", self.path.display());
+        }
+    }
+}
+
+/// Counts every frame, with desktop updates that is every frame rendered, and what it showed
+fn track_usage(
+    time: Res<Time<Real>>,
+    mut usage: ResMut<UsageStats>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    mut stills: MessageReader<StillSaved>,
+    mut web_pages: MessageReader<ExportWebBundle>,
+) {
+    let usage = &mut *usage;
+    let seconds = time.delta_secs_f64();
+    usage.unsaved += time.delta();
+    let totals = &mut usage.totals;
+    totals.seconds += seconds;
+    totals.frames += 1;
+    totals.exports += (stills.read().count() + web_pages.read().count()) as u64;
+
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let formula = if mat.geometry_graph {
+        "Geometry Graph"
+    } else {
+        mat.formula.label()
+    };
+    let palette = if mat.custom_shading {
+        "Shading Graph"
+    } else {
+        palette_label(mat.shading.palette_id)
+    };
+    *totals.formulas.entry(formula.to_string()).or_default() += seconds;
+    *totals.palettes.entry(palette.to_string()).or_default() += seconds;
+}
+
+fn save_usage(mut usage: ResMut<UsageStats>, mut exit: MessageReader<AppExit>) {
+    if exit.read().count() > 0 || usage.unsaved >= SAVE_INTERVAL {
+        usage.save();
+    }
+}
+
+/// "3 h 25 min", "12 min" or "40 s"
+#[cfg(feature = "ui")]
+fn format_duration(seconds: f64) -> String {
+    let minutes = (seconds / 60.0) as u64;
+    match minutes {
+        0 => format!("{} s", seconds as u64),
+        1..60 => format!("{minutes} min"),
+        _ => format!("{} h {} min", minutes / 60, minutes % 60),
+    }
+}
+
+/// The entries with the most time, as shares of the whole
+#[cfg(feature = "ui")]
+fn most_used(ui: &mut egui::Ui, heading: &str, seconds: &BTreeMap<String, f64>) {
+    let total: f64 = seconds.values().sum();
+    let mut entries: Vec<_> = seconds.iter().collect();
+    entries.sort_by(|a, b| b.1.total_cmp(a.1));
+    ui.separator();
+    ui.strong(heading);
+    if entries.is_empty() {
+        ui.label("Nothing yet");
+    }
+    for (label, seconds) in entries.into_iter().take(MOST_USED) {
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::ProgressBar::new((seconds / total) as f32)
+                    .desired_width(120.0)
+                    .text(format!("{:.0}%", 100.0 * seconds / total)),
+            );
+            ui.label(format!("{label}, {}", format_duration(*seconds)));
+        });
+    }
+}
+
+#[cfg(feature = "ui")]
+fn usage_window(mut contexts: EguiContexts, mut usage: ResMut<UsageStats>) {
+    if !usage.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let usage = &mut *usage;
+
+    let mut open = usage.open;
+    let mut reset = false;
+    egui::Window::new("Usage")
+        .open(&mut open)
+        .default_width(300.0)
+        .show(ctx, |ui| {
+            let totals = &usage.totals;
+            egui::Grid::new("usage_totals")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Time Spent");
+                    ui.label(format_duration(totals.seconds));
+                    ui.end_row();
+                    ui.label("Sessions");
+                    ui.label(totals.sessions.to_string());
+                    ui.end_row();
+                    ui.label("Frames Rendered");
+                    ui.label(totals.frames.to_string());
+                    ui.end_row();
+                    ui.label("Exports");
+                    ui.label(totals.exports.to_string())
+                        .on_hover_text("Saved images and web pages");
+                    ui.end_row();
+                });
+            most_used(ui, "Most Used Formulas", &totals.formulas);
+            most_used(ui, "Most Used Palettes", &totals.palettes);
+            ui.separator();
+            ui.small(format!(
+                "Only kept on this computer, in {}",
+                usage.path.display()
+            ));
+            if ui.button("Reset").clicked() {
+                reset = true;
+            }
+        });
+    usage.open = open;
+
+    if reset {
+        // this session still counts
+        usage.totals = Totals {
+            sessions: 1,
+            ..default()
+        };
+        usage.save();
+    }
+}
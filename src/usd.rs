@@ -0,0 +1,261 @@
+//! USD export of extracted surfaces, as a text layer (`.usda`) or packaged (`.usdz`), with a
+//! `UsdPreviewSurface` material bound to the mesh so it shades the same in every DCC
+
+use crate::mesh_export::SceneSetup;
+use crate::surface::SurfaceMesh;
+use bevy::prelude::*;
+use std::fmt::Write as _;
+use std::path::Path;
+
+/// Focal length of the exported camera in tenths of a scene unit, the apertures are derived from
+/// it and the field of view
+const FOCAL_LENGTH: f32 = 50.0;
+
+/// Intensity of the exported sphere light. The shader's light has no physical unit, this lights
+/// a fractal of the default size well at exposure 0.
+const LIGHT_INTENSITY: f32 = 30.0;
+
+/// Name of the layer inside a `.usdz` package
+const USDZ_LAYER: &str = "fractal.usda";
+
+/// The mesh and, when given, the view's camera and light as a USD text layer whose unit is
+/// `meters_per_unit` long
+pub fn write_usda(mesh: &SurfaceMesh, scene: Option<&SceneSetup>, meters_per_unit: f32) -> String {
+    let mut usda = String::new();
+    // writing to a String can't fail
+    let _ = write_layer(&mut usda, mesh, scene, meters_per_unit);
+    usda
+}
+
+/// [`write_usda`] packaged as a `.usdz`: an uncompressed zip with the layer's data aligned to
+/// 64 bytes
+pub fn write_usdz(
+    path: &Path,
+    mesh: &SurfaceMesh,
+    scene: Option<&SceneSetup>,
+    meters_per_unit: f32,
+) -> Result<(), String> {
+    let layer = write_usda(mesh, scene, meters_per_unit);
+    std::fs::write(path, zip_stored(USDZ_LAYER, layer.as_bytes())).map_err(|err| err.to_string())
+}
+
+fn write_layer(
+    out: &mut String,
+    mesh: &SurfaceMesh,
+    scene: Option<&SceneSetup>,
+    meters_per_unit: f32,
+) -> std::fmt::Result {
+    writeln!(out, "#usda 1.0")?;
+    writeln!(out, "(")?;
+    writeln!(out, "    defaultPrim = \"Fractal\"")?;
+    writeln!(out, "    metersPerUnit = {meters_per_unit}")?;
+    writeln!(out, "    upAxis = \"Y\"")?;
+    writeln!(out, ")")?;
+    writeln!(out)?;
+    writeln!(out, "def Xform \"Fractal\"")?;
+    writeln!(out, "{{")?;
+
+    let (min, max) = mesh.bounds();
+    writeln!(
+        out,
+        "    def Mesh \"Surface\" (prepend apiSchemas = [\"MaterialBindingAPI\"])"
+    )?;
+    writeln!(out, "    {{")?;
+    writeln!(
+        out,
+        "        float3[] extent = [{}, {}]",
+        tuple(min),
+        tuple(max)
+    )?;
+    write!(out, "        int[] faceVertexCounts = [")?;
+    write_list(out, (0..mesh.triangle_count()).map(|_| "3"))?;
+    writeln!(out, "]")?;
+    write!(out, "        int[] faceVertexIndices = [")?;
+    write_list(out, mesh.indices.iter())?;
+    writeln!(out, "]")?;
+    writeln!(
+        out,
+        "        rel material:binding = </Fractal/Materials/Surface>"
+    )?;
+    write!(out, "        normal3f[] normals = [")?;
+    write_list(out, mesh.normals.iter().map(|n| tuple(*n)))?;
+    writeln!(out, "] (")?;
+    writeln!(out, "            interpolation = \"vertex\"")?;
+    writeln!(out, "        )")?;
+    write!(out, "        point3f[] points = [")?;
+    write_list(out, mesh.positions.iter().map(|p| tuple(*p)))?;
+    writeln!(out, "]")?;
+    writeln!(out, "        uniform token subdivisionScheme = \"none\"")?;
+    writeln!(out, "    }}")?;
+    writeln!(out)?;
+
+    writeln!(out, "    def Scope \"Materials\"")?;
+    writeln!(out, "    {{")?;
+    writeln!(out, "        def Material \"Surface\"")?;
+    writeln!(out, "        {{")?;
+    writeln!(
+        out,
+        "            token outputs:surface.connect = </Fractal/Materials/Surface/PreviewSurface.outputs:surface>"
+    )?;
+    writeln!(out)?;
+    writeln!(out, "            def Shader \"PreviewSurface\"")?;
+    writeln!(out, "            {{")?;
+    writeln!(
+        out,
+        "                uniform token info:id = \"UsdPreviewSurface\""
+    )?;
+    writeln!(
+        out,
+        "                color3f inputs:diffuseColor = (0.8, 0.8, 0.8)"
+    )?;
+    writeln!(out, "                float inputs:metallic = 0")?;
+    writeln!(out, "                float inputs:roughness = 0.6")?;
+    writeln!(out, "                token outputs:surface")?;
+    writeln!(out, "            }}")?;
+    writeln!(out, "        }}")?;
+    writeln!(out, "    }}")?;
+
+    if let Some(scene) = scene {
+        let transform =
+            Mat4::from_rotation_translation(scene.camera_rotation(), scene.camera_position());
+        // USD multiplies row vectors, so its rows are the columns of a column vector matrix
+        let rows: Vec<String> = transform
+            .to_cols_array_2d()
+            .iter()
+            .map(|col| format!("({}, {}, {}, {})", col[0], col[1], col[2], col[3]))
+            .collect();
+        let vertical_aperture = 2.0 * FOCAL_LENGTH * (scene.yfov() * 0.5).tan();
+        writeln!(out)?;
+        writeln!(out, "    def Camera \"View\"")?;
+        writeln!(out, "    {{")?;
+        writeln!(out, "        float2 clippingRange = (0.001, 1000)")?;
+        writeln!(out, "        float focalLength = {FOCAL_LENGTH}")?;
+        writeln!(
+            out,
+            "        float horizontalAperture = {}",
+            vertical_aperture * scene.aspect()
+        )?;
+        writeln!(out, "        float verticalAperture = {vertical_aperture}")?;
+        writeln!(
+            out,
+            "        matrix4d xformOp:transform = ({})",
+            rows.join(", ")
+        )?;
+        writeln!(
+            out,
+            "        uniform token[] xformOpOrder = [\"xformOp:transform\"]"
+        )?;
+        writeln!(out, "    }}")?;
+
+        writeln!(out)?;
+        writeln!(out, "    def SphereLight \"Light\"")?;
+        writeln!(out, "    {{")?;
+        writeln!(out, "        float inputs:intensity = {LIGHT_INTENSITY}")?;
+        writeln!(out, "        float inputs:radius = {}", 0.05 * scene.scale)?;
+        writeln!(
+            out,
+            "        double3 xformOp:translate = {}",
+            tuple(scene.light_position())
+        )?;
+        writeln!(
+            out,
+            "        uniform token[] xformOpOrder = [\"xformOp:translate\"]"
+        )?;
+        writeln!(out, "    }}")?;
+    }
+    writeln!(out, "}}")
+}
+
+fn tuple(v: Vec3) -> String {
+    format!("({}, {}, {})", v.x, v.y, v.z)
+}
+
+fn write_list(
+    out: &mut String,
+    items: impl Iterator<Item = impl std::fmt::Display>,
+) -> std::fmt::Result {
+    for (i, item) in items.enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        write!(out, "{item}")?;
+    }
+    Ok(())
+}
+
+/// A zip archive holding one uncompressed file, its data aligned to 64 bytes as USDZ requires
+fn zip_stored(name: &str, data: &[u8]) -> Vec<u8> {
+    const LOCAL_HEADER: usize = 30;
+    // padding goes in an extra field, which has a 4 byte header of its own
+    let unpadded = LOCAL_HEADER + name.len() + 4;
+    let extra_len = 4 + (64 - unpadded % 64) % 64;
+    let crc = crc32(data);
+    let size = data.len() as u32;
+
+    let mut zip = Vec::with_capacity(LOCAL_HEADER + name.len() + extra_len + data.len() + 128);
+    let u16le = |zip: &mut Vec<u8>, v: u16| zip.extend_from_slice(&v.to_le_bytes());
+    let u32le = |zip: &mut Vec<u8>, v: u32| zip.extend_from_slice(&v.to_le_bytes());
+
+    // local file header
+    u32le(&mut zip, 0x0403_4b50);
+    u16le(&mut zip, 20); // version needed
+    u16le(&mut zip, 0); // flags
+    u16le(&mut zip, 0); // stored
+    u16le(&mut zip, 0); // time
+    u16le(&mut zip, 0x21); // date, 1980-01-01
+    u32le(&mut zip, crc);
+    u32le(&mut zip, size);
+    u32le(&mut zip, size);
+    u16le(&mut zip, name.len() as u16);
+    u16le(&mut zip, extra_len as u16);
+    zip.extend_from_slice(name.as_bytes());
+    u16le(&mut zip, 0x1986); // padding field id
+    u16le(&mut zip, (extra_len - 4) as u16);
+    zip.resize(zip.len() + extra_len - 4, 0);
+    zip.extend_from_slice(data);
+
+    // central directory
+    let directory = zip.len();
+    u32le(&mut zip, 0x0201_4b50);
+    u16le(&mut zip, 20); // version made by
+    u16le(&mut zip, 20); // version needed
+    u16le(&mut zip, 0);
+    u16le(&mut zip, 0);
+    u16le(&mut zip, 0);
+    u16le(&mut zip, 0x21);
+    u32le(&mut zip, crc);
+    u32le(&mut zip, size);
+    u32le(&mut zip, size);
+    u16le(&mut zip, name.len() as u16);
+    u16le(&mut zip, 0); // extra
+    u16le(&mut zip, 0); // comment
+    u16le(&mut zip, 0); // disk
+    u16le(&mut zip, 0); // internal attributes
+    u32le(&mut zip, 0); // external attributes
+    u32le(&mut zip, 0); // local header offset
+    zip.extend_from_slice(name.as_bytes());
+    let directory_size = zip.len() - directory;
+
+    // end of central directory
+    u32le(&mut zip, 0x0605_4b50);
+    u16le(&mut zip, 0);
+    u16le(&mut zip, 0);
+    u16le(&mut zip, 1);
+    u16le(&mut zip, 1);
+    u32le(&mut zip, directory_size as u32);
+    u32le(&mut zip, directory as u32);
+    u16le(&mut zip, 0);
+    zip
+}
+
+/// CRC-32 as used by zip
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
@@ -0,0 +1,490 @@
+//! Guards against parameter values that leave the screen black or full of noise. Values no
+//! input should ever produce, like zero iterations or NaN from an expression, are clamped or
+//! dropped as they are applied. Combinations that are valid on their own but not together, like
+//! a max distance shorter than the way to the fractal, are reported with an explanation and a
+//! fix the settings window offers. When the screen is black anyway, a diagnosis probes the
+//! view with the CPU estimator and lists the likely causes, most likely first.
+
+use crate::estimator::CpuEstimator;
+#[cfg(feature = "ui")]
+use crate::estimator::March;
+#[cfg(feature = "ui")]
+use crate::material::{CameraUniform, move_all_views};
+use crate::material::{Formula, FractalMaterial, MandelbulbMaterial};
+use crate::params::Param;
+#[cfg(feature = "ui")]
+use crate::params::ParamChanged;
+use bevy::prelude::*;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+
+/// Clamp notes kept for the settings window, the oldest are dropped
+const MAX_NOTES: usize = 4;
+
+/// Rays across the view the diagnosis probes, as (columns, rows)
+#[cfg(feature = "ui")]
+const PROBE_GRID: (u32, u32) = (32, 18);
+
+/// Where the shader puts the light on the z axis, only x and y are parameters
+#[cfg(feature = "ui")]
+const LIGHT_Z: f32 = -3.0;
+
+pub struct ValidationPlugin;
+
+impl Plugin for ValidationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Validation>()
+            .add_systems(Update, check_combinations);
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, diagnosis_window);
+    }
+}
+
+/// A problem with the current parameters
+pub struct Issue {
+    pub message: String,
+    /// Button label and the change that resolves it
+    #[cfg(feature = "ui")]
+    pub fix: Option<(&'static str, ParamChanged)>,
+}
+
+#[derive(Resource, Default)]
+pub struct Validation {
+    /// Problems with the current parameters, found again every frame
+    pub issues: Vec<Issue>,
+    /// Values that were clamped or dropped as they were applied, newest last
+    pub notes: Vec<String>,
+    #[cfg(feature = "ui")]
+    pub diagnosis_open: bool,
+    /// Findings of the last diagnosis, `None` to probe again
+    #[cfg(feature = "ui")]
+    pub diagnosis: Option<Vec<Finding>>,
+}
+
+impl Validation {
+    pub fn note(&mut self, note: String) {
+        warn!("{note}");
+        self.notes.retain(|existing| *existing != note);
+        self.notes.push(note);
+        if self.notes.len() > MAX_NOTES {
+            self.notes.remove(0);
+        }
+    }
+}
+
+/// `value` raised to the least `param` works with, and why when it was raised. `None` for NaN
+/// and infinities, which would spread through the shader and blank the screen.
+pub fn clamp(param: Param, value: f32) -> Option<(f32, Option<&'static str>)> {
+    if !value.is_finite() {
+        return None;
+    }
+    let (least, reason) = match param {
+        Param::Iterations => (1.0, "with no iterations there is no shape"),
+        Param::RaySteps => (1.0, "rays need at least one step"),
+        Param::HitThreshold => (1e-6, "rays never get within a threshold of zero"),
+        Param::MaxDist => (0.1, "rays need room to travel"),
+        Param::Zoom => (1e-4, "the camera can't sit on its target"),
+        Param::FocalLength => (0.01, "a zero focal length has no field of view"),
+        Param::PixelAspect => (0.01, "pixels need a width"),
+        _ => return Some((value, None)),
+    };
+    Some(if value < least {
+        (least, Some(reason))
+    } else {
+        (value, None)
+    })
+}
+
+/// Finds combinations of the main material's parameters that can't render
+fn check_combinations(
+    mut validation: ResMut<Validation>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+) {
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    let issues = find_issues(mat);
+    // only written when the result changes, so change detection means something changed
+    let unchanged = issues.len() == validation.issues.len()
+        && issues
+            .iter()
+            .zip(&validation.issues)
+            .all(|(a, b)| a.message == b.message);
+    if !unchanged {
+        validation.issues = issues;
+    }
+}
+
+fn find_issues(mat: &MandelbulbMaterial) -> Vec<Issue> {
+    let shape = &mat.shape;
+    let mut issues = Vec::new();
+    if shape.mandel_iters == 0 {
+        issues.push(Issue {
+            message: "Iterations is 0, so there is no shape to hit.".to_string(),
+            #[cfg(feature = "ui")]
+            fix: Some((
+                "Use 10 Iterations",
+                ParamChanged {
+                    param: Param::Iterations,
+                    value: 10.0,
+                },
+            )),
+        });
+    }
+
+    // the Mandelbulb fits in a ball of radius 1.2 around the origin, the box and plugins get
+    // a generous bound
+    let radius = if mat.formula == Formula::Mandelbulb {
+        1.5
+    } else {
+        6.0
+    };
+    let origin = mat.camera.ray_origin();
+    let to_fractal = (origin.length() - radius).max(0.0);
+    if shape.max_dist < to_fractal {
+        issues.push(Issue {
+            message: format!(
+                "Max Dist {:.1} is shorter than the camera's distance to the fractal ({:.1}). \
+                 Rays give up before they get there and the view stays black.",
+                shape.max_dist, to_fractal
+            ),
+            #[cfg(feature = "ui")]
+            fix: Some((
+                "Raise Max Dist",
+                ParamChanged {
+                    param: Param::MaxDist,
+                    value: (origin.length() + radius * 2.0).min(*Param::MaxDist.range().end()),
+                },
+            )),
+        });
+    }
+
+    // the estimator tells how close the camera is to the surface, not available for plugins
+    let Some(estimator) = CpuEstimator::new(mat) else {
+        return issues;
+    };
+    let distance = estimator.distance(origin);
+    if distance <= shape.hit_threshold {
+        issues.push(Issue {
+            message: "The camera is inside the surface. Every ray hits at once, which shows as a \
+                      flat or noisy screen."
+                .to_string(),
+            #[cfg(feature = "ui")]
+            fix: Some((
+                "Zoom Out",
+                ParamChanged {
+                    param: Param::Zoom,
+                    value: (mat.camera.zoom * 2.0).min(*Param::Zoom.range().end()),
+                },
+            )),
+        });
+    } else if shape.hit_threshold > 0.25 * distance {
+        issues.push(Issue {
+            message: format!(
+                "Threshold {:.4} is large next to the camera's distance to the surface ({:.4}). \
+                 The surface swells into a blob and loses its detail.",
+                shape.hit_threshold, distance
+            ),
+            #[cfg(feature = "ui")]
+            fix: Some((
+                "Lower Threshold",
+                ParamChanged {
+                    param: Param::HitThreshold,
+                    value: (distance * 0.01).clamp(
+                        *Param::HitThreshold.range().start(),
+                        *Param::HitThreshold.range().end(),
+                    ),
+                },
+            )),
+        });
+    }
+    issues
+}
+
+/// A possible cause of a black screen
+#[cfg(feature = "ui")]
+pub struct Finding {
+    message: String,
+    /// Button label and what it does
+    fix: Option<(&'static str, Fix)>,
+}
+
+#[cfg(feature = "ui")]
+impl Finding {
+    fn new(message: impl Into<String>, fix: Option<(&'static str, Fix)>) -> Self {
+        Self {
+            message: message.into(),
+            fix,
+        }
+    }
+}
+
+#[cfg(feature = "ui")]
+pub enum Fix {
+    Params(Vec<ParamChanged>),
+    /// Points the camera's orbit at the origin, where every built-in formula sits
+    AimAtFractal,
+}
+
+#[cfg(feature = "ui")]
+fn set(param: Param, value: f32) -> Fix {
+    Fix::Params(vec![ParamChanged { param, value }])
+}
+
+/// Probes the view like the renderer would and explains what keeps it dark
+#[cfg(feature = "ui")]
+fn diagnose(mat: &MandelbulbMaterial) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let Some(estimator) = CpuEstimator::new(mat) else {
+        findings.push(Finding::new(
+            "Plugin formulas and the geometry graph only exist on the GPU, so the view can't be \
+             probed.",
+            None,
+        ));
+        return findings;
+    };
+    let shape = &mat.shape;
+    let origin = mat.camera.ray_origin();
+    if estimator.distance(origin) <= shape.hit_threshold {
+        findings.insert(
+            0,
+            Finding::new(
+                "The camera is inside the fractal, so every ray hits at once.",
+                Some((
+                    "Zoom Out",
+                    set(
+                        Param::Zoom,
+                        (mat.camera.zoom * 2.0).min(*Param::Zoom.range().end()),
+                    ),
+                )),
+            ),
+        );
+        return findings;
+    }
+
+    // the same rays again with room and steps to spare tell what the limits cut off
+    let mut generous = *shape;
+    generous.max_dist = origin.length() + 50.0;
+    generous.ray_steps = 2000;
+    let (columns, rows) = PROBE_GRID;
+    let light = Vec3::new(mat.shading.light_pos_x, mat.shading.light_pos_y, LIGHT_Z);
+    let mut hits = 0;
+    let (mut escaped, mut out_of_steps) = (0, 0);
+    let mut farthest_needed: f32 = 0.0;
+    let (mut diffuse, mut fog, mut ao) = (0.0, 0.0, 0.0);
+    let mut hit_points = Vec::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let uv = Vec2::new(
+                (column as f32 + 0.5) / columns as f32,
+                (row as f32 + 0.5) / rows as f32,
+            );
+            let (origin, dir) = mat.camera.ray(uv);
+            match estimator.march(shape, origin, dir) {
+                March::Hit { t, steps } => {
+                    hits += 1;
+                    let p = origin + dir * t;
+                    let normal = estimator.normal(p);
+                    diffuse += normal.dot((light - p).normalize()).max(0.0);
+                    fog += (-mat.shading.fog_density * t).exp();
+                    ao += 1.0 - steps as f32 / shape.ray_steps as f32 * mat.shading.ao_strength;
+                    hit_points.push((p, normal));
+                }
+                fate => {
+                    if let March::Hit { t, .. } = estimator.march(&generous, origin, dir) {
+                        if fate == March::Escaped {
+                            escaped += 1;
+                            farthest_needed = farthest_needed.max(t);
+                        } else {
+                            out_of_steps += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    let total = (columns * rows) as f32;
+    if hits == 0 {
+        if escaped > 0 && escaped >= out_of_steps {
+            let value = (farthest_needed * 1.2).min(*Param::MaxDist.range().end());
+            findings.insert(
+                0,
+                Finding::new(
+                    format!(
+                        "Max Dist {:.1} ends the rays before they reach the fractal, which \
+                         starts {:.1} away.",
+                        shape.max_dist, farthest_needed
+                    ),
+                    Some(("Raise Max Dist", set(Param::MaxDist, value))),
+                ),
+            );
+        } else if out_of_steps > 0 {
+            findings.insert(
+                0,
+                Finding::new(
+                    format!(
+                        "{} ray steps run out before the rays reach the surface.",
+                        shape.ray_steps
+                    ),
+                    Some((
+                        "Use More Steps",
+                        set(Param::RaySteps, *Param::RaySteps.range().end()),
+                    )),
+                ),
+            );
+        } else {
+            findings.insert(
+                0,
+                Finding::new(
+                    "The camera looks away from the fractal, no ray comes near it.",
+                    Some(("Aim at Fractal", Fix::AimAtFractal)),
+                ),
+            );
+        }
+        if mat.shading.background_glow_intensity <= 0.0 {
+            findings.push(Finding::new(
+                "Background Brightness is 0, so the empty background is black too.",
+                Some(("Brighten Background", set(Param::BackgroundGlow, 1.0))),
+            ));
+        }
+        return findings;
+    }
+
+    let hits_f = hits as f32;
+    let mut likely = Vec::new();
+    if fog / hits_f < 0.1 {
+        likely.push(Finding::new(
+            format!(
+                "Fog Density {:.2} hides the surface at this distance.",
+                mat.shading.fog_density
+            ),
+            Some(("Clear Fog", set(Param::FogDensity, 0.0))),
+        ));
+    }
+    if ao / hits_f < 0.1 {
+        likely.push(Finding::new(
+            format!(
+                "Ambient Occlusion {:.1} darkens every surface the rays took many steps to reach.",
+                mat.shading.ao_strength
+            ),
+            Some(("Use 1.0", set(Param::AoStrength, 1.0))),
+        ));
+    }
+    if diffuse / hits_f < 0.1 {
+        // the light moves in x and y only, over to the camera's side is the best it can do
+        let range = Param::LightX.range();
+        let moved = Vec3::new(
+            (origin.x * 4.0).clamp(*range.start(), *range.end()),
+            (origin.y * 4.0).clamp(*range.start(), *range.end()),
+            LIGHT_Z,
+        );
+        let lit = hit_points
+            .iter()
+            .map(|(p, normal)| normal.dot((moved - *p).normalize()).max(0.0))
+            .sum::<f32>();
+        let fix = (lit > diffuse * 2.0).then(|| {
+            (
+                "Move Light",
+                Fix::Params(vec![
+                    ParamChanged {
+                        param: Param::LightX,
+                        value: moved.x,
+                    },
+                    ParamChanged {
+                        param: Param::LightY,
+                        value: moved.y,
+                    },
+                ]),
+            )
+        });
+        likely.push(Finding::new(
+            "The light is behind the surface in view, only the dim ambient light reaches it. \
+             The light sits towards -z, orbiting the camera there shows the lit side.",
+            fix,
+        ));
+    }
+    if hits_f < total * 0.02 {
+        likely.push(Finding::new(
+            "The fractal covers only a few pixels at the edge of the view or in the distance.",
+            Some(("Aim at Fractal", Fix::AimAtFractal)),
+        ));
+    }
+    findings.splice(0..0, likely);
+    findings
+}
+
+#[cfg(feature = "ui")]
+fn diagnosis_window(
+    mut contexts: EguiContexts,
+    mut validation: ResMut<Validation>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    mut param_changes: MessageWriter<ParamChanged>,
+) {
+    if !validation.diagnosis_open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let validation = &mut *validation;
+    if validation.diagnosis.is_none()
+        && let Some(mat) = materials.get(&fractal.0)
+    {
+        validation.diagnosis = Some(diagnose(mat));
+    }
+
+    let mut open = validation.diagnosis_open;
+    let mut applied = None;
+    egui::Window::new("Why is my screen black?")
+        .open(&mut open)
+        .default_width(380.0)
+        .show(ctx, |ui| {
+            let findings = validation.diagnosis.as_deref().unwrap_or_default();
+            if findings.is_empty() {
+                ui.label(
+                    "Nothing found: rays reach the surface and it is lit. If the screen stays \
+                     black, the GPU or its driver may be failing, the log has details.",
+                );
+            }
+            for (index, finding) in findings.iter().enumerate() {
+                ui.horizontal_wrapped(|ui| {
+                    if index == 0 {
+                        ui.strong("Most likely:");
+                    }
+                    ui.label(finding.message.as_str());
+                    if let Some((label, _)) = &finding.fix
+                        && ui.button(*label).clicked()
+                    {
+                        applied = Some(index);
+                    }
+                });
+                ui.separator();
+            }
+            if ui.button("Check Again").clicked() {
+                validation.diagnosis = None;
+            }
+        });
+    validation.diagnosis_open = open;
+
+    let Some(index) = applied else {
+        return;
+    };
+    let fix = validation
+        .diagnosis
+        .take()
+        .and_then(|mut findings| findings.swap_remove(index).fix);
+    match fix {
+        Some((_, Fix::Params(changes))) => {
+            param_changes.write_batch(changes);
+        }
+        Some((_, Fix::AimAtFractal)) => {
+            move_all_views(&mut materials, |camera| camera.position = Vec3::ZERO);
+            param_changes.write(ParamChanged {
+                param: Param::Zoom,
+                value: CameraUniform::default().zoom,
+            });
+        }
+        None => {}
+    }
+}
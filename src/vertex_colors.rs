@@ -0,0 +1,59 @@
+//! Bakes the renderer's surface coloring into exported meshes, one linear RGB color per
+//! vertex, with the palette table the shader gets its colors from.
+
+use crate::estimator::CpuEstimator;
+use crate::material::{ColoringMode, MandelbulbMaterial};
+use crate::palette::{CosinePalette, palette};
+use crate::surface::SurfaceMesh;
+use bevy::prelude::*;
+
+/// The coloring settings of a material, without the GPU resources
+#[derive(Clone, Copy, Debug)]
+pub struct VertexColoring {
+    mode: ColoringMode,
+    palette: CosinePalette,
+    color_scale: f32,
+    color_offset: f32,
+}
+
+impl VertexColoring {
+    /// `None` with a shading graph, which only exists as WGSL
+    pub fn new(material: &MandelbulbMaterial) -> Option<Self> {
+        (!material.custom_shading).then_some(Self {
+            mode: material.coloring,
+            palette: palette(material.shading.palette_id),
+            color_scale: material.shading.color_scale,
+            color_offset: material.shading.color_offset,
+        })
+    }
+
+    /// Fills `mesh.colors`. The renderer adds the ray's step count to the orbit trap, which
+    /// depends on the view, so baked orbit trap colors use the trap alone. Equalized coloring
+    /// spreads the colors evenly over the vertices instead of over the frame's pixels.
+    pub fn bake(&self, mesh: &mut SurfaceMesh, estimator: &CpuEstimator) {
+        let mut values: Vec<f32> = match self.mode {
+            ColoringMode::OrbitTrap | ColoringMode::Equalized => mesh
+                .positions
+                .iter()
+                .map(|p| estimator.orbit_trap(*p))
+                .collect(),
+            ColoringMode::Normal => mesh
+                .normals
+                .iter()
+                .map(|n| n.dot(Vec3::splat(0.577)) * 0.5 + 0.5)
+                .collect(),
+        };
+        if self.mode == ColoringMode::Equalized {
+            let mut order: Vec<usize> = (0..values.len()).collect();
+            order.sort_unstable_by(|a, b| values[*a].total_cmp(&values[*b]));
+            let count = values.len().max(1) as f32;
+            for (rank, i) in order.into_iter().enumerate() {
+                values[i] = (rank + 1) as f32 / count;
+            }
+        }
+        mesh.colors = values
+            .into_iter()
+            .map(|v| self.palette.color(v * self.color_scale + self.color_offset))
+            .collect();
+    }
+}
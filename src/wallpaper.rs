@@ -0,0 +1,454 @@
+//! Wallpaper mode: renders a new view on a timer and makes it the desktop background. Started
+//! with `--wallpaper`, which hides the window and renders at the primary monitor's resolution,
+//! or from the Wallpaper window. The picture is an ordinary still export, handed to the
+//! platform's own tool for setting the background once it's written.
+
+use crate::args;
+use crate::export::{ExportSettings, ExportStill, StillSaved, view_path};
+use crate::material::{MandelbulbMaterial, aim_all_views};
+use crate::navigation::View;
+use crate::params::{ParamChanged, apply_param_changes};
+use crate::playlist::Playlist;
+use crate::power::keep_awake;
+use crate::rng::Rng;
+use bevy::prelude::*;
+use bevy::window::{Monitor, PrimaryMonitor, PrimaryWindow, WindowResolution};
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+/// Minutes between wallpapers unless `--wallpaper-interval` says otherwise
+const DEFAULT_INTERVAL_MINUTES: f32 = 30.0;
+
+pub struct WallpaperPlugin;
+
+impl Plugin for WallpaperPlugin {
+    fn build(&self, app: &mut App) {
+        let mut wallpaper = Wallpaper::default();
+        if let Some(args) = wallpaper_arguments() {
+            match args {
+                Ok((interval, source)) => {
+                    wallpaper.enabled = true;
+                    wallpaper.headless = true;
+                    wallpaper.interval_minutes = interval;
+                    wallpaper.source = source;
+                    // the first wallpaper goes up as soon as the scene exists
+                    wallpaper.timer = Timer::new(Duration::ZERO, TimerMode::Once);
+                }
+                Err(err) => error!("Ignoring --wallpaper: {err}"),
+            }
+        }
+        app.insert_resource(wallpaper).add_systems(
+            Update,
+            (
+                size_window_to_monitor.run_if(|w: Res<Wallpaper>| w.headless && !w.sized),
+                // the still is taken of the view opened in the same frame
+                schedule_wallpaper.before(apply_param_changes),
+                set_rendered_wallpaper,
+                keep_awake.run_if(|w: Res<Wallpaper>| w.is_rendering()),
+            )
+                .chain(),
+        );
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, wallpaper_window);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WallpaperSource {
+    /// A random camera angle, power and coloring of the current formula
+    Random,
+    /// The views saved next to stills in the export directory, in turn
+    Saved,
+    /// The entries of the playlist, in turn
+    Playlist,
+}
+
+impl WallpaperSource {
+    #[cfg(feature = "ui")]
+    pub const ALL: [WallpaperSource; 3] = [
+        WallpaperSource::Random,
+        WallpaperSource::Saved,
+        WallpaperSource::Playlist,
+    ];
+
+    #[cfg(feature = "ui")]
+    pub fn label(self) -> &'static str {
+        match self {
+            WallpaperSource::Random => "Random",
+            WallpaperSource::Saved => "Saved Views",
+            WallpaperSource::Playlist => "Playlist",
+        }
+    }
+
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "random" => Some(WallpaperSource::Random),
+            "saved" => Some(WallpaperSource::Saved),
+            "playlist" => Some(WallpaperSource::Playlist),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Wallpaper {
+    #[cfg(feature = "ui")]
+    pub open: bool,
+    pub enabled: bool,
+    pub source: WallpaperSource,
+    pub interval_minutes: f32,
+    /// Started from the command line: the window stays hidden and sized to the monitor
+    headless: bool,
+    sized: bool,
+    timer: Timer,
+    /// Which saved view or playlist entry is next
+    next: usize,
+    /// "Set Now" was clicked
+    requested: bool,
+    /// A still was requested and its path is awaited
+    rendering: bool,
+    /// The wallpaper set last, removed when the next one replaces it
+    current: Option<PathBuf>,
+    rng: Rng,
+    /// Outcome of the last wallpaper, shown in the window
+    #[cfg(feature = "ui")]
+    status: Option<Result<String, String>>,
+}
+
+impl Default for Wallpaper {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "ui")]
+            open: false,
+            enabled: false,
+            source: WallpaperSource::Random,
+            interval_minutes: DEFAULT_INTERVAL_MINUTES,
+            headless: false,
+            sized: false,
+            timer: interval_timer(DEFAULT_INTERVAL_MINUTES),
+            next: 0,
+            requested: false,
+            rendering: false,
+            current: None,
+            rng: Rng::stream("wallpaper"),
+            #[cfg(feature = "ui")]
+            status: None,
+        }
+    }
+}
+
+impl Wallpaper {
+    /// Whether frames are needed for a wallpaper being rendered
+    pub fn is_rendering(&self) -> bool {
+        self.rendering
+    }
+
+    /// Logs how the last wallpaper went, and keeps it for the window
+    fn report(&mut self, status: Result<String, String>) {
+        match &status {
+            Ok(message) => info!("{message}"),
+            Err(err) => error!("Wallpaper: {err}"),
+        }
+        #[cfg(feature = "ui")]
+        {
+            self.status = Some(status);
+        }
+    }
+
+    fn random(&mut self) -> f32 {
+        self.rng.next_f32()
+    }
+
+    fn random_view(&mut self) -> View {
+        let yaw = self.random() * 360.0;
+        let pitch = self.random() * 120.0 - 60.0;
+        let power = 3.0 + (self.random() * 8.0).round();
+        let zoom = 1.8 + self.random() * 1.2;
+        let palette = (self.random() * 4.0).floor().min(3.0);
+        let offset = self.random();
+        let code = format!(
+            "target=0,0,0;angles={yaw},{pitch},0;power={power};zoom={zoom};palette={palette};color_offset={offset}"
+        );
+        View::parse(&code).expect("random view code is well formed")
+    }
+}
+
+fn interval_timer(minutes: f32) -> Timer {
+    Timer::new(
+        Duration::from_secs_f32(minutes.max(1.0) * 60.0),
+        TimerMode::Once,
+    )
+}
+
+/// `--wallpaper`, with `--wallpaper-interval <minutes>` and `--wallpaper-source
+/// random|saved|playlist`, as (interval, source). `None` without `--wallpaper`.
+fn wallpaper_arguments() -> Option<Result<(f32, WallpaperSource), String>> {
+    if !args::is_set("--wallpaper") {
+        return None;
+    }
+    let value = |flag: &str| args::value(flag).transpose();
+    let parse = || -> Result<(f32, WallpaperSource), String> {
+        let interval = match value("--wallpaper-interval")? {
+            Some(minutes) => minutes
+                .parse()
+                .map_err(|_| format!("'{minutes}' is not a number of minutes"))?,
+            None => DEFAULT_INTERVAL_MINUTES,
+        };
+        let source = match value("--wallpaper-source")? {
+            Some(name) => {
+                WallpaperSource::parse(&name).ok_or_else(|| format!("unknown source '{name}'"))?
+            }
+            None => WallpaperSource::Random,
+        };
+        Ok((interval, source))
+    };
+    Some(parse())
+}
+
+/// Hides the window and gives it the monitor's physical size, so stills come out at the
+/// desktop resolution
+fn size_window_to_monitor(
+    mut wallpaper: ResMut<Wallpaper>,
+    monitors: Query<&Monitor, With<PrimaryMonitor>>,
+    mut window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let (Ok(monitor), Ok(mut win)) = (monitors.single(), window.single_mut()) else {
+        return;
+    };
+    win.visible = false;
+    win.resolution = WindowResolution::new(monitor.physical_width, monitor.physical_height)
+        .with_scale_factor_override(1.0);
+    wallpaper.sized = true;
+}
+
+/// Opens the next view and asks for a still of it when the timer runs out
+fn schedule_wallpaper(
+    time: Res<Time>,
+    mut wallpaper: ResMut<Wallpaper>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    playlist: Res<Playlist>,
+    settings: Res<ExportSettings>,
+    mut param_changes: MessageWriter<ParamChanged>,
+    mut exports: MessageWriter<ExportStill>,
+) {
+    if wallpaper.rendering || (wallpaper.headless && !wallpaper.sized) {
+        return;
+    }
+    let due = wallpaper.enabled && wallpaper.timer.tick(time.delta()).just_finished();
+    if !due && !wallpaper.requested {
+        return;
+    }
+    wallpaper.requested = false;
+    wallpaper.timer = interval_timer(wallpaper.interval_minutes);
+
+    match wallpaper.source {
+        WallpaperSource::Random => {
+            let view = wallpaper.random_view();
+            view.open(&mut materials, &mut param_changes);
+        }
+        WallpaperSource::Saved => {
+            let views = saved_views(&settings.directory);
+            if views.is_empty() {
+                wallpaper.report(Err(format!(
+                    "No saved views in {}",
+                    settings.directory.display()
+                )));
+                return;
+            }
+            let path = &views[wallpaper.next % views.len()];
+            wallpaper.next += 1;
+            match std::fs::read_to_string(path)
+                .map_err(|err| err.to_string())
+                .and_then(|code| View::parse(&code))
+            {
+                Ok(view) => view.open(&mut materials, &mut param_changes),
+                Err(err) => {
+                    wallpaper.report(Err(format!("{}: {err}", path.display())));
+                    return;
+                }
+            }
+        }
+        WallpaperSource::Playlist => {
+            if playlist.entries.is_empty() {
+                wallpaper.report(Err("The playlist is empty".to_string()));
+                return;
+            }
+            let entry = &playlist.entries[wallpaper.next % playlist.entries.len()];
+            wallpaper.next += 1;
+            aim_all_views(&mut materials, &entry.material.camera);
+            param_changes.write_batch(ParamChanged::all(&entry.material));
+        }
+    }
+    wallpaper.rendering = true;
+    exports.write(ExportStill);
+}
+
+/// The `.view` files in the export directory, sorted by name, which is oldest first
+fn saved_views(directory: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return Vec::new();
+    };
+    let mut views: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "view"))
+        .collect();
+    views.sort();
+    views
+}
+
+fn set_rendered_wallpaper(mut wallpaper: ResMut<Wallpaper>, mut saved: MessageReader<StillSaved>) {
+    let Some(still) = saved.read().last() else {
+        return;
+    };
+    if !wallpaper.rendering {
+        return;
+    }
+    wallpaper.rendering = false;
+    let result = std::fs::canonicalize(&still.path)
+        .map_err(|err| err.to_string())
+        .and_then(|path| set_desktop_background(&path).map(|()| path));
+    match result {
+        Ok(path) => {
+            wallpaper.report(Ok(format!("Set {} as the wallpaper", path.display())));
+            if let Some(previous) = wallpaper.current.replace(path) {
+                let _ = std::fs::remove_file(view_path(&previous));
+                let _ = std::fs::remove_file(previous);
+            }
+        }
+        Err(err) => wallpaper.report(Err(format!("Failed to set the wallpaper: {err}"))),
+    }
+}
+
+/// Hands `path`, which must be absolute, to the platform's wallpaper setting
+fn set_desktop_background(path: &Path) -> Result<(), String> {
+    let path_text = path.to_string_lossy();
+    let mut command = if cfg!(target_os = "windows") {
+        // SystemParametersInfo(SPI_SETDESKWALLPAPER, ..., SPIF_UPDATEINIFILE | SPIF_SENDCHANGE)
+        let script = format!(
+            "Add-Type -TypeDefinition 'using System.Runtime.InteropServices; public class W {{ \
+             [DllImport(\"user32.dll\", CharSet = CharSet.Unicode)] public static extern int \
+             SystemParametersInfo(int a, int b, string c, int d); }}'; \
+             [void][W]::SystemParametersInfo(20, 0, '{}', 3)",
+            path_text.replace('\'', "''")
+        );
+        let mut command = Command::new("powershell");
+        command.args(["-NoProfile", "-NonInteractive", "-Command", &script]);
+        command
+    } else if cfg!(target_os = "macos") {
+        let script = format!(
+            "tell application \"System Events\" to tell every desktop to set picture to \"{}\"",
+            path_text.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        let mut command = Command::new("osascript");
+        command.args(["-e", &script]);
+        command
+    } else {
+        return set_linux_background(path);
+    };
+    run(&mut command)
+}
+
+/// GNOME and its relatives through gsettings, KDE Plasma through its own tool, anything else
+/// through feh
+fn set_linux_background(path: &Path) -> Result<(), String> {
+    let desktop = std::env::var("XDG_CURRENT_DESKTOP")
+        .unwrap_or_default()
+        .to_lowercase();
+    if desktop.contains("kde") {
+        return run(Command::new("plasma-apply-wallpaperimage").arg(path));
+    }
+    if ["gnome", "unity", "budgie", "pantheon"]
+        .iter()
+        .any(|name| desktop.contains(name))
+    {
+        let uri = format!("file://{}", path.display());
+        for key in ["picture-uri", "picture-uri-dark"] {
+            // older GNOME has no dark variant, only the first key has to succeed
+            let result = run(Command::new("gsettings").args([
+                "set",
+                "org.gnome.desktop.background",
+                key,
+                &uri,
+            ]));
+            if key == "picture-uri" {
+                result?;
+            }
+        }
+        return Ok(());
+    }
+    run(Command::new("feh").arg("--bg-fill").arg(path))
+}
+
+fn run(command: &mut Command) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|err| format!("couldn't run {program}: {err}"))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+#[cfg(feature = "ui")]
+fn wallpaper_window(mut contexts: EguiContexts, mut wallpaper: ResMut<Wallpaper>) {
+    if !wallpaper.open {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+    let wallpaper = &mut *wallpaper;
+
+    let mut open = wallpaper.open;
+    egui::Window::new("Wallpaper")
+        .open(&mut open)
+        .default_width(280.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Source");
+                egui::ComboBox::from_id_salt("wallpaper_source_combo")
+                    .selected_text(wallpaper.source.label())
+                    .show_ui(ui, |ui| {
+                        for source in WallpaperSource::ALL {
+                            ui.selectable_value(&mut wallpaper.source, source, source.label());
+                        }
+                    });
+            });
+            ui.add(
+                egui::Slider::new(&mut wallpaper.interval_minutes, 1.0..=1440.0)
+                    .logarithmic(true)
+                    .text("Minutes"),
+            );
+            ui.horizontal(|ui| {
+                if ui
+                    .checkbox(&mut wallpaper.enabled, "Change on schedule")
+                    .changed()
+                {
+                    wallpaper.timer = interval_timer(wallpaper.interval_minutes);
+                }
+                if ui
+                    .add_enabled(!wallpaper.rendering, egui::Button::new("Set Now"))
+                    .clicked()
+                {
+                    wallpaper.requested = true;
+                }
+            });
+            ui.weak("Rendered at the window's size, --wallpaper renders at the monitor's.");
+            match &wallpaper.status {
+                Some(Ok(message)) => {
+                    ui.label(message);
+                }
+                Some(Err(err)) => {
+                    ui.colored_label(egui::Color32::LIGHT_RED, err);
+                }
+                None => {}
+            }
+        });
+    wallpaper.open = open;
+}
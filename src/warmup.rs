@@ -0,0 +1,340 @@
+use crate::material::{
+    ColoringMode, Formula, FractalMaterial, MandelbulbMaterial, MandelbulbMaterialKey,
+    OUTPUT_MODE_SDR,
+};
+use crate::navigation::StartupView;
+use crate::playlist::Playlist;
+use crate::startup::StartupScene;
+use bevy::camera::visibility::RenderLayers;
+use bevy::camera::{ClearColorConfig, RenderTarget};
+use bevy::core_pipeline::tonemapping::Tonemapping;
+use bevy::prelude::*;
+use bevy::render::gpu_readback::{Readback, ReadbackComplete};
+use bevy::render::render_resource::{TextureFormat, TextureUsages};
+use bevy::render::view::Hdr;
+#[cfg(feature = "ui")]
+use bevy_egui::{EguiContexts, EguiPrimaryContextPass, egui};
+use std::collections::HashSet;
+use std::time::Duration;
+
+/// First render layer used by warm-up views, each view gets its own layer so it only sees the
+/// quad it is compiling
+const FIRST_WARMUP_LAYER: usize = 8;
+
+/// Cleared to this color, so any other color in the readback means the fractal quad was drawn
+const SENTINEL: Color = Color::srgb(1.0, 0.0, 1.0);
+
+/// Size of the offscreen targets, only needs to be large enough to cover a few pixels of the quad
+const WARMUP_SIZE: u32 = 16;
+
+/// How long the splash waits for the variants before giving up on the rest
+const WARMUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct WarmupPlugin;
+
+impl Plugin for WarmupPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Warmup>()
+            .add_systems(Startup, spawn_warmup_views.after(crate::setup))
+            .add_systems(
+                Update,
+                (
+                    warm_playlist.run_if(resource_changed::<Playlist>),
+                    give_up_warmup,
+                ),
+            );
+        #[cfg(feature = "ui")]
+        app.add_systems(EguiPrimaryContextPass, warmup_splash);
+    }
+}
+
+/// Tracks pipelines that are still compiling, at startup or for a playlist just loaded
+#[derive(Resource, Default)]
+pub struct Warmup {
+    pub pending: usize,
+    pub total: usize,
+    /// Every variant a view was spawned for, with whether it rendered HDR
+    warmed: HashSet<(MandelbulbMaterialKey, bool)>,
+    /// When the first of the pending views was spawned
+    started: Duration,
+}
+
+impl Warmup {
+    pub fn is_done(&self) -> bool {
+        self.pending == 0
+    }
+}
+
+/// The quads and cameras of the warm-up views
+#[derive(Component)]
+struct WarmupView;
+
+/// The startup material and every variant one switch away from it: another formula, the
+/// geometry graph, a coloring mode, one shading or performance option flipped, or the data pass
+/// of an export. Variants two or more switches away compile when they are first used.
+fn warmup_materials(base: &MandelbulbMaterial) -> Vec<MandelbulbMaterial> {
+    let mut variants = vec![base.clone()];
+    variants.extend(Formula::all().map(|formula| MandelbulbMaterial {
+        formula,
+        ..base.clone()
+    }));
+    variants.extend(ColoringMode::ALL.map(|coloring| MandelbulbMaterial {
+        coloring,
+        ..base.clone()
+    }));
+    let toggles: [fn(&mut MandelbulbMaterial); 6] = [
+        |m| m.geometry_graph = !m.geometry_graph,
+        |m| m.soft_shadows = !m.soft_shadows,
+        |m| m.streamlines = !m.streamlines,
+        |m| m.custom_shading = !m.custom_shading,
+        |m| m.brick_map = !m.brick_map,
+        |m| m.reprojection = !m.reprojection,
+    ];
+    for toggle in toggles {
+        let mut variant = base.clone();
+        toggle(&mut variant);
+        variants.push(variant);
+    }
+    let mut seen = HashSet::new();
+    variants.retain(|variant| seen.insert(MandelbulbMaterialKey::from(variant)));
+    variants
+}
+
+/// Renders each material variant once into a tiny offscreen target, so their pipelines are
+/// specialized and compiled before the user first needs them instead of hitching when a mode is
+/// toggled. Variants render with the main camera's setup, SDR unless an HDR output mode is
+/// selected, and the startup material also with the other one, which exports, crossfades and
+/// layers use. The data pass only renders for exports, which are always HDR. The variants are
+/// those of the view opened at startup, and of every saved view the startup scene can pick.
+fn spawn_warmup_views(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut warmup: ResMut<Warmup>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    startup_view: Option<Res<StartupView>>,
+    scene: Res<StartupScene>,
+) {
+    let Some(mut base) = materials.get(&fractal.0).cloned() else {
+        return;
+    };
+    // the startup view is opened later, in PostStartup
+    if let Some(view) = startup_view {
+        view.0.apply(&mut base);
+    }
+    let main_hdr = base.shading.output_mode != OUTPUT_MODE_SDR;
+
+    let mut views = Vec::new();
+    for (index, material) in warmup_materials(&base).into_iter().enumerate() {
+        views.push((material, main_hdr));
+        if index == 0 {
+            views.push((base.clone(), !main_hdr));
+        }
+    }
+    views.push((
+        MandelbulbMaterial {
+            data_output: true,
+            ..base.clone()
+        },
+        true,
+    ));
+    for view in scene.saved_views() {
+        let mut material = base.clone();
+        view.apply(&mut material);
+        views.push((material, main_hdr));
+    }
+
+    let mesh = meshes.add(Rectangle::default());
+    for (material, hdr) in views {
+        spawn_warmup_view(
+            &mut commands,
+            &mut warmup,
+            &mut images,
+            &mut materials,
+            &mesh,
+            material,
+            hdr,
+            time.elapsed(),
+        );
+    }
+}
+
+/// Compiles the variants of playlist entries as they are added, both for the main view and the
+/// offscreen one a crossfade pre-renders the next entry in
+fn warm_playlist(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut warmup: ResMut<Warmup>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    playlist: Res<Playlist>,
+) {
+    let Some(main_hdr) = materials
+        .get(&fractal.0)
+        .map(|mat| mat.shading.output_mode != OUTPUT_MODE_SDR)
+    else {
+        return;
+    };
+    // the playlist changes every frame while it plays, only new variants are cloned
+    let views: Vec<_> = playlist
+        .entries
+        .iter()
+        .flat_map(|entry| [(entry, main_hdr), (entry, !main_hdr)])
+        .filter(|(entry, hdr)| {
+            let key = MandelbulbMaterialKey::from(&entry.material);
+            !warmup.warmed.contains(&(key, *hdr))
+        })
+        .map(|(entry, hdr)| (entry.material.clone(), hdr))
+        .collect();
+    if views.is_empty() {
+        return;
+    }
+    let mesh = meshes.add(Rectangle::default());
+    for (material, hdr) in views {
+        spawn_warmup_view(
+            &mut commands,
+            &mut warmup,
+            &mut images,
+            &mut materials,
+            &mesh,
+            material,
+            hdr,
+            time.elapsed(),
+        );
+    }
+}
+
+/// Spawns a view of `material` that despawns once it has drawn, unless one was spawned for the
+/// same variant before
+fn spawn_warmup_view(
+    commands: &mut Commands,
+    warmup: &mut Warmup,
+    images: &mut Assets<Image>,
+    materials: &mut Assets<MandelbulbMaterial>,
+    mesh: &Handle<Mesh>,
+    material: MandelbulbMaterial,
+    hdr: bool,
+    now: Duration,
+) {
+    if !warmup
+        .warmed
+        .insert((MandelbulbMaterialKey::from(&material), hdr))
+    {
+        return;
+    }
+    // a new batch, the views of the last one all drew or were given up and despawned
+    if warmup.is_done() {
+        warmup.started = now;
+        warmup.total = 0;
+    }
+    // each view gets a layer of its own
+    let layer = FIRST_WARMUP_LAYER + warmup.total;
+    let material = materials.add(material);
+    let mut target = Image::new_target_texture(WARMUP_SIZE, WARMUP_SIZE, TextureFormat::Rgba8Unorm);
+    target.texture_descriptor.usage |= TextureUsages::COPY_SRC;
+    let target = images.add(target);
+
+    let quad = commands
+        .spawn((
+            Mesh2d(mesh.clone()),
+            MeshMaterial2d(material),
+            Transform::default().with_scale(Vec3::splat(WARMUP_SIZE as f32 * 2.0)),
+            RenderLayers::layer(layer),
+            WarmupView,
+        ))
+        .id();
+    let camera = commands
+        .spawn((
+            Camera2d,
+            Camera {
+                target: RenderTarget::Image(target.clone().into()),
+                order: -2,
+                clear_color: ClearColorConfig::Custom(SENTINEL),
+                ..default()
+            },
+            RenderLayers::layer(layer),
+            WarmupView,
+        ))
+        .id();
+    if hdr {
+        commands.entity(camera).insert((Hdr, Tonemapping::None));
+    }
+
+    commands
+        .entity(camera)
+        .insert(Readback::texture(target))
+        .observe(
+            move |event: On<ReadbackComplete>,
+                  mut commands: Commands,
+                  mut warmup: ResMut<Warmup>| {
+                let drawn = event
+                    .data
+                    .chunks_exact(4)
+                    .any(|px| px[..3] != [255, 0, 255]);
+                if drawn {
+                    warmup.pending = warmup.pending.saturating_sub(1);
+                    commands.entity(camera).despawn();
+                    commands.entity(quad).despawn();
+                }
+            },
+        );
+
+    warmup.pending += 1;
+    warmup.total += 1;
+}
+
+/// Stops waiting for views that never drew, e.g. because their shader failed to compile, so the
+/// splash doesn't cover the window for good
+fn give_up_warmup(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut warmup: ResMut<Warmup>,
+    views: Query<Entity, With<WarmupView>>,
+) {
+    if warmup.is_done() || time.elapsed() < warmup.started + WARMUP_TIMEOUT {
+        return;
+    }
+    warn!(
+        "{} of {} shader variants didn't draw within {}s, continuing without them",
+        warmup.pending,
+        warmup.total,
+        WARMUP_TIMEOUT.as_secs()
+    );
+    warmup.pending = 0;
+    for entity in views.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+/// Covers the window with a loading message until every warm-up view has drawn
+#[cfg(feature = "ui")]
+fn warmup_splash(mut contexts: EguiContexts, warmup: Res<Warmup>) {
+    if warmup.is_done() {
+        return;
+    }
+    let ctx = contexts.ctx_mut().unwrap();
+
+    egui::Area::new(egui::Id::new("warmup_splash"))
+        .order(egui::Order::Foreground)
+        .fixed_pos(egui::Pos2::ZERO)
+        .show(ctx, |ui| {
+            let rect = ui.ctx().content_rect();
+            ui.painter()
+                .rect_filled(rect, 0.0, egui::Color32::from_rgb(5, 5, 12));
+            ui.painter().text(
+                rect.center(),
+                egui::Align2::CENTER_CENTER,
+                format!(
+                    "Compiling shaders... {}/{}",
+                    warmup.total - warmup.pending,
+                    warmup.total
+                ),
+                egui::FontId::proportional(20.0),
+                egui::Color32::LIGHT_GRAY,
+            );
+        });
+}
@@ -0,0 +1,115 @@
+//! Publishes the current view as a static web page: a folder with an `index.html` that renders
+//! the fractal with WebGL 2 and orbit controls, the exported parameters baked in. The page is
+//! `assets/web/viewer.html`, a port of the shader's built-in formulas and basic shading.
+
+use crate::export::{ExportSettings, timestamped_path};
+#[cfg(feature = "export")]
+use crate::material::{ColoringMode, Formula};
+use crate::material::{FractalMaterial, MandelbulbMaterial};
+use bevy::prelude::*;
+#[cfg(feature = "export")]
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "export")]
+const VIEWER: &str = include_str!("../assets/web/viewer.html");
+
+pub struct WebBundlePlugin;
+
+impl Plugin for WebBundlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_message::<ExportWebBundle>()
+            .add_systems(Update, export_web_bundle);
+    }
+}
+
+/// Request to save the current view as a web page into the export directory
+#[derive(Message)]
+pub struct ExportWebBundle;
+
+fn export_web_bundle(
+    mut requests: MessageReader<ExportWebBundle>,
+    materials: Res<Assets<MandelbulbMaterial>>,
+    fractal: Res<FractalMaterial>,
+    settings: Res<ExportSettings>,
+) {
+    if requests.read().count() == 0 {
+        return;
+    }
+    let Some(mat) = materials.get(&fractal.0) else {
+        return;
+    };
+    // a folder per page, named like a single export without the extension
+    let directory = timestamped_path(&settings.directory, "fractal_web", "html").with_extension("");
+    match write_web_bundle(&directory, mat) {
+        Ok(path) => info!("Saved web page to {}", path.display()),
+        Err(err) => error!("Web export failed: {err}"),
+    }
+}
+
+/// Writes the page into `directory`, returning the path of its `index.html`
+#[cfg(feature = "export")]
+pub fn write_web_bundle(directory: &Path, mat: &MandelbulbMaterial) -> Result<PathBuf, String> {
+    let mandelbox = match mat.formula {
+        Formula::Mandelbulb => false,
+        Formula::Mandelbox => true,
+        Formula::Plugin(_) => return Err("Plugin formulas only exist as WGSL".to_string()),
+    };
+    if mat.geometry_graph {
+        return Err("The geometry graph only exists as WGSL".to_string());
+    }
+    let (camera, shape, shading) = (&mat.camera, &mat.shape, &mat.shading);
+    // equalized and graph coloring need the renderer, the page falls back to the orbit trap
+    let view = json!({
+        "camera": {
+            "position": camera.position.to_array(),
+            "zoom": camera.zoom,
+            "rotation": camera.rotation.to_array(),
+            "focal_length": camera.focal_length,
+            "fov_fit": camera.fov_fit,
+        },
+        "shape": {
+            "mandelbox": mandelbox,
+            "power": shape.power,
+            "iterations": shape.mandel_iters,
+            "julia": shape.julia.to_array(),
+            "ray_steps": shape.ray_steps,
+            "max_dist": shape.max_dist,
+            "hit_threshold": shape.hit_threshold,
+        },
+        "shading": {
+            "palette": shading.palette.map(|v| v.truncate().to_array()),
+            "color_by_normal": mat.coloring == ColoringMode::Normal && !mat.custom_shading,
+            "color_scale": shading.color_scale,
+            "color_offset": shading.color_offset,
+            "light": [shading.light_pos_x, shading.light_pos_y],
+            "background_glow": shading.background_glow_intensity,
+            "ao_strength": shading.ao_strength,
+            "rim_strength": shading.rim_strength,
+            "light_color": shading.light_color.truncate().to_array(),
+            "ambient_color": shading.ambient_color.truncate().to_array(),
+            "specular_intensity": shading.specular_intensity,
+            "shininess": shading.shininess,
+            "rim_color": shading.rim_color.truncate().to_array(),
+            "rim_direction": shading.rim_direction,
+            "lens_strength": shading.lens_strength,
+            "lens_distortion": shading.lens_distortion,
+            "chromatic_aberration": shading.chromatic_aberration,
+            "fog_density": shading.fog_density,
+        },
+    });
+    let title = if mandelbox { "Mandelbox" } else { "Mandelbulb" };
+    let page = VIEWER
+        .replace("{{TITLE}}", &format!("{title} - fractal3D"))
+        .replace("{{VIEW}}", &view.to_string());
+
+    std::fs::create_dir_all(directory).map_err(|err| err.to_string())?;
+    let path = directory.join("index.html");
+    std::fs::write(&path, page).map_err(|err| err.to_string())?;
+    Ok(path)
+}
+
+#[cfg(not(feature = "export"))]
+pub fn write_web_bundle(_directory: &Path, _mat: &MandelbulbMaterial) -> Result<PathBuf, String> {
+    Err("Built without the export feature".to_string())
+}